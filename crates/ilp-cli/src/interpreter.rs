@@ -4,7 +4,7 @@ use reqwest::{
     self,
     blocking::{Client, Response},
 };
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, fs};
 use tungstenite::{connect, handshake::client::Request};
 use url::Url;
 
@@ -26,6 +26,10 @@ pub enum Error {
     UrlErr(#[from] url::ParseError),
     #[error("WebSocket error: {0}")]
     WebsocketErr(#[from] tungstenite::error::Error),
+    #[error("Error reading file: {0}")]
+    FileErr(#[from] std::io::Error),
+    #[error("Error parsing JSON: {0}")]
+    JsonErr(#[from] serde_json::Error),
 }
 
 pub fn run(matches: &ArgMatches) -> Result<Response, Error> {
@@ -45,10 +49,26 @@ pub fn run(matches: &ArgMatches) -> Result<Response, Error> {
             }
             ("info", Some(submatches)) => client.get_account(submatches),
             ("list", Some(submatches)) => client.get_accounts(submatches),
+            ("set-status", Some(submatches)) => client.put_account_status(submatches),
             ("update", Some(submatches)) => client.put_account(submatches),
             ("update-settings", Some(submatches)) => client.put_account_settings(submatches),
             _ => Err(Error::UsageErr("ilp-cli help accounts")),
         },
+        ("api-keys", Some(api_keys_matches)) => match api_keys_matches.subcommand() {
+            ("create", Some(submatches)) => client.post_api_keys(submatches),
+            ("delete", Some(submatches)) => client.delete_api_key(submatches),
+            ("list", Some(submatches)) => client.get_api_keys(submatches),
+            _ => Err(Error::UsageErr("ilp-cli help api-keys")),
+        },
+        ("fees", Some(fees_matches)) => match fees_matches.subcommand() {
+            ("list", Some(submatches)) => client.get_fees(submatches),
+            _ => Err(Error::UsageErr("ilp-cli help fees")),
+        },
+        ("node", Some(node_matches)) => match node_matches.subcommand() {
+            ("export", Some(submatches)) => client.post_export(submatches),
+            ("import", Some(submatches)) => client.post_import(submatches),
+            _ => Err(Error::UsageErr("ilp-cli help node")),
+        },
         ("pay", Some(pay_matches)) => client.post_account_payments(pay_matches),
         ("rates", Some(rates_matches)) => match rates_matches.subcommand() {
             ("list", Some(submatches)) => client.get_rates(submatches),
@@ -61,6 +81,10 @@ pub fn run(matches: &ArgMatches) -> Result<Response, Error> {
             ("set-all", Some(submatches)) => client.put_routes_static(submatches),
             _ => Err(Error::UsageErr("ilp-cli help routes")),
         },
+        ("secret-seed", Some(secret_seed_matches)) => match secret_seed_matches.subcommand() {
+            ("rotate", Some(submatches)) => client.put_secret_seed(submatches),
+            _ => Err(Error::UsageErr("ilp-cli help secret-seed")),
+        },
         ("settlement-engines", Some(settlement_matches)) => match settlement_matches.subcommand() {
             ("set-all", Some(submatches)) => client.put_settlement_engines(submatches),
             _ => Err(Error::UsageErr("ilp-cli help settlement-engines")),
@@ -167,10 +191,11 @@ impl NodeClient<'_> {
 
     // GET /accounts
     fn get_accounts(&self, matches: &ArgMatches) -> Result<Response, Error> {
-        let (auth, _) = extract_args(matches);
+        let (auth, args) = extract_args(matches);
         self.client
             .get(&format!("{}/accounts", self.url))
             .bearer_auth(auth)
+            .query(&args)
             .send()
             .map_err(Error::SendErr)
     }
@@ -187,6 +212,18 @@ impl NodeClient<'_> {
             .map_err(Error::SendErr)
     }
 
+    // PUT /accounts/:username/status
+    fn put_account_status(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, mut args) = extract_args(matches);
+        let user = args.remove("username").unwrap(); // infallible unwrap
+        self.client
+            .put(&format!("{}/accounts/{}/status", self.url, user))
+            .bearer_auth(auth)
+            .json(&args)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
     // POST /accounts/:username/payments
     fn post_account_payments(&self, matches: &ArgMatches) -> Result<Response, Error> {
         let (auth, mut args) = extract_args(matches);
@@ -199,6 +236,87 @@ impl NodeClient<'_> {
             .map_err(Error::SendErr)
     }
 
+    // POST /accounts/:username/api-keys
+    fn post_api_keys(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        // extract_args only keeps the first value of each argument
+        let scopes: Vec<&str> = matches.values_of("scope").unwrap().collect(); // infallible unwrap
+        self.client
+            .post(&format!(
+                "{}/accounts/{}/api-keys",
+                self.url, args["username"]
+            ))
+            .bearer_auth(auth)
+            .json(&serde_json::json!({
+                "scopes": scopes,
+                "description": args.get("description"),
+            }))
+            .send()
+            .map_err(Error::SendErr)
+    }
+
+    // DELETE /accounts/:username/api-keys/:id
+    fn delete_api_key(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        self.client
+            .delete(&format!(
+                "{}/accounts/{}/api-keys/{}",
+                self.url, args["username"], args["id"]
+            ))
+            .bearer_auth(auth)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
+    // GET /accounts/:username/api-keys
+    fn get_api_keys(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        self.client
+            .get(&format!(
+                "{}/accounts/{}/api-keys",
+                self.url, args["username"]
+            ))
+            .bearer_auth(auth)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
+    // GET /fees
+    fn get_fees(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, _) = extract_args(matches);
+        self.client
+            .get(&format!("{}/fees", self.url))
+            .bearer_auth(auth)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
+    // POST /export
+    fn post_export(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        self.client
+            .post(&format!("{}/export", self.url))
+            .bearer_auth(auth)
+            .json(&args)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
+    // POST /import
+    fn post_import(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        let snapshot: serde_json::Value = serde_json::from_slice(&fs::read(args["file"])?)?;
+        self.client
+            .post(&format!("{}/import", self.url))
+            .bearer_auth(auth)
+            .json(&serde_json::json!({
+                "passphrase": args["passphrase"],
+                "snapshot": snapshot,
+            }))
+            .send()
+            .map_err(Error::SendErr)
+    }
+
     // GET /rates
     fn get_rates(&self, _matches: &ArgMatches) -> Result<Response, Error> {
         self.client
@@ -248,6 +366,17 @@ impl NodeClient<'_> {
             .map_err(Error::SendErr)
     }
 
+    // PUT /secret_seed
+    fn put_secret_seed(&self, matches: &ArgMatches) -> Result<Response, Error> {
+        let (auth, args) = extract_args(matches);
+        self.client
+            .put(&format!("{}/secret_seed", self.url))
+            .bearer_auth(auth)
+            .json(&args)
+            .send()
+            .map_err(Error::SendErr)
+    }
+
     // PUT /settlement/engines
     fn put_settlement_engines(&self, matches: &ArgMatches) -> Result<Response, Error> {
         let (auth, engine_pairs) = unflatten_pairs(matches);
@@ -75,7 +75,7 @@ mod interface_tests {
     #[test]
     fn accounts_create() {
         should_parse(&[
-            "ilp-cli accounts create alice --auth foo --asset-code XYZ --asset-scale 6 --ilp-address bar --max-packet-amount 100 --min-balance 0 --ilp-over-http-url qux --ilp-over-http-incoming-token baz --ilp-over-http-outgoing-token qaz --ilp-over-btp-url spam --ilp-over-btp-outgoing-token ham --ilp-over-btp-incoming-token eggs --settle-threshold 0 --settle-to 0 --routing-relation foobar --round-trip-time 1000 --amount-per-minute-limit 42 --packets-per-minute-limit 4 --settlement-engine-url if_you_can_read_this_congratulations_youve_scrolled_too_far_right", // maximal
+            "ilp-cli accounts create alice --auth foo --asset-code XYZ --asset-scale 6 --ilp-address bar --max-packet-amount 100 --min-balance 0 --ilp-over-http-url qux --ilp-over-http-incoming-token baz --ilp-over-http-outgoing-token qaz --ilp-over-btp-url spam --ilp-over-btp-outgoing-token ham --ilp-over-btp-incoming-token eggs --settle-threshold 0 --settle-to 0 --routing-relation foobar --round-trip-time 1000 --amount-per-minute-limit 42 --packets-per-minute-limit 4 --amount-burst-limit 84 --packets-burst-limit 8 --amount-per-day-limit 1000 --amount-per-month-limit 10000 --fixed-fee 10 --fee-basis-points 25 --settlement-engine-url if_you_can_read_this_congratulations_youve_scrolled_too_far_right", // maximal
             "ilp-cli accounts create alice --auth foo --asset-code ABC --asset-scale 3 --min-balance -1000 --settle-threshold -10", // negative numbers
         ]);
     }
@@ -84,7 +84,7 @@ mod interface_tests {
     fn accounts_update() {
         should_parse(&[
             "ilp-cli accounts update alice --auth foo --asset-code ABC --asset-scale 9", // minimal
-            "ilp-cli accounts update alice --auth foo --asset-code XYZ --asset-scale 6 --ilp-address bar --max-packet-amount 100 --min-balance 0 --ilp-over-http-url qux --ilp-over-http-incoming-token baz --ilp-over-http-outgoing-token qaz --ilp-over-btp-url spam --ilp-over-btp-outgoing-token ham --ilp-over-btp-incoming-token eggs --settle-threshold 0 --settle-to 0 --routing-relation foobar --round-trip-time 1000 --amount-per-minute-limit 42 --packets-per-minute-limit 4 --settlement-engine-url if_you_can_read_this_congratulations_youve_scrolled_too_far_right", // maximal
+            "ilp-cli accounts update alice --auth foo --asset-code XYZ --asset-scale 6 --ilp-address bar --max-packet-amount 100 --min-balance 0 --ilp-over-http-url qux --ilp-over-http-incoming-token baz --ilp-over-http-outgoing-token qaz --ilp-over-btp-url spam --ilp-over-btp-outgoing-token ham --ilp-over-btp-incoming-token eggs --settle-threshold 0 --settle-to 0 --routing-relation foobar --round-trip-time 1000 --amount-per-minute-limit 42 --packets-per-minute-limit 4 --amount-burst-limit 84 --packets-burst-limit 8 --amount-per-day-limit 1000 --amount-per-month-limit 10000 --fixed-fee 10 --fee-basis-points 25 --settlement-engine-url if_you_can_read_this_congratulations_youve_scrolled_too_far_right", // maximal
         ]);
     }
 
@@ -113,6 +113,14 @@ mod interface_tests {
     fn accounts_list() {
         should_parse(&[
             "ilp-cli accounts list --auth foo", // minimal
+            "ilp-cli accounts list --auth foo --cursor bar --limit 10 --asset-code XYZ --routing-relation Child --username-prefix al", // maximal
+        ]);
+    }
+
+    #[test]
+    fn accounts_set_status() {
+        should_parse(&[
+            "ilp-cli accounts set-status alice suspended --auth foo", // minimal
         ]);
     }
 
@@ -125,6 +133,54 @@ mod interface_tests {
         ]);
     }
 
+    #[test]
+    fn api_keys_create() {
+        should_parse(&[
+            "ilp-cli api-keys create alice --scope read-balance --auth foo", // minimal
+            "ilp-cli api-keys create alice --scope read-balance --scope pay --description wallet --auth foo", // maximal
+        ]);
+    }
+
+    #[test]
+    fn api_keys_delete() {
+        should_parse(&[
+            "ilp-cli api-keys delete alice 9dd7b0cf-5d4c-4a39-8f21-6cf19fa1d0b2 --auth foo", // minimal
+        ]);
+    }
+
+    #[test]
+    fn api_keys_list() {
+        should_parse(&[
+            "ilp-cli api-keys list alice --auth foo", // minimal
+        ]);
+    }
+
+    #[test]
+    fn fees_list() {
+        should_parse(&[
+            "ilp-cli fees list --auth foo", // minimal
+        ]);
+    }
+
+    #[test]
+    fn node_export() {
+        should_parse(&[
+            "ilp-cli node export --passphrase correct-horse-battery-staple --auth foo", // minimal
+        ]);
+    }
+
+    #[test]
+    fn node_import() {
+        let file = std::env::temp_dir().join("ilp-cli-node-import-test.json");
+        std::fs::write(&file, "{}").unwrap();
+        should_parse(&[
+            &format!(
+                "ilp-cli node import {} --passphrase correct-horse-battery-staple --auth foo",
+                file.display()
+            ), // minimal
+        ]);
+    }
+
     #[test]
     fn pay() {
         should_parse(&[
@@ -173,6 +229,13 @@ mod interface_tests {
         ])
     }
 
+    #[test]
+    fn secret_seed_rotate() {
+        should_parse(&[
+            "ilp-cli secret-seed rotate 0000000000000000000000000000000000000000000000000000000000000000 --auth foo", // minimal
+        ]);
+    }
+
     #[test]
     fn settlement_engines_set_all() {
         should_parse(&[
@@ -9,12 +9,17 @@ pub fn build<'a, 'b>() -> App<'a, 'b> {
             accounts_incoming_payments(),
             accounts_info(),
             accounts_list(),
+            accounts_set_status(),
             accounts_update(),
             accounts_update_settings(),
         ]),
+        api_keys().subcommands(vec![api_keys_create(), api_keys_delete(), api_keys_list()]),
+        fees().subcommands(vec![fees_list()]),
+        node().subcommands(vec![node_export(), node_import()]),
         pay(),
         rates().subcommands(vec![rates_list(), rates_set_all()]),
         routes().subcommands(vec![routes_list(), routes_set(), routes_set_all()]),
+        secret_seed().subcommands(vec![secret_seed_rotate()]),
         settlement_engines().subcommands(vec![settlement_engines_set_all()]),
         status(),
         testnet().subcommands(vec![testnet_setup()]),
@@ -141,6 +146,24 @@ fn accounts_create<'a, 'b>() -> App<'a, 'b> {
             Arg::with_name("packets_per_minute_limit")
                 .long("packets-per-minute-limit")
                 .takes_value(true),
+            Arg::with_name("amount_burst_limit")
+                .long("amount-burst-limit")
+                .takes_value(true),
+            Arg::with_name("packets_burst_limit")
+                .long("packets-burst-limit")
+                .takes_value(true),
+            Arg::with_name("amount_per_day_limit")
+                .long("amount-per-day-limit")
+                .takes_value(true),
+            Arg::with_name("amount_per_month_limit")
+                .long("amount-per-month-limit")
+                .takes_value(true),
+            Arg::with_name("fixed_fee")
+                .long("fixed-fee")
+                .takes_value(true),
+            Arg::with_name("fee_basis_points")
+                .long("fee-basis-points")
+                .takes_value(true),
             Arg::with_name("settlement_engine_url")
                 .long("settlement-engine-url")
                 .takes_value(true),
@@ -212,6 +235,24 @@ fn accounts_update<'a, 'b>() -> App<'a, 'b> {
             Arg::with_name("packets_per_minute_limit")
                 .long("packets-per-minute-limit")
                 .takes_value(true),
+            Arg::with_name("amount_burst_limit")
+                .long("amount-burst-limit")
+                .takes_value(true),
+            Arg::with_name("packets_burst_limit")
+                .long("packets-burst-limit")
+                .takes_value(true),
+            Arg::with_name("amount_per_day_limit")
+                .long("amount-per-day-limit")
+                .takes_value(true),
+            Arg::with_name("amount_per_month_limit")
+                .long("amount-per-month-limit")
+                .takes_value(true),
+            Arg::with_name("fixed_fee")
+                .long("fixed-fee")
+                .takes_value(true),
+            Arg::with_name("fee_basis_points")
+                .long("fee-basis-points")
+                .takes_value(true),
             Arg::with_name("settlement_engine_url")
                 .long("settlement-engine-url")
                 .takes_value(true),
@@ -258,7 +299,48 @@ fn accounts_info<'a, 'b>() -> App<'a, 'b> {
 }
 
 fn accounts_list<'a, 'b>() -> App<'a, 'b> {
-    AuthorizedSubCommand::with_name("list").about("List all accounts on this node")
+    AuthorizedSubCommand::with_name("list")
+        .about("List the accounts on this node, optionally one page at a time")
+        .args(&[
+            Arg::with_name("cursor")
+                .long("cursor")
+                .takes_value(true)
+                .help("The next_cursor returned with the previous page"),
+            Arg::with_name("limit")
+                .long("limit")
+                .takes_value(true)
+                .help("The maximum number of accounts to list"),
+            Arg::with_name("asset_code")
+                .long("asset-code")
+                .takes_value(true)
+                .help("Only list accounts with this asset code"),
+            Arg::with_name("routing_relation")
+                .long("routing-relation")
+                .takes_value(true)
+                .help("Only list accounts with this routing relation"),
+            Arg::with_name("username_prefix")
+                .long("username-prefix")
+                .takes_value(true)
+                .help("Only list accounts whose username starts with this prefix"),
+        ])
+}
+
+fn accounts_set_status<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("set-status")
+        .about("Suspend, close or reactivate an account without deleting it")
+        .args(&[
+            Arg::with_name("username")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The username of the account"),
+            Arg::with_name("status")
+                .index(2)
+                .takes_value(true)
+                .required(true)
+                .possible_values(&["active", "suspended", "closing"])
+                .help("The new status of the account"),
+        ])
 }
 
 fn accounts_update_settings<'a, 'b>() -> App<'a, 'b> {
@@ -297,6 +379,74 @@ fn accounts_update_settings<'a, 'b>() -> App<'a, 'b> {
         ])
 }
 
+fn api_keys<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("api-keys").about(
+        "Operations for managing the API keys which authorize requests on behalf of accounts",
+    )
+}
+
+fn api_keys_create<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("create")
+        .about("Create an API key for an account; its token is only returned by this operation")
+        .args(&[
+            Arg::with_name("username")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The username of the account the key acts on behalf of"),
+            Arg::with_name("scope")
+                .long("scope")
+                .takes_value(true)
+                .required(true)
+                .multiple(true)
+                .possible_values(&["read-account", "read-balance", "pay", "modify-settings"])
+                .help("An operation the key is allowed to perform; may appear multiple times"),
+            Arg::with_name("description")
+                .long("description")
+                .takes_value(true)
+                .help("A note to tell the account's keys apart"),
+        ])
+}
+
+fn api_keys_delete<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("delete")
+        .about("Revoke an API key of an account")
+        .args(&[
+            Arg::with_name("username")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The username of the account the key belongs to"),
+            Arg::with_name("id")
+                .index(2)
+                .takes_value(true)
+                .required(true)
+                .help("The id of the key to revoke"),
+        ])
+}
+
+fn api_keys_list<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("list")
+        .about("List the API keys of an account, without their tokens")
+        .arg(
+            Arg::with_name("username")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The username of the account whose keys to list"),
+        )
+}
+
+fn fees<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("fees")
+        .about("Operations for interacting with the fees charged by this node")
+}
+
+fn fees_list<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("list")
+        .about("List the fees earned on packets forwarded between each pair of accounts")
+}
+
 fn pay<'a, 'b>() -> App<'a, 'b> {
     // TODO: this endpoint currently only works with user authorization, not admin authorization
     AuthorizedSubCommand::with_name("pay")
@@ -320,6 +470,41 @@ fn pay<'a, 'b>() -> App<'a, 'b> {
         ])
 }
 
+fn node<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("node").about("Operations for moving the node's state to another node")
+}
+
+fn node_export<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("export")
+        .about("Export the accounts, balances, routes, settlement engines and exchange rates of this node")
+        .arg(
+            Arg::with_name("passphrase")
+                .long("passphrase")
+                .takes_value(true)
+                .env("ILP_CLI_EXPORT_PASSPHRASE")
+                .required(true)
+                .help("A passphrase of at least 16 characters from which the key that encrypts the account tokens is derived; it is needed to import the export"),
+        )
+}
+
+fn node_import<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("import")
+        .about("Import the state exported from another node into this node")
+        .args(&[
+            Arg::with_name("file")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The file containing the output of `node export`"),
+            Arg::with_name("passphrase")
+                .long("passphrase")
+                .takes_value(true)
+                .env("ILP_CLI_EXPORT_PASSPHRASE")
+                .required(true)
+                .help("The passphrase which the state was exported with"),
+        ])
+}
+
 fn rates<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("rates").about("Operations for interacting with exchange rates")
 }
@@ -377,6 +562,23 @@ fn routes_set_all<'a, 'b>() -> App<'a, 'b> {
         )
 }
 
+fn secret_seed<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("secret-seed")
+        .about("Operations for interacting with the node's secret seed")
+}
+
+fn secret_seed_rotate<'a, 'b>() -> App<'a, 'b> {
+    AuthorizedSubCommand::with_name("rotate")
+        .about("Re-encrypt the stored account tokens with a secret derived from a new seed")
+        .arg(
+            Arg::with_name("secret_seed")
+                .index(1)
+                .takes_value(true)
+                .required(true)
+                .help("The new secret seed, as 64 hex characters; the node must be restarted with this seed afterwards"),
+        )
+}
+
 fn settlement_engines<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("settlement-engines")
         .about("Interact with the settlement engine configurations")
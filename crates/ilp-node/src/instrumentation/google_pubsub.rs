@@ -51,6 +51,9 @@ struct PacketRecord {
     next_hop_asset_code: String,
     next_hop_asset_scale: u8,
     next_hop_amount: u64,
+    /// The rate (including the spread) the exchange rate service converted the amount
+    /// from the previous hop's asset to the next hop's with, or none if the amount was 0
+    exchange_rate: Option<f64>,
     destination_ilp_address: Address,
     fulfillment: String,
     timestamp: String,
@@ -61,6 +64,8 @@ type BoxedIlpFuture = Box<dyn Future<Output = IlpResult> + Send + 'static>;
 
 /// Create an Interledger service wrapper that publishes records
 /// of fulfilled packets to Google Cloud PubSub.
+/// The records include the exchange rate applied to the packet, so the wrapper
+/// must come after the `ExchangeRateService`, which converts the amount.
 ///
 /// This is an experimental feature that may be removed in the future.
 pub fn create_google_pubsub_wrapper<A: Account + 'static>(
@@ -109,6 +114,7 @@ pub fn create_google_pubsub_wrapper<A: Account + 'static>(
         let next_hop_asset_scale = request.to.asset_scale();
         let next_hop_amount = request.prepare.amount();
         let destination_ilp_address = request.prepare.destination();
+        let exchange_rate = request.applied_rate;
 
         Box::pin(async move {
             let result = next.send_request(request).await;
@@ -140,6 +146,7 @@ pub fn create_google_pubsub_wrapper<A: Account + 'static>(
                         next_hop_asset_code,
                         next_hop_asset_scale,
                         next_hop_amount,
+                        exchange_rate,
                         destination_ilp_address,
                         fulfillment,
                         timestamp: Utc::now().to_rfc3339(),
@@ -2,7 +2,11 @@
 mod instrumentation;
 mod node;
 
+#[cfg(feature = "memory")]
+mod memory_store;
 #[cfg(feature = "redis")]
 mod redis_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
 
 pub use node::*;
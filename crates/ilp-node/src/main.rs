@@ -2,8 +2,12 @@
 mod instrumentation;
 pub mod node;
 
+#[cfg(feature = "memory")]
+mod memory_store;
 #[cfg(feature = "redis")]
 mod redis_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
 
 use clap::{crate_version, App, Arg, ArgMatches};
 use config::{Config, Source};
@@ -78,7 +82,11 @@ async fn main() {
             .alias("redis_url")
             .takes_value(true)
             .default_value("redis://127.0.0.1:6379")
-            .help("Redis URI (for example, \"redis://127.0.0.1:6379\" or \"unix:/tmp/redis.sock\")"),
+            .help("Data store URI (for example, \"redis://127.0.0.1:6379\", \"unix:/tmp/redis.sock\", \"sqlite:///var/lib/ilp/node.db\" or \"memory://\" for a store which is not persisted)"),
+        Arg::with_name("redis_key_prefix")
+            .long("redis_key_prefix")
+            .takes_value(true)
+            .help("Prefix of the Redis keys and pub/sub channels, which lets several nodes share the same Redis database. Defaults to \"ilp\". It must not be changed once the node has stored data"),
         Arg::with_name("http_bind_address")
             .long("http_bind_address")
             .takes_value(true)
@@ -129,6 +137,10 @@ async fn main() {
                 old data. For example, a value of 1000ms (1 second) would mean that the \
                 node forgets the oldest 1 second of histogram data points every second. \
                 Defaults to 10000ms (10 seconds)."),
+        Arg::with_name("migrate_only")
+            .long("migrate-only")
+            .help("Migrate the data in the store to the layout used by this version of the node and exit, \
+                without running the node. The data is also migrated whenever the node is started."),
         ]);
 
     let mut config = get_env_config("ilp");
@@ -153,6 +165,12 @@ async fn main() {
     let node = config
         .try_into::<InterledgerNode>()
         .expect("Could not parse provided configuration options into an Interledger Node config");
+    if node.migrate_only {
+        if node.migrate().await.is_err() {
+            std::process::exit(1);
+        }
+        return;
+    }
     node.serve().await.unwrap();
 
     // Add a future which is always pending. This will ensure main does not exist
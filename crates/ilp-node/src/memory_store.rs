@@ -0,0 +1,19 @@
+#![cfg(feature = "memory")]
+
+use crate::node::InterledgerNode;
+use interledger::{packet::Address, store::memory::InMemoryStoreBuilder};
+
+pub fn default_memory_url() -> String {
+    String::from("memory://")
+}
+
+// Like serve_redis_node, this lives in its own module to keep the
+// conditionally-compiled code in as few places as possible.
+// Nothing is persisted, so every node started this way begins with an empty store.
+pub async fn serve_memory_node(node: InterledgerNode, ilp_address: Address) -> Result<(), ()> {
+    let store = InMemoryStoreBuilder::new()
+        .node_ilp_address(ilp_address.clone())
+        .build();
+    // The in-memory store does not encrypt anything, so its secret does not matter
+    node.chain_services(store, ilp_address, |seed| *seed).await
+}
@@ -38,9 +38,10 @@ use interledger::{
         outgoing_service_fn, Account as AccountTrait, AccountStore, OutgoingRequest, Username,
     },
     service_util::{
-        BalanceStore, EchoService, ExchangeRateFetcher, ExchangeRateService, ExchangeRateStore,
-        ExpiryShortenerService, MaxPacketAmountService, RateLimitService, RateLimitStore,
-        ValidatorService,
+        AccountStatusService, BalanceStore, DestinationFilterService, EchoService,
+        ExchangeRateFetcher, ExchangeRateHistoryStore, ExchangeRateService, ExchangeRateStore,
+        ExpiryShortenerService, FeeService, FeeStore, MaxPacketAmountService, RateLimitService,
+        RateLimitStore, Spreads, ValidatorService,
     },
     settlement::{
         api::{create_settlements_filter, SettlementMessageService},
@@ -55,15 +56,21 @@ use interledger::{
 use lazy_static::lazy_static;
 use num_bigint::BigUint;
 use serde::{de::Error as DeserializeError, Deserialize, Deserializer};
-use std::{convert::TryFrom, net::SocketAddr, str, str::FromStr, time::Duration};
+use std::{
+    collections::HashMap, convert::TryFrom, net::SocketAddr, str, str::FromStr, time::Duration,
+};
 use tokio::spawn;
 use tracing::{debug, error, info};
 use url::Url;
 use uuid::Uuid;
 use warp::{self, Filter};
 
+#[cfg(feature = "memory")]
+use crate::memory_store::*;
 #[cfg(feature = "redis")]
 use crate::redis_store::*;
+#[cfg(feature = "sqlite")]
+use crate::sqlite_store::*;
 #[cfg(feature = "balance-tracking")]
 use interledger::service_util::BalanceService;
 
@@ -88,6 +95,10 @@ fn default_http_bind_address() -> SocketAddr {
 fn default_database_url() -> String {
     #[cfg(feature = "redis")]
     return default_redis_url();
+    #[cfg(feature = "sqlite")]
+    return default_sqlite_url();
+    #[cfg(feature = "memory")]
+    return default_memory_url();
     panic!("no backing store configured")
 }
 
@@ -129,6 +140,27 @@ where
     }
 }
 
+fn deserialize_optional_error_code<'de, D>(deserializer: D) -> Result<Option<ErrorCode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if let Ok(code) = String::deserialize(deserializer) {
+        match code.as_bytes() {
+            [class @ b'F', a, b] | [class @ b'T', a, b] | [class @ b'R', a, b]
+                if a.is_ascii_digit() && b.is_ascii_digit() =>
+            {
+                Ok(Some(ErrorCode::new([*class, *a, *b])))
+            }
+            _ => Err(DeserializeError::custom(format!(
+                "Invalid error code (must be like F02): {}",
+                code
+            ))),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
 /// Configuration for calculating exchange rates between various pairs.
 #[derive(Deserialize, Clone, Default)]
 pub struct ExchangeRateConfig {
@@ -143,6 +175,8 @@ pub struct ExchangeRateConfig {
     /// API to poll for exchange rates. Currently the supported options are:
     /// - [CoinCap](https://docs.coincap.io)
     /// - [CryptoCompare](https://cryptocompare.com) (note this requires an API key)
+    /// - Static rates, a JSON or TOML file with the rates, or any HTTP endpoint returning them as JSON
+    /// - Aggregate, which combines the rates of several of the other providers
     /// If this value is not set, the node will not poll for exchange rates and will
     /// instead use the rates configured via the HTTP API.
     #[serde(default)]
@@ -153,8 +187,20 @@ pub struct ExchangeRateConfig {
     /// For example, take an incoming packet with an amount of 100. If the
     /// exchange rate is 1:2 and the spread is 0.01, the amount on the
     /// outgoing packet would be 198 (instead of 200 without the spread).
+    /// This is the default for the asset pairs which are not in `pair_spreads`.
     #[serde(default)]
     pub spread: f64,
+    /// Spreads of specific asset pairs, keyed by the source asset code and then
+    /// the destination asset code. These apply until spreads are set via
+    /// `PUT /rates/spreads`, which are saved and used instead from then on.
+    #[serde(default)]
+    pub pair_spreads: HashMap<String, HashMap<String, f64>>,
+    /// Maximum age, in milliseconds, of the exchange rates used to convert packets.
+    /// Packets between different assets are rejected if either rate has not been
+    /// updated for longer than this. If this value is not set, rates are used
+    /// regardless of their age.
+    #[serde(default)]
+    pub max_rate_age: Option<u64>,
 }
 
 impl ExchangeRateConfig {
@@ -181,13 +227,23 @@ pub struct InterledgerNode {
     pub secret_seed: [u8; 32],
     /// HTTP Authorization token for the node admin (sent as a Bearer token)
     pub admin_auth_token: String,
-    /// Data store URI (for example, "redis://127.0.0.1:6379" or "redis+unix:/tmp/redis.sock")
+    /// Data store URI (for example, "redis://127.0.0.1:6379", "redis+unix:/tmp/redis.sock", "sqlite:///var/lib/ilp/node.db" or "memory://")
     #[serde(
         default = "default_database_url",
         // temporary alias for backwards compatibility
         alias = "redis_url"
     )]
     pub database_url: String,
+    /// Redis Sentinel to ask for the address of the Redis master instead of
+    /// connecting to the server of the `database_url`
+    #[cfg(feature = "redis")]
+    #[serde(default)]
+    pub redis_sentinel: Option<RedisSentinelConfig>,
+    /// Prefix of the Redis keys and pub/sub channels, which lets several
+    /// nodes share the same Redis database. Defaults to "ilp"
+    #[cfg(feature = "redis")]
+    #[serde(default)]
+    pub redis_key_prefix: Option<String>,
     /// IP address and port to listen for HTTP connections
     /// This is used for both the API and ILP over HTTP packets
     #[serde(default = "default_http_bind_address")]
@@ -206,6 +262,14 @@ pub struct InterledgerNode {
     #[serde(default)]
     /// Configuration for calculating exchange rates between various pairs.
     pub exchange_rate: ExchangeRateConfig,
+    /// Error code to reject packets with when the account they are from may not
+    /// send packets to their destination (for example "F00"). Defaults to "F02"
+    #[serde(default, deserialize_with = "deserialize_optional_error_code")]
+    pub destination_reject_code: Option<ErrorCode>,
+    /// Migrate the data in the store to the layout used by this version and
+    /// exit instead of running the node
+    #[serde(default)]
+    pub migrate_only: bool,
     /// Configuration for [Prometheus](https://prometheus.io) metrics collection.
     /// If this configuration is not provided, the node will not collect metrics.
     /// Needs the feature flag "monitoring" to be enabled
@@ -242,6 +306,36 @@ impl InterledgerNode {
         f.await
     }
 
+    /// Migrates the data in the store to the layout used by this version of the node.
+    /// This is also done when the node is started.
+    pub async fn migrate(self) -> Result<(), ()> {
+        match self.parse_database_url()?.scheme() {
+            #[cfg(feature = "redis")]
+            "redis" | "redis+unix" => migrate_redis_store(self).await,
+            #[cfg(feature = "sqlite")]
+            "sqlite" => migrate_sqlite_store(self).await,
+            #[cfg(feature = "memory")]
+            "memory" => {
+                info!("The in-memory store has no data to migrate");
+                Ok(())
+            }
+            other => {
+                error!("unsupported data source scheme: {}", other);
+                Err(())
+            }
+        }
+    }
+
+    // TODO: store a Url directly in InterledgerNode rather than a String?
+    fn parse_database_url(&self) -> Result<Url, ()> {
+        Url::parse(&self.database_url).map_err(|e| {
+            error!(
+                "The string '{}' could not be parsed as a URL: {}",
+                &self.database_url, e
+            )
+        })
+    }
+
     async fn serve_node(self) -> Result<(), ()> {
         let ilp_address = if let Some(address) = &self.ilp_address {
             address.clone()
@@ -249,21 +343,13 @@ impl InterledgerNode {
             DEFAULT_ILP_ADDRESS.clone()
         };
 
-        // TODO: store a Url directly in InterledgerNode rather than a String?
-        let database_url = match Url::parse(&self.database_url) {
-            Ok(url) => url,
-            Err(e) => {
-                error!(
-                    "The string '{}' could not be parsed as a URL: {}",
-                    &self.database_url, e
-                );
-                return Err(());
-            }
-        };
-
-        match database_url.scheme() {
+        match self.parse_database_url()?.scheme() {
             #[cfg(feature = "redis")]
             "redis" | "redis+unix" => serve_redis_node(self, ilp_address).await,
+            #[cfg(feature = "sqlite")]
+            "sqlite" => serve_sqlite_node(self, ilp_address).await,
+            #[cfg(feature = "memory")]
+            "memory" => serve_memory_node(self, ilp_address).await,
             other => {
                 error!("unsupported data source scheme: {}", other);
                 Err(())
@@ -271,8 +357,15 @@ impl InterledgerNode {
         }
     }
 
+    /// `store_secret_from_seed` is how the store's secret was derived from the
+    /// secret seed, which the API uses when the seed is rotated
     #[allow(clippy::cognitive_complexity)]
-    pub(crate) async fn chain_services<S>(self, store: S, ilp_address: Address) -> Result<(), ()>
+    pub(crate) async fn chain_services<S>(
+        self,
+        store: S,
+        ilp_address: Address,
+        store_secret_from_seed: fn(&[u8; 32]) -> [u8; 32],
+    ) -> Result<(), ()>
     where
         S: NodeStore<Account = Account>
             + BtpStore<Account = Account>
@@ -281,11 +374,13 @@ impl InterledgerNode {
             + BalanceStore
             + SettlementStore<Account = Account>
             + ExchangeRateStore
+            + ExchangeRateHistoryStore
             + BalanceStore
             + SettlementStore<Account = Account>
             + RouterStore<Account = Account>
             + RouteManagerStore<Account = Account>
             + RateLimitStore<Account = Account>
+            + FeeStore
             + LeftoversStore<AccountId = Uuid, AssetType = BigUint>
             + IdempotentStore
             + AccountStore<Account = Account>
@@ -310,7 +405,12 @@ impl InterledgerNode {
         let exchange_rate_provider = self.exchange_rate.provider.clone();
         let exchange_rate_poll_interval = self.exchange_rate.poll_interval;
         let exchange_rate_poll_failure_tolerance = self.exchange_rate.poll_failure_tolerance;
-        let exchange_rate_spread = self.exchange_rate.spread;
+        let exchange_rate_max_age = self.exchange_rate.max_rate_age;
+        let destination_reject_code = self.destination_reject_code;
+        let spreads = Spreads {
+            default: self.exchange_rate.spread,
+            pairs: self.exchange_rate.pair_spreads.clone(),
+        };
         #[cfg(feature = "google-pubsub")]
         let google_pubsub = self.google_pubsub.clone();
 
@@ -319,6 +419,18 @@ impl InterledgerNode {
             .map_err(|_| error!(target: "interledger-node", "Error getting accounts"))
             .await?;
 
+        if !spreads.is_valid() {
+            error!(
+                target: "interledger-node",
+                "Spreads must be fractions between 0 and 1 (inclusive)"
+            );
+            return Err(());
+        }
+        // The configured spreads apply until spreads are set via the API, which the store saves
+        store
+            .set_default_spreads(spreads)
+            .map_err(|_| error!(target: "interledger-node", "Error setting spreads"))?;
+
         let outgoing_service = outgoing_service_fn(move |request: OutgoingRequest<Account>| {
             // Don't log anything for failed route updates sent to child accounts
             // because there's a good chance they'll be offline
@@ -373,11 +485,18 @@ impl InterledgerNode {
             StreamReceiverService::new(secret_seed.clone(), store.clone(), outgoing_service);
         #[cfg(feature = "balance-tracking")]
         let outgoing_service = BalanceService::new(store.clone(), outgoing_service);
-        let outgoing_service =
-            ExchangeRateService::new(exchange_rate_spread, store.clone(), outgoing_service);
-
+        // Packets to suspended or closing accounts are rejected before touching their balances
+        let outgoing_service = AccountStatusService::outgoing(store.clone(), outgoing_service);
+        // Packet records are published with the amount forwarded to the next hop
+        // and the rate the exchange rate service converted it with
         #[cfg(feature = "google-pubsub")]
         let outgoing_service = outgoing_service.wrap(create_google_pubsub_wrapper(google_pubsub));
+        // Fees are charged on the amount after it has been converted to the outgoing asset
+        let outgoing_service = FeeService::new(store.clone(), outgoing_service);
+        let mut outgoing_service = ExchangeRateService::new(store.clone(), outgoing_service);
+        if let Some(max_rate_age) = exchange_rate_max_age {
+            outgoing_service.max_rate_age(Duration::from_millis(max_rate_age));
+        }
 
         // Add tracing to add the outgoing request details to the incoming span
         #[cfg(feature = "monitoring")]
@@ -390,6 +509,12 @@ impl InterledgerNode {
 
         // Set up the Router and Routing Manager
         let incoming_service = Router::new(store.clone(), outgoing_service_fwd);
+        // Destinations are only filtered for packets which are routed, so that
+        // the packets of the CCP, ILDCP and echo protocols are not rejected
+        let mut incoming_service = DestinationFilterService::new(store.clone(), incoming_service);
+        if let Some(reject_code) = destination_reject_code {
+            incoming_service.reject_code(reject_code);
+        }
 
         // Add tracing to track the outgoing request details
         #[cfg(feature = "monitoring")]
@@ -413,6 +538,7 @@ impl InterledgerNode {
         let incoming_service = MaxPacketAmountService::new(store.clone(), incoming_service);
         let incoming_service = ValidatorService::incoming(store.clone(), incoming_service);
         let incoming_service = RateLimitService::new(store.clone(), incoming_service);
+        let incoming_service = AccountStatusService::incoming(store.clone(), incoming_service);
 
         // Add tracing to track the incoming request details
         #[cfg(feature = "monitoring")]
@@ -471,6 +597,7 @@ impl InterledgerNode {
             api.default_spsp_account(username);
         }
         api.node_version(env!("CARGO_PKG_VERSION").to_string());
+        api.store_secret_from_seed(store_secret_from_seed);
 
         #[cfg(feature = "monitoring")]
         let incoming_service_http = incoming_service
@@ -10,7 +10,8 @@ pub use interledger::{
 };
 pub use redis_crate::{ConnectionInfo, IntoConnectionInfo};
 use ring::hmac;
-use tracing::error;
+use serde::Deserialize;
+use tracing::{error, info};
 
 static REDIS_SECRET_GENERATION_STRING: &str = "ilp_redis_secret";
 
@@ -18,19 +19,67 @@ pub fn default_redis_url() -> String {
     String::from("redis://127.0.0.1:6379")
 }
 
+/// Configuration for finding the Redis master with [Redis Sentinel](https://redis.io/topics/sentinel)
+#[derive(Deserialize, Clone, Debug)]
+pub struct RedisSentinelConfig {
+    /// The name the sentinels monitor the master under
+    pub master_name: String,
+    /// URLs of the sentinels, such as "redis://127.0.0.1:26379"
+    pub urls: Vec<String>,
+}
+
+/// Creates the store builder for the node's Redis configuration
+fn redis_store_builder(
+    node: &InterledgerNode,
+    redis_secret: [u8; 32],
+) -> Result<RedisStoreBuilder, ()> {
+    let redis_connection_info = node
+        .database_url
+        .clone()
+        .into_connection_info()
+        .map_err(|err| error!(target: "interledger-node", "Invalid Redis URL: {:?}", err))?;
+    let mut builder = RedisStoreBuilder::new(redis_connection_info, redis_secret);
+    if let Some(key_prefix) = &node.redis_key_prefix {
+        builder.key_prefix(key_prefix);
+    }
+    if let Some(sentinel) = &node.redis_sentinel {
+        let sentinels = sentinel
+            .urls
+            .iter()
+            .map(|url| url.as_str().into_connection_info())
+            .collect::<Result<Vec<ConnectionInfo>, _>>()
+            .map_err(
+                |err| error!(target: "interledger-node", "Invalid Redis Sentinel URL: {:?}", err),
+            )?;
+        builder.sentinel(sentinel.master_name.clone(), sentinels);
+    }
+    Ok(builder)
+}
+
 // This function could theoretically be defined as an inherent method on InterledgerNode itself.
 // However, we define it in this module in order to consolidate conditionally-compiled code
 // into as few discrete units as possible.
 pub async fn serve_redis_node(node: InterledgerNode, ilp_address: Address) -> Result<(), ()> {
-    let redis_connection_info = node.database_url.clone().into_connection_info().unwrap();
-    let redis_addr = redis_connection_info.addr.clone();
+    let redis_addr = node.database_url.clone();
     let redis_secret = generate_redis_secret(&node.secret_seed);
-    let store = RedisStoreBuilder::new(redis_connection_info, redis_secret)
+    let store = redis_store_builder(&node, redis_secret)?
         .node_ilp_address(ilp_address.clone())
         .connect()
         .map_err(move |err| error!(target: "interledger-node", "Error connecting to Redis: {:?} {:?}", redis_addr, err))
         .await?;
-    node.chain_services(store, ilp_address).await
+    node.chain_services(store, ilp_address, generate_redis_secret)
+        .await
+}
+
+pub async fn migrate_redis_store(node: InterledgerNode) -> Result<(), ()> {
+    let redis_addr = node.database_url.clone();
+    let redis_secret = generate_redis_secret(&node.secret_seed);
+    let version = redis_store_builder(&node, redis_secret)?
+        .migrate()
+        .map_err(move |err| error!(target: "interledger-node", "Error migrating the data in Redis: {:?} {:?}", redis_addr, err))
+        .await?;
+    info!(target: "interledger-node", "The data in Redis has schema version {}", version);
+    Ok(())
 }
 
 pub fn generate_redis_secret(secret_seed: &[u8; 32]) -> [u8; 32] {
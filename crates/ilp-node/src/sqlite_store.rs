@@ -0,0 +1,67 @@
+#![cfg(feature = "sqlite")]
+
+use crate::node::InterledgerNode;
+use interledger::{packet::Address, store::sqlite::SqliteStoreBuilder};
+use ring::hmac;
+use tracing::{error, info};
+use url::Url;
+
+static SQLITE_SECRET_GENERATION_STRING: &str = "ilp_sqlite_secret";
+
+pub fn default_sqlite_url() -> String {
+    String::from("sqlite://ilp-node.db")
+}
+
+/// Gets the database file from URLs such as `sqlite:///var/lib/ilp/node.db` (absolute path),
+/// `sqlite://node.db` (relative path) or `sqlite::memory:` (not persisted)
+fn sqlite_path(database_url: &Url) -> String {
+    format!(
+        "{}{}",
+        database_url.host_str().unwrap_or(""),
+        database_url.path()
+    )
+}
+
+// Like serve_redis_node, this lives in its own module to keep the
+// conditionally-compiled code in as few places as possible.
+pub async fn serve_sqlite_node(node: InterledgerNode, ilp_address: Address) -> Result<(), ()> {
+    let database_url = Url::parse(&node.database_url)
+        .map_err(|err| error!(target: "interledger-node", "Invalid SQLite URL: {:?}", err))?;
+    let path = sqlite_path(&database_url);
+    let sqlite_secret = generate_sqlite_secret(&node.secret_seed);
+    let store = SqliteStoreBuilder::new(path.clone(), sqlite_secret)
+        .node_ilp_address(ilp_address.clone())
+        .connect()
+        .await
+        .map_err(
+            |_| error!(target: "interledger-node", "Error opening SQLite database: {}", path),
+        )?;
+    node.chain_services(store, ilp_address, generate_sqlite_secret)
+        .await
+}
+
+/// Upgrades the tables to the schema version used by this node. Fails without
+/// changing anything if the database was written by a newer version of the node
+pub async fn migrate_sqlite_store(node: InterledgerNode) -> Result<(), ()> {
+    let database_url = Url::parse(&node.database_url)
+        .map_err(|err| error!(target: "interledger-node", "Invalid SQLite URL: {:?}", err))?;
+    let path = sqlite_path(&database_url);
+    let version = SqliteStoreBuilder::new(path.clone(), generate_sqlite_secret(&node.secret_seed))
+        .migrate()
+        .await
+        .map_err(
+            |_| error!(target: "interledger-node", "Error migrating the SQLite database: {}", path),
+        )?;
+    info!(target: "interledger-node", "The data in SQLite has schema version {}", version);
+    Ok(())
+}
+
+pub fn generate_sqlite_secret(secret_seed: &[u8; 32]) -> [u8; 32] {
+    let mut sqlite_secret: [u8; 32] = [0; 32];
+    let sig = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, secret_seed),
+        SQLITE_SECRET_GENERATION_STRING.as_bytes(),
+    );
+    sqlite_secret.copy_from_slice(sig.as_ref());
+    sqlite_secret
+}
@@ -0,0 +1,154 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+/// Separates the key's id from its secret in the tokens of API keys
+const TOKEN_SEPARATOR: char = '.';
+const SECRET_LENGTH: usize = 32;
+
+/// What an API key allows its holder to do with the account it belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKeyScope {
+    /// Get the account's details and be notified of its incoming payments
+    ReadAccount,
+    /// Get the account's balance and balance history
+    ReadBalance,
+    /// Send payments from the account
+    Pay,
+    /// Change the account's tokens, URLs and settlement thresholds
+    ModifySettings,
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string {
+            "read-account" => Ok(ApiKeyScope::ReadAccount),
+            "read-balance" => Ok(ApiKeyScope::ReadBalance),
+            "pay" => Ok(ApiKeyScope::Pay),
+            "modify-settings" => Ok(ApiKeyScope::ModifySettings),
+            _ => Err(()),
+        }
+    }
+}
+
+impl AsRef<str> for ApiKeyScope {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            ApiKeyScope::ReadAccount => "read-account",
+            ApiKeyScope::ReadBalance => "read-balance",
+            ApiKeyScope::Pay => "pay",
+            ApiKeyScope::ModifySettings => "modify-settings",
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// A key which authorizes requests to the API on behalf of an account, unlike
+/// the account's incoming ILP over HTTP token which authorizes its packets.
+/// Stores only keep a hash of the key's secret, which is returned once when the key is created.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub scopes: Vec<ApiKeyScope>,
+    /// Note to tell the keys apart, such as who the key was given to
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl ApiKey {
+    /// Creates a key for the account along with its random secret
+    pub fn generate(
+        account_id: Uuid,
+        scopes: Vec<ApiKeyScope>,
+        description: Option<String>,
+    ) -> (Self, SecretString) {
+        let mut secret = [0; SECRET_LENGTH];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("Unable to get sufficient entropy for API key");
+        let key = ApiKey {
+            id: Uuid::new_v4(),
+            account_id,
+            scopes,
+            description,
+        };
+        (key, SecretString::new(hex::encode(&secret[..])))
+    }
+
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// The bearer token of the key, which is made of its id and secret
+    pub fn token(&self, secret: &SecretString) -> SecretString {
+        SecretString::new(format!(
+            "{}{}{}",
+            self.id,
+            TOKEN_SEPARATOR,
+            secret.expose_secret()
+        ))
+    }
+}
+
+/// Splits an API key's token into the key id and secret, or returns `None`
+/// if the token is not the token of an API key
+pub fn parse_api_key_token(token: &str) -> Option<(Uuid, &str)> {
+    let mut parts = token.splitn(2, TOKEN_SEPARATOR);
+    let id = Uuid::from_str(parts.next()?).ok()?;
+    let secret = parts.next()?;
+    if secret.is_empty() {
+        None
+    } else {
+        Some((id, secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_generated_token() {
+        let (key, secret) = ApiKey::generate(Uuid::new_v4(), vec![ApiKeyScope::Pay], None);
+        let token = key.token(&secret);
+        assert_eq!(
+            parse_api_key_token(token.expose_secret()),
+            Some((key.id, secret.expose_secret().as_str()))
+        );
+        assert!(key.has_scope(ApiKeyScope::Pay));
+        assert!(!key.has_scope(ApiKeyScope::ModifySettings));
+    }
+
+    #[test]
+    fn does_not_parse_other_tokens() {
+        assert_eq!(parse_api_key_token("incoming_auth_token"), None);
+        assert_eq!(parse_api_key_token("alice:password.secret"), None);
+        assert_eq!(
+            parse_api_key_token("0a1b3c5d-0000-4000-8000-000000000000."),
+            None
+        );
+    }
+
+    #[test]
+    fn serializes_scopes_in_kebab_case() {
+        assert_eq!(
+            serde_json::to_string(&ApiKeyScope::ModifySettings).unwrap(),
+            "\"modify-settings\""
+        );
+        assert_eq!(
+            ApiKeyScope::from_str("read-balance"),
+            Ok(ApiKeyScope::ReadBalance)
+        );
+    }
+}
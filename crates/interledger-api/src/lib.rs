@@ -1,14 +1,20 @@
 use async_trait::async_trait;
 use bytes::Bytes;
 use interledger_btp::{BtpAccount, BtpOutgoingService};
-use interledger_ccp::CcpRoutingAccount;
+use interledger_ccp::{CcpRoutingAccount, RoutingRelation};
 use interledger_http::{HttpAccount, HttpStore};
 use interledger_packet::Address;
 use interledger_router::RouterStore;
-use interledger_service::{Account, AddressStore, IncomingService, OutgoingService, Username};
-use interledger_service_util::{BalanceStore, ExchangeRateStore};
-use interledger_settlement::core::types::{SettlementAccount, SettlementStore};
+use interledger_service::{
+    Account, AccountStatus, AddressStore, IncomingService, OutgoingService, Username,
+};
+use interledger_service_util::{
+    BalanceStore, ExchangeRate, ExchangeRateHistoryStore, ExchangeRateStore, FeeStore,
+    RateLimitAccount, RateLimitStore,
+};
+use interledger_settlement::core::types::{LeftoversStore, SettlementAccount, SettlementStore};
 use interledger_stream::StreamNotificationsStore;
+use num_bigint::BigUint;
 use secrecy::SecretString;
 use serde::{de, Deserialize, Serialize};
 use std::{boxed::*, collections::HashMap, fmt::Display, net::SocketAddr, str::FromStr};
@@ -16,8 +22,16 @@ use url::Url;
 use uuid::Uuid;
 use warp::{self, Filter};
 
+mod api_keys;
 pub(crate) mod http_retry;
 mod routes;
+mod snapshot;
+
+pub use api_keys::{parse_api_key_token, ApiKey, ApiKeyScope};
+pub use snapshot::{
+    AccountSnapshot, ExportedAccount, NodeSnapshot, SnapshotTokens, MIN_PASSPHRASE_LENGTH,
+    SNAPSHOT_VERSION,
+};
 
 // This enum and the following functions are used to allow clients to send either
 // numbers or strings and have them be properly deserialized into the appropriate
@@ -94,10 +108,41 @@ pub trait NodeStore: AddressStore + Clone + Send + Sync + 'static {
         settings: AccountSettings,
     ) -> Result<Self::Account, ()>;
 
-    // TODO limit the number of results and page through them
+    /// Sets the status of the account corresponding to the provided id.
+    /// The account's balance and settlement data are kept whatever its status is
+    async fn set_account_status(
+        &self,
+        id: Uuid,
+        status: AccountStatus,
+    ) -> Result<Self::Account, ()>;
+
+    /// Saves the account's new API key. Stores should only keep a hash of the secret
+    async fn insert_api_key(&self, key: ApiKey, secret: SecretString) -> Result<ApiKey, ()>;
+
+    /// Gets the API keys of the account corresponding to the provided id
+    async fn get_api_keys(&self, account_id: Uuid) -> Result<Vec<ApiKey>, ()>;
+
+    /// Deletes the account's API key corresponding to the provided id and returns it
+    async fn delete_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<ApiKey, ()>;
+
+    /// Returns the account's API key corresponding to the provided id if the secret matches
+    async fn get_api_key_from_auth(
+        &self,
+        account_id: Uuid,
+        key_id: Uuid,
+        secret: &str,
+    ) -> Result<ApiKey, ()>;
+
     /// Gets all stored accounts
     async fn get_all_accounts(&self) -> Result<Vec<Self::Account>, ()>;
 
+    /// Gets the accounts matching the query's filters, ordered by account id.
+    /// The page starts after the query's cursor and contains at most `limit` accounts.
+    async fn get_accounts_page(
+        &self,
+        query: AccountsQuery,
+    ) -> Result<AccountsPage<Self::Account>, ()>;
+
     /// Sets the static routes for routing
     async fn set_static_routes<R>(&self, routes: R) -> Result<(), ()>
     where
@@ -105,6 +150,9 @@ pub trait NodeStore: AddressStore + Clone + Send + Sync + 'static {
         // https://github.com/dtolnay/async-trait/issues/8#issuecomment-514812245
         R: IntoIterator<Item = (String, Uuid)> + Send + 'async_trait;
 
+    /// Gets the static routes, mapping ILP address prefixes to account ids
+    async fn get_static_routes(&self) -> Result<HashMap<String, Uuid>, ()>;
+
     /// Sets a single static route
     async fn set_static_route(&self, prefix: String, account_id: Uuid) -> Result<(), ()>;
 
@@ -122,12 +170,107 @@ pub trait NodeStore: AddressStore + Clone + Send + Sync + 'static {
 
     /// Gets the default settlement engine for the provided asset code
     async fn get_asset_settlement_engine(&self, asset_code: &str) -> Result<Option<Url>, ()>;
+
+    /// Gets the default settlement engines of all asset codes
+    async fn get_settlement_engines(&self) -> Result<HashMap<String, Url>, ()>;
+
+    /// Gets every account's details along with its tokens as they are stored,
+    /// so that the accounts can be imported into another store with `import_account`
+    async fn export_accounts(&self) -> Result<Vec<ExportedAccount>, ()>;
+
+    /// Inserts an account which was exported from another store, keeping its id.
+    /// Unlike `insert_account`, incoming tokens which were hashed are stored as they are
+    async fn import_account(&self, account: ExportedAccount) -> Result<Self::Account, ()>;
+
+    /// Re-encrypts every account's tokens with keys derived from the new secret,
+    /// which the store then uses instead of its current secret.
+    /// Accounts must remain usable while the tokens are being re-encrypted, also
+    /// by the other stores sharing the same database, which switch to the new secret.
+    async fn rotate_secret(&self, new_secret: [u8; 32]) -> Result<(), ()>;
+}
+
+/// Cursor and filters used to page through the accounts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountsQuery {
+    /// Only return accounts whose id comes after this one (the `next_cursor` of the previous page)
+    pub cursor: Option<Uuid>,
+    /// The maximum number of accounts to return. All matching accounts are returned if none is set
+    pub limit: Option<usize>,
+    /// Only return accounts with this asset code
+    pub asset_code: Option<String>,
+    /// Only return accounts with this routing relation
+    pub routing_relation: Option<RoutingRelation>,
+    /// Only return accounts whose username starts with this prefix
+    pub username_prefix: Option<String>,
 }
 
+impl AccountsQuery {
+    /// Checks whether the account passes the query's filters (the cursor is not checked)
+    pub fn matches<A: CcpRoutingAccount>(&self, account: &A) -> bool {
+        if let Some(ref asset_code) = self.asset_code {
+            if !account.asset_code().eq_ignore_ascii_case(asset_code) {
+                return false;
+            }
+        }
+        if let Some(routing_relation) = self.routing_relation {
+            if account.routing_relation() != routing_relation {
+                return false;
+            }
+        }
+        if let Some(ref prefix) = self.username_prefix {
+            // Usernames are compared case-insensitively
+            if !account
+                .username()
+                .to_lowercase()
+                .starts_with(&prefix.to_lowercase())
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One page of the accounts returned for an `AccountsQuery`
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountsPage<A> {
+    pub accounts: Vec<A>,
+    /// The cursor to pass in the next query, or `None` if this is the last page
+    pub next_cursor: Option<Uuid>,
+}
+
+impl<A: CcpRoutingAccount> AccountsPage<A> {
+    /// Builds the page from accounts which are already sorted by id,
+    /// applying the query's cursor, filters and limit
+    pub fn from_sorted_accounts(
+        accounts: impl IntoIterator<Item = A>,
+        query: &AccountsQuery,
+    ) -> Self {
+        let limit = query.limit.unwrap_or(usize::max_value());
+        // Take one more account than needed to know whether there is a next page
+        let mut accounts: Vec<A> = accounts
+            .into_iter()
+            .filter(|account| query.cursor.map_or(true, |cursor| account.id() > cursor))
+            .filter(|account| query.matches(account))
+            .take(limit.saturating_add(1))
+            .collect();
+        let next_cursor = if accounts.len() > limit {
+            accounts.truncate(limit);
+            accounts.last().map(|account| account.id())
+        } else {
+            None
+        };
+        AccountsPage {
+            accounts,
+            next_cursor,
+        }
+    }
+}
+
+/// Asset code -> rate, where the rates may be decimal strings (which are kept exactly)
+/// or numbers
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ExchangeRates(
-    #[serde(deserialize_with = "map_of_number_or_string")] HashMap<String, f64>,
-);
+pub struct ExchangeRates(HashMap<String, ExchangeRate>);
 
 /// AccountSettings is a subset of the user parameters defined in
 /// AccountDetails. Its purpose is to allow a user to modify certain of their
@@ -161,8 +304,8 @@ pub struct AccountSettings {
     pub settle_to: Option<u64>,
 }
 
-/// EncryptedAccountSettings is created by encrypting the incoming and outgoing
-/// HTTP and BTP tokens of an AccountSettings object. The rest of the fields
+/// EncryptedAccountSettings is created by hashing the incoming and encrypting the
+/// outgoing HTTP and BTP tokens of an AccountSettings object. The rest of the fields
 /// remain the same. It is intended to be consumed by the internal store
 /// implementation which operates only on encrypted data.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -187,6 +330,11 @@ pub struct AccountDetails {
     /// The account's Interledger Protocol address.
     /// If none is provided, the node should generate one
     pub ilp_address: Option<Address>,
+    /// Other address prefixes which are routed to the account, such as
+    /// those of a child which owns several prefixes. They may not be routed
+    /// to another account already, nor cover the node's own address
+    #[serde(default)]
+    pub additional_prefixes: Vec<Address>,
     /// The account's username
     pub username: Username,
     /// The account's currency
@@ -240,6 +388,35 @@ pub struct AccountDetails {
     /// The limit of packets the account can send per minute
     #[serde(default, deserialize_with = "optional_number_or_string")]
     pub packets_per_minute_limit: Option<u32>,
+    /// The maximum amount the account can send at once before it is limited
+    /// to its amount per minute. Must be at least the amount per minute limit,
+    /// which it defaults to
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub amount_burst_limit: Option<u64>,
+    /// The number of packets the account can send at once before it is limited
+    /// to its packets per minute. Must be at least the packets per minute limit,
+    /// which it defaults to
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub packets_burst_limit: Option<u32>,
+    /// The maximum amount the account can send per calendar day (in UTC)
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub amount_per_day_limit: Option<u64>,
+    /// The maximum amount the account can send per calendar month (in UTC)
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub amount_per_month_limit: Option<u64>,
+    /// Fixed fee deducted from every packet forwarded to the account
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub fixed_fee: Option<u64>,
+    /// Fee deducted from every packet forwarded to the account, in basis points of its amount
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    pub fee_basis_points: Option<u32>,
+    /// Address prefixes the account may send packets to. If there are none,
+    /// it may send packets to any destination which is not denied
+    #[serde(default)]
+    pub allowed_destination_prefixes: Vec<String>,
+    /// Address prefixes the account may not send packets to
+    #[serde(default)]
+    pub denied_destination_prefixes: Vec<String>,
     /// The account's settlement engine URL. If a global engine url is configured
     /// for the account's asset code,  that will be used instead (even if the account is
     /// configured with a specific one)
@@ -262,6 +439,8 @@ pub struct NodeApi<S, I, O, B, A: Account> {
     /// Server secret used to instantiate SPSP/Stream connections
     server_secret: Bytes,
     node_version: Option<String>,
+    /// Derives the store's secret from a secret seed when the seed is rotated
+    store_secret_from_seed: fn(&[u8; 32]) -> [u8; 32],
 }
 
 impl<S, I, O, B, A> NodeApi<S, I, O, B, A>
@@ -271,8 +450,12 @@ where
         + BalanceStore<Account = A>
         + SettlementStore<Account = A>
         + StreamNotificationsStore<Account = A>
+        + RateLimitStore<Account = A>
+        + LeftoversStore<AccountId = Uuid, AssetType = BigUint>
         + RouterStore
-        + ExchangeRateStore,
+        + ExchangeRateStore
+        + ExchangeRateHistoryStore
+        + FeeStore,
     I: IncomingService<A> + Clone + Send + Sync + 'static,
     O: OutgoingService<A> + Clone + Send + Sync + 'static,
     B: OutgoingService<A> + Clone + Send + Sync + 'static,
@@ -280,6 +463,7 @@ where
         + CcpRoutingAccount
         + Account
         + HttpAccount
+        + RateLimitAccount
         + SettlementAccount
         + Serialize
         + Send
@@ -303,6 +487,7 @@ where
             btp,
             server_secret,
             node_version: None,
+            store_secret_from_seed: |seed| *seed,
         }
     }
 
@@ -320,6 +505,14 @@ where
         self
     }
 
+    /// Sets how the store's secret is derived from the node's secret seed, so that
+    /// the store can re-encrypt its data when the seed is rotated via `PUT /secret_seed`.
+    /// By default, the seed itself is used as the store's secret.
+    pub fn store_secret_from_seed(&mut self, derive: fn(&[u8; 32]) -> [u8; 32]) -> &mut Self {
+        self.store_secret_from_seed = derive;
+        self
+    }
+
     /// Returns a Warp Filter which exposes the accounts and admin APIs
     pub fn into_warp_filter(self) -> warp::filters::BoxedFilter<(impl warp::Reply,)> {
         routes::accounts_api(
@@ -334,6 +527,7 @@ where
         .or(routes::node_settings_api(
             self.admin_api_token,
             self.node_version,
+            self.store_secret_from_seed,
             self.store,
         ))
         .boxed()
@@ -1,4 +1,7 @@
-use crate::{http_retry::Client, number_or_string, AccountDetails, AccountSettings, NodeStore};
+use crate::{
+    http_retry::Client, number_or_string, parse_api_key_token, AccountDetails, AccountSettings,
+    AccountsQuery, ApiKey, ApiKeyScope, NodeStore,
+};
 use bytes::Bytes;
 use futures::{future::join_all, Future, FutureExt, StreamExt, TryFutureExt};
 use interledger_btp::{connect_to_service_account, BtpAccount, BtpOutgoingService};
@@ -8,9 +11,13 @@ use interledger_ildcp::IldcpRequest;
 use interledger_ildcp::IldcpResponse;
 use interledger_router::RouterStore;
 use interledger_service::{
-    Account, AddressStore, IncomingService, OutgoingRequest, OutgoingService, Username,
+    Account, AccountStatus, AddressStore, IncomingService, OutgoingRequest, OutgoingService,
+    Username,
+};
+use interledger_service_util::{
+    AmountLimitPeriod, BalanceHistoryQuery, BalanceStore, ExchangeRateStore, RateLimitAccount,
+    RateLimitStore,
 };
-use interledger_service_util::{BalanceStore, ExchangeRateStore};
 use interledger_settlement::core::types::SettlementAccount;
 use interledger_spsp::{pay, SpspResponder};
 use interledger_stream::{PaymentNotification, StreamNotificationsStore};
@@ -18,11 +25,100 @@ use log::{debug, error, trace};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::convert::TryFrom;
+use std::{convert::TryFrom, str::FromStr};
 use uuid::Uuid;
 use warp::{self, reply::Json, Filter, Rejection};
 
 pub const BEARER_TOKEN_START: usize = 7;
+/// Number of accounts returned by `GET /accounts` if the request sets a cursor but no limit
+const DEFAULT_ACCOUNTS_PAGE_LIMIT: usize = 100;
+/// Upper bound for the `limit` of `GET /accounts`
+const MAX_ACCOUNTS_PAGE_LIMIT: usize = 1000;
+
+#[derive(Deserialize, Debug)]
+struct GetAccountsQuery {
+    cursor: Option<Uuid>,
+    limit: Option<usize>,
+    asset_code: Option<String>,
+    routing_relation: Option<String>,
+    username_prefix: Option<String>,
+}
+
+impl TryFrom<GetAccountsQuery> for AccountsQuery {
+    type Error = ();
+
+    fn try_from(query: GetAccountsQuery) -> Result<Self, ()> {
+        let routing_relation = match query.routing_relation {
+            Some(ref relation) => Some(RoutingRelation::from_str(relation)?),
+            None => None,
+        };
+        // Without a limit or cursor all matching accounts are returned, as before paging existed
+        let limit = match (query.limit, query.cursor) {
+            (Some(0), _) => return Err(()),
+            (Some(limit), _) => Some(limit.min(MAX_ACCOUNTS_PAGE_LIMIT)),
+            (None, Some(_)) => Some(DEFAULT_ACCOUNTS_PAGE_LIMIT),
+            (None, None) => None,
+        };
+        Ok(AccountsQuery {
+            cursor: query.cursor,
+            limit,
+            asset_code: query.asset_code,
+            routing_relation,
+            username_prefix: query.username_prefix,
+        })
+    }
+}
+
+/// Number of entries returned by `GET /accounts/:username/balance/history`
+/// if the request does not set a limit
+const DEFAULT_BALANCE_HISTORY_LIMIT: usize = 100;
+/// Upper bound for the `limit` of `GET /accounts/:username/balance/history`
+const MAX_BALANCE_HISTORY_LIMIT: usize = 1000;
+
+/// Time range of `GET /accounts/:username/balance/history`, in milliseconds
+/// since the UNIX epoch, and the page of entries within it
+#[derive(Deserialize, Debug)]
+struct GetBalanceHistoryQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl TryFrom<GetBalanceHistoryQuery> for BalanceHistoryQuery {
+    type Error = ();
+
+    fn try_from(query: GetBalanceHistoryQuery) -> Result<Self, ()> {
+        let from = query.from.unwrap_or(0);
+        let to = query.to.unwrap_or(std::u64::MAX);
+        if from > to {
+            return Err(());
+        }
+        let limit = match query.limit {
+            Some(0) => return Err(()),
+            Some(limit) => limit.min(MAX_BALANCE_HISTORY_LIMIT),
+            None => DEFAULT_BALANCE_HISTORY_LIMIT,
+        };
+        Ok(BalanceHistoryQuery {
+            from,
+            to,
+            cursor: query.cursor,
+            limit,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountStatusRequest {
+    status: AccountStatus,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiKeyRequest {
+    scopes: Vec<ApiKeyScope>,
+    #[serde(default)]
+    description: Option<String>,
+}
 
 #[derive(Deserialize, Debug)]
 struct SpspPayRequest {
@@ -48,6 +144,7 @@ where
         + HttpStore<Account = A>
         + BalanceStore<Account = A>
         + StreamNotificationsStore<Account = A>
+        + RateLimitStore<Account = A>
         + ExchangeRateStore
         + RouterStore,
     A: BtpAccount
@@ -55,6 +152,7 @@ where
         + SettlementAccount
         + Account
         + HttpAccount
+        + RateLimitAccount
         + Serialize
         + Send
         + Sync
@@ -101,74 +199,117 @@ where
         })
         .boxed();
 
-    let is_authorized_user = move |store: S, path_username: Username, auth_string: SecretString| {
-        async move {
-            if auth_string.expose_secret().len() < BEARER_TOKEN_START {
-                return Err(Rejection::from(ApiError::bad_request()));
-            }
+    // Checks the API key of the account or, if the account has no API keys, its
+    // incoming ILP over HTTP token, which authorized every request before there were API keys
+    let is_authorized_user =
+        move |store: S, path_username: Username, auth_string: SecretString, scope: ApiKeyScope| {
+            async move {
+                if auth_string.expose_secret().len() < BEARER_TOKEN_START {
+                    return Err(Rejection::from(ApiError::bad_request()));
+                }
+                let token = &auth_string.expose_secret()[BEARER_TOKEN_START..];
 
-            // Try getting the account from the store
-            let authorized_account = store
-                .get_account_from_http_auth(
-                    &path_username,
-                    &auth_string.expose_secret()[BEARER_TOKEN_START..],
-                )
-                .map_err(|_| Rejection::from(ApiError::unauthorized()))
-                .await?;
+                if let Some((key_id, secret)) = parse_api_key_token(token) {
+                    let account_id = store
+                        .get_account_id_from_username(&path_username)
+                        .map_err(|_| Rejection::from(ApiError::unauthorized()))
+                        .await?;
+                    let key = store
+                        .get_api_key_from_auth(account_id, key_id, secret)
+                        .map_err(|_| Rejection::from(ApiError::unauthorized()))
+                        .await?;
+                    if !key.has_scope(scope) {
+                        return Err(ApiError::unauthorized()
+                            .detail(format!("The API key does not have the {} scope", scope))
+                            .into());
+                    }
+                    let mut accounts = store
+                        .get_accounts(vec![account_id])
+                        .map_err(|_| Rejection::from(ApiError::account_not_found()))
+                        .await?;
+                    return accounts
+                        .pop()
+                        .ok_or_else(|| Rejection::from(ApiError::account_not_found()));
+                }
 
-            // Only return the account if the provided username matched the fetched one
-            // This maybe is redundant?
-            if &path_username == authorized_account.username() {
-                Ok(authorized_account)
-            } else {
-                Err(ApiError::unauthorized().into())
-            }
-        }
-    };
+                // Try getting the account from the store
+                let authorized_account = store
+                    .get_account_from_http_auth(&path_username, token)
+                    .map_err(|_| Rejection::from(ApiError::unauthorized()))
+                    .await?;
 
-    // Checks if the account is an admin or if they have provided a valid password
-    let admin_or_authorized_user_only = warp::path::param::<Username>()
-        .and(warp::header::<SecretString>("authorization"))
-        .and(with_store.clone())
-        .and(with_admin_auth_header)
-        .and_then(
-            move |path_username: Username,
-                  auth_string: SecretString,
-                  store: S,
-                  admin_auth_header: String| {
-                async move {
-                    // If it's an admin, there's no need for more checks
-                    if auth_string.expose_secret() == &admin_auth_header {
-                        let account_id = store
-                            .get_account_id_from_username(&path_username)
-                            .map_err(|_| {
-                                // TODO differentiate between server error and not found
-                                error!("Error getting account id from username: {}", path_username);
-                                Rejection::from(ApiError::account_not_found())
-                            })
-                            .await?;
-                        return Ok(account_id);
-                    }
-                    let account = is_authorized_user(store, path_username, auth_string).await?;
-                    Ok::<Uuid, Rejection>(account.id())
+                // Only return the account if the provided username matched the fetched one
+                // This maybe is redundant?
+                if &path_username != authorized_account.username() {
+                    return Err(ApiError::unauthorized().into());
                 }
-            },
-        )
-        .boxed();
 
-    // Checks if the account has provided a valid password (same as admin-or-auth call, minus one call, can we refactor them together?)
-    let authorized_user_only = warp::path::param::<Username>()
-        .and(warp::header::<SecretString>("authorization"))
-        .and(with_store.clone())
-        .and_then(
-            move |path_username: Username, auth_string: SecretString, store: S| {
-                async move {
-                    let account = is_authorized_user(store, path_username, auth_string).await?;
-                    Ok::<A, Rejection>(account)
+                let api_keys = store
+                    .get_api_keys(authorized_account.id())
+                    .map_err(|_| Rejection::from(ApiError::internal_server_error()))
+                    .await?;
+                if api_keys.is_empty() {
+                    Ok(authorized_account)
+                } else {
+                    Err(ApiError::unauthorized()
+                        .detail("The account must use one of its API keys")
+                        .into())
                 }
-            },
-        )
-        .boxed();
+            }
+        };
+
+    // Checks if the account is an admin or if they have provided a valid API key with the scope
+    let with_store_clone = with_store.clone();
+    let admin_or_authorized_user_only = move |scope: ApiKeyScope| {
+        warp::path::param::<Username>()
+            .and(warp::header::<SecretString>("authorization"))
+            .and(with_store_clone.clone())
+            .and(with_admin_auth_header.clone())
+            .and_then(
+                move |path_username: Username,
+                      auth_string: SecretString,
+                      store: S,
+                      admin_auth_header: String| {
+                    async move {
+                        // If it's an admin, there's no need for more checks
+                        if auth_string.expose_secret() == &admin_auth_header {
+                            let account_id = store
+                                .get_account_id_from_username(&path_username)
+                                .map_err(|_| {
+                                    // TODO differentiate between server error and not found
+                                    error!(
+                                        "Error getting account id from username: {}",
+                                        path_username
+                                    );
+                                    Rejection::from(ApiError::account_not_found())
+                                })
+                                .await?;
+                            return Ok(account_id);
+                        }
+                        let account =
+                            is_authorized_user(store, path_username, auth_string, scope).await?;
+                        Ok::<Uuid, Rejection>(account.id())
+                    }
+                },
+            )
+            .boxed()
+    };
+
+    // Checks if the account has provided a valid API key with the scope (same as admin-or-auth call, minus one call, can we refactor them together?)
+    let with_store_clone = with_store.clone();
+    let authorized_user_only = move |scope: ApiKeyScope| {
+        warp::path::param::<Username>()
+            .and(warp::header::<SecretString>("authorization"))
+            .and(with_store_clone.clone())
+            .and_then(
+                move |path_username: Username, auth_string: SecretString, store: S| async move {
+                    let account =
+                        is_authorized_user(store, path_username, auth_string, scope).await?;
+                    Ok::<A, Rejection>(account)
+                },
+            )
+            .boxed()
+    };
 
     // POST /accounts
     let btp_clone = btp.clone();
@@ -204,14 +345,21 @@ where
         .and(warp::path("accounts"))
         .and(warp::path::end())
         .and(admin_only.clone())
+        .and(warp::query::<GetAccountsQuery>())
         .and(with_store.clone())
-        .and_then(|store: S| {
-            async move {
-                let accounts = store
-                    .get_all_accounts()
-                    .map_err(|_| Rejection::from(ApiError::internal_server_error()))
-                    .await?;
-                Ok::<Json, Rejection>(warp::reply::json(&accounts))
+        .and_then(|query: GetAccountsQuery, store: S| async move {
+            // Requests without paging parameters get the plain array of accounts
+            let paginated = query.limit.is_some() || query.cursor.is_some();
+            let query = AccountsQuery::try_from(query)
+                .map_err(|_| Rejection::from(ApiError::bad_request()))?;
+            let page = store
+                .get_accounts_page(query)
+                .map_err(|_| Rejection::from(ApiError::internal_server_error()))
+                .await?;
+            if paginated {
+                Ok::<Json, Rejection>(warp::reply::json(&page))
+            } else {
+                Ok::<Json, Rejection>(warp::reply::json(&page.accounts))
             }
         })
         .boxed();
@@ -243,18 +391,16 @@ where
     let get_account = warp::get()
         .and(warp::path("accounts"))
         // takes the username and the authorization header and checks if it's authorized, returns the uid
-        .and(admin_or_authorized_user_only.clone())
+        .and(admin_or_authorized_user_only(ApiKeyScope::ReadAccount))
         .and(warp::path::end())
         .and(with_store.clone())
-        .and_then(|id: Uuid, store: S| {
-            async move {
-                let accounts = store
-                    .get_accounts(vec![id])
-                    .map_err(|_| Rejection::from(ApiError::account_not_found()))
-                    .await?;
+        .and_then(|id: Uuid, store: S| async move {
+            let accounts = store
+                .get_accounts(vec![id])
+                .map_err(|_| Rejection::from(ApiError::account_not_found()))
+                .await?;
 
-                Ok::<Json, Rejection>(warp::reply::json(&accounts[0]))
-            }
+            Ok::<Json, Rejection>(warp::reply::json(&accounts[0]))
         })
         .boxed();
 
@@ -262,7 +408,7 @@ where
     let get_account_balance = warp::get()
         .and(warp::path("accounts"))
         // takes the username and the authorization header and checks if it's authorized, returns the uid
-        .and(admin_or_authorized_user_only.clone())
+        .and(admin_or_authorized_user_only(ApiKeyScope::ReadBalance))
         .and(warp::path("balance"))
         .and(warp::path::end())
         .and(with_store.clone())
@@ -285,10 +431,72 @@ where
 
                 let asset_scale = account.asset_scale();
                 let asset_code = account.asset_code().to_owned();
-                Ok::<Json, Rejection>(warp::reply::json(&json!({
-                    // normalize to the base unit
-                    "balance": balance as f64 / 10_u64.pow(asset_scale.into()) as f64,
+                // normalize to the base unit
+                let scale = 10_u64.pow(asset_scale.into()) as f64;
+                let mut body = json!({
+                    "balance": balance as f64 / scale,
                     "asset_code": asset_code,
+                });
+
+                // The daily and monthly limits are only included if the account has them
+                if account.amount_per_day_limit().is_some()
+                    || account.amount_per_month_limit().is_some()
+                {
+                    let sent = store
+                        .get_cumulative_amounts(account.clone())
+                        .map_err(move |_| {
+                            error!("Error getting amounts sent by account: {}", id);
+                            Rejection::from(ApiError::internal_server_error())
+                        })
+                        .await?;
+                    for period in AmountLimitPeriod::ALL.iter() {
+                        if let Some(limit) = account.amount_limit(*period) {
+                            body[format!("amount_per_{}_limit", period.as_ref())] = json!({
+                                "limit": limit as f64 / scale,
+                                "sent": sent.get(*period) as f64 / scale,
+                            });
+                        }
+                    }
+                }
+                Ok::<Json, Rejection>(warp::reply::json(&body))
+            }
+        })
+        .boxed();
+
+    // GET /accounts/:username/balance/history
+    let get_account_balance_history = warp::get()
+        .and(warp::path("accounts"))
+        .and(admin_or_authorized_user_only(ApiKeyScope::ReadBalance))
+        .and(warp::path("balance"))
+        .and(warp::path("history"))
+        .and(warp::path::end())
+        .and(warp::query::<GetBalanceHistoryQuery>())
+        .and(with_store.clone())
+        .and_then(|id: Uuid, query: GetBalanceHistoryQuery, store: S| {
+            async move {
+                let query = BalanceHistoryQuery::try_from(query)
+                    .map_err(|_| Rejection::from(ApiError::bad_request()))?;
+                let mut accounts = store
+                    .get_accounts(vec![id])
+                    .map_err(|_| warp::reject::not_found())
+                    .await?;
+                let account = accounts.pop().unwrap();
+
+                let page = store
+                    .get_balance_history(account.clone(), query)
+                    .map_err(move |_| {
+                        error!("Error getting balance history for account: {}", id);
+                        Rejection::from(ApiError::internal_server_error())
+                    })
+                    .await?;
+
+                // Unlike the balance, the amounts are in the account's smallest unit
+                // so that they add up exactly
+                Ok::<Json, Rejection>(warp::reply::json(&json!({
+                    "asset_code": account.asset_code(),
+                    "asset_scale": account.asset_scale(),
+                    "entries": page.entries,
+                    "next_cursor": page.next_cursor,
                 })))
             }
         })
@@ -299,47 +507,141 @@ where
         .and(warp::path("accounts"))
         .and(account_username_to_id.clone())
         .and(warp::path::end())
-        .and(admin_only)
+        .and(admin_only.clone())
         .and(with_store.clone())
-        .and_then(|id: Uuid, store: S| {
-            async move {
+        .and_then(|id: Uuid, store: S| async move {
+            let account = store
+                .delete_account(id)
+                .map_err(|_| {
+                    error!("Error deleting account {}", id);
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&account))
+        })
+        .boxed();
+
+    // PUT /accounts/:username/status
+    let put_account_status = warp::put()
+        .and(warp::path("accounts"))
+        .and(account_username_to_id.clone())
+        .and(warp::path("status"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(
+            |id: Uuid, request: AccountStatusRequest, store: S| async move {
                 let account = store
-                    .delete_account(id)
-                    .map_err(|_| {
-                        error!("Error deleting account {}", id);
+                    .set_account_status(id, request.status)
+                    .map_err(move |_| {
+                        error!("Error setting the status of account {}", id);
                         Rejection::from(ApiError::internal_server_error())
                     })
                     .await?;
                 Ok::<Json, Rejection>(warp::reply::json(&account))
-            }
+            },
+        )
+        .boxed();
+
+    // GET /accounts/:username/api-keys
+    let get_api_keys = warp::get()
+        .and(warp::path("accounts"))
+        .and(account_username_to_id.clone())
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(with_store.clone())
+        .and_then(|id: Uuid, store: S| async move {
+            let keys = store
+                .get_api_keys(id)
+                .map_err(move |_| {
+                    error!("Error getting the API keys of account {}", id);
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&keys))
         })
         .boxed();
 
-    // PUT /accounts/:username/settings
-    let put_account_settings = warp::put()
+    // POST /accounts/:username/api-keys
+    let post_api_keys = warp::post()
         .and(warp::path("accounts"))
-        .and(admin_or_authorized_user_only.clone())
-        .and(warp::path("settings"))
+        .and(account_username_to_id.clone())
+        .and(warp::path("api-keys"))
         .and(warp::path::end())
+        .and(admin_only.clone())
         .and(deserialize_json())
         .and(with_store.clone())
-        .and_then(|id: Uuid, settings: AccountSettings, store: S| {
+        .and_then(|id: Uuid, request: ApiKeyRequest, store: S| {
             async move {
-                let modified_account = store
-                    .modify_account_settings(id, settings)
+                if request.scopes.is_empty() {
+                    return Err(Rejection::from(
+                        ApiError::bad_request().detail("API keys must have at least one scope"),
+                    ));
+                }
+                let (key, secret) = ApiKey::generate(id, request.scopes, request.description);
+                let token = key.token(&secret);
+                let key = store
+                    .insert_api_key(key, secret)
                     .map_err(move |_| {
-                        error!("Error updating account settings {}", id);
+                        error!("Error inserting an API key for account {}", id);
                         Rejection::from(ApiError::internal_server_error())
                     })
                     .await?;
-                Ok::<Json, Rejection>(warp::reply::json(&modified_account))
+                // This is the only time the token is returned, since stores only keep a hash of it
+                let mut response = serde_json::to_value(&key)
+                    .map_err(|_| Rejection::from(ApiError::internal_server_error()))?;
+                response["token"] = json!(token.expose_secret());
+                Ok::<Json, Rejection>(warp::reply::json(&response))
             }
         })
         .boxed();
 
+    // DELETE /accounts/:username/api-keys/:id
+    let delete_api_key = warp::delete()
+        .and(warp::path("accounts"))
+        .and(account_username_to_id.clone())
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path::end())
+        .and(admin_only)
+        .and(with_store.clone())
+        .and_then(|id: Uuid, key_id: Uuid, store: S| async move {
+            let key = store
+                .delete_api_key(id, key_id)
+                .map_err(move |_| {
+                    error!("Error deleting API key {} of account {}", key_id, id);
+                    Rejection::from(ApiError::not_found())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&key))
+        })
+        .boxed();
+
+    // PUT /accounts/:username/settings
+    let put_account_settings = warp::put()
+        .and(warp::path("accounts"))
+        .and(admin_or_authorized_user_only(ApiKeyScope::ModifySettings))
+        .and(warp::path("settings"))
+        .and(warp::path::end())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(|id: Uuid, settings: AccountSettings, store: S| async move {
+            let modified_account = store
+                .modify_account_settings(id, settings)
+                .map_err(move |_| {
+                    error!("Error updating account settings {}", id);
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&modified_account))
+        })
+        .boxed();
+
     // (Websocket) /accounts/:username/payments/incoming
     let incoming_payment_notifications = warp::path("accounts")
-        .and(admin_or_authorized_user_only)
+        .and(admin_or_authorized_user_only(ApiKeyScope::ReadAccount))
         .and(warp::path("payments"))
         .and(warp::path("incoming"))
         .and(warp::path::end())
@@ -355,7 +657,7 @@ where
     // POST /accounts/:username/payments
     let post_payments = warp::post()
         .and(warp::path("accounts"))
-        .and(authorized_user_only)
+        .and(authorized_user_only(ApiKeyScope::Pay))
         .and(warp::path("payments"))
         .and(warp::path::end())
         .and(deserialize_json())
@@ -462,9 +764,17 @@ where
         .or(delete_account)
         .or(get_account)
         .or(get_account_balance)
+        .or(get_account_balance_history)
+        .or(put_account_status)
+        .or(get_api_keys)
+        .or(delete_api_key)
         .or(put_account_settings)
         .or(incoming_payment_notifications)
         .or(post_payments)
+        // post_payments rejects admin requests as unauthorized before checking the path.
+        // Rejections from later filters take precedence, so this must come after it
+        // for its own errors (such as a request without scopes) to be returned
+        .or(post_api_keys)
         .boxed()
 }
 
@@ -516,6 +826,7 @@ where
             to: parent.clone(),
             prepare,
             original_amount: 0,
+            applied_rate: None,
         })
         .map_err(|err| error!("Error getting ILDCP info: {:?}", err))
         .await?;
@@ -554,6 +865,7 @@ where
                     to: parent.clone(),
                     original_amount: prepare.amount(),
                     prepare: prepare.clone(),
+                    applied_rate: None,
                 })
                 .map_err(|_| ())
                 .map_ok(|_| ()),
@@ -681,6 +993,108 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 401);
     }
 
+    #[tokio::test]
+    async fn only_admin_can_set_account_status() {
+        let api = test_accounts_api();
+        let status = Some(serde_json::json!({ "status": "suspended" }));
+        let resp = api_call(
+            &api,
+            "PUT",
+            "/accounts/alice/status",
+            "admin",
+            status.clone(),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "PUT", "/accounts/alice/status", "wrong", status).await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let resp = api_call(
+            &api,
+            "PUT",
+            "/accounts/alice/status",
+            "admin",
+            Some(serde_json::json!({ "status": "deleted" })),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_manage_api_keys() {
+        let api = test_accounts_api();
+        let request = Some(serde_json::json!({ "scopes": ["read-balance", "pay"] }));
+        let resp = api_call(
+            &api,
+            "POST",
+            "/accounts/alice/api-keys",
+            "admin",
+            request.clone(),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let key: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(key["token"]
+            .as_str()
+            .unwrap()
+            .starts_with(key["id"].as_str().unwrap()));
+
+        let resp = api_call(
+            &api,
+            "POST",
+            "/accounts/alice/api-keys",
+            "password",
+            request,
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let resp = api_call(
+            &api,
+            "POST",
+            "/accounts/alice/api-keys",
+            "admin",
+            Some(serde_json::json!({ "scopes": [] })),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let resp = api_call(&api, "GET", "/accounts/alice/api-keys", "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let endpoint = format!("/accounts/alice/api-keys/{}", API_KEY_ID);
+        let resp = api_call(&api, "DELETE", &endpoint, "password", None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+        let resp = api_call(&api, "DELETE", &endpoint, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+    }
+
+    #[tokio::test]
+    async fn api_keys_only_authorize_their_scopes() {
+        let api = test_accounts_api();
+        let token = format!("{}.secret", API_KEY_ID);
+        let resp = api_call(&api, "GET", "/accounts/alice/balance", &token, None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "GET", "/accounts/alice", &token, None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let resp = api_call(
+            &api,
+            "PUT",
+            "/accounts/alice/settings",
+            &token,
+            Some(serde_json::json!({ "settle_to": 0 })),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let wrong_secret = format!("{}.wrong", API_KEY_ID);
+        let resp = api_call(&api, "GET", "/accounts/alice/balance", wrong_secret, None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
     #[tokio::test]
     async fn only_admin_can_get_all_accounts() {
         let api = test_accounts_api();
@@ -691,6 +1105,39 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 401);
     }
 
+    #[tokio::test]
+    async fn get_accounts_only_returns_a_page_if_asked_for_one() {
+        let api = test_accounts_api();
+        let resp = api_call(&api, "GET", "/accounts", "admin", None).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let resp = api_call(&api, "GET", "/accounts?limit=2", "admin", None).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["accounts"].as_array().unwrap().len(), 2);
+        assert!(body["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn get_accounts_with_filters() {
+        let api = test_accounts_api();
+        let resp = api_call(
+            &api,
+            "GET",
+            "/accounts?limit=1&asset_code=XYZ&routing_relation=child&username_prefix=al",
+            "admin",
+            None,
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "GET", "/accounts?routing_relation=foo", "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let resp = api_call(&api, "GET", "/accounts?limit=0", "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
     #[tokio::test]
     async fn only_admin_or_user_can_get_account() {
         let api = test_accounts_api();
@@ -719,6 +1166,45 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 401);
     }
 
+    #[tokio::test]
+    async fn reports_amount_limits_with_balance() {
+        let api = test_accounts_api();
+        let resp = api_call(&api, "GET", "/accounts/alice/balance", "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body["amount_per_day_limit"],
+            serde_json::json!({ "limit": 2.0, "sent": 0.5 })
+        );
+        // The account has no monthly limit
+        assert!(body.get("amount_per_month_limit").is_none());
+    }
+
+    #[tokio::test]
+    async fn only_admin_or_user_can_get_accounts_balance_history() {
+        let api = test_accounts_api();
+        let path = "/accounts/alice/balance/history?from=1000&to=2000";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["entries"][0]["timestamp"], 1000);
+        assert_eq!(body["entries"][0]["kind"], "incoming_settlement");
+
+        let resp = api_call(&api, "GET", path, "password", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "GET", path, "wrong", None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let path = "/accounts/alice/balance/history?from=2000&to=1000";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let path = "/accounts/alice/balance/history?limit=0";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
     #[tokio::test]
     async fn only_admin_or_user_can_modify_accounts_settings() {
         let api = test_accounts_api();
@@ -1,17 +1,28 @@
-use crate::{http_retry::Client, ExchangeRates, NodeStore};
+use crate::{
+    http_retry::Client,
+    snapshot::{export_snapshot, import_snapshot},
+    ExchangeRates, NodeSnapshot, NodeStore, MIN_PASSPHRASE_LENGTH, SNAPSHOT_VERSION,
+};
 use bytes::Bytes;
 use futures::TryFutureExt;
+use hex::FromHex;
 use interledger_http::{deserialize_json, error::*, HttpAccount, HttpStore};
 use interledger_packet::Address;
 use interledger_router::RouterStore;
 use interledger_service::{Account, Username};
-use interledger_service_util::{BalanceStore, ExchangeRateStore};
-use interledger_settlement::core::types::SettlementAccount;
+use interledger_service_util::{
+    BalanceStore, ExchangeRateHistoryQuery, ExchangeRateHistoryStore, ExchangeRateStore, FeeStore,
+    PairQuote, Spreads,
+};
+use interledger_settlement::core::types::{LeftoversStore, SettlementAccount};
 use log::{error, trace};
+use num_bigint::BigUint;
 use secrecy::{ExposeSecret, SecretString};
-use serde::Serialize;
+use serde::{de::Error as DeserializeError, Deserialize, Deserializer, Serialize};
+use serde_json::json;
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap},
+    convert::TryFrom,
     iter::FromIterator,
     str::{self, FromStr},
 };
@@ -28,9 +39,84 @@ struct StatusResponse {
     version: Option<String>,
 }
 
+/// Number of records returned by `GET /rates/history` if the request does not set a limit
+const DEFAULT_RATE_HISTORY_LIMIT: usize = 100;
+/// Upper bound for the `limit` of `GET /rates/history`
+const MAX_RATE_HISTORY_LIMIT: usize = 1000;
+
+/// Asset code and time range (in milliseconds since the UNIX epoch) of `GET /rates/history`,
+/// and the page of records within them
+#[derive(Deserialize, Debug)]
+struct RateHistoryQuery {
+    asset: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl TryFrom<RateHistoryQuery> for ExchangeRateHistoryQuery {
+    type Error = ();
+
+    fn try_from(query: RateHistoryQuery) -> Result<Self, ()> {
+        let from = query.from.unwrap_or(0);
+        let to = query.to.unwrap_or(std::u64::MAX);
+        if from > to {
+            return Err(());
+        }
+        let limit = match query.limit {
+            Some(0) => return Err(()),
+            Some(limit) => limit.min(MAX_RATE_HISTORY_LIMIT),
+            None => DEFAULT_RATE_HISTORY_LIMIT,
+        };
+        Ok(ExchangeRateHistoryQuery {
+            asset_code: query.asset,
+            from,
+            to,
+            cursor: query.cursor,
+            limit,
+        })
+    }
+}
+
+/// Body of `PUT /secret_seed`
+#[derive(Deserialize)]
+struct SecretSeedRotation {
+    #[serde(deserialize_with = "deserialize_32_bytes_hex")]
+    secret_seed: [u8; 32],
+}
+
+/// Body of `POST /export`
+#[derive(Deserialize)]
+struct ExportRequest {
+    /// The passphrase from which the key that encrypts the account tokens is derived
+    passphrase: SecretString,
+}
+
+/// Body of `POST /import`
+#[derive(Deserialize)]
+struct ImportRequest {
+    /// The passphrase which the snapshot was exported with
+    passphrase: SecretString,
+    snapshot: NodeSnapshot,
+}
+
+fn deserialize_32_bytes_hex<'de, D>(deserializer: D) -> Result<[u8; 32], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    <[u8; 32]>::from_hex(String::deserialize(deserializer)?).map_err(|err| {
+        DeserializeError::custom(format!(
+            "Invalid hex value (must be 32 hex-encoded bytes): {:?}",
+            err
+        ))
+    })
+}
+
 pub fn node_settings_api<S, A>(
     admin_api_token: String,
     node_version: Option<String>,
+    store_secret_from_seed: fn(&[u8; 32]) -> [u8; 32],
     store: S,
 ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
 where
@@ -38,6 +124,9 @@ where
         + HttpStore<Account = A>
         + BalanceStore<Account = A>
         + ExchangeRateStore
+        + ExchangeRateHistoryStore
+        + FeeStore
+        + LeftoversStore<AccountId = Uuid, AssetType = BigUint>
         + RouterStore,
     A: Account + HttpAccount + Send + Sync + SettlementAccount + Serialize + 'static,
 {
@@ -80,14 +169,12 @@ where
         .and(admin_only.clone())
         .and(deserialize_json())
         .and(with_store.clone())
-        .and_then(|rates: ExchangeRates, store: S| {
-            async move {
-                if store.set_exchange_rates(rates.0.clone()).is_ok() {
-                    Ok(warp::reply::json(&rates))
-                } else {
-                    error!("Error setting exchange rates");
-                    Err(Rejection::from(ApiError::internal_server_error()))
-                }
+        .and_then(|rates: ExchangeRates, store: S| async move {
+            if store.set_exchange_rates(rates.0.clone(), "api").is_ok() {
+                Ok(warp::reply::json(&rates))
+            } else {
+                error!("Error setting exchange rates");
+                Err(Rejection::from(ApiError::internal_server_error()))
             }
         })
         .boxed();
@@ -97,18 +184,102 @@ where
         .and(warp::path("rates"))
         .and(warp::path::end())
         .and(with_store.clone())
-        .and_then(|store: S| {
+        .and_then(|store: S| async move {
+            if let Ok(rates) = store.get_all_exchange_rates() {
+                Ok::<Json, Rejection>(warp::reply::json(&rates))
+            } else {
+                error!("Error getting exchange rates");
+                Err(Rejection::from(ApiError::internal_server_error()))
+            }
+        })
+        .boxed();
+
+    // PUT /rates/spreads
+    // Body: { "default": <spread>, "pairs": { "<source asset>": { "<destination asset>": <spread> } } }
+    // The spreads are saved, so they replace those of the node's configuration until they are set again
+    let put_spreads = warp::put()
+        .and(warp::path("rates"))
+        .and(warp::path("spreads"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(|spreads: Spreads, store: S| async move {
+            if !spreads.is_valid() {
+                return Err(Rejection::from(
+                    ApiError::bad_request()
+                        .detail("Spreads must be fractions between 0 and 1 (inclusive)"),
+                ));
+            }
+            if store.set_spreads(spreads.clone()).is_ok() {
+                Ok(warp::reply::json(&spreads))
+            } else {
+                error!("Error setting spreads");
+                Err(Rejection::from(ApiError::internal_server_error()))
+            }
+        })
+        .boxed();
+
+    // GET /rates/spreads
+    let get_spreads = warp::get()
+        .and(warp::path("rates"))
+        .and(warp::path("spreads"))
+        .and(warp::path::end())
+        .and(with_store.clone())
+        .and_then(|store: S| async move {
+            if let Ok(spreads) = store.get_all_spreads() {
+                Ok::<Json, Rejection>(warp::reply::json(&spreads))
+            } else {
+                error!("Error getting spreads");
+                Err(Rejection::from(ApiError::internal_server_error()))
+            }
+        })
+        .boxed();
+
+    // PUT /rates/pairs
+    // Body: [{ "base": <asset code>, "quote": <asset code>, "bid": <price>, "ask": <price> }]
+    // Replaces all of the quotes, which take precedence over the rates of their two assets
+    let put_pair_quotes = warp::put()
+        .and(warp::path("rates"))
+        .and(warp::path("pairs"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(|quotes: Vec<PairQuote>, store: S| {
             async move {
-                if let Ok(rates) = store.get_all_exchange_rates() {
-                    Ok::<Json, Rejection>(warp::reply::json(&rates))
+                if let Some(quote) = quotes.iter().find(|quote| !quote.is_valid()) {
+                    return Err(Rejection::from(ApiError::bad_request().detail(format!(
+                        "Invalid quote for {}/{}: the assets must differ and the bid must be positive and not above the ask",
+                        quote.base, quote.quote
+                    ))));
+                }
+                if store.set_pair_quotes(quotes.clone()).is_ok() {
+                    Ok(warp::reply::json(&quotes))
                 } else {
-                    error!("Error getting exchange rates");
+                    error!("Error setting pair quotes");
                     Err(Rejection::from(ApiError::internal_server_error()))
                 }
             }
         })
         .boxed();
 
+    // GET /rates/pairs
+    let get_pair_quotes = warp::get()
+        .and(warp::path("rates"))
+        .and(warp::path("pairs"))
+        .and(warp::path::end())
+        .and(with_store.clone())
+        .and_then(|store: S| async move {
+            if let Ok(quotes) = store.get_all_pair_quotes() {
+                Ok::<Json, Rejection>(warp::reply::json(&quotes))
+            } else {
+                error!("Error getting pair quotes");
+                Err(Rejection::from(ApiError::internal_server_error()))
+            }
+        })
+        .boxed();
+
     // GET /routes
     // Response: Map of ILP Address prefix -> Username
     let get_routes = warp::get()
@@ -139,6 +310,83 @@ where
         })
         .boxed();
 
+    // GET /rates/history?asset=<asset code>&from=<ms>&to=<ms>&cursor=<cursor>&limit=<limit>
+    // Response: A page of the rates that were set, oldest first, with their sources
+    let get_rate_history = warp::get()
+        .and(warp::path("rates"))
+        .and(warp::path("history"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(warp::query::<RateHistoryQuery>())
+        .and(with_store.clone())
+        .and_then(|query: RateHistoryQuery, store: S| async move {
+            let query = ExchangeRateHistoryQuery::try_from(query)
+                .map_err(|_| Rejection::from(ApiError::bad_request()))?;
+            let page = store
+                .get_exchange_rate_history(query)
+                .map_err(|_| {
+                    error!("Error getting exchange rate history");
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&page))
+        })
+        .boxed();
+
+    // GET /fees
+    // Response: List of the fees earned on packets forwarded between each pair of accounts,
+    // in the asset of the account they were forwarded to
+    let get_fees = warp::get()
+        .and(warp::path("fees"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(with_store.clone())
+        .and_then(|store: S| {
+            async move {
+                let fees = store
+                    .get_fees_earned()
+                    .map_err(|_| {
+                        error!("Error getting fees earned from store");
+                        Rejection::from(ApiError::internal_server_error())
+                    })
+                    .await?;
+
+                // Accounts are loaded one at a time because some of them may have been
+                // deleted since, in which case the fees earned with them are left out
+                let mut accounts: HashMap<Uuid, Option<A>> = HashMap::new();
+                for id in fees
+                    .iter()
+                    .flat_map(|fees| vec![fees.from_account_id, fees.to_account_id])
+                {
+                    if let Entry::Vacant(entry) = accounts.entry(id) {
+                        let account = store
+                            .get_accounts(vec![id])
+                            .await
+                            .ok()
+                            .and_then(|mut accounts| accounts.pop());
+                        entry.insert(account);
+                    }
+                }
+
+                let fees: Vec<_> = fees
+                    .iter()
+                    .filter_map(|fees| {
+                        let from = accounts.get(&fees.from_account_id)?.as_ref()?;
+                        let to = accounts.get(&fees.to_account_id)?.as_ref()?;
+                        let scale = 10_u64.pow(to.asset_scale().into()) as f64;
+                        Some(json!({
+                            "from": from.username().to_string(),
+                            "to": to.username().to_string(),
+                            "asset_code": to.asset_code(),
+                            "amount": fees.amount as f64 / scale,
+                        }))
+                    })
+                    .collect();
+                Ok::<Json, Rejection>(warp::reply::json(&fees))
+            }
+        })
+        .boxed();
+
     // PUT /routes/static
     // Body: Map of ILP Address prefix -> Username
     let put_static_routes = warp::put()
@@ -222,6 +470,84 @@ where
         })
         .boxed();
 
+    // PUT /secret_seed
+    // Body: { "secret_seed": "<32 hex-encoded bytes>" }
+    // Re-encrypts the stored account tokens with the secret derived from the new seed.
+    // The node's configuration must be updated with the new seed before it is restarted
+    let put_secret_seed = warp::put()
+        .and(warp::path("secret_seed"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(move |rotation: SecretSeedRotation, store: S| async move {
+            let new_secret = store_secret_from_seed(&rotation.secret_seed);
+            store
+                .rotate_secret(new_secret)
+                .map_err(|_| {
+                    error!("Error rotating the secret seed");
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&json!({})))
+        })
+        .boxed();
+
+    // POST /export
+    // Body: { "passphrase": "<at least 16 characters>" }
+    // Response: the node's state, with the account tokens encrypted with a key derived from the passphrase
+    let post_export = warp::post()
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(|request: ExportRequest, store: S| async move {
+            if request.passphrase.expose_secret().chars().count() < MIN_PASSPHRASE_LENGTH {
+                error!(
+                    "The export passphrase must be at least {} characters long",
+                    MIN_PASSPHRASE_LENGTH
+                );
+                return Err(Rejection::from(ApiError::bad_request()));
+            }
+            let snapshot = export_snapshot(&store, request.passphrase)
+                .map_err(|_| {
+                    error!("Error exporting the node's state");
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&snapshot))
+        })
+        .boxed();
+
+    // POST /import
+    // Body: { "passphrase": "<the export passphrase>", "snapshot": <the response of POST /export> }
+    // Response: the imported accounts
+    let post_import = warp::post()
+        .and(warp::path("import"))
+        .and(warp::path::end())
+        .and(admin_only.clone())
+        .and(deserialize_json())
+        .and(with_store.clone())
+        .and_then(|request: ImportRequest, store: S| async move {
+            let snapshot = request.snapshot;
+            if snapshot.version != SNAPSHOT_VERSION {
+                error!(
+                    "Cannot import snapshot of version {} (expected version {})",
+                    snapshot.version, SNAPSHOT_VERSION
+                );
+                return Err(Rejection::from(ApiError::bad_request()));
+            }
+            let accounts = import_snapshot(&store, request.passphrase, snapshot)
+                .map_err(|_| {
+                    error!("Error importing snapshot");
+                    Rejection::from(ApiError::internal_server_error())
+                })
+                .await?;
+            Ok::<Json, Rejection>(warp::reply::json(&accounts))
+        })
+        .boxed();
+
     // PUT /settlement/engines
     let put_settlement_engines = warp::put()
         .and(warp::path("settlement"))
@@ -274,9 +600,18 @@ where
     get_root
         .or(put_rates)
         .or(get_rates)
+        .or(put_spreads)
+        .or(get_spreads)
+        .or(put_pair_quotes)
+        .or(get_pair_quotes)
+        .or(get_rate_history)
         .or(get_routes)
+        .or(get_fees)
         .or(put_static_routes)
         .or(put_static_route)
+        .or(put_secret_seed)
+        .or(post_export)
+        .or(post_import)
         .or(put_settlement_engines)
         .boxed()
 }
@@ -304,7 +639,7 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 200);
         assert_eq!(
             serde_json::from_slice::<Value>(resp.body()).unwrap(),
-            json!({"XYZ":2.0,"ABC":1.0})
+            json!({"XYZ":"2","ABC":"1"})
         );
     }
 
@@ -315,6 +650,21 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 200);
     }
 
+    #[tokio::test]
+    async fn only_admin_can_get_fees() {
+        let api = test_node_settings_api();
+        let resp = api_call(&api, "GET", "/fees", "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let fees: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            fees,
+            json!([{"from": "alice", "to": "alice", "asset_code": "XYZ", "amount": 0.00005}])
+        );
+
+        let resp = api_call(&api, "GET", "/fees", "wrong", None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
     #[tokio::test]
     async fn only_admin_can_put_rates() {
         let api = test_node_settings_api();
@@ -326,6 +676,130 @@ mod tests {
         assert_eq!(resp.status().as_u16(), 401);
     }
 
+    #[tokio::test]
+    async fn puts_exact_decimal_rates() {
+        let api = test_node_settings_api();
+        let rates = json!({"ABC": "0.1000000000000000000001", "XYZ": 2, "DEF": "2.50"});
+        let resp = api_call(&api, "PUT", "/rates", "admin", Some(rates)).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(
+            serde_json::from_slice::<Value>(resp.body()).unwrap(),
+            json!({"ABC": "0.1000000000000000000001", "XYZ": "2", "DEF": "2.5"})
+        );
+
+        for rates in &[
+            json!({"ABC": "-1"}),
+            json!({"ABC": "abc"}),
+            json!({"ABC": -1}),
+        ] {
+            let resp = api_call(&api, "PUT", "/rates", "admin", Some(rates.clone())).await;
+            assert_eq!(resp.status().as_u16(), 400);
+        }
+    }
+
+    #[tokio::test]
+    async fn gets_spreads() {
+        let api = test_node_settings_api();
+        let resp = api_call(&api, "GET", "/rates/spreads", "", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.body(), &b"{\"default\":0.01,\"pairs\":{}}"[..]);
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_put_spreads() {
+        let api = test_node_settings_api();
+        let spreads = json!({"default": 0.01, "pairs": {"ABC": {"XYZ": 0.02}}});
+        let resp = api_call(
+            &api,
+            "PUT",
+            "/rates/spreads",
+            "admin",
+            Some(spreads.clone()),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "PUT", "/rates/spreads", "wrong", Some(spreads)).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_spreads() {
+        let api = test_node_settings_api();
+        for spreads in &[
+            json!({"default": -0.01}),
+            json!({"default": 1.01}),
+            json!({"default": 0.01, "pairs": {"ABC": {"XYZ": 2}}}),
+        ] {
+            let resp = api_call(
+                &api,
+                "PUT",
+                "/rates/spreads",
+                "admin",
+                Some(spreads.clone()),
+            )
+            .await;
+            assert_eq!(resp.status().as_u16(), 400);
+        }
+    }
+
+    #[tokio::test]
+    async fn gets_pair_quotes() {
+        let api = test_node_settings_api();
+        let resp = api_call(&api, "GET", "/rates/pairs", "", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(
+            resp.body(),
+            &b"[{\"base\":\"ABC\",\"quote\":\"XYZ\",\"bid\":0.4,\"ask\":0.5}]"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_get_rate_history() {
+        let api = test_node_settings_api();
+        let path = "/rates/history?asset=XYZ&from=1000&to=2000";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let body: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "records": [{"timestamp": 1000, "asset_code": "XYZ", "rate": "1", "source": "api"}],
+                "next_cursor": null,
+            })
+        );
+
+        let resp = api_call(&api, "GET", path, "wrong", None).await;
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let path = "/rates/history?from=2000&to=1000";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let path = "/rates/history?limit=0";
+        let resp = api_call(&api, "GET", path, "admin", None).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_put_pair_quotes() {
+        let api = test_node_settings_api();
+        let quotes = json!([{"base": "ABC", "quote": "XYZ", "bid": 0.4, "ask": 0.5}]);
+        let resp = api_call(&api, "PUT", "/rates/pairs", "admin", Some(quotes.clone())).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "PUT", "/rates/pairs", "wrong", Some(quotes)).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_pair_quotes() {
+        let api = test_node_settings_api();
+        let quotes = json!([{"base": "ABC", "quote": "XYZ", "bid": 0.5, "ask": 0.4}]);
+        let resp = api_call(&api, "PUT", "/rates/pairs", "admin", Some(quotes)).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
     #[tokio::test]
     async fn only_admin_can_put_static_routes() {
         let api = test_node_settings_api();
@@ -377,4 +851,118 @@ mod tests {
         let resp = api_call(&api, "PUT", "/settlement/engines", "wrong", Some(engines)).await;
         assert_eq!(resp.status().as_u16(), 401);
     }
+
+    #[tokio::test]
+    async fn only_admin_can_rotate_secret_seed() {
+        let api = test_node_settings_api();
+        let seed = json!({ "secret_seed": "1604966725982139900555208458637022875563691455429373719368053354" });
+        let resp = api_call(&api, "PUT", "/secret_seed", "admin", Some(seed.clone())).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "PUT", "/secret_seed", "wrong", Some(seed)).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_export() {
+        let api = test_node_settings_api();
+        let request = json!({ "passphrase": "correct horse battery staple" });
+        let resp = api_call(&api, "POST", "/export", "admin", Some(request.clone())).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let snapshot: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(snapshot["version"], 2);
+        assert_eq!(snapshot["key_salt"].as_str().unwrap().len(), 32);
+        assert_eq!(snapshot["exchange_rates"], json!({"XYZ":"2","ABC":"1"}));
+        assert_eq!(
+            snapshot["pair_quotes"],
+            json!([{"base":"ABC","quote":"XYZ","bid":0.4,"ask":0.5}])
+        );
+        assert_eq!(snapshot["spreads"], json!({"default":0.01,"pairs":{}}));
+
+        let resp = api_call(&api, "POST", "/export", "wrong", Some(request)).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn rejects_short_export_passphrase() {
+        let api = test_node_settings_api();
+        let request = json!({ "passphrase": "too short" });
+        let resp = api_call(&api, "POST", "/export", "admin", Some(request)).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
+    #[tokio::test]
+    async fn only_admin_can_import() {
+        let api = test_node_settings_api();
+        let request = json!({
+            "passphrase": "correct horse battery staple",
+            "snapshot": {
+                "version": 2,
+                "key_salt": "00000000000000000000000000000000",
+                "accounts": [],
+                "static_routes": {},
+                "settlement_engines": {},
+                "exchange_rates": {"ABC": 1.0},
+            },
+        });
+        let resp = api_call(&api, "POST", "/import", "admin", Some(request.clone())).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let resp = api_call(&api, "POST", "/import", "wrong", Some(request)).await;
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn rejects_snapshot_of_existing_accounts() {
+        // The test store has every account
+        let api = test_node_settings_api();
+        let request = json!({
+            "passphrase": "correct horse battery staple",
+            "snapshot": {
+                "version": 2,
+                "key_salt": "00000000000000000000000000000000",
+                "accounts": [{
+                    "id": "7b8d1e0c-1f3a-4b5c-9d6e-2f1a3b4c5d6e",
+                    "details": {
+                        "username": "alice",
+                        "asset_code": "XYZ",
+                        "asset_scale": 9,
+                    },
+                    "tokens": {},
+                    "balance": 0,
+                }],
+                "static_routes": {},
+                "settlement_engines": {},
+                "exchange_rates": {},
+            },
+        });
+        let resp = api_call(&api, "POST", "/import", "admin", Some(request)).await;
+        assert_eq!(resp.status().as_u16(), 500);
+    }
+
+    #[tokio::test]
+    async fn rejects_snapshot_of_other_version() {
+        let api = test_node_settings_api();
+        let request = json!({
+            "passphrase": "correct horse battery staple",
+            "snapshot": {
+                "version": 1,
+                "key_salt": "00000000000000000000000000000000",
+                "accounts": [],
+                "static_routes": {},
+                "settlement_engines": {},
+                "exchange_rates": {},
+            },
+        });
+        let resp = api_call(&api, "POST", "/import", "admin", Some(request)).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_secret_seed() {
+        let api = test_node_settings_api();
+        let seed = json!({ "secret_seed": "not hex" });
+        let resp = api_call(&api, "PUT", "/secret_seed", "admin", Some(seed)).await;
+        assert_eq!(resp.status().as_u16(), 400);
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
     routes::{accounts_api, node_settings_api},
-    AccountDetails, AccountSettings, NodeStore,
+    AccountDetails, AccountSettings, AccountsPage, AccountsQuery, ApiKey, ApiKeyScope,
+    ExportedAccount, NodeStore,
 };
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -13,18 +14,28 @@ use interledger_http::{HttpAccount, HttpStore};
 use interledger_packet::{Address, ErrorCode, FulfillBuilder, RejectBuilder};
 use interledger_router::RouterStore;
 use interledger_service::{
-    incoming_service_fn, outgoing_service_fn, Account, AccountStore, AddressStore, Username,
+    incoming_service_fn, outgoing_service_fn, Account, AccountStatus, AccountStore, AddressStore,
+    Username,
+};
+use interledger_service_util::{
+    BalanceChangeKind, BalanceHistoryPage, BalanceHistoryQuery, BalanceJournalEntry, BalanceStore,
+    CumulativeAmounts, ExchangeRate, ExchangeRateHistoryPage, ExchangeRateHistoryQuery,
+    ExchangeRateHistoryStore, ExchangeRateRecord, ExchangeRateStore, FeeStore, FeesEarned,
+    PacketContext, PairQuote, RateLimitAccount, RateLimitError, RateLimitStore, Spreads,
+};
+use interledger_settlement::core::types::{
+    LeftoversStore, SettlementAccount, SettlementEngineDetails,
 };
-use interledger_service_util::{BalanceStore, ExchangeRateStore};
-use interledger_settlement::core::types::{SettlementAccount, SettlementEngineDetails};
 use interledger_stream::{PaymentNotification, StreamNotificationsStore};
 use lazy_static::lazy_static;
+use num_bigint::BigUint;
 use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::SystemTime;
 use url::Url;
 use uuid::Uuid;
 use warp::{self, Filter};
@@ -54,7 +65,8 @@ where
 
 pub fn test_node_settings_api(
 ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    node_settings_api("admin".to_owned(), None, TestStore).recover(default_rejection_handler)
+    node_settings_api("admin".to_owned(), None, |seed| *seed, TestStore)
+        .recover(default_rejection_handler)
 }
 
 pub fn test_accounts_api(
@@ -113,6 +125,9 @@ lazy_static! {
     }));
 }
 const AUTH_PASSWORD: &str = "password";
+/// API key of alice which is only allowed to read her balance
+pub const API_KEY_ID: &str = "9dd7b0cf-5d4c-4a39-8f21-6cf19fa1d0b2";
+const API_KEY_SECRET: &str = "secret";
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TestAccount;
@@ -171,6 +186,12 @@ impl CcpRoutingAccount for TestAccount {
     }
 }
 
+impl RateLimitAccount for TestAccount {
+    fn amount_per_day_limit(&self) -> Option<u64> {
+        Some(2_000_000_000)
+    }
+}
+
 #[async_trait]
 impl AccountStore for TestStore {
     type Account = TestAccount;
@@ -186,20 +207,64 @@ impl AccountStore for TestStore {
 }
 
 impl ExchangeRateStore for TestStore {
-    fn get_exchange_rates(&self, _asset_codes: &[&str]) -> Result<Vec<f64>, ()> {
-        Ok(vec![1.0, 2.0])
+    fn get_exchange_rates(&self, _asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()> {
+        Ok(vec![
+            ExchangeRate::one(),
+            ExchangeRate::from_str("2").unwrap(),
+        ])
+    }
+
+    fn get_exchange_rates_updated_at(&self, asset_codes: &[&str]) -> Result<Vec<SystemTime>, ()> {
+        Ok(vec![SystemTime::now(); asset_codes.len()])
     }
 
-    fn set_exchange_rates(&self, _rates: HashMap<String, f64>) -> Result<(), ()> {
+    fn set_exchange_rates(
+        &self,
+        _rates: HashMap<String, ExchangeRate>,
+        _source: &str,
+    ) -> Result<(), ()> {
         Ok(())
     }
 
-    fn get_all_exchange_rates(&self) -> Result<HashMap<String, f64>, ()> {
+    fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
         let mut ret = HashMap::new();
-        ret.insert("ABC".to_owned(), 1.0);
-        ret.insert("XYZ".to_owned(), 2.0);
+        ret.insert("ABC".to_owned(), ExchangeRate::one());
+        ret.insert("XYZ".to_owned(), ExchangeRate::from_str("2").unwrap());
         Ok(ret)
     }
+
+    fn set_spreads(&self, _spreads: Spreads) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn set_default_spreads(&self, _spreads: Spreads) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn get_spread(&self, _from_asset_code: &str, _to_asset_code: &str) -> f64 {
+        0.0
+    }
+
+    fn get_all_spreads(&self) -> Result<Spreads, ()> {
+        Ok(Spreads::new(0.01))
+    }
+
+    fn set_pair_quotes(&self, _quotes: Vec<PairQuote>) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn get_pair_quote(&self, _base: &str, _quote: &str) -> Option<(PairQuote, SystemTime)> {
+        None
+    }
+
+    fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()> {
+        Ok(vec![PairQuote {
+            base: "ABC".to_owned(),
+            quote: "XYZ".to_owned(),
+            bid: 0.4,
+            ask: 0.5,
+        }])
+    }
 }
 
 impl RouterStore for TestStore {
@@ -236,10 +301,63 @@ impl NodeStore for TestStore {
         Ok(TestAccount)
     }
 
+    async fn set_account_status(
+        &self,
+        _id: Uuid,
+        _status: AccountStatus,
+    ) -> Result<Self::Account, ()> {
+        Ok(TestAccount)
+    }
+
+    async fn insert_api_key(&self, key: ApiKey, _secret: SecretString) -> Result<ApiKey, ()> {
+        Ok(key)
+    }
+
+    async fn get_api_keys(&self, _account_id: Uuid) -> Result<Vec<ApiKey>, ()> {
+        Ok(Vec::new())
+    }
+
+    async fn delete_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<ApiKey, ()> {
+        Ok(ApiKey {
+            id: key_id,
+            account_id,
+            scopes: vec![ApiKeyScope::ReadBalance],
+            description: None,
+        })
+    }
+
+    async fn get_api_key_from_auth(
+        &self,
+        account_id: Uuid,
+        key_id: Uuid,
+        secret: &str,
+    ) -> Result<ApiKey, ()> {
+        if key_id.to_string() == API_KEY_ID && secret == API_KEY_SECRET {
+            Ok(ApiKey {
+                id: key_id,
+                account_id,
+                scopes: vec![ApiKeyScope::ReadBalance],
+                description: None,
+            })
+        } else {
+            Err(())
+        }
+    }
+
     async fn get_all_accounts(&self) -> Result<Vec<Self::Account>, ()> {
         Ok(vec![TestAccount, TestAccount])
     }
 
+    async fn get_accounts_page(
+        &self,
+        _query: AccountsQuery,
+    ) -> Result<AccountsPage<Self::Account>, ()> {
+        Ok(AccountsPage {
+            accounts: vec![TestAccount, TestAccount],
+            next_cursor: None,
+        })
+    }
+
     async fn set_static_routes<R>(&self, _routes: R) -> Result<(), ()>
     where
         R: IntoIterator<Item = (String, Uuid)> + Send + 'async_trait,
@@ -247,6 +365,10 @@ impl NodeStore for TestStore {
         Ok(())
     }
 
+    async fn get_static_routes(&self) -> Result<HashMap<String, Uuid>, ()> {
+        Ok(HashMap::new())
+    }
+
     async fn set_static_route(&self, _prefix: String, _account_id: Uuid) -> Result<(), ()> {
         Ok(())
     }
@@ -265,6 +387,22 @@ impl NodeStore for TestStore {
     async fn get_asset_settlement_engine(&self, _asset_code: &str) -> Result<Option<Url>, ()> {
         Ok(None)
     }
+
+    async fn get_settlement_engines(&self) -> Result<HashMap<String, Url>, ()> {
+        Ok(HashMap::new())
+    }
+
+    async fn export_accounts(&self) -> Result<Vec<ExportedAccount>, ()> {
+        Ok(Vec::new())
+    }
+
+    async fn import_account(&self, _account: ExportedAccount) -> Result<Self::Account, ()> {
+        Ok(TestAccount)
+    }
+
+    async fn rotate_secret(&self, _new_secret: [u8; 32]) -> Result<(), ()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -306,10 +444,33 @@ impl BalanceStore for TestStore {
         Ok(1)
     }
 
+    async fn set_balance(&self, _account: TestAccount, _balance: i64) -> Result<(), ()> {
+        Ok(())
+    }
+
+    async fn get_balance_history(
+        &self,
+        _account: TestAccount,
+        query: BalanceHistoryQuery,
+    ) -> Result<BalanceHistoryPage, ()> {
+        Ok(BalanceHistoryPage {
+            entries: vec![BalanceJournalEntry {
+                timestamp: query.from,
+                kind: BalanceChangeKind::IncomingSettlement,
+                delta: 1,
+                balance: 1,
+                execution_condition: None,
+                counterparty: None,
+            }],
+            next_cursor: None,
+        })
+    }
+
     async fn update_balances_for_prepare(
         &self,
         _from_account: TestAccount,
         _incoming_amount: u64,
+        _context: PacketContext,
     ) -> Result<(), ()> {
         unimplemented!()
     }
@@ -318,6 +479,7 @@ impl BalanceStore for TestStore {
         &self,
         _to_account: TestAccount,
         _outgoing_amount: u64,
+        _context: PacketContext,
     ) -> Result<(i64, u64), ()> {
         unimplemented!()
     }
@@ -326,9 +488,116 @@ impl BalanceStore for TestStore {
         &self,
         _from_account: TestAccount,
         _incoming_amount: u64,
+        _context: PacketContext,
+    ) -> Result<(), ()> {
+        unimplemented!()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for TestStore {
+    type Account = TestAccount;
+
+    async fn apply_rate_limits(
+        &self,
+        _account: TestAccount,
+        _prepare_amount: u64,
+    ) -> Result<(), RateLimitError> {
+        unimplemented!()
+    }
+
+    async fn refund_throughput_limit(
+        &self,
+        _account: TestAccount,
+        _prepare_amount: u64,
+    ) -> Result<(), ()> {
+        unimplemented!()
+    }
+
+    async fn get_cumulative_amounts(&self, _account: TestAccount) -> Result<CumulativeAmounts, ()> {
+        Ok(CumulativeAmounts {
+            day: 500_000_000,
+            month: 500_000_000,
+        })
+    }
+}
+
+#[async_trait]
+impl ExchangeRateHistoryStore for TestStore {
+    async fn get_exchange_rate_history(
+        &self,
+        query: ExchangeRateHistoryQuery,
+    ) -> Result<ExchangeRateHistoryPage, ()> {
+        Ok(ExchangeRateHistoryPage {
+            records: vec![ExchangeRateRecord {
+                timestamp: query.from,
+                asset_code: query.asset_code.unwrap_or_else(|| "ABC".to_owned()),
+                rate: ExchangeRate::one(),
+                source: "api".to_owned(),
+            }],
+            next_cursor: None,
+        })
+    }
+}
+
+#[async_trait]
+impl FeeStore for TestStore {
+    async fn record_fee(
+        &self,
+        _from_account_id: Uuid,
+        _to_account_id: Uuid,
+        _amount: u64,
     ) -> Result<(), ()> {
         unimplemented!()
     }
+
+    async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()> {
+        Ok(vec![FeesEarned {
+            from_account_id: Uuid::new_v4(),
+            to_account_id: Uuid::new_v4(),
+            amount: 50_000,
+        }])
+    }
+}
+
+#[async_trait]
+impl LeftoversStore for TestStore {
+    type AccountId = Uuid;
+    type AssetType = BigUint;
+
+    async fn save_uncredited_settlement_amount(
+        &self,
+        _account_id: Uuid,
+        _uncredited_settlement_amount: (BigUint, u8),
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+
+    async fn load_uncredited_settlement_amount(
+        &self,
+        _account_id: Uuid,
+        _local_scale: u8,
+    ) -> Result<BigUint, ()> {
+        unimplemented!()
+    }
+
+    async fn clear_uncredited_settlement_amount(&self, _account_id: Uuid) -> Result<(), ()> {
+        unimplemented!()
+    }
+
+    async fn get_uncredited_settlement_amount(
+        &self,
+        _account_id: Uuid,
+    ) -> Result<(BigUint, u8), ()> {
+        Ok((BigUint::from(0u32), 0))
+    }
+
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        _account_id: Uuid,
+    ) -> Result<(BigUint, u8), ()> {
+        Ok((BigUint::from(0u32), 0))
+    }
 }
 
 #[async_trait]
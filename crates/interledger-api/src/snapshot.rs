@@ -0,0 +1,515 @@
+use crate::{AccountDetails, NodeStore};
+use bytes::Bytes;
+use futures::TryFutureExt;
+use interledger_service::{Account, AccountStatus, Username};
+use interledger_service_util::{BalanceStore, ExchangeRate, ExchangeRateStore, PairQuote, Spreads};
+use interledger_settlement::core::types::LeftoversStore;
+use log::{debug, error};
+use num_bigint::BigUint;
+use ring::{
+    aead, pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, num::NonZeroU32, str::FromStr};
+use url::Url;
+use uuid::Uuid;
+
+/// The version of the snapshot format. Snapshots of other versions are not imported.
+/// The tokens of version 1 snapshots were encrypted for the importing node's secret seed
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Export passphrases must be at least this long
+pub const MIN_PASSPHRASE_LENGTH: usize = 16;
+
+const NONCE_LENGTH: usize = 12;
+const KEY_SALT_LENGTH: usize = 16;
+const KEY_ITERATIONS: u32 = 100_000;
+
+/// An account's details along with its tokens as they are stored, used to move
+/// accounts from one store to another.
+///
+/// Stores which hash the incoming tokens cannot recover them, so the incoming
+/// tokens are either the tokens themselves or hashes. The importing store must
+/// keep hashes as they are (they are salted with the account's username).
+#[derive(Debug, Clone)]
+pub struct ExportedAccount {
+    /// The account's id, which is kept so that the settlement engines
+    /// can still find the account after it was imported
+    pub id: Uuid,
+    /// The account's details. Its incoming tokens are not set
+    pub details: AccountDetails,
+    pub status: AccountStatus,
+    pub ilp_over_http_incoming_token: Option<Bytes>,
+    pub ilp_over_btp_incoming_token: Option<Bytes>,
+}
+
+/// The state of a node, as returned by `POST /export` and accepted by `POST /import`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    pub version: u32,
+    /// Hex-encoded salt of the key which encrypts the account tokens,
+    /// which is derived from the passphrase provided to `POST /export`
+    pub key_salt: String,
+    pub accounts: Vec<AccountSnapshot>,
+    /// Map of ILP address prefix -> username
+    pub static_routes: HashMap<String, Username>,
+    /// Map of asset code -> default settlement engine URL
+    pub settlement_engines: HashMap<String, Url>,
+    /// Map of asset code -> rate. The rates are exported as decimal strings, but snapshots
+    /// with rates written as numbers can be imported too
+    pub exchange_rates: HashMap<String, ExchangeRate>,
+    /// Snapshots made before pairs of assets could be quoted have no quotes
+    #[serde(default)]
+    pub pair_quotes: Vec<PairQuote>,
+    /// Snapshots made before the spreads were saved have none, so
+    /// importing them keeps the spreads of the importing node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spreads: Option<Spreads>,
+}
+
+/// An account in a `NodeSnapshot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub id: Uuid,
+    /// The account's details, without its tokens
+    pub details: AccountDetails,
+    /// Accounts of snapshots made before accounts had a status are active
+    #[serde(default)]
+    pub status: AccountStatus,
+    /// The account's tokens, encrypted with the key derived from the export passphrase
+    pub tokens: SnapshotTokens,
+    pub balance: i64,
+    /// The settlement amount which could not be credited yet (as a decimal
+    /// string, since it may not fit in a JSON number) and its asset scale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncredited_settlement_amount: Option<(String, u8)>,
+}
+
+/// Hex-encoded tokens, encrypted with a key derived from the export passphrase
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotTokens {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ilp_over_http_incoming_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ilp_over_http_outgoing_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ilp_over_btp_incoming_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ilp_over_btp_outgoing_token: Option<String>,
+}
+
+/// Derives the key which encrypts the tokens of a snapshot from the export passphrase
+fn derive_snapshot_key(passphrase: &str, salt: &[u8]) -> aead::LessSafeKey {
+    let mut key = [0; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(KEY_ITERATIONS).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, &key).unwrap())
+}
+
+/// Derives the snapshot key on the blocking thread pool, because
+/// stretching the passphrase takes a while
+async fn snapshot_key(passphrase: SecretString, salt: Vec<u8>) -> Result<aead::LessSafeKey, ()> {
+    tokio::task::spawn_blocking(move || derive_snapshot_key(passphrase.expose_secret(), &salt))
+        .await
+        .map_err(|err| error!("Error deriving the snapshot key: {:?}", err))
+}
+
+/// Encrypts the token and hex-encodes it along with the nonce
+fn encrypt_token(key: &aead::LessSafeKey, token: &[u8]) -> String {
+    let mut nonce = [0; NONCE_LENGTH];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .expect("Unable to get sufficient entropy for nonce");
+    let mut sealed = token.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce),
+        aead::Aad::empty(),
+        &mut sealed,
+    )
+    .expect("Unable to encrypt token");
+    sealed.extend_from_slice(&nonce);
+    hex::encode(sealed)
+}
+
+fn decrypt_token(key: &aead::LessSafeKey, encrypted: &str) -> Result<Vec<u8>, ()> {
+    let mut sealed =
+        hex::decode(encrypted).map_err(|err| error!("Token is not valid hex: {:?}", err))?;
+    if sealed.len() < NONCE_LENGTH + aead::MAX_TAG_LEN {
+        error!("Encrypted token is too short");
+        return Err(());
+    }
+    let nonce_bytes = sealed.split_off(sealed.len() - NONCE_LENGTH);
+    let mut nonce = [0; NONCE_LENGTH];
+    nonce.copy_from_slice(&nonce_bytes);
+    let token = key
+        .open_in_place(
+            aead::Nonce::assume_unique_for_key(nonce),
+            aead::Aad::empty(),
+            &mut sealed,
+        )
+        .map_err(|_| {
+            error!("Unable to decrypt token. Was the snapshot exported with this passphrase?")
+        })?;
+    Ok(token.to_vec())
+}
+
+fn decrypt_outgoing_token(
+    key: &aead::LessSafeKey,
+    encrypted: &Option<String>,
+) -> Result<Option<SecretString>, ()> {
+    match encrypted {
+        Some(encrypted) => {
+            let token = String::from_utf8(decrypt_token(key, encrypted)?)
+                .map_err(|_| error!("Outgoing token is not valid UTF-8"))?;
+            Ok(Some(SecretString::new(token)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decrypt_incoming_token(
+    key: &aead::LessSafeKey,
+    encrypted: &Option<String>,
+) -> Result<Option<Bytes>, ()> {
+    match encrypted {
+        Some(encrypted) => Ok(Some(Bytes::from(decrypt_token(key, encrypted)?))),
+        None => Ok(None),
+    }
+}
+
+impl AccountSnapshot {
+    fn new(
+        mut account: ExportedAccount,
+        balance: i64,
+        uncredited_settlement_amount: Option<(String, u8)>,
+        key: &aead::LessSafeKey,
+    ) -> Self {
+        let details = &mut account.details;
+        let tokens = SnapshotTokens {
+            ilp_over_http_incoming_token: account
+                .ilp_over_http_incoming_token
+                .map(|token| encrypt_token(key, &token)),
+            ilp_over_http_outgoing_token: details
+                .ilp_over_http_outgoing_token
+                .take()
+                .map(|token| encrypt_token(key, token.expose_secret().as_bytes())),
+            ilp_over_btp_incoming_token: account
+                .ilp_over_btp_incoming_token
+                .map(|token| encrypt_token(key, &token)),
+            ilp_over_btp_outgoing_token: details
+                .ilp_over_btp_outgoing_token
+                .take()
+                .map(|token| encrypt_token(key, token.expose_secret().as_bytes())),
+        };
+        details.ilp_over_http_incoming_token = None;
+        details.ilp_over_btp_incoming_token = None;
+        AccountSnapshot {
+            id: account.id,
+            details: account.details,
+            status: account.status,
+            tokens,
+            balance,
+            uncredited_settlement_amount,
+        }
+    }
+
+    fn into_exported_account(self, key: &aead::LessSafeKey) -> Result<ExportedAccount, ()> {
+        let mut details = self.details;
+        details.ilp_over_http_incoming_token = None;
+        details.ilp_over_btp_incoming_token = None;
+        details.ilp_over_http_outgoing_token =
+            decrypt_outgoing_token(key, &self.tokens.ilp_over_http_outgoing_token)?;
+        details.ilp_over_btp_outgoing_token =
+            decrypt_outgoing_token(key, &self.tokens.ilp_over_btp_outgoing_token)?;
+        Ok(ExportedAccount {
+            id: self.id,
+            details,
+            status: self.status,
+            ilp_over_http_incoming_token: decrypt_incoming_token(
+                key,
+                &self.tokens.ilp_over_http_incoming_token,
+            )?,
+            ilp_over_btp_incoming_token: decrypt_incoming_token(
+                key,
+                &self.tokens.ilp_over_btp_incoming_token,
+            )?,
+        })
+    }
+}
+
+/// Exports the state of the node, encrypting the account tokens
+/// with a key derived from the passphrase
+pub(crate) async fn export_snapshot<S, A>(
+    store: &S,
+    passphrase: SecretString,
+) -> Result<NodeSnapshot, ()>
+where
+    S: NodeStore<Account = A>
+        + BalanceStore<Account = A>
+        + ExchangeRateStore
+        + LeftoversStore<AccountId = Uuid, AssetType = BigUint>,
+    A: Account,
+{
+    let mut salt = vec![0; KEY_SALT_LENGTH];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| error!("Unable to get sufficient entropy for the snapshot key salt"))?;
+    let key_salt = hex::encode(&salt);
+    let key = snapshot_key(passphrase, salt).await?;
+    let exported = store.export_accounts().await?;
+    let accounts = store
+        .get_accounts(exported.iter().map(|account| account.id).collect())
+        .await?;
+
+    let mut usernames = HashMap::new();
+    let mut snapshots = Vec::with_capacity(exported.len());
+    for (exported, account) in exported.into_iter().zip(accounts.into_iter()) {
+        usernames.insert(exported.id, exported.details.username.clone());
+        let balance = store.get_balance(account).await?;
+        let (amount, scale) = store.peek_uncredited_settlement_amount(exported.id).await?;
+        let uncredited_settlement_amount = if amount > BigUint::from(0u32) {
+            Some((amount.to_string(), scale))
+        } else {
+            None
+        };
+        snapshots.push(AccountSnapshot::new(
+            exported,
+            balance,
+            uncredited_settlement_amount,
+            &key,
+        ));
+    }
+
+    let mut static_routes = HashMap::new();
+    for (prefix, id) in store.get_static_routes().await? {
+        let username = usernames
+            .get(&id)
+            .ok_or_else(|| error!("Static route {} points to unknown account {}", prefix, id))?;
+        static_routes.insert(prefix, username.clone());
+    }
+
+    debug!("Exported {} accounts", snapshots.len());
+    Ok(NodeSnapshot {
+        version: SNAPSHOT_VERSION,
+        key_salt,
+        accounts: snapshots,
+        static_routes,
+        settlement_engines: store.get_settlement_engines().await?,
+        exchange_rates: store.get_all_exchange_rates()?,
+        pair_quotes: store.get_all_pair_quotes()?,
+        spreads: Some(store.get_all_spreads()?),
+    })
+}
+
+/// Imports the snapshot which was exported with the passphrase
+/// and returns the imported accounts.
+///
+/// The snapshot is checked before the store is changed, and if the import
+/// fails anyway, the accounts, static routes, exchange rates, pair quotes
+/// and spreads which were already imported are rolled back.
+pub(crate) async fn import_snapshot<S, A>(
+    store: &S,
+    passphrase: SecretString,
+    snapshot: NodeSnapshot,
+) -> Result<Vec<A>, ()>
+where
+    S: NodeStore<Account = A>
+        + BalanceStore<Account = A>
+        + ExchangeRateStore
+        + LeftoversStore<AccountId = Uuid, AssetType = BigUint>,
+    A: Account,
+{
+    if let Some(ref spreads) = snapshot.spreads {
+        if !spreads.is_valid() {
+            error!("Snapshot contains invalid spreads: {:?}", spreads);
+            return Err(());
+        }
+    }
+    let salt = hex::decode(&snapshot.key_salt)
+        .map_err(|err| error!("Snapshot key salt is not valid hex: {:?}", err))?;
+    let key = snapshot_key(passphrase, salt).await?;
+    // Decrypt every account before importing any of them so that
+    // a snapshot exported with another passphrase is not partially imported
+    let mut exported = Vec::with_capacity(snapshot.accounts.len());
+    for account in snapshot.accounts {
+        let balance = account.balance;
+        let uncredited_settlement_amount = match account.uncredited_settlement_amount {
+            Some((ref amount, scale)) => Some((
+                BigUint::from_str(amount)
+                    .map_err(|err| error!("Invalid uncredited settlement amount: {:?}", err))?,
+                scale,
+            )),
+            None => None,
+        };
+        exported.push((
+            account.into_exported_account(&key)?,
+            balance,
+            uncredited_settlement_amount,
+        ));
+    }
+
+    // The accounts keep their ids and usernames, so none of them may exist yet
+    let mut usernames: Vec<(Username, Uuid)> = Vec::with_capacity(exported.len());
+    for (account, _, _) in exported.iter() {
+        let username = &account.details.username;
+        if usernames
+            .iter()
+            .any(|(other, id)| other == username || *id == account.id)
+        {
+            error!(
+                "Snapshot contains account {} or user {} more than once",
+                account.id, username
+            );
+            return Err(());
+        }
+        if store.get_accounts(vec![account.id]).await.is_ok()
+            || store.get_account_id_from_username(username).await.is_ok()
+        {
+            error!(
+                "Cannot import account {} because an account with the same id or username ({}) already exists",
+                account.id, username
+            );
+            return Err(());
+        }
+        usernames.push((username.clone(), account.id));
+    }
+
+    let mut static_routes = Vec::with_capacity(snapshot.static_routes.len());
+    for (prefix, username) in snapshot.static_routes {
+        let id = match usernames.iter().find(|(other, _)| *other == username) {
+            Some((_, id)) => *id,
+            None => {
+                store
+                    .get_account_id_from_username(&username)
+                    .map_err(|_| {
+                        error!(
+                            "Static route {} points to unknown user {}",
+                            prefix, username
+                        )
+                    })
+                    .await?
+            }
+        };
+        static_routes.push((prefix, id));
+    }
+
+    let previous_static_routes = store.get_static_routes().await?;
+    let previous_exchange_rates = store.get_all_exchange_rates()?;
+    let previous_pair_quotes = store.get_all_pair_quotes()?;
+    let previous_spreads = store.get_all_spreads()?;
+
+    let mut accounts = Vec::with_capacity(exported.len());
+    let mut result = Ok(());
+    for (exported, balance, uncredited_settlement_amount) in exported {
+        let id = exported.id;
+        result = import_account(store, exported, balance, uncredited_settlement_amount)
+            .await
+            .map(|account| accounts.push(account));
+        if result.is_err() {
+            // The account itself may have been imported before setting its balance failed
+            if store.get_accounts(vec![id]).await.is_ok() && store.delete_account(id).await.is_err()
+            {
+                error!("Error removing imported account {}", id);
+            }
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = store.set_static_routes(static_routes).await;
+    }
+    if result.is_ok() {
+        result = store.set_exchange_rates(snapshot.exchange_rates, "import");
+    }
+    if result.is_ok() {
+        result = store.set_pair_quotes(snapshot.pair_quotes);
+    }
+    if let (true, Some(spreads)) = (result.is_ok(), snapshot.spreads) {
+        result = store.set_spreads(spreads);
+    }
+    // This is done last because the engines of assets which had
+    // none before cannot be removed when rolling back
+    if result.is_ok() {
+        result = store
+            .set_settlement_engines(snapshot.settlement_engines)
+            .await;
+    }
+
+    if result.is_err() {
+        error!("Error importing snapshot, rolling back the changes");
+        for account in accounts.iter() {
+            let id = account.id();
+            if store.delete_account(id).await.is_err()
+                || store.clear_uncredited_settlement_amount(id).await.is_err()
+            {
+                error!("Error removing imported account {}", id);
+            }
+        }
+        if store
+            .set_static_routes(previous_static_routes)
+            .await
+            .is_err()
+            || store
+                .set_exchange_rates(previous_exchange_rates, "import")
+                .is_err()
+            || store.set_pair_quotes(previous_pair_quotes).is_err()
+            || store.set_spreads(previous_spreads).is_err()
+        {
+            error!("Error restoring the static routes, exchange rates, pair quotes and spreads");
+        }
+        return Err(());
+    }
+
+    debug!("Imported {} accounts", accounts.len());
+    Ok(accounts)
+}
+
+/// Imports the account with its balance and uncredited settlement amount
+async fn import_account<S, A>(
+    store: &S,
+    account: ExportedAccount,
+    balance: i64,
+    uncredited_settlement_amount: Option<(BigUint, u8)>,
+) -> Result<A, ()>
+where
+    S: NodeStore<Account = A>
+        + BalanceStore<Account = A>
+        + LeftoversStore<AccountId = Uuid, AssetType = BigUint>,
+    A: Account,
+{
+    let id = account.id;
+    let account = store.import_account(account).await?;
+    store.set_balance(account.clone(), balance).await?;
+    if let Some(amount) = uncredited_settlement_amount {
+        store.save_uncredited_settlement_amount(id, amount).await?;
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_same_passphrase_and_salt_decrypt_tokens() {
+        let key = derive_snapshot_key("correct horse battery staple", &[1; 16]);
+        let encrypted = encrypt_token(&key, b"token");
+        assert_eq!(decrypt_token(&key, &encrypted).unwrap(), b"token");
+        assert!(decrypt_token(
+            &derive_snapshot_key("correct horse battery stapler", &[1; 16]),
+            &encrypted
+        )
+        .is_err());
+        assert!(decrypt_token(
+            &derive_snapshot_key("correct horse battery staple", &[2; 16]),
+            &encrypted
+        )
+        .is_err());
+    }
+}
@@ -285,6 +285,7 @@ mod client_server {
                     data: b"test data",
                 }
                 .build(),
+                applied_rate: None,
             })
             .await;
         assert!(res.is_ok());
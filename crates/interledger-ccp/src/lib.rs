@@ -92,6 +92,12 @@ pub trait CcpRoutingAccount: Account {
         self.routing_relation() == RoutingRelation::Parent
             || self.routing_relation() == RoutingRelation::Peer
     }
+
+    /// Indicates whether packets may be routed to this account. The routes
+    /// to accounts which are not routable (e.g. because they were suspended) are withdrawn
+    fn is_routable(&self) -> bool {
+        true
+    }
 }
 
 // key = Bytes, key should be Address -- TODO
@@ -514,6 +514,7 @@ where
                 to: account,
                 original_amount: prepare.amount(),
                 prepare,
+                applied_rate: None,
             })
             .await;
 
@@ -739,6 +740,7 @@ where
                         to: account.clone(),
                         original_amount: prepare.amount(),
                         prepare: prepare.clone(),
+                        applied_rate: None,
                     })
                     .await;
                 results.push((account, res));
@@ -893,6 +895,7 @@ where
                 to: account,
                 original_amount: prepare.amount(),
                 prepare,
+                applied_rate: None,
             })
             .await;
 
@@ -920,7 +923,10 @@ fn get_best_route_for_prefix<A: CcpRoutingAccount>(
     let segments: Vec<&str> = prefix.split(|c| c == '.').collect();
     for i in 0..segments.len() {
         let prefix = &segments[0..segments.len() - i].join(".");
-        if let Some(account) = configured_routes.get(prefix) {
+        if let Some(account) = configured_routes
+            .get(prefix)
+            .filter(|account| account.is_routable())
+        {
             return Some((
                 account.clone(),
                 Route {
@@ -933,11 +939,15 @@ fn get_best_route_for_prefix<A: CcpRoutingAccount>(
         }
     }
 
-    if let Some(account) = local_routes.get(prefix) {
+    if let Some(account) = local_routes
+        .get(prefix)
+        .filter(|account| account.is_routable())
+    {
         return Some((
             account.clone(),
             Route {
-                prefix: account.ilp_address().to_string(),
+                // The prefix may be one of the account's additional prefixes
+                prefix: prefix.to_string(),
                 auth: [0; 32],
                 path: Vec::new(),
                 props: Vec::new(),
@@ -947,7 +957,8 @@ fn get_best_route_for_prefix<A: CcpRoutingAccount>(
 
     let mut candidate_routes = incoming_tables
         .values()
-        .filter_map(|incoming_table| incoming_table.get_route(prefix));
+        .filter_map(|incoming_table| incoming_table.get_route(prefix))
+        .filter(|(account, _route)| account.is_routable());
     if let Some((account, route)) = candidate_routes.next() {
         let (best_account, best_route) = candidate_routes.fold(
             (account, route),
@@ -1123,6 +1134,21 @@ mod ranking_routes {
         );
     }
 
+    #[test]
+    fn uses_prefix_of_local_route() {
+        let id = Uuid::from_slice(&[9; 16]).unwrap();
+        // The account owns a prefix other than its address
+        let local = HashMap::from_iter(vec![(
+            "test.other".to_string(),
+            TestAccount::new(id, "example.other"),
+        )]);
+        let (account, route) =
+            get_best_route_for_prefix(&local, &HashMap::new(), &HashMap::new(), "test.other")
+                .unwrap();
+        assert_eq!(account.id(), id);
+        assert_eq!(route.prefix, "test.other");
+    }
+
     #[test]
     fn prioritizes_children_over_peers() {
         let best_route = get_best_route_for_prefix(&LOCAL, &CONFIGURED, &INCOMING, "example.d");
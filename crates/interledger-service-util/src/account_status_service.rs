@@ -0,0 +1,260 @@
+use async_trait::async_trait;
+use interledger_packet::{ErrorCode, RejectBuilder};
+use interledger_service::*;
+use log::debug;
+use std::marker::PhantomData;
+
+/// Extension trait for [`Account`](../interledger_service/trait.Account.html) with the
+/// account's status, which allows it to be suspended or closed without deleting it
+pub trait StatusAccount: Account {
+    fn status(&self) -> AccountStatus;
+}
+
+/// # Account Status Service
+///
+/// Incoming or Outgoing Service which cuts off accounts that are not active.
+/// Packets from suspended accounts are rejected with `F00: Bad Request`. Packets
+/// to accounts which are suspended or closing are rejected with `F02: Unreachable`,
+/// since their routes are withdrawn but requests may still be routed to them
+/// until the routing table is updated.
+///
+/// Unlike deleting the account, this keeps its balance and settlement data.
+/// Requires a `StatusAccount` and an `AddressStore`.
+#[derive(Clone)]
+pub struct AccountStatusService<IO, S, A> {
+    store: S,
+    next: IO,
+    account_type: PhantomData<A>,
+}
+
+impl<I, S, A> AccountStatusService<I, S, A>
+where
+    I: IncomingService<A>,
+    S: AddressStore,
+    A: StatusAccount,
+{
+    /// Create an incoming account status service, which rejects
+    /// packets from accounts that are suspended
+    pub fn incoming(store: S, next: I) -> Self {
+        AccountStatusService {
+            store,
+            next,
+            account_type: PhantomData,
+        }
+    }
+}
+
+impl<O, S, A> AccountStatusService<O, S, A>
+where
+    O: OutgoingService<A>,
+    S: AddressStore,
+    A: StatusAccount,
+{
+    /// Create an outgoing account status service, which rejects
+    /// packets to accounts that are suspended or closing
+    pub fn outgoing(store: S, next: O) -> Self {
+        AccountStatusService {
+            store,
+            next,
+            account_type: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<I, S, A> IncomingService<A> for AccountStatusService<I, S, A>
+where
+    I: IncomingService<A> + Send + Sync + 'static,
+    S: AddressStore + Send + Sync + 'static,
+    A: StatusAccount + Send + Sync + 'static,
+{
+    /// On receive request:
+    /// 1. if the account the request is from accepts incoming packets, forward the request, else reject
+    async fn handle_request(&mut self, request: IncomingRequest<A>) -> IlpResult {
+        let status = request.from.status();
+        if status.accepts_incoming_packets() {
+            self.next.handle_request(request).await
+        } else {
+            debug!(
+                "Rejecting packet from account {} because it is {}",
+                request.from.id(),
+                status
+            );
+            Err(RejectBuilder {
+                code: ErrorCode::F00_BAD_REQUEST,
+                message: format!("Account is {}", status).as_bytes(),
+                triggered_by: Some(&self.store.get_ilp_address()),
+                data: &[],
+            }
+            .build())
+        }
+    }
+}
+
+#[async_trait]
+impl<O, S, A> OutgoingService<A> for AccountStatusService<O, S, A>
+where
+    O: OutgoingService<A> + Send + Sync + 'static,
+    S: AddressStore + Send + Sync + 'static,
+    A: StatusAccount + Send + Sync + 'static,
+{
+    /// On send request:
+    /// 1. if the account the request is to accepts outgoing packets, forward the request, else reject
+    async fn send_request(&mut self, request: OutgoingRequest<A>) -> IlpResult {
+        let status = request.to.status();
+        if status.accepts_outgoing_packets() {
+            self.next.send_request(request).await
+        } else {
+            debug!(
+                "Rejecting packet to account {} because it is {}",
+                request.to.id(),
+                status
+            );
+            Err(RejectBuilder {
+                code: ErrorCode::F02_UNREACHABLE,
+                message: format!("Account is {}", status).as_bytes(),
+                triggered_by: Some(&self.store.get_ilp_address()),
+                data: &[],
+            }
+            .build())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interledger_packet::{Address, FulfillBuilder, PrepareBuilder};
+    use interledger_service::{incoming_service_fn, outgoing_service_fn, Username};
+    use lazy_static::lazy_static;
+    use std::{
+        str::FromStr,
+        time::{Duration, SystemTime},
+    };
+    use uuid::Uuid;
+
+    lazy_static! {
+        pub static ref ALICE: Username = Username::from_str("alice").unwrap();
+        pub static ref EXAMPLE_ADDRESS: Address = Address::from_str("example.alice").unwrap();
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestAccount(Uuid, AccountStatus);
+    impl Account for TestAccount {
+        fn id(&self) -> Uuid {
+            self.0
+        }
+
+        fn username(&self) -> &Username {
+            &ALICE
+        }
+
+        fn asset_code(&self) -> &str {
+            "XYZ"
+        }
+
+        fn asset_scale(&self) -> u8 {
+            9
+        }
+
+        fn ilp_address(&self) -> &Address {
+            &EXAMPLE_ADDRESS
+        }
+    }
+
+    impl StatusAccount for TestAccount {
+        fn status(&self) -> AccountStatus {
+            self.1
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestStore;
+
+    #[async_trait]
+    impl AddressStore for TestStore {
+        async fn set_ilp_address(&self, _ilp_address: Address) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        async fn clear_ilp_address(&self) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn get_ilp_address(&self) -> Address {
+            Address::from_str("example.connector").unwrap()
+        }
+    }
+
+    fn fulfill() -> IlpResult {
+        Ok(FulfillBuilder {
+            fulfillment: &[0; 32],
+            data: &[],
+        }
+        .build())
+    }
+
+    fn incoming_request(status: AccountStatus) -> IncomingRequest<TestAccount> {
+        IncomingRequest {
+            from: TestAccount(Uuid::new_v4(), status),
+            prepare: PrepareBuilder {
+                destination: Address::from_str("example.destination").unwrap(),
+                amount: 100,
+                expires_at: SystemTime::now() + Duration::from_secs(30),
+                data: &[],
+                execution_condition: &[0; 32],
+            }
+            .build(),
+        }
+    }
+
+    fn outgoing_request(status: AccountStatus) -> OutgoingRequest<TestAccount> {
+        let request = incoming_request(AccountStatus::Active);
+        OutgoingRequest {
+            from: request.from,
+            to: TestAccount(Uuid::new_v4(), status),
+            prepare: request.prepare,
+            original_amount: 100,
+            applied_rate: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_from_suspended_accounts() {
+        let mut service =
+            AccountStatusService::incoming(TestStore, incoming_service_fn(|_| fulfill()));
+        let reject = service
+            .handle_request(incoming_request(AccountStatus::Suspended))
+            .await
+            .unwrap_err();
+        assert_eq!(reject.code(), ErrorCode::F00_BAD_REQUEST);
+        assert_eq!(reject.message(), b"Account is suspended");
+
+        // Closing accounts may still send packets
+        service
+            .handle_request(incoming_request(AccountStatus::Closing))
+            .await
+            .unwrap();
+        service
+            .handle_request(incoming_request(AccountStatus::Active))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_to_inactive_accounts() {
+        let mut service =
+            AccountStatusService::outgoing(TestStore, outgoing_service_fn(|_| fulfill()));
+        for status in &[AccountStatus::Suspended, AccountStatus::Closing] {
+            let reject = service
+                .send_request(outgoing_request(*status))
+                .await
+                .unwrap_err();
+            assert_eq!(reject.code(), ErrorCode::F02_UNREACHABLE);
+        }
+        service
+            .send_request(outgoing_request(AccountStatus::Active))
+            .await
+            .unwrap();
+    }
+}
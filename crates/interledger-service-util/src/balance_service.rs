@@ -7,7 +7,134 @@ use interledger_settlement::{
     core::types::{SettlementAccount, SettlementStore},
 };
 use log::{debug, error};
+use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// The packet which caused a balance change, recorded alongside it in the balance journal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PacketContext {
+    /// The Prepare packet's execution condition
+    pub execution_condition: [u8; 32],
+    /// The account on the other side of the packet
+    pub counterparty: Uuid,
+}
+
+/// The operation that changed an account's balance
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceChangeKind {
+    Prepare,
+    Fulfill,
+    Reject,
+    /// The amount deducted when a fulfill brings the balance over the settle threshold
+    OutgoingSettlement,
+    IncomingSettlement,
+    /// An outgoing settlement that failed and was added back to the balance
+    SettlementRefund,
+}
+
+impl BalanceChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BalanceChangeKind::Prepare => "prepare",
+            BalanceChangeKind::Fulfill => "fulfill",
+            BalanceChangeKind::Reject => "reject",
+            BalanceChangeKind::OutgoingSettlement => "outgoing_settlement",
+            BalanceChangeKind::IncomingSettlement => "incoming_settlement",
+            BalanceChangeKind::SettlementRefund => "settlement_refund",
+        }
+    }
+}
+
+impl std::str::FromStr for BalanceChangeKind {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string {
+            "prepare" => Ok(BalanceChangeKind::Prepare),
+            "fulfill" => Ok(BalanceChangeKind::Fulfill),
+            "reject" => Ok(BalanceChangeKind::Reject),
+            "outgoing_settlement" => Ok(BalanceChangeKind::OutgoingSettlement),
+            "incoming_settlement" => Ok(BalanceChangeKind::IncomingSettlement),
+            "settlement_refund" => Ok(BalanceChangeKind::SettlementRefund),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A single change to an account's balance. Entries are only ever appended,
+/// so the journal shows how the account arrived at its current balance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceJournalEntry {
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub kind: BalanceChangeKind,
+    /// The change to the balance (including the prepaid amount)
+    pub delta: i64,
+    /// The balance (including the prepaid amount) after the change
+    pub balance: i64,
+    /// Hex-encoded execution condition of the packet, for packet balance changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_condition: Option<String>,
+    /// Id of the account on the other side of the packet, for packet balance changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<Uuid>,
+}
+
+impl BalanceJournalEntry {
+    /// Creates an entry timestamped with the current time
+    pub fn new(
+        kind: BalanceChangeKind,
+        delta: i64,
+        balance: i64,
+        context: Option<PacketContext>,
+    ) -> Self {
+        BalanceJournalEntry {
+            timestamp: journal_timestamp(),
+            kind,
+            delta,
+            balance,
+            execution_condition: context.map(|c| hex::encode(&c.execution_condition)),
+            counterparty: context.map(|c| c.counterparty),
+        }
+    }
+}
+
+/// How long the entries of the balance journal are kept, in milliseconds (90 days).
+/// The stores delete the older entries of an account as they add new ones
+pub const BALANCE_JOURNAL_RETENTION: u64 = 90 * 24 * 60 * 60 * 1000;
+
+/// Which entries of an account's balance journal to return
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceHistoryQuery {
+    /// Only return entries recorded at or after this time, in milliseconds since the UNIX epoch
+    pub from: u64,
+    /// Only return entries recorded at or before this time, in milliseconds since the UNIX epoch
+    pub to: u64,
+    /// Only return entries after this one (the `next_cursor` of the previous page).
+    /// The format of the cursor depends on the store
+    pub cursor: Option<String>,
+    /// The maximum number of entries to return
+    pub limit: usize,
+}
+
+/// One page of the entries returned for a `BalanceHistoryQuery`, oldest first
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BalanceHistoryPage {
+    pub entries: Vec<BalanceJournalEntry>,
+    /// The cursor to pass in the next query, or `None` if this is the last page
+    pub next_cursor: Option<String>,
+}
+
+/// The current time in milliseconds since the UNIX epoch, as used for journal entries
+pub fn journal_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
 
 // TODO: Remove AccountStore dependency, use `AccountId: ToString` as associated type
 /// Trait responsible for managing an account's balance in the store
@@ -17,11 +144,24 @@ pub trait BalanceStore: AccountStore {
     /// Fetch the current balance for the given account.
     async fn get_balance(&self, account: Self::Account) -> Result<i64, ()>;
 
+    /// Overwrites the account's balance, which is used to restore
+    /// the balances of accounts imported from another node
+    async fn set_balance(&self, account: Self::Account, balance: i64) -> Result<(), ()>;
+
+    /// Returns a page of the journal entries of the account's balance changes
+    /// which match the query, oldest first
+    async fn get_balance_history(
+        &self,
+        account: Self::Account,
+        query: BalanceHistoryQuery,
+    ) -> Result<BalanceHistoryPage, ()>;
+
     /// Decreases the sending account's balance before forwarding out a prepare packet
     async fn update_balances_for_prepare(
         &self,
         from_account: Self::Account,
         incoming_amount: u64,
+        context: PacketContext,
     ) -> Result<(), ()>;
 
     /// Increases the receiving account's balance, and returns the updated balance
@@ -30,12 +170,14 @@ pub trait BalanceStore: AccountStore {
         &self,
         to_account: Self::Account,
         outgoing_amount: u64,
+        context: PacketContext,
     ) -> Result<(i64, u64), ()>;
 
     async fn update_balances_for_reject(
         &self,
         from_account: Self::Account,
         incoming_amount: u64,
+        context: PacketContext,
     ) -> Result<(), ()>;
 }
 
@@ -111,6 +253,16 @@ where
         let ilp_address = self.store.get_ilp_address();
         let settlement_client = self.settlement_client.clone();
         let to_has_engine = to.settlement_engine_details().is_some();
+        let mut execution_condition = [0; 32];
+        execution_condition.copy_from_slice(request.prepare.execution_condition());
+        let from_context = PacketContext {
+            execution_condition,
+            counterparty: to_id,
+        };
+        let to_context = PacketContext {
+            execution_condition,
+            counterparty: from_id,
+        };
 
         // Update the balance _before_ sending the settlement so that we don't accidentally send
         // multiple settlements for the same balance. While there will be a small moment of time (the delta
@@ -123,7 +275,7 @@ where
         // operate as-if the settlement engine has completed. Finally, if the request to the settlement-engine
         // fails, this amount will be re-added back to balance.
         self.store
-            .update_balances_for_prepare(from.clone(), incoming_amount)
+            .update_balances_for_prepare(from.clone(), incoming_amount, from_context)
             .map_err(move |_| {
                 debug!("Rejecting packet because it would exceed a balance limit");
                 RejectBuilder {
@@ -148,7 +300,7 @@ where
                 // relay the fulfillment _even if saving to the DB fails._
                 tokio::spawn(async move {
                     let (balance, amount_to_settle) = match store
-                        .update_balances_for_fulfill(to.clone(), outgoing_amount)
+                        .update_balances_for_fulfill(to.clone(), outgoing_amount, to_context)
                         .await
                     {
                         Ok(r) => r,
@@ -202,6 +354,7 @@ where
                         store_clone.update_balances_for_reject(
                             from_clone.clone(),
                             incoming_amount,
+                            from_context,
                         ).map_err(move |_| error!("Error rolling back balance change for accounts: {} and {}. Incoming amount was: {}, outgoing amount was: {}", from_clone.id(), to_clone.id(), incoming_amount, outgoing_amount)).await
                     }
                 });
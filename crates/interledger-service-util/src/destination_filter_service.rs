@@ -0,0 +1,277 @@
+use async_trait::async_trait;
+use interledger_packet::{ErrorCode, RejectBuilder};
+use interledger_service::*;
+use log::debug;
+use std::marker::PhantomData;
+
+/// Extension trait for [`Account`](../interledger_service/trait.Account.html) with the
+/// address prefixes the account may and may not send packets to.
+/// The prefixes are matched against the start of the destination address,
+/// the same way the Router matches its routes.
+pub trait DestinationFilterAccount: Account {
+    /// Prefixes the account may send packets to. If this is empty,
+    /// the account may send packets to any destination which is not denied
+    fn allowed_destination_prefixes(&self) -> &[String] {
+        &[]
+    }
+
+    /// Prefixes the account may not send packets to, even if they are allowed
+    fn denied_destination_prefixes(&self) -> &[String] {
+        &[]
+    }
+}
+
+/// Returns true if the account may send packets to the destination
+pub fn is_destination_allowed<A: DestinationFilterAccount>(account: &A, destination: &str) -> bool {
+    let matches = |prefixes: &[String]| {
+        prefixes
+            .iter()
+            .any(|prefix| destination.starts_with(prefix.as_str()))
+    };
+    let allowed = account.allowed_destination_prefixes();
+    !matches(account.denied_destination_prefixes()) && (allowed.is_empty() || matches(allowed))
+}
+
+/// # Destination Filter Service
+///
+/// Incoming Service which rejects packets from accounts to destinations they
+/// are not allowed to send to. This can stop a child from paying into sanctioned
+/// or test networks, or stop a peer from using the node to reach anyone but the
+/// node's own accounts.
+///
+/// Packets to denied prefixes are always rejected. If the account has any allowed
+/// prefixes, packets to destinations which match none of them are rejected too.
+/// Rejects use `F02: Unreachable` unless another code is configured.
+///
+/// This should come right before the Router, so that the node's own protocols
+/// (such as ILDCP and CCP) are not filtered.
+/// Requires a `DestinationFilterAccount` and an `AddressStore`.
+#[derive(Clone)]
+pub struct DestinationFilterService<S, I, A> {
+    store: S,
+    next: I,
+    reject_code: ErrorCode,
+    account_type: PhantomData<A>,
+}
+
+impl<S, I, A> DestinationFilterService<S, I, A>
+where
+    S: AddressStore,
+    I: IncomingService<A>,
+    A: DestinationFilterAccount,
+{
+    pub fn new(store: S, next: I) -> Self {
+        DestinationFilterService {
+            store,
+            next,
+            reject_code: ErrorCode::F02_UNREACHABLE,
+            account_type: PhantomData,
+        }
+    }
+
+    /// Reject packets to destinations which are not allowed with the given code
+    pub fn reject_code(&mut self, reject_code: ErrorCode) -> &mut Self {
+        self.reject_code = reject_code;
+        self
+    }
+}
+
+#[async_trait]
+impl<S, I, A> IncomingService<A> for DestinationFilterService<S, I, A>
+where
+    S: AddressStore + Send + Sync + 'static,
+    I: IncomingService<A> + Send + Sync + 'static,
+    A: DestinationFilterAccount + Send + Sync + 'static,
+{
+    /// On receive request:
+    /// 1. if the account the request is from may send packets to its destination, forward the request, else reject
+    async fn handle_request(&mut self, request: IncomingRequest<A>) -> IlpResult {
+        let destination = request.prepare.destination();
+        if is_destination_allowed(&request.from, &destination) {
+            self.next.handle_request(request).await
+        } else {
+            debug!(
+                "Rejecting packet from account {} to destination {} which it may not send to",
+                request.from.id(),
+                destination
+            );
+            Err(RejectBuilder {
+                code: self.reject_code,
+                message: format!("Destination is not allowed: {}", destination).as_bytes(),
+                triggered_by: Some(&self.store.get_ilp_address()),
+                data: &[],
+            }
+            .build())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interledger_packet::{Address, FulfillBuilder, PrepareBuilder};
+    use interledger_service::{incoming_service_fn, Username};
+    use lazy_static::lazy_static;
+    use std::{
+        str::FromStr,
+        time::{Duration, SystemTime},
+    };
+    use uuid::Uuid;
+
+    lazy_static! {
+        pub static ref ALICE: Username = Username::from_str("alice").unwrap();
+        pub static ref EXAMPLE_ADDRESS: Address = Address::from_str("example.alice").unwrap();
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestAccount {
+        allowed: Vec<String>,
+        denied: Vec<String>,
+    }
+
+    impl TestAccount {
+        fn new(allowed: &[&str], denied: &[&str]) -> Self {
+            TestAccount {
+                allowed: allowed.iter().map(|prefix| prefix.to_string()).collect(),
+                denied: denied.iter().map(|prefix| prefix.to_string()).collect(),
+            }
+        }
+    }
+
+    impl Account for TestAccount {
+        fn id(&self) -> Uuid {
+            Uuid::nil()
+        }
+
+        fn username(&self) -> &Username {
+            &ALICE
+        }
+
+        fn asset_code(&self) -> &str {
+            "XYZ"
+        }
+
+        fn asset_scale(&self) -> u8 {
+            9
+        }
+
+        fn ilp_address(&self) -> &Address {
+            &EXAMPLE_ADDRESS
+        }
+    }
+
+    impl DestinationFilterAccount for TestAccount {
+        fn allowed_destination_prefixes(&self) -> &[String] {
+            &self.allowed
+        }
+
+        fn denied_destination_prefixes(&self) -> &[String] {
+            &self.denied
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestStore;
+
+    #[async_trait]
+    impl AddressStore for TestStore {
+        async fn set_ilp_address(&self, _ilp_address: Address) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        async fn clear_ilp_address(&self) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn get_ilp_address(&self) -> Address {
+            Address::from_str("example.connector").unwrap()
+        }
+    }
+
+    fn incoming_request(from: TestAccount, destination: &str) -> IncomingRequest<TestAccount> {
+        IncomingRequest {
+            from,
+            prepare: PrepareBuilder {
+                destination: Address::from_str(destination).unwrap(),
+                amount: 100,
+                expires_at: SystemTime::now() + Duration::from_secs(30),
+                data: &[],
+                execution_condition: &[0; 32],
+            }
+            .build(),
+        }
+    }
+
+    fn fulfilling_service() -> impl IncomingService<TestAccount> + Clone + Send + Sync {
+        incoming_service_fn(|_| {
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        })
+    }
+
+    #[test]
+    fn checks_allowed_and_denied_prefixes() {
+        let account = TestAccount::new(&[], &[]);
+        assert!(is_destination_allowed(&account, "g.anyone"));
+
+        let account = TestAccount::new(&[], &["g.sanctioned.", "test"]);
+        assert!(is_destination_allowed(&account, "g.anyone"));
+        assert!(is_destination_allowed(&account, "g.sanctioned"));
+        assert!(!is_destination_allowed(&account, "g.sanctioned.bob"));
+        assert!(!is_destination_allowed(&account, "test1.bob"));
+
+        let account = TestAccount::new(&["example.", "g.partner."], &[]);
+        assert!(is_destination_allowed(&account, "example.bob"));
+        assert!(is_destination_allowed(&account, "g.partner.bob"));
+        assert!(!is_destination_allowed(&account, "g.anyone"));
+
+        // Denied prefixes take precedence over allowed ones
+        let account = TestAccount::new(&["g.partner."], &["g.partner.blocked"]);
+        assert!(is_destination_allowed(&account, "g.partner.bob"));
+        assert!(!is_destination_allowed(&account, "g.partner.blocked.bob"));
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_to_destinations_which_are_not_allowed() {
+        let mut service = DestinationFilterService::new(TestStore, fulfilling_service());
+        let account = TestAccount::new(&["example."], &["example.blocked"]);
+
+        service
+            .handle_request(incoming_request(account.clone(), "example.bob"))
+            .await
+            .unwrap();
+        for destination in &["example.blocked.bob", "g.anyone"] {
+            let reject = service
+                .handle_request(incoming_request(account.clone(), destination))
+                .await
+                .unwrap_err();
+            assert_eq!(reject.code(), ErrorCode::F02_UNREACHABLE);
+            assert_eq!(
+                reject.triggered_by(),
+                Some(Address::from_str("example.connector").unwrap())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_with_the_configured_code() {
+        let mut service = DestinationFilterService::new(TestStore, fulfilling_service());
+        service.reject_code(ErrorCode::F00_BAD_REQUEST);
+
+        let reject = service
+            .handle_request(incoming_request(
+                TestAccount::new(&[], &["g.sanctioned."]),
+                "g.sanctioned.bob",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(reject.code(), ErrorCode::F00_BAD_REQUEST);
+        assert_eq!(
+            reject.message(),
+            b"Destination is not allowed: g.sanctioned.bob"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{ExchangeRate, ExchangeRateProvider};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Combines the rates of several exchange rate providers
+#[derive(Debug, Clone, Deserialize)]
+pub struct AggregateProvider {
+    /// The providers to query, in order of preference
+    pub providers: Vec<ExchangeRateProvider>,
+    /// How far, as a fraction of the median, a rate may be from the median
+    /// of all the providers' rates for the same asset before it is discarded
+    #[serde(default = "AggregateProvider::default_max_deviation")]
+    pub max_deviation: f64,
+}
+
+impl AggregateProvider {
+    fn default_max_deviation() -> f64 {
+        0.05
+    }
+}
+
+/// Combines the rates returned by the providers which responded, ordered by preference.
+///
+/// For each asset, rates which deviate from the median of all the rates by more than
+/// `max_deviation` are discarded and the median of the remaining rates is used.
+/// If every rate was discarded (which can happen when an even number of providers disagree),
+/// this falls back to the rate of the first provider in order.
+/// The rates are only compared as floats, so the rate used is exactly one of the
+/// providers' rates or the midpoint of two of them.
+pub fn aggregate_rates(
+    responses: &[HashMap<String, ExchangeRate>],
+    max_deviation: f64,
+) -> HashMap<String, ExchangeRate> {
+    let asset_codes: HashSet<&String> = responses.iter().flat_map(|rates| rates.keys()).collect();
+    asset_codes
+        .into_iter()
+        .filter_map(|asset_code| {
+            let rates: Vec<&ExchangeRate> = responses
+                .iter()
+                .filter_map(|rates| rates.get(asset_code))
+                .collect();
+            let middle = median(&rates)?.to_f64();
+            let consensus: Vec<&ExchangeRate> = rates
+                .iter()
+                .cloned()
+                .filter(|rate| (rate.to_f64() - middle).abs() <= max_deviation * middle)
+                .collect();
+            let rate = median(&consensus).unwrap_or_else(|| rates[0].clone());
+            Some((asset_code.clone(), rate))
+        })
+        .collect()
+}
+
+fn median(rates: &[&ExchangeRate]) -> Option<ExchangeRate> {
+    if rates.is_empty() {
+        return None;
+    }
+    let mut rates = rates.to_vec();
+    // Rates are finite, so they can be compared
+    rates.sort_by(|a, b| a.to_f64().partial_cmp(&b.to_f64()).unwrap());
+    let middle = rates.len() / 2;
+    if rates.len() % 2 == 0 {
+        Some(rates[middle - 1].midpoint(rates[middle]))
+    } else {
+        Some(rates[middle].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn rates(rates: &[(&str, &str)]) -> HashMap<String, ExchangeRate> {
+        rates
+            .iter()
+            .map(|(asset_code, rate)| {
+                (
+                    asset_code.to_string(),
+                    ExchangeRate::from_str(rate).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn takes_the_median_without_outliers() {
+        let aggregated = aggregate_rates(
+            &[
+                rates(&[("ABC", "1"), ("XYZ", "10")]),
+                rates(&[("ABC", "1.02"), ("XYZ", "1000")]),
+                rates(&[("ABC", "1.04"), ("XYZ", "10.5")]),
+            ],
+            0.05,
+        );
+        assert_eq!(aggregated, rates(&[("ABC", "1.02"), ("XYZ", "10.25")]));
+    }
+
+    #[test]
+    fn falls_back_in_order() {
+        // Only one provider knows the rate
+        let aggregated = aggregate_rates(&[rates(&[]), rates(&[("ABC", "2")])], 0.05);
+        assert_eq!(aggregated, rates(&[("ABC", "2")]));
+
+        // Two providers disagree
+        let aggregated = aggregate_rates(&[rates(&[("ABC", "2")]), rates(&[("ABC", "3")])], 0.05);
+        assert_eq!(aggregated, rates(&[("ABC", "2")]));
+
+        // Two providers agree
+        let aggregated =
+            aggregate_rates(&[rates(&[("ABC", "4")]), rates(&[("ABC", "4.125")])], 0.05);
+        assert_eq!(aggregated, rates(&[("ABC", "4.0625")]));
+    }
+
+    #[test]
+    fn keeps_the_exact_rates() {
+        let aggregated = aggregate_rates(
+            &[
+                rates(&[("ABC", "0.1000000000000000000001"), ("XYZ", "0.1")]),
+                rates(&[("ABC", "0.1000000000000000000003"), ("XYZ", "0.2")]),
+                rates(&[("XYZ", "0.3")]),
+            ],
+            0.05,
+        );
+        assert_eq!(
+            aggregated,
+            rates(&[("ABC", "0.1000000000000000000002"), ("XYZ", "0.2")])
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ExchangeRate;
 use futures::TryFutureExt;
 use lazy_static::lazy_static;
 use log::{error, warn};
@@ -25,27 +26,29 @@ struct RateResponse {
     data: Vec<Rate>,
 }
 
-pub async fn query_coincap(client: &Client) -> Result<HashMap<String, f64>, ()> {
+pub async fn query_coincap(client: &Client) -> Result<HashMap<String, ExchangeRate>, ()> {
     let (assets, rates) = futures::future::join(
         query_coincap_endpoint(client, COINCAP_ASSETS_URL.clone()),
         query_coincap_endpoint(client, COINCAP_RATES_URL.clone()),
     )
     .await;
 
-    let all_rates: HashMap<String, f64> = assets?
+    let all_rates: HashMap<String, ExchangeRate> = assets?
         .data
         .into_iter()
         .chain(rates?.data.into_iter())
-        .filter_map(|record| match f64::from_str(record.rate_usd.as_str()) {
-            Ok(rate) => Some((record.symbol.to_uppercase(), rate)),
-            Err(err) => {
-                warn!(
-                    "Unable to parse {} rate as an f64: {} {:?}",
-                    record.symbol, record.rate_usd, err
-                );
-                None
-            }
-        })
+        .filter_map(
+            |record| match ExchangeRate::from_str(record.rate_usd.as_str()) {
+                Ok(rate) => Some((record.symbol.to_uppercase(), rate)),
+                Err(err) => {
+                    warn!(
+                        "Unable to parse {} rate: {} {:?}",
+                        record.symbol, record.rate_usd, err
+                    );
+                    None
+                }
+            },
+        )
         .collect();
     Ok(all_rates)
 }
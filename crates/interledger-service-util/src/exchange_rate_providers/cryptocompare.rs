@@ -1,3 +1,4 @@
+use crate::ExchangeRate;
 use futures::TryFutureExt;
 use lazy_static::lazy_static;
 use log::error;
@@ -50,7 +51,7 @@ struct Response {
 pub async fn query_cryptocompare(
     client: &Client,
     api_key: &SecretString,
-) -> Result<HashMap<String, f64>, ()> {
+) -> Result<HashMap<String, ExchangeRate>, ()> {
     // ref: https://github.com/rust-lang/rust/pull/64856
     let header = format!("Apikey {}", api_key.expose_secret());
     let res = client
@@ -87,12 +88,9 @@ pub async fn query_cryptocompare(
         .data
         .into_iter()
         .filter_map(|asset| {
-            if let Some(raw) = asset.raw {
-                Some((asset.coin_info.name.to_uppercase(), raw.usd.price))
-            } else {
-                None
-            }
+            let rate = ExchangeRate::from_f64(asset.raw?.usd.price)?;
+            Some((asset.coin_info.name.to_uppercase(), rate))
         })
-        .chain(once(("USD".to_string(), 1.0)));
+        .chain(once(("USD".to_string(), ExchangeRate::one())));
     Ok(HashMap::from_iter(rates))
 }
@@ -0,0 +1,92 @@
+use super::json_endpoint::rates_from_json;
+use crate::ExchangeRate;
+use log::error;
+use serde_json::Value;
+use std::{collections::HashMap, path::Path};
+
+/// Reads the rates from a file containing an object of `asset code -> rate`.
+/// Files with the `.toml` extension are parsed as TOML and all others as JSON.
+pub async fn query_file(path: &Path) -> Result<HashMap<String, ExchangeRate>, ()> {
+    let contents = tokio::fs::read_to_string(path).await.map_err(|err| {
+        error!(
+            "Error reading exchange rates file {}: {:?}",
+            path.display(),
+            err
+        )
+    })?;
+
+    let is_toml = path
+        .extension()
+        .map_or(false, |extension| extension == "toml");
+    let rates: Value = if is_toml {
+        let rates: toml::Value = toml::from_str(&contents).map_err(|err| {
+            error!(
+                "Exchange rates file {} is not valid TOML: {:?}",
+                path.display(),
+                err
+            )
+        })?;
+        serde_json::to_value(rates).map_err(|err| {
+            error!(
+                "Error converting exchange rates file {}: {:?}",
+                path.display(),
+                err
+            )
+        })?
+    } else {
+        serde_json::from_str(&contents).map_err(|err| {
+            error!(
+                "Exchange rates file {} is not valid JSON: {:?}",
+                path.display(),
+                err
+            )
+        })?
+    };
+    rates_from_json(&rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+    use uuid::Uuid;
+
+    fn write_file(extension: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rates-{}.{}", Uuid::new_v4(), extension));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn reads_json_and_toml_files() {
+        let path = write_file("json", r#"{"ABC": 1.5, "XYZ": "2"}"#);
+        let rates = query_file(&path).await.unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates["ABC"].as_ref(), "1.5");
+        assert_eq!(rates["XYZ"].as_ref(), "2");
+
+        // The file is read again on every call
+        fs::write(&path, r#"{"ABC": 3}"#).unwrap();
+        let rates = query_file(&path).await.unwrap();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates["ABC"].as_ref(), "3");
+        fs::remove_file(&path).unwrap();
+
+        let path = write_file("toml", "ABC = 1.5\nXYZ = 2\n");
+        let rates = query_file(&path).await.unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates["ABC"].as_ref(), "1.5");
+        assert_eq!(rates["XYZ"].as_ref(), "2");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_files() {
+        let path = write_file("json", "ABC = 1.5");
+        assert!(query_file(&path).await.is_err());
+        fs::remove_file(&path).unwrap();
+
+        let missing = env::temp_dir().join(format!("rates-{}.json", Uuid::new_v4()));
+        assert!(query_file(&missing).await.is_err());
+    }
+}
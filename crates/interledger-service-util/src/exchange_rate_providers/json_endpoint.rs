@@ -0,0 +1,185 @@
+use crate::ExchangeRate;
+use futures::TryFutureExt;
+use log::{error, warn};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::HashMap, str::FromStr};
+
+/// An HTTP endpoint which returns the exchange rates in a JSON document
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonEndpoint {
+    /// URL to send the GET requests to
+    pub url: String,
+    /// Path to the object of `asset code -> rate` in the response, written in the subset of
+    /// [JSONPath](https://goessner.net/articles/JsonPath/) which selects a single value
+    /// with dots and brackets (for example `$.data.rates` or `$['data'][0]`).
+    /// The rates may be numbers or strings containing numbers.
+    #[serde(default = "JsonEndpoint::default_rates_path")]
+    pub rates_path: String,
+}
+
+impl JsonEndpoint {
+    fn default_rates_path() -> String {
+        "$".to_string()
+    }
+}
+
+pub async fn query_json_endpoint(
+    client: &Client,
+    endpoint: &JsonEndpoint,
+) -> Result<HashMap<String, ExchangeRate>, ()> {
+    let url = Url::parse(&endpoint.url).map_err(|err| {
+        error!(
+            "Invalid URL for exchange rate endpoint {}: {:?}",
+            endpoint.url, err
+        )
+    })?;
+    let res = client
+        .get(url)
+        .send()
+        .map_err(|err| {
+            error!(
+                "Error fetching exchange rates from {}: {:?}",
+                endpoint.url, err
+            );
+        })
+        .await?;
+
+    let res = res.error_for_status().map_err(|err| {
+        error!(
+            "HTTP error getting exchange rates from {}: {:?}",
+            endpoint.url, err
+        );
+    })?;
+
+    let body: Value = res
+        .json()
+        .map_err(|err| {
+            error!(
+                "Error getting exchange rate response body from {}, invalid JSON: {:?}",
+                endpoint.url, err
+            );
+        })
+        .await?;
+
+    let rates = select(&body, &endpoint.rates_path).ok_or_else(|| {
+        error!(
+            "Exchange rate response from {} has nothing at path: {}",
+            endpoint.url, endpoint.rates_path
+        )
+    })?;
+    rates_from_json(rates)
+}
+
+/// Parses an object of `asset code -> rate`, where the rates are numbers or numeric strings.
+/// Strings are parsed exactly, while numbers are only as precise as a float.
+/// Rates which cannot be parsed are skipped.
+pub(crate) fn rates_from_json(rates: &Value) -> Result<HashMap<String, ExchangeRate>, ()> {
+    let rates = rates.as_object().ok_or_else(|| {
+        error!(
+            "Exchange rates must be an object of asset code to rate, got: {}",
+            rates
+        )
+    })?;
+    Ok(rates
+        .iter()
+        .filter_map(|(asset_code, rate)| {
+            let parsed = match rate {
+                Value::Number(number) => ExchangeRate::from_str(&number.to_string()).ok(),
+                Value::String(string) => ExchangeRate::from_str(string).ok(),
+                _ => None,
+            };
+            if parsed.is_none() {
+                warn!("Unable to parse {} rate: {}", asset_code, rate);
+            }
+            Some((asset_code.clone(), parsed?))
+        })
+        .collect())
+}
+
+/// Selects the value at the path, which must start with `$` (the whole document)
+/// followed by any number of `.key`, `['key']`, `["key"]` or `[index]` segments
+pub(crate) fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    if !path.starts_with('$') {
+        return None;
+    }
+    let mut rest = &path[1..];
+    let mut value = value;
+    while !rest.is_empty() {
+        if rest.starts_with('.') {
+            let end = rest[1..]
+                .find(|c: char| c == '.' || c == '[')
+                .map_or(rest.len(), |i| i + 1);
+            value = value.get(&rest[1..end])?;
+            rest = &rest[end..];
+        } else if rest.starts_with('[') {
+            let end = rest.find(']')?;
+            let segment = rest[1..end].trim();
+            value = if segment.len() >= 2
+                && (segment.starts_with('\'') && segment.ends_with('\'')
+                    || segment.starts_with('"') && segment.ends_with('"'))
+            {
+                value.get(&segment[1..segment.len() - 1])?
+            } else {
+                value.get(usize::from_str(segment).ok()?)?
+            };
+            rest = &rest[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn selects_paths() {
+        let document = json!({
+            "data": {
+                "rates": { "ABC": 1.5 },
+                "list": [{ "XYZ": "2" }]
+            },
+            "odd.key": 3
+        });
+        assert_eq!(select(&document, "$"), Some(&document));
+        assert_eq!(
+            select(&document, "$.data.rates"),
+            Some(&json!({ "ABC": 1.5 }))
+        );
+        assert_eq!(
+            select(&document, "$['data'][\"list\"][0]"),
+            Some(&json!({ "XYZ": "2" }))
+        );
+        assert_eq!(select(&document, "$['odd.key']"), Some(&json!(3)));
+        assert_eq!(select(&document, "$.data.missing"), None);
+        assert_eq!(select(&document, "$.data.list[1]"), None);
+        assert_eq!(select(&document, "data.rates"), None);
+        assert_eq!(select(&document, "$.data[rates]"), None);
+    }
+
+    #[test]
+    fn parses_numbers_and_numeric_strings() {
+        let rates = rates_from_json(&json!({
+            "ABC": 1.5,
+            "XYZ": "2",
+            "USD": 1,
+            "EXACT": "0.1000000000000000000001",
+            "BAD": "not a number",
+            "NULL": null
+        }))
+        .unwrap();
+        assert_eq!(rates.len(), 4);
+        assert_eq!(rates["ABC"].as_ref(), "1.5");
+        assert_eq!(rates["XYZ"].as_ref(), "2");
+        assert_eq!(rates["USD"].as_ref(), "1");
+        assert_eq!(rates["EXACT"].as_ref(), "0.1000000000000000000001");
+
+        assert!(rates_from_json(&json!([1, 2])).is_err());
+    }
+}
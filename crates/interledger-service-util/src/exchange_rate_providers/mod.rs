@@ -1,7 +1,16 @@
+/// Exchange rate provider which combines the rates of several other providers
+mod aggregate;
 /// Exchange rate provider for [CoinCap](https://coincap.io/)
 mod coincap;
 /// Exchange rate provider for [CryptoCompare](https://www.cryptocompare.com/). REQUIRES [API KEY](https://min-api.cryptocompare.com/).
 mod cryptocompare;
+/// Exchange rate provider which reads the rates from a local JSON or TOML file
+mod file;
+/// Exchange rate provider for any HTTP endpoint which returns the rates as JSON
+mod json_endpoint;
 
+pub use aggregate::*;
 pub use coincap::*;
 pub use cryptocompare::*;
+pub use file::*;
+pub use json_endpoint::*;
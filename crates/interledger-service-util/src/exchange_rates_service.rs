@@ -1,21 +1,31 @@
 use super::exchange_rate_providers::*;
 use async_trait::async_trait;
-use futures::TryFutureExt;
+use futures::{
+    future::{join_all, BoxFuture},
+    TryFutureExt,
+};
 use interledger_packet::{ErrorCode, RejectBuilder};
 use interledger_service::*;
-use interledger_settlement::core::types::{Convert, ConvertDetails};
 use log::{debug, error, trace, warn};
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
 use reqwest::Client;
 use secrecy::SecretString;
-use serde::Deserialize;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize,
+};
 use std::{
     collections::HashMap,
+    fmt,
     marker::PhantomData,
+    path::PathBuf,
+    str::FromStr,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 // TODO should this whole file be moved to its own crate?
@@ -24,11 +34,21 @@ use std::{
 pub trait ExchangeRateStore: Clone {
     // TODO we may want to make this async if/when we use pubsub to broadcast
     // rate changes to different instances of a horizontally-scalable node
-    /// Sets the exchange rate by providing an AssetCode->USD price mapping
-    fn set_exchange_rates(&self, rates: HashMap<String, f64>) -> Result<(), ()>;
+    /// Sets the exchange rate by providing an AssetCode->USD price mapping.
+    /// The current time is recorded as the time each of the rates was last updated,
+    /// and the rates are appended to the rate history along with their `source`
+    /// (the name of the exchange rate provider, or how else they were set)
+    fn set_exchange_rates(
+        &self,
+        rates: HashMap<String, ExchangeRate>,
+        source: &str,
+    ) -> Result<(), ()>;
 
     /// Gets the exchange rates for the provided asset codes
-    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<f64>, ()>;
+    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()>;
+
+    /// Gets the times at which the exchange rates for the provided asset codes were last updated
+    fn get_exchange_rates_updated_at(&self, asset_codes: &[&str]) -> Result<Vec<SystemTime>, ()>;
 
     // TODO should this be on the API instead? That's where it's actually used
     // TODO should we combine this method with get_exchange_rates?
@@ -37,18 +57,342 @@ pub trait ExchangeRateStore: Clone {
     // but in the normal case of getting the rate between two assets, we don't want to
     // copy all the rate data
     /// Gets the exchange rates for all stored asset codes
-    fn get_all_exchange_rates(&self) -> Result<HashMap<String, f64>, ()>;
+    fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()>;
+
+    /// Replaces the spreads applied on top of the exchange rates. The spreads are
+    /// saved (if the store persists data), so they are kept when the node restarts
+    fn set_spreads(&self, spreads: Spreads) -> Result<(), ()>;
+
+    /// Sets the spreads to apply until spreads are set with `set_spreads`,
+    /// such as those of the node's configuration. These are not saved
+    fn set_default_spreads(&self, spreads: Spreads) -> Result<(), ()>;
+
+    /// Gets the spread to apply when converting from one asset to another
+    fn get_spread(&self, from_asset_code: &str, to_asset_code: &str) -> f64;
+
+    /// Gets the default spread and the spreads of all configured asset pairs
+    fn get_all_spreads(&self) -> Result<Spreads, ()>;
+
+    /// Replaces the quotes for converting directly between pairs of assets.
+    /// The current time is recorded as the time each of the quotes was last updated
+    fn set_pair_quotes(&self, quotes: Vec<PairQuote>) -> Result<(), ()>;
+
+    /// Gets the quote with the provided base and quote assets and the time it was last updated
+    fn get_pair_quote(&self, base: &str, quote: &str) -> Option<(PairQuote, SystemTime)>;
+
+    /// Gets the quotes of all pairs of assets
+    fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()>;
+}
+
+/// The largest exponent accepted when parsing rates written in exponent notation,
+/// which keeps rates such as `1e999999999` from being expanded into huge strings
+const MAX_RATE_EXPONENT: i32 = 400;
+
+/// An exchange rate (the price of an asset in the base asset), kept as the exact
+/// decimal number it was set as instead of the binary float closest to it.
+///
+/// Rates are non-negative and may be parsed from plain decimals or from exponent
+/// notation (`1.5e-3`). They are normalized to a plain decimal without redundant
+/// zeros, so equal rates have the same string. Rates are serialized as strings and
+/// may be deserialized from strings or numbers, though numbers are only as precise
+/// as the float they are parsed into.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct ExchangeRate(String);
+
+impl ExchangeRate {
+    /// The rate of the base asset itself
+    pub fn one() -> Self {
+        ExchangeRate("1".to_string())
+    }
+
+    /// Uses the shortest decimal which parses back to the float, so a rate of 0.1
+    /// becomes exactly `0.1`. Returns `None` for negative, infinite and NaN values.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        // abs turns -0.0 into 0.0, which would otherwise be displayed with its sign
+        ExchangeRate::from_str(&value.abs().to_string()).ok()
+    }
+
+    /// The float closest to the rate
+    pub fn to_f64(&self) -> f64 {
+        // Rates are only created from decimals which parse as finite floats
+        f64::from_str(&self.0).unwrap_or(0.0)
+    }
+
+    /// Returns the digits of the rate without the decimal point and the number of
+    /// them after the decimal point, such that the rate is `digits / 10^scale`
+    fn to_parts(&self) -> (BigUint, usize) {
+        let (integer, fraction) = split_decimal(&self.0);
+        let digits = BigUint::parse_bytes([integer, fraction].concat().as_bytes(), 10)
+            .unwrap_or_else(BigUint::zero);
+        (digits, fraction.len())
+    }
+
+    /// Creates the rate `digits / 10^scale`
+    fn from_parts(digits: &BigUint, scale: usize) -> Self {
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        ExchangeRate(normalize_decimal(integer, fraction))
+    }
+
+    /// The rate halfway between the two rates, which is exact because
+    /// halving a decimal only ever adds one more decimal place
+    pub(crate) fn midpoint(&self, other: &ExchangeRate) -> Self {
+        let (digits, digits_scale) = self.to_parts();
+        let (other_digits, other_scale) = other.to_parts();
+        let scale = digits_scale.max(other_scale);
+        let sum = digits * power_of_ten(scale - digits_scale)
+            + other_digits * power_of_ten(scale - other_scale);
+        // x / 2 = 5x / 10
+        ExchangeRate::from_parts(&(sum * BigUint::from(5u8)), scale + 1)
+    }
+}
+
+impl fmt::Display for ExchangeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for ExchangeRate {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl FromStr for ExchangeRate {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid exchange rate: {}", src);
+        let (mantissa, exponent) = match src.find(&['e', 'E'][..]) {
+            Some(index) => (
+                &src[..index],
+                i32::from_str(&src[index + 1..]).map_err(|_| invalid())?,
+            ),
+            None => (src, 0),
+        };
+        if exponent.abs() > MAX_RATE_EXPONENT {
+            return Err(invalid());
+        }
+        let (integer, fraction) = split_decimal(mantissa);
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        // Move the decimal point by the exponent
+        let digits = [integer, fraction].concat();
+        let point = integer.len() as i32 + exponent;
+        let rate = if point <= 0 {
+            let fraction = format!("{}{}", "0".repeat(-point as usize), digits);
+            normalize_decimal("", &fraction)
+        } else if point as usize >= digits.len() {
+            let integer = format!("{}{}", digits, "0".repeat(point as usize - digits.len()));
+            normalize_decimal(&integer, "")
+        } else {
+            let (integer, fraction) = digits.split_at(point as usize);
+            normalize_decimal(integer, fraction)
+        };
+
+        // Rates are also used as floats, so they must not be too large for one
+        if !f64::from_str(&rate).map_or(false, f64::is_finite) {
+            return Err(invalid());
+        }
+        Ok(ExchangeRate(rate))
+    }
+}
+
+impl<'de> Deserialize<'de> for ExchangeRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RateVisitor;
+
+        impl<'de> Visitor<'de> for RateVisitor {
+            type Value = ExchangeRate;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a non-negative decimal number or string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<ExchangeRate, E> {
+                ExchangeRate::from_str(value).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<ExchangeRate, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<ExchangeRate, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<ExchangeRate, E> {
+                ExchangeRate::from_f64(value)
+                    .ok_or_else(|| E::custom(format!("invalid exchange rate: {}", value)))
+            }
+        }
+
+        deserializer.deserialize_any(RateVisitor)
+    }
+}
+
+/// Splits a decimal into the digits before and after the decimal point
+fn split_decimal(decimal: &str) -> (&str, &str) {
+    match decimal.find('.') {
+        Some(index) => (&decimal[..index], &decimal[index + 1..]),
+        None => (decimal, ""),
+    }
+}
+
+/// Joins the digits before and after the decimal point without leading or trailing zeros
+fn normalize_decimal(integer: &str, fraction: &str) -> String {
+    let integer = integer.trim_start_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+/// A rate set for an asset, as recorded in the exchange rate history
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRateRecord {
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub asset_code: String,
+    pub rate: ExchangeRate,
+    /// The name of the exchange rate provider, `api` for rates set via the
+    /// HTTP API or `import` for rates imported from a node snapshot
+    pub source: String,
+}
+
+/// The number of rates of each asset kept in the exchange rate history.
+/// The oldest rates of an asset are deleted once it has more
+pub const EXCHANGE_RATE_HISTORY_LENGTH: usize = 100_000;
+
+/// Which records of the exchange rate history to return
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExchangeRateHistoryQuery {
+    /// Only return the rates of this asset
+    pub asset_code: Option<String>,
+    /// Only return rates set at or after this time, in milliseconds since the UNIX epoch
+    pub from: u64,
+    /// Only return rates set at or before this time, in milliseconds since the UNIX epoch
+    pub to: u64,
+    /// Only return records after this one (the `next_cursor` of the previous page).
+    /// The format of the cursor depends on the store
+    pub cursor: Option<String>,
+    /// The maximum number of records to return
+    pub limit: usize,
+}
+
+/// One page of the records returned for an `ExchangeRateHistoryQuery`, oldest first
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExchangeRateHistoryPage {
+    pub records: Vec<ExchangeRateRecord>,
+    /// The cursor to pass in the next query, or `None` if this is the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Store trait responsible for keeping the exchange rates that were set (up to
+/// `EXCHANGE_RATE_HISTORY_LENGTH` of each asset), so that the rate applied to
+/// a packet can still be determined after the rates changed
+#[async_trait]
+pub trait ExchangeRateHistoryStore {
+    /// Returns a page of the rates set with timestamps between `from` and `to` (inclusive),
+    /// oldest first. If an asset code is given, only the rates of that asset are returned
+    async fn get_exchange_rate_history(
+        &self,
+        query: ExchangeRateHistoryQuery,
+    ) -> Result<ExchangeRateHistoryPage, ()>;
+}
+
+/// A quote for converting directly between two assets, such as one from a liquidity
+/// provider. It takes precedence over the rates of the two assets against the base asset
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PairQuote {
+    /// Asset code of the asset being priced
+    pub base: String,
+    /// Asset code of the asset the prices are denominated in
+    pub quote: String,
+    /// Units of the quote asset paid for one unit of the base asset
+    pub bid: f64,
+    /// Units of the quote asset charged for one unit of the base asset
+    pub ask: f64,
+}
+
+impl PairQuote {
+    /// Whether the quote is between two different assets and has positive prices,
+    /// with the bid not above the ask
+    pub fn is_valid(&self) -> bool {
+        self.base != self.quote
+            && self.bid.is_finite()
+            && self.ask.is_finite()
+            && self.bid > 0.0
+            && self.bid <= self.ask
+    }
+}
+
+/// The spreads, as fractions, taken on top of the exchange rate when converting between assets
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Spreads {
+    /// Spread of the pairs without a spread of their own, including same-currency pairs
+    #[serde(default)]
+    pub default: f64,
+    /// Source asset code -> destination asset code -> spread
+    #[serde(default)]
+    pub pairs: HashMap<String, HashMap<String, f64>>,
+}
+
+impl Spreads {
+    /// Creates a table which applies the same spread to every pair
+    pub fn new(default: f64) -> Self {
+        Spreads {
+            default,
+            pairs: HashMap::new(),
+        }
+    }
+
+    /// Whether every spread is a fraction between 0 and 1 (inclusive)
+    pub fn is_valid(&self) -> bool {
+        let is_valid = |spread: &f64| spread.is_finite() && *spread >= 0.0 && *spread <= 1.0;
+        is_valid(&self.default)
+            && self
+                .pairs
+                .values()
+                .flat_map(|spreads| spreads.values())
+                .all(is_valid)
+    }
+
+    /// Returns the spread of the pair, falling back to the default spread
+    pub fn get(&self, from_asset_code: &str, to_asset_code: &str) -> f64 {
+        self.pairs
+            .get(from_asset_code)
+            .and_then(|spreads| spreads.get(to_asset_code))
+            .cloned()
+            .unwrap_or(self.default)
+    }
 }
 
 /// # Exchange Rates Service
 ///
-/// Responsible for getting the exchange rates for the two assets in the outgoing request (`request.from.asset_code`, `request.to.asset_code`).
+/// Responsible for getting the exchange rates for the two assets in the outgoing request (`request.from.asset_code`, `request.to.asset_code`)
+/// and applying the spread configured for that pair of assets.
+/// If a maximum rate age is set, packets are rejected when either rate is older than that.
 /// Requires a `ExchangeRateStore`
 #[derive(Clone)]
 pub struct ExchangeRateService<S, O, A> {
-    spread: f64,
     store: S,
     next: O,
+    max_rate_age: Option<Duration>,
     account_type: PhantomData<A>,
 }
 
@@ -58,14 +402,43 @@ where
     O: OutgoingService<A>,
     A: Account,
 {
-    pub fn new(spread: f64, store: S, next: O) -> Self {
+    pub fn new(store: S, next: O) -> Self {
         ExchangeRateService {
-            spread,
             store,
             next,
+            max_rate_age: None,
             account_type: PhantomData,
         }
     }
+
+    /// Reject packets instead of converting them with rates older than the given age
+    pub fn max_rate_age(&mut self, max_rate_age: Duration) -> &mut Self {
+        self.max_rate_age = Some(max_rate_age);
+        self
+    }
+
+    /// Returns the first of the asset codes whose rate is older than the maximum rate age
+    fn stale_asset_code<'a>(&self, asset_codes: &[&'a str]) -> Option<&'a str> {
+        self.max_rate_age?;
+        // Missing rates are rejected once the rates themselves are looked up
+        let updated_at = self.store.get_exchange_rates_updated_at(asset_codes).ok()?;
+        asset_codes
+            .iter()
+            .zip(updated_at)
+            .find(|(_, updated_at)| self.is_stale(*updated_at))
+            .map(|(asset_code, _)| *asset_code)
+    }
+
+    fn is_stale(&self, updated_at: SystemTime) -> bool {
+        match self.max_rate_age {
+            // Rates set in the future (for example by a node with a different clock) are fresh
+            Some(max_rate_age) => SystemTime::now()
+                .duration_since(updated_at)
+                .map(|age| age > max_rate_age)
+                .unwrap_or(false),
+            None => false,
+        }
+    }
 }
 
 #[async_trait]
@@ -79,23 +452,56 @@ where
     /// On send request:
     /// 1. If the prepare packet's amount is 0, it just forwards
     /// 1. Retrieves the exchange rate from the store (the store independently is responsible for polling the rates)
+    ///     - uses the quote for the pair of assets if there is one, instead of the rates of both assets
     ///     - return reject if the call to the store fails
-    /// 1. Calculates the exchange rate AND scales it up/down depending on how many decimals each asset requires
-    /// 1. Updates the amount in the prepare packet and forwards it
+    ///     - return reject if the quote or either rate is older than the maximum rate age
+    /// 1. Calculates the exchange rate with the spread of the asset pair AND scales it up/down depending on how many decimals each asset requires
+    /// 1. Updates the amount in the prepare packet, records the rate it was converted with and forwards it
+    ///
+    /// The conversion is done with exact fractions, so the outgoing amount only
+    /// ever loses precision when it is rounded down to a whole number of units.
     async fn send_request(&mut self, mut request: OutgoingRequest<A>) -> IlpResult {
         let ilp_address = self.store.get_ilp_address();
         if request.prepare.amount() > 0 {
-            let rate: f64 = if request.from.asset_code() == request.to.asset_code() {
-                1f64
-            } else if let Ok(rates) = self
-                .store
-                .get_exchange_rates(&[&request.from.asset_code(), &request.to.asset_code()])
-            {
-                // Exchange rates are expressed as `base asset / asset`. To calculate the outgoing amount,
-                // we multiply by the incoming asset's rate and divide by the outgoing asset's rate. For example,
-                // if an incoming packet is denominated in an asset worth 1 USD and the outgoing asset is worth
-                // 10 USD, the outgoing amount will be 1/10th of the source amount.
-                rates[0] / rates[1]
+            let asset_codes = [request.from.asset_code(), request.to.asset_code()];
+            let stale_rate = || {
+                RejectBuilder {
+                    code: ErrorCode::T00_INTERNAL_ERROR,
+                    message: format!(
+                        "Exchange rate from asset: {} to: {} is stale",
+                        request.from.asset_code(),
+                        request.to.asset_code()
+                    )
+                    .as_bytes(),
+                    triggered_by: Some(&ilp_address),
+                    data: &[],
+                }
+                .build()
+            };
+            let rate = if request.from.asset_code() == request.to.asset_code() {
+                Some(Fraction::one())
+            } else if let Some((rate, updated_at)) = quoted_rate(
+                &self.store,
+                request.from.asset_code(),
+                request.to.asset_code(),
+            ) {
+                if self.is_stale(updated_at) {
+                    warn!(
+                        "Quote for assets: {} and {} is older than the maximum rate age, rejecting packet",
+                        request.from.asset_code(),
+                        request.to.asset_code()
+                    );
+                    return Err(stale_rate());
+                }
+                rate
+            } else if let Some(stale_asset_code) = self.stale_asset_code(&asset_codes) {
+                warn!(
+                    "Exchange rate for asset: {} is older than the maximum rate age, rejecting packet",
+                    stale_asset_code
+                );
+                return Err(stale_rate());
+            } else if let Ok(rates) = self.store.get_exchange_rates(&asset_codes) {
+                rate_between(&rates[0], &rates[1])
             } else {
                 error!(
                     "No exchange rates available for assets: {}, {}",
@@ -116,78 +522,44 @@ where
                 .build());
             };
 
-            // Apply spread
-            // TODO should this be applied differently for "local" or same-currency packets?
-            let rate = rate * (1.0 - self.spread);
-            let rate = if rate.is_finite() && rate.is_sign_positive() {
-                rate
-            } else {
-                warn!(
-                    "Exchange rate would have been {} based on rate and spread, using 0.0 instead",
-                    rate
-                );
-                0.0
-            };
+            let rate = with_spread(
+                &self.store,
+                rate,
+                request.from.asset_code(),
+                request.to.asset_code(),
+            );
 
-            // Can we overflow here?
-            let outgoing_amount = (request.prepare.amount() as f64) * rate;
-            let outgoing_amount = outgoing_amount.normalize_scale(ConvertDetails {
-                from: request.from.asset_scale(),
-                to: request.to.asset_scale(),
-            });
-
-            match outgoing_amount {
-                Ok(outgoing_amount) => {
-                    // The conversion succeeded, but the produced f64
-                    // is larger than the maximum value for a u64.
-                    // When it gets cast to a u64, it will end up being 0.
-                    if outgoing_amount != 0.0 && outgoing_amount as u64 == 0 {
-                        let (code, message) = if outgoing_amount < 1.0 {
-                            // user wanted to send a positive value but it got rounded down to 0
-                            (
-                                ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT,
-                                format!(
-                                    "Could not cast to f64, amount too small: {}",
-                                    outgoing_amount
-                                ),
-                            )
-                        } else {
-                            // amount that arrived was too large for us to forward
-                            (
-                                ErrorCode::F08_AMOUNT_TOO_LARGE,
-                                format!(
-                                    "Could not cast to f64, amount too large: {}",
-                                    outgoing_amount
-                                ),
-                            )
-                        };
-
-                        return Err(RejectBuilder {
-                            code,
-                            message: message.as_bytes(),
-                            triggered_by: Some(&ilp_address),
-                            data: &[],
-                        }
-                        .build());
-                    }
-                    request.prepare.set_amount(outgoing_amount as u64);
+            let outgoing_amount = rate.convert(
+                request.prepare.amount(),
+                request.from.asset_scale(),
+                request.to.asset_scale(),
+            );
+            match outgoing_amount.to_u64() {
+                // A rate of 0 (for example, with a spread of 1 or more) means the node takes everything
+                Some(outgoing_amount) if outgoing_amount > 0 || rate.is_zero() => {
+                    request.prepare.set_amount(outgoing_amount);
+                    request.applied_rate = rate.to_f64();
                     trace!("Converted incoming amount of: {} {} (scale {}) from account {} to outgoing amount of: {} {} (scale {}) for account {}",
                         request.original_amount, request.from.asset_code(), request.from.asset_scale(), request.from.id(),
                         outgoing_amount, request.to.asset_code(), request.to.asset_scale(), request.to.id());
                 }
-                Err(_) => {
-                    // This branch gets executed when the `Convert` trait
-                    // returns an error. Happens due to float
-                    // multiplication overflow .
-                    // (float overflow in Rust produces +inf)
+                outgoing_amount => {
+                    let (code, reason) = if outgoing_amount.is_some() {
+                        // user wanted to send a positive value but it got rounded down to 0
+                        (ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT, "too small")
+                    } else {
+                        // amount that arrived was too large for us to forward
+                        (ErrorCode::F08_AMOUNT_TOO_LARGE, "too large")
+                    };
                     return Err(RejectBuilder {
-                        code: ErrorCode::F08_AMOUNT_TOO_LARGE,
+                        code,
                         message: format!(
-                            "Could not convert exchange rate from {}:{} to: {}:{}. Got incoming amount: {}",
+                            "Could not convert amount from {}:{} to: {}:{}, amount {}. Got incoming amount: {}",
                             request.from.asset_code(),
                             request.from.asset_scale(),
                             request.to.asset_code(),
                             request.to.asset_scale(),
+                            reason,
                             request.prepare.amount(),
                         )
                         .as_bytes(),
@@ -203,6 +575,150 @@ where
     }
 }
 
+/// Returns the rate of the quote for converting directly from one asset to the other,
+/// if there is one, and the time the quote was last updated
+fn quoted_rate<S: ExchangeRateStore>(
+    store: &S,
+    from: &str,
+    to: &str,
+) -> Option<(Option<Fraction>, SystemTime)> {
+    if let Some((quote, updated_at)) = store.get_pair_quote(from, to) {
+        // The node is sold the base asset, so it pays the bid
+        Some((Fraction::from_f64(quote.bid), updated_at))
+    } else if let Some((quote, updated_at)) = store.get_pair_quote(to, from) {
+        // The node sells the base asset, so it charges the ask
+        let rate = Fraction::from_f64(quote.ask).and_then(|ask| Fraction::one().divide(&ask));
+        Some((rate, updated_at))
+    } else {
+        None
+    }
+}
+
+/// Exchange rates are expressed as `base asset / asset`. To calculate the outgoing amount,
+/// we multiply by the incoming asset's rate and divide by the outgoing asset's rate. For example,
+/// if an incoming packet is denominated in an asset worth 1 USD and the outgoing asset is worth
+/// 10 USD, the outgoing amount will be 1/10th of the source amount.
+fn rate_between(from_rate: &ExchangeRate, to_rate: &ExchangeRate) -> Option<Fraction> {
+    Fraction::from_rate(from_rate).divide(&Fraction::from_rate(to_rate))
+}
+
+/// Applies the spread of the asset pair (same-currency pairs may have their own, too)
+fn with_spread<S: ExchangeRateStore>(
+    store: &S,
+    rate: Option<Fraction>,
+    from: &str,
+    to: &str,
+) -> Fraction {
+    let spread = store.get_spread(from, to);
+    rate.and_then(|rate| Some(rate.multiply(&Fraction::from_spread(spread)?)))
+        .unwrap_or_else(|| {
+            warn!(
+                "Exchange rate from {} to {} with spread {} is not a non-negative number, using 0 instead",
+                from, to, spread
+            );
+            Fraction::zero()
+        })
+}
+
+/// A non-negative rational number, used to convert amounts without the
+/// precision loss of floating point arithmetic
+#[derive(Clone, Debug, PartialEq)]
+struct Fraction {
+    numerator: BigUint,
+    denominator: BigUint,
+}
+
+impl Fraction {
+    fn zero() -> Self {
+        Fraction {
+            numerator: BigUint::zero(),
+            denominator: BigUint::one(),
+        }
+    }
+
+    fn one() -> Self {
+        Fraction {
+            numerator: BigUint::one(),
+            denominator: BigUint::one(),
+        }
+    }
+
+    /// The exact value of the shortest decimal which parses back to the float, so 0.1
+    /// becomes exactly 1/10 rather than the binary float closest to it.
+    /// Returns `None` for negative, infinite and NaN values.
+    fn from_f64(value: f64) -> Option<Self> {
+        Some(Fraction::from_rate(&ExchangeRate::from_f64(value)?))
+    }
+
+    /// The exact value of the decimal rate
+    fn from_rate(rate: &ExchangeRate) -> Self {
+        let (numerator, scale) = rate.to_parts();
+        Fraction {
+            numerator,
+            denominator: power_of_ten(scale),
+        }
+    }
+
+    /// The factor `1 - spread` that a rate is multiplied by, which is 0 if the spread is 1 or more
+    fn from_spread(spread: f64) -> Option<Self> {
+        let magnitude = Fraction::from_f64(spread.abs())?;
+        let numerator = if spread < 0.0 {
+            &magnitude.denominator + &magnitude.numerator
+        } else if magnitude.numerator >= magnitude.denominator {
+            BigUint::zero()
+        } else {
+            &magnitude.denominator - &magnitude.numerator
+        };
+        Some(Fraction {
+            numerator,
+            denominator: magnitude.denominator,
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(self.numerator.to_f64()? / self.denominator.to_f64()?)
+    }
+
+    fn multiply(&self, other: &Fraction) -> Fraction {
+        Fraction {
+            numerator: &self.numerator * &other.numerator,
+            denominator: &self.denominator * &other.denominator,
+        }
+    }
+
+    /// Returns `None` when dividing by zero
+    fn divide(&self, other: &Fraction) -> Option<Fraction> {
+        if other.is_zero() {
+            return None;
+        }
+        Some(Fraction {
+            numerator: &self.numerator * &other.denominator,
+            denominator: &self.denominator * &other.numerator,
+        })
+    }
+
+    /// Multiplies the amount by this rate and converts it from one asset scale to the other.
+    /// The result is rounded down, so that the node never forwards more than the exact amount.
+    fn convert(&self, amount: u64, from_scale: u8, to_scale: u8) -> BigUint {
+        let mut numerator = &self.numerator * BigUint::from(amount);
+        let mut denominator = self.denominator.clone();
+        if to_scale >= from_scale {
+            numerator *= power_of_ten(usize::from(to_scale - from_scale));
+        } else {
+            denominator *= power_of_ten(usize::from(from_scale - to_scale));
+        }
+        numerator / denominator
+    }
+}
+
+fn power_of_ten(exponent: usize) -> BigUint {
+    num_traits::pow(BigUint::from(10u8), exponent)
+}
+
 /// This determines which external API service to poll for exchange rates.
 #[derive(Debug, Clone, Deserialize)]
 pub enum ExchangeRateProvider {
@@ -224,6 +740,50 @@ pub enum ExchangeRateProvider {
     /// [CryptoCompare]: https://cryptocompare.com
     #[serde(alias = "cryptocompare")]
     CryptoCompare(SecretString),
+    /// Use fixed rates, for example for assets pegged to another asset.
+    ///
+    /// Note that when configured with YAML, this MUST be specified as
+    /// "Static", not "static".
+    #[serde(alias = "static")]
+    Static(HashMap<String, ExchangeRate>),
+    /// Read the rates from a local JSON or TOML file containing an object of
+    /// `asset code -> rate`. The file is read again on every poll, so changes
+    /// to it are picked up without restarting the node.
+    ///
+    /// Note that when configured with YAML, this MUST be specified as
+    /// "File", not "file".
+    #[serde(alias = "file")]
+    File(PathBuf),
+    /// Query any HTTP endpoint which returns the rates in a JSON document,
+    /// such as an internal price service.
+    ///
+    /// Note that when configured with YAML, this MUST be specified as
+    /// "JsonEndpoint", not "json_endpoint".
+    #[serde(alias = "json_endpoint")]
+    JsonEndpoint(JsonEndpoint),
+    /// Query several of the other providers and combine their rates.
+    /// For each asset, the median of the rates is used after discarding outliers.
+    /// Providers which fail to respond are skipped, and the rates are only
+    /// considered unavailable if all of them fail.
+    ///
+    /// Note that when configured with YAML, this MUST be specified as
+    /// "Aggregate", not "aggregate".
+    #[serde(alias = "aggregate")]
+    Aggregate(AggregateProvider),
+}
+
+impl ExchangeRateProvider {
+    /// The name of the provider, which is recorded as the source of the rates it returns
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExchangeRateProvider::CoinCap => "CoinCap",
+            ExchangeRateProvider::CryptoCompare(_) => "CryptoCompare",
+            ExchangeRateProvider::Static(_) => "Static",
+            ExchangeRateProvider::File(_) => "File",
+            ExchangeRateProvider::JsonEndpoint(_) => "JsonEndpoint",
+            ExchangeRateProvider::Aggregate(_) => "Aggregate",
+        }
+    }
 }
 
 /// Poll exchange rate providers for the current exchange rates
@@ -273,13 +833,8 @@ where
     }
 
     /// Calls the proper exchange rate provider
-    async fn fetch_rates(&self) -> Result<HashMap<String, f64>, ()> {
-        match self.provider {
-            ExchangeRateProvider::CryptoCompare(ref api_key) => {
-                query_cryptocompare(&self.client, api_key).await
-            }
-            ExchangeRateProvider::CoinCap => query_coincap(&self.client).await,
-        }
+    async fn fetch_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
+        query_provider(&self.client, &self.provider).await
     }
 
     /// Gets the exchange rates and proceeds to update the store with the newly polled values
@@ -290,6 +845,7 @@ where
         let store = self.store.clone();
         let store_clone = self.store.clone();
         let provider = self.provider.clone();
+        let source = provider.name();
         let mut rates = self.fetch_rates()
             .map_err(move |_| {
                 // Note that a race between the read on this line and the check on the line after
@@ -300,7 +856,7 @@ where
                 } else {
                     error!("Failed to update exchange rates (previous consecutive failed attempts: {}), removing old rates for safety", failed_polls);
                     // Clear out all of the old rates
-                    if store.set_exchange_rates(HashMap::new()).is_err() {
+                    if store.set_exchange_rates(HashMap::new(), source).is_err() {
                         error!("Failed to clear exchange rates cache after exchange rates server became unresponsive; panicking");
                         panic!("Failed to clear exchange rates cache after exchange rates server became unresponsive");
                     }
@@ -309,8 +865,8 @@ where
 
         trace!("Fetched exchange rates: {:?}", rates);
         let num_rates = rates.len();
-        rates.insert("USD".to_string(), 1.0);
-        if store_clone.set_exchange_rates(rates).is_ok() {
+        rates.insert("USD".to_string(), ExchangeRate::one());
+        if store_clone.set_exchange_rates(rates, source).is_ok() {
             // Reset our invalidation counter
             consecutive_failed_polls_zeroer.store(0, Ordering::Relaxed);
             debug!("Updated {} exchange rates from {:?}", num_rates, provider);
@@ -322,6 +878,53 @@ where
     }
 }
 
+// The future is boxed because aggregate providers query their providers recursively
+fn query_provider<'a>(
+    client: &'a Client,
+    provider: &'a ExchangeRateProvider,
+) -> BoxFuture<'a, Result<HashMap<String, ExchangeRate>, ()>> {
+    Box::pin(async move {
+        match provider {
+            ExchangeRateProvider::CryptoCompare(ref api_key) => {
+                query_cryptocompare(client, api_key).await
+            }
+            ExchangeRateProvider::CoinCap => query_coincap(client).await,
+            ExchangeRateProvider::Static(ref rates) => Ok(rates.clone()),
+            ExchangeRateProvider::File(ref path) => query_file(path).await,
+            ExchangeRateProvider::JsonEndpoint(ref endpoint) => {
+                query_json_endpoint(client, endpoint).await
+            }
+            ExchangeRateProvider::Aggregate(ref aggregate) => {
+                let results = join_all(
+                    aggregate
+                        .providers
+                        .iter()
+                        .map(|provider| query_provider(client, provider)),
+                )
+                .await;
+                let responses: Vec<HashMap<String, ExchangeRate>> = results
+                    .into_iter()
+                    .zip(aggregate.providers.iter())
+                    .filter_map(|(result, provider)| {
+                        if result.is_err() {
+                            warn!(
+                                "Skipping exchange rate provider {:?} because it failed to respond",
+                                provider
+                            );
+                        }
+                        result.ok()
+                    })
+                    .collect();
+                if responses.is_empty() {
+                    error!("None of the aggregated exchange rate providers responded");
+                    return Err(());
+                }
+                Ok(aggregate_rates(&responses, aggregate.max_deviation))
+            }
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -353,29 +956,408 @@ mod tests {
 
     #[tokio::test]
     async fn exchange_conversion_error() {
-        // rejects f64 that does not fit in u64
+        // rejects amounts that do not fit in u64
         let ret = exchange_rate(std::u64::MAX, 1, 2.0, 1, 1.0, 0.0).await;
         let reject = ret.0.unwrap_err();
         assert_eq!(reject.code(), ErrorCode::F08_AMOUNT_TOO_LARGE);
         assert!(reject
             .message()
-            .starts_with(b"Could not cast to f64, amount too large"));
+            .starts_with(b"Could not convert amount from ABC:1 to: XYZ:1, amount too large"));
 
-        // rejects f64 which gets rounded down to 0
+        // rejects amounts which get rounded down to 0
         let ret = exchange_rate(1, 2, 1.0, 1, 1.0, 0.0).await;
         let reject = ret.0.unwrap_err();
         assert_eq!(reject.code(), ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT);
         assert!(reject
             .message()
-            .starts_with(b"Could not cast to f64, amount too small"));
+            .starts_with(b"Could not convert amount from ABC:2 to: XYZ:1, amount too small"));
 
-        // `Convert` errored
+        // would have overflowed a f64
         let ret = exchange_rate(std::u64::MAX, 1, std::f64::MAX, 255, 1.0, 0.0).await;
         let reject = ret.0.unwrap_err();
         assert_eq!(reject.code(), ErrorCode::F08_AMOUNT_TOO_LARGE);
         assert!(reject.message().starts_with(b"Could not convert"));
     }
 
+    #[tokio::test]
+    async fn converts_large_amounts_exactly() {
+        let ret = exchange_rate(std::u64::MAX, 9, 1.0, 9, 1.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), std::u64::MAX);
+
+        // 2^53 + 1 cannot be represented as a f64, and 0.3 / 0.1 is not 3 in floating point
+        let ret = exchange_rate(9_007_199_254_740_993, 9, 0.3, 9, 0.1, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 27_021_597_764_222_979);
+
+        let ret = exchange_rate(std::u64::MAX, 9, 1.0, 9, 1.0, 0.01).await;
+        assert_eq!(ret.1[0].prepare.amount(), 18_262_276_632_972_456_098);
+    }
+
+    #[tokio::test]
+    async fn converts_between_extreme_scales() {
+        let ret = exchange_rate(1, 0, 1.0, 19, 1.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 10_000_000_000_000_000_000);
+
+        let ret = exchange_rate(1, 0, 1.0, 20, 1.0, 0.0).await;
+        assert_eq!(ret.0.unwrap_err().code(), ErrorCode::F08_AMOUNT_TOO_LARGE);
+
+        let ret = exchange_rate(std::u64::MAX, 18, 1.0, 0, 1.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 18);
+
+        let ret = exchange_rate(std::u64::MAX, 255, 1.0, 0, 1.0, 0.0).await;
+        assert_eq!(
+            ret.0.unwrap_err().code(),
+            ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT
+        );
+
+        // Tiny rates are applied exactly, too
+        let ret = exchange_rate(1, 0, 1e-250, 255, 1.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 100_000);
+        let ret = exchange_rate(1, 255, 1.0, 0, 1e-250, 0.0).await;
+        assert_eq!(
+            ret.0.unwrap_err().code(),
+            ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT
+        );
+    }
+
+    #[tokio::test]
+    async fn rounds_down() {
+        let ret = exchange_rate(10, 1, 1.0, 1, 3.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 3);
+
+        let ret = exchange_rate(20, 1, 1.0, 1, 3.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 6);
+
+        let ret = exchange_rate(999, 3, 1.0, 1, 1.0, 0.0).await;
+        assert_eq!(ret.1[0].prepare.amount(), 9);
+    }
+
+    #[tokio::test]
+    async fn applies_spread_of_asset_pair() {
+        let mut spreads = Spreads::new(0.01);
+        spreads
+            .pairs
+            .entry("ABC".to_owned())
+            .or_default()
+            .insert("XYZ".to_owned(), 0.1);
+        let mut store = test_store(1.0, 1.0, 0.0);
+        store.spreads = spreads;
+        // Spreads are directional
+        assert_eq!(store.get_spread("ABC", "XYZ"), 0.1);
+        assert_eq!(store.get_spread("XYZ", "ABC"), 0.01);
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_clone = requests.clone();
+        let outgoing = outgoing_service_fn(move |request: OutgoingRequest<TestAccount>| {
+            requests_clone
+                .lock()
+                .unwrap()
+                .push(request.prepare.amount());
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        });
+        let mut service = ExchangeRateService::new(store, outgoing);
+        for to in &["XYZ", "ABC"] {
+            service
+                .send_request(OutgoingRequest {
+                    from: TestAccount::new("ABC".to_owned(), 1),
+                    to: TestAccount::new(to.to_string(), 1),
+                    original_amount: 1000,
+                    prepare: PrepareBuilder {
+                        destination: Address::from_str("example.destination").unwrap(),
+                        amount: 1000,
+                        expires_at: SystemTime::now(),
+                        execution_condition: &[1; 32],
+                        data: &[],
+                    }
+                    .build(),
+                    applied_rate: None,
+                })
+                .await
+                .unwrap();
+        }
+        // Same-currency packets use the default spread unless their pair has its own
+        assert_eq!(*requests.lock().unwrap(), vec![900, 990]);
+    }
+
+    #[tokio::test]
+    async fn rejects_stale_rates() {
+        let mut store = test_store(1.0, 2.0, 0.0);
+        store.rates_updated_at = SystemTime::now() - Duration::from_secs(120);
+        let outgoing = outgoing_service_fn(|_| {
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        });
+        let mut service = ExchangeRateService::new(store, outgoing);
+        let request = |to: &str| OutgoingRequest {
+            from: TestAccount::new("ABC".to_owned(), 1),
+            to: TestAccount::new(to.to_owned(), 1),
+            original_amount: 100,
+            prepare: PrepareBuilder {
+                destination: Address::from_str("example.destination").unwrap(),
+                amount: 100,
+                expires_at: SystemTime::now(),
+                execution_condition: &[1; 32],
+                data: &[],
+            }
+            .build(),
+            applied_rate: None,
+        };
+
+        // Rates are used regardless of their age unless a maximum age is set
+        service.send_request(request("XYZ")).await.unwrap();
+
+        service.max_rate_age(Duration::from_secs(300));
+        service.send_request(request("XYZ")).await.unwrap();
+
+        service.max_rate_age(Duration::from_secs(60));
+        let reject = service.send_request(request("XYZ")).await.unwrap_err();
+        assert_eq!(reject.code(), ErrorCode::T00_INTERNAL_ERROR);
+        assert_eq!(
+            reject.message(),
+            b"Exchange rate from asset: ABC to: XYZ is stale"
+        );
+
+        // Same-currency packets do not need a rate
+        service.send_request(request("ABC")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn uses_pair_quotes() {
+        let mut store = test_store(1.0, 2.0, 0.0);
+        store.pair_quotes.push(PairQuote {
+            base: "ABC".to_owned(),
+            quote: "XYZ".to_owned(),
+            bid: 0.4,
+            ask: 0.8,
+        });
+        store.rates_updated_at = SystemTime::now() - Duration::from_secs(120);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_clone = requests.clone();
+        let outgoing = outgoing_service_fn(move |request: OutgoingRequest<TestAccount>| {
+            requests_clone
+                .lock()
+                .unwrap()
+                .push(request.prepare.amount());
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        });
+        let mut service = ExchangeRateService::new(store, outgoing);
+        let request = |from: &str, to: &str| OutgoingRequest {
+            from: TestAccount::new(from.to_owned(), 1),
+            to: TestAccount::new(to.to_owned(), 1),
+            original_amount: 1000,
+            prepare: PrepareBuilder {
+                destination: Address::from_str("example.destination").unwrap(),
+                amount: 1000,
+                expires_at: SystemTime::now(),
+                execution_condition: &[1; 32],
+                data: &[],
+            }
+            .build(),
+            applied_rate: None,
+        };
+
+        // Packets of the base asset are converted at the bid and those
+        // of the quote asset at the ask, instead of the rates of 1 and 2
+        service.send_request(request("ABC", "XYZ")).await.unwrap();
+        service.send_request(request("XYZ", "ABC")).await.unwrap();
+        assert_eq!(*requests.lock().unwrap(), vec![400, 1250]);
+
+        service.max_rate_age(Duration::from_secs(60));
+        let reject = service
+            .send_request(request("ABC", "XYZ"))
+            .await
+            .unwrap_err();
+        assert_eq!(reject.code(), ErrorCode::T00_INTERNAL_ERROR);
+    }
+
+    #[tokio::test]
+    async fn records_applied_rates() {
+        let rates = Arc::new(Mutex::new(Vec::new()));
+        let rates_clone = rates.clone();
+        let outgoing = outgoing_service_fn(move |request: OutgoingRequest<TestAccount>| {
+            rates_clone.lock().unwrap().push(request.applied_rate);
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        });
+        let request = |to: &str| OutgoingRequest {
+            from: TestAccount::new("ABC".to_owned(), 1),
+            to: TestAccount::new(to.to_owned(), 1),
+            original_amount: 1000,
+            prepare: PrepareBuilder {
+                destination: Address::from_str("example.destination").unwrap(),
+                amount: 1000,
+                expires_at: SystemTime::now(),
+                execution_condition: &[1; 32],
+                data: &[],
+            }
+            .build(),
+            applied_rate: None,
+        };
+        let mut store = test_store(1.0, 4.0, 0.5);
+        let mut service = ExchangeRateService::new(store.clone(), outgoing.clone());
+        service.send_request(request("XYZ")).await.unwrap();
+        service.send_request(request("ABC")).await.unwrap();
+
+        store.pair_quotes.push(PairQuote {
+            base: "XYZ".to_owned(),
+            quote: "ABC".to_owned(),
+            bid: 0.25,
+            ask: 0.5,
+        });
+        let mut service = ExchangeRateService::new(store, outgoing);
+        service.send_request(request("XYZ")).await.unwrap();
+        assert_eq!(
+            *rates.lock().unwrap(),
+            vec![Some(0.125), Some(0.5), Some(1.0)]
+        );
+    }
+
+    #[test]
+    fn parses_and_normalizes_rates() {
+        let rate = |rate: &str| ExchangeRate::from_str(rate).map(|rate| rate.to_string());
+        assert_eq!(rate("1").unwrap(), "1");
+        assert_eq!(rate("001.2500").unwrap(), "1.25");
+        assert_eq!(rate(".5").unwrap(), "0.5");
+        assert_eq!(rate("5.").unwrap(), "5");
+        assert_eq!(rate("0.000").unwrap(), "0");
+        assert_eq!(rate("1.0e-05").unwrap(), "0.00001");
+        assert_eq!(rate("1.5E3").unwrap(), "1500");
+        assert_eq!(rate("12.5e-1").unwrap(), "1.25");
+        // More digits than a float can hold
+        assert_eq!(
+            rate("0.12345678901234567890123").unwrap(),
+            "0.12345678901234567890123"
+        );
+        assert!(rate("").is_err());
+        assert!(rate(".").is_err());
+        assert!(rate("-1").is_err());
+        assert!(rate("+1").is_err());
+        assert!(rate("1,5").is_err());
+        assert!(rate("NaN").is_err());
+        assert!(rate("inf").is_err());
+        assert!(rate("1e").is_err());
+        assert!(rate("1e400").is_err());
+        assert!(rate("1e999999999").is_err());
+
+        assert_eq!(ExchangeRate::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert_eq!(ExchangeRate::from_f64(-0.0).unwrap().to_string(), "0");
+        assert!(ExchangeRate::from_f64(-1.0).is_none());
+        assert!(ExchangeRate::from_f64(std::f64::NAN).is_none());
+
+        let json: HashMap<String, ExchangeRate> =
+            serde_json::from_str(r#"{"ABC": "0.1", "XYZ": 2, "DEF": 0.25}"#).unwrap();
+        assert_eq!(json["ABC"].as_ref(), "0.1");
+        assert_eq!(json["XYZ"].as_ref(), "2");
+        assert_eq!(json["DEF"].as_ref(), "0.25");
+        assert!(serde_json::from_str::<ExchangeRate>("-1").is_err());
+        assert!(serde_json::from_str::<ExchangeRate>("\"abc\"").is_err());
+        assert_eq!(serde_json::to_string(&json["ABC"]).unwrap(), "\"0.1\"");
+
+        let midpoint = |a: &str, b: &str| {
+            ExchangeRate::from_str(a)
+                .unwrap()
+                .midpoint(&ExchangeRate::from_str(b).unwrap())
+                .to_string()
+        };
+        assert_eq!(midpoint("4", "4.125"), "4.0625");
+        assert_eq!(midpoint("0.1", "0.2"), "0.15");
+        assert_eq!(midpoint("3", "3"), "3");
+        assert_eq!(midpoint("0", "0.001"), "0.0005");
+    }
+
+    #[tokio::test]
+    async fn converts_with_exact_decimal_rates() {
+        // A float cannot tell 1.000000000000000001 from 1, so it would convert this to exactly 10^19
+        let mut rates = HashMap::new();
+        rates.insert(
+            vec!["ABC".to_owned(), "XYZ".to_owned()],
+            (
+                ExchangeRate::from_str("1.000000000000000001").unwrap(),
+                ExchangeRate::one(),
+            ),
+        );
+        let store = TestStore {
+            rates,
+            ..test_store(1.0, 1.0, 0.0)
+        };
+        let mut service = ExchangeRateService::new(
+            store,
+            outgoing_service_fn(|request: OutgoingRequest<TestAccount>| {
+                assert_eq!(request.prepare.amount(), 10_000_000_000_000_000_010);
+                Ok(FulfillBuilder {
+                    fulfillment: &[0; 32],
+                    data: b"",
+                }
+                .build())
+            }),
+        );
+        service
+            .send_request(OutgoingRequest {
+                from: TestAccount::new("ABC".to_owned(), 0),
+                to: TestAccount::new("XYZ".to_owned(), 0),
+                original_amount: 10_000_000_000_000_000_000,
+                prepare: PrepareBuilder {
+                    destination: Address::from_str("example.destination").unwrap(),
+                    amount: 10_000_000_000_000_000_000,
+                    expires_at: SystemTime::now(),
+                    execution_condition: &[1; 32],
+                    data: b"",
+                }
+                .build(),
+                applied_rate: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn validates_pair_quotes() {
+        let quote = |base: &str, bid: f64, ask: f64| PairQuote {
+            base: base.to_owned(),
+            quote: "XYZ".to_owned(),
+            bid,
+            ask,
+        };
+        assert!(quote("ABC", 0.4, 0.8).is_valid());
+        assert!(quote("ABC", 0.4, 0.4).is_valid());
+        assert!(!quote("XYZ", 0.4, 0.8).is_valid());
+        assert!(!quote("ABC", 0.8, 0.4).is_valid());
+        assert!(!quote("ABC", 0.0, 0.8).is_valid());
+        assert!(!quote("ABC", 0.4, std::f64::INFINITY).is_valid());
+    }
+
+    #[test]
+    fn validates_spreads() {
+        let spreads = |default: f64, pair: f64| {
+            let mut spreads = Spreads::new(default);
+            spreads
+                .pairs
+                .entry("ABC".to_owned())
+                .or_default()
+                .insert("XYZ".to_owned(), pair);
+            spreads
+        };
+        assert!(spreads(0.0, 1.0).is_valid());
+        assert!(spreads(0.01, 0.5).is_valid());
+        assert!(!spreads(-0.01, 0.5).is_valid());
+        assert!(!spreads(0.01, 1.5).is_valid());
+        assert!(!spreads(0.01, std::f64::NAN).is_valid());
+        assert!(!spreads(std::f64::INFINITY, 0.5).is_valid());
+    }
+
     #[tokio::test]
     async fn applies_spread() {
         let ret = exchange_rate(100, 1, 1.0, 1, 2.0, 0.01).await;
@@ -433,6 +1415,7 @@ mod tests {
                     data: b"hello",
                 }
                 .build(),
+                applied_rate: None,
             })
             .await;
 
@@ -497,36 +1480,92 @@ mod tests {
 
     #[derive(Debug, Clone)]
     struct TestStore {
-        rates: HashMap<Vec<String>, (f64, f64)>,
+        rates: HashMap<Vec<String>, (ExchangeRate, ExchangeRate)>,
+        rates_updated_at: SystemTime,
+        spreads: Spreads,
+        pair_quotes: Vec<PairQuote>,
     }
 
     impl ExchangeRateStore for TestStore {
-        fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<f64>, ()> {
+        fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()> {
             let mut ret = Vec::new();
             let key = vec![asset_codes[0].to_owned(), asset_codes[1].to_owned()];
             let v = self.rates.get(&key);
             if let Some(v) = v {
-                ret.push(v.0);
-                ret.push(v.1);
+                ret.push(v.0.clone());
+                ret.push(v.1.clone());
             } else {
                 return Err(());
             }
             Ok(ret)
         }
 
-        fn set_exchange_rates(&self, _rates: HashMap<String, f64>) -> Result<(), ()> {
+        fn get_exchange_rates_updated_at(
+            &self,
+            asset_codes: &[&str],
+        ) -> Result<Vec<SystemTime>, ()> {
+            Ok(vec![self.rates_updated_at; asset_codes.len()])
+        }
+
+        fn set_exchange_rates(
+            &self,
+            _rates: HashMap<String, ExchangeRate>,
+            _source: &str,
+        ) -> Result<(), ()> {
             unimplemented!()
         }
 
-        fn get_all_exchange_rates(&self) -> Result<HashMap<String, f64>, ()> {
+        fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
+            unimplemented!()
+        }
+
+        fn set_spreads(&self, _spreads: Spreads) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn set_default_spreads(&self, _spreads: Spreads) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn get_spread(&self, from_asset_code: &str, to_asset_code: &str) -> f64 {
+            self.spreads.get(from_asset_code, to_asset_code)
+        }
+
+        fn get_all_spreads(&self) -> Result<Spreads, ()> {
+            unimplemented!()
+        }
+
+        fn set_pair_quotes(&self, _quotes: Vec<PairQuote>) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn get_pair_quote(&self, base: &str, quote: &str) -> Option<(PairQuote, SystemTime)> {
+            self.pair_quotes
+                .iter()
+                .find(|pair_quote| pair_quote.base == base && pair_quote.quote == quote)
+                .map(|pair_quote| (pair_quote.clone(), self.rates_updated_at))
+        }
+
+        fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()> {
             unimplemented!()
         }
     }
 
-    fn test_store(rate1: f64, rate2: f64) -> TestStore {
+    fn test_store(rate1: f64, rate2: f64, spread: f64) -> TestStore {
         let mut rates = HashMap::new();
-        rates.insert(vec!["ABC".to_owned(), "XYZ".to_owned()], (rate1, rate2));
-        TestStore { rates }
+        rates.insert(
+            vec!["ABC".to_owned(), "XYZ".to_owned()],
+            (
+                ExchangeRate::from_f64(rate1).unwrap(),
+                ExchangeRate::from_f64(rate2).unwrap(),
+            ),
+        );
+        TestStore {
+            rates,
+            rates_updated_at: SystemTime::now(),
+            spreads: Spreads::new(spread),
+            pair_quotes: Vec::new(),
+        }
     }
 
     fn test_service(
@@ -539,7 +1578,7 @@ mod tests {
         impl OutgoingService<TestAccount> + Clone + Send + Sync,
         TestAccount,
     > {
-        let store = test_store(rate1, rate2);
-        ExchangeRateService::new(spread, store, handler)
+        let store = test_store(rate1, rate2, spread);
+        ExchangeRateService::new(store, handler)
     }
 }
@@ -160,6 +160,7 @@ mod tests {
                 }
                 .build(),
                 original_amount: 10,
+                applied_rate: None,
             })
             .await
             .expect("Should have shortened expiry");
@@ -199,6 +200,7 @@ mod tests {
                 }
                 .build(),
                 original_amount: 10,
+                applied_rate: None,
             })
             .await
             .expect("Should have shortened expiry");
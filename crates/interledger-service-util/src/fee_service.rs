@@ -0,0 +1,386 @@
+use async_trait::async_trait;
+use interledger_packet::{ErrorCode, RejectBuilder};
+use interledger_service::*;
+use log::{debug, error, trace};
+use serde::{Deserialize, Serialize};
+use std::{cmp::min, marker::PhantomData};
+use uuid::Uuid;
+
+/// Extension trait for [`Account`](../interledger_service/trait.Account.html) with the
+/// fees the node charges for forwarding packets to the account.
+/// Both fees are denominated in the account's asset code and scale.
+pub trait FeeAccount: Account {
+    /// Fixed amount deducted from every packet forwarded to this account
+    fn fixed_fee(&self) -> u64 {
+        0
+    }
+
+    /// Proportional fee deducted from every packet forwarded to this account,
+    /// in basis points (hundredths of a percent) of the outgoing amount
+    fn fee_basis_points(&self) -> u32 {
+        0
+    }
+}
+
+/// The total fees earned on packets forwarded from one account to another,
+/// denominated in the asset code and scale of the account they were forwarded to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeesEarned {
+    pub from_account_id: Uuid,
+    pub to_account_id: Uuid,
+    pub amount: u64,
+}
+
+/// Store trait responsible for keeping track of the fees earned by the node
+#[async_trait]
+pub trait FeeStore {
+    /// Adds the fee charged on a fulfilled packet to the total for the pair of accounts
+    async fn record_fee(
+        &self,
+        from_account_id: Uuid,
+        to_account_id: Uuid,
+        amount: u64,
+    ) -> Result<(), ()>;
+
+    /// Returns the total fees earned for every pair of accounts packets were forwarded between
+    async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()>;
+}
+
+/// Calculates the fee charged on a packet of the given amount forwarded to the account
+pub fn calculate_fee<A: FeeAccount>(account: &A, amount: u64) -> u64 {
+    let proportional = u128::from(amount) * u128::from(account.fee_basis_points()) / 10_000;
+    account
+        .fixed_fee()
+        .saturating_add(min(proportional, u128::from(std::u64::MAX)) as u64)
+}
+
+/// # Fee Service
+///
+/// Outgoing Service which deducts the fees configured on the account a packet is
+/// forwarded to from the packet's amount, and records the fee once the packet is fulfilled.
+///
+/// This must come after the `ExchangeRateService` so that the fees are charged on the
+/// converted amount. Unlike the exchange rate spread, fees are also charged on packets
+/// between accounts of the same asset. Packets with an amount of 0 are forwarded as-is.
+///
+/// Requires a `FeeAccount` and a `FeeStore`.
+#[derive(Clone)]
+pub struct FeeService<S, O, A> {
+    store: S,
+    next: O,
+    account_type: PhantomData<A>,
+}
+
+impl<S, O, A> FeeService<S, O, A>
+where
+    S: AddressStore + FeeStore,
+    O: OutgoingService<A>,
+    A: FeeAccount,
+{
+    pub fn new(store: S, next: O) -> Self {
+        FeeService {
+            store,
+            next,
+            account_type: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<S, O, A> OutgoingService<A> for FeeService<S, O, A>
+where
+    S: AddressStore + FeeStore + Clone + Send + Sync + 'static,
+    O: OutgoingService<A> + Send + Sync + Clone + 'static,
+    A: FeeAccount + Send + Sync + 'static,
+{
+    /// On send request:
+    /// 1. Calculates the fee for the account the request is to, forwarding the request if there is none
+    /// 1. Rejects the request if the fee is not less than the prepare packet's amount
+    /// 1. Deducts the fee from the prepare packet's amount and forwards it
+    /// 1. If the packet was fulfilled, records the fee in the store
+    async fn send_request(&mut self, mut request: OutgoingRequest<A>) -> IlpResult {
+        let amount = request.prepare.amount();
+        let fee = calculate_fee(&request.to, amount);
+        if amount == 0 || fee == 0 {
+            return self.next.send_request(request).await;
+        }
+
+        if fee >= amount {
+            debug!(
+                "Rejecting packet of amount {} to account {} because it does not cover the fee of {}",
+                amount,
+                request.to.id(),
+                fee
+            );
+            return Err(RejectBuilder {
+                code: ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT,
+                message: format!(
+                    "Amount does not cover the connector fee. Amount: {}, fee: {}",
+                    amount, fee
+                )
+                .as_bytes(),
+                triggered_by: Some(&self.store.get_ilp_address()),
+                data: &[],
+            }
+            .build());
+        }
+
+        let from_account_id = request.from.id();
+        let to_account_id = request.to.id();
+        request.prepare.set_amount(amount - fee);
+        trace!(
+            "Charging fee of {} on packet from account {} to account {}",
+            fee,
+            from_account_id,
+            to_account_id
+        );
+
+        let result = self.next.send_request(request).await;
+        if result.is_ok() {
+            // The packet was already fulfilled, so it should not be rejected
+            // just because the fee could not be recorded
+            if self
+                .store
+                .record_fee(from_account_id, to_account_id, fee)
+                .await
+                .is_err()
+            {
+                error!(
+                    "Error recording fee of {} on packet from account {} to account {}",
+                    fee, from_account_id, to_account_id
+                );
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interledger_packet::{Address, FulfillBuilder, PrepareBuilder};
+    use interledger_service::{outgoing_service_fn, Username};
+    use lazy_static::lazy_static;
+    use std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    lazy_static! {
+        pub static ref ALICE: Username = Username::from_str("alice").unwrap();
+        pub static ref EXAMPLE_ADDRESS: Address = Address::from_str("example.alice").unwrap();
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestAccount {
+        id: Uuid,
+        fixed_fee: u64,
+        fee_basis_points: u32,
+    }
+
+    impl TestAccount {
+        fn new(fixed_fee: u64, fee_basis_points: u32) -> Self {
+            TestAccount {
+                id: Uuid::new_v4(),
+                fixed_fee,
+                fee_basis_points,
+            }
+        }
+    }
+
+    impl Account for TestAccount {
+        fn id(&self) -> Uuid {
+            self.id
+        }
+
+        fn username(&self) -> &Username {
+            &ALICE
+        }
+
+        fn asset_code(&self) -> &str {
+            "XYZ"
+        }
+
+        fn asset_scale(&self) -> u8 {
+            9
+        }
+
+        fn ilp_address(&self) -> &Address {
+            &EXAMPLE_ADDRESS
+        }
+    }
+
+    impl FeeAccount for TestAccount {
+        fn fixed_fee(&self) -> u64 {
+            self.fixed_fee
+        }
+
+        fn fee_basis_points(&self) -> u32 {
+            self.fee_basis_points
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestStore {
+        fees: Arc<Mutex<Vec<FeesEarned>>>,
+    }
+
+    #[async_trait]
+    impl AddressStore for TestStore {
+        async fn set_ilp_address(&self, _ilp_address: Address) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        async fn clear_ilp_address(&self) -> Result<(), ()> {
+            unimplemented!()
+        }
+
+        fn get_ilp_address(&self) -> Address {
+            Address::from_str("example.connector").unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl FeeStore for TestStore {
+        async fn record_fee(
+            &self,
+            from_account_id: Uuid,
+            to_account_id: Uuid,
+            amount: u64,
+        ) -> Result<(), ()> {
+            self.fees.lock().unwrap().push(FeesEarned {
+                from_account_id,
+                to_account_id,
+                amount,
+            });
+            Ok(())
+        }
+
+        async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()> {
+            Ok(self.fees.lock().unwrap().clone())
+        }
+    }
+
+    fn outgoing_request(to: TestAccount, amount: u64) -> OutgoingRequest<TestAccount> {
+        OutgoingRequest {
+            from: TestAccount::new(0, 0),
+            to,
+            original_amount: amount,
+            prepare: PrepareBuilder {
+                destination: Address::from_str("example.destination").unwrap(),
+                amount,
+                expires_at: SystemTime::now() + Duration::from_secs(30),
+                data: &[],
+                execution_condition: &[0; 32],
+            }
+            .build(),
+            applied_rate: None,
+        }
+    }
+
+    fn fulfilling_service(
+        amounts: Arc<Mutex<Vec<u64>>>,
+    ) -> impl OutgoingService<TestAccount> + Clone + Send + Sync {
+        outgoing_service_fn(move |request: OutgoingRequest<TestAccount>| {
+            amounts.lock().unwrap().push(request.prepare.amount());
+            Ok(FulfillBuilder {
+                fulfillment: &[0; 32],
+                data: &[],
+            }
+            .build())
+        })
+    }
+
+    #[test]
+    fn calculates_fixed_and_proportional_fees() {
+        assert_eq!(calculate_fee(&TestAccount::new(0, 0), 1000), 0);
+        assert_eq!(calculate_fee(&TestAccount::new(5, 0), 1000), 5);
+        assert_eq!(calculate_fee(&TestAccount::new(0, 250), 1000), 25);
+        assert_eq!(calculate_fee(&TestAccount::new(5, 250), 1000), 30);
+        // Proportional fees are rounded down
+        assert_eq!(calculate_fee(&TestAccount::new(0, 1), 9999), 0);
+        assert_eq!(
+            calculate_fee(&TestAccount::new(std::u64::MAX, 20_000), std::u64::MAX),
+            std::u64::MAX
+        );
+    }
+
+    #[tokio::test]
+    async fn deducts_and_records_fees() {
+        let store = TestStore::default();
+        let amounts = Arc::new(Mutex::new(Vec::new()));
+        let mut service = FeeService::new(store.clone(), fulfilling_service(amounts.clone()));
+
+        let request = outgoing_request(TestAccount::new(10, 100), 1000);
+        let (from_account_id, to_account_id) = (request.from.id, request.to.id);
+        service.send_request(request).await.unwrap();
+
+        assert_eq!(*amounts.lock().unwrap(), vec![980]);
+        assert_eq!(
+            store.get_fees_earned().await.unwrap(),
+            vec![FeesEarned {
+                from_account_id,
+                to_account_id,
+                amount: 20,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn forwards_packets_without_fees() {
+        let store = TestStore::default();
+        let amounts = Arc::new(Mutex::new(Vec::new()));
+        let mut service = FeeService::new(store.clone(), fulfilling_service(amounts.clone()));
+
+        service
+            .send_request(outgoing_request(TestAccount::new(0, 0), 1000))
+            .await
+            .unwrap();
+        service
+            .send_request(outgoing_request(TestAccount::new(10, 100), 0))
+            .await
+            .unwrap();
+
+        assert_eq!(*amounts.lock().unwrap(), vec![1000, 0]);
+        assert!(store.get_fees_earned().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_which_do_not_cover_the_fee() {
+        let store = TestStore::default();
+        let amounts = Arc::new(Mutex::new(Vec::new()));
+        let mut service = FeeService::new(store.clone(), fulfilling_service(amounts.clone()));
+
+        let reject = service
+            .send_request(outgoing_request(TestAccount::new(100, 0), 100))
+            .await
+            .unwrap_err();
+        assert_eq!(reject.code(), ErrorCode::R01_INSUFFICIENT_SOURCE_AMOUNT);
+        assert!(amounts.lock().unwrap().is_empty());
+        assert!(store.get_fees_earned().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_record_fees_for_rejected_packets() {
+        let store = TestStore::default();
+        let mut service = FeeService::new(
+            store.clone(),
+            outgoing_service_fn(|_| {
+                Err(RejectBuilder {
+                    code: ErrorCode::F02_UNREACHABLE,
+                    message: &[],
+                    triggered_by: None,
+                    data: &[],
+                }
+                .build())
+            }),
+        );
+
+        service
+            .send_request(outgoing_request(TestAccount::new(10, 0), 100))
+            .await
+            .unwrap_err();
+        assert!(store.get_fees_earned().await.unwrap().is_empty());
+    }
+}
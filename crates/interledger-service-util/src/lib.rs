@@ -2,8 +2,12 @@
 //!
 //! Miscellaneous, small Interledger Services.
 
+/// Service responsible for rejecting packets from and to accounts which are not active
+mod account_status_service;
 /// Balance tracking service
 mod balance_service;
+/// Service responsible for rejecting packets to destinations the sending account may not send to
+mod destination_filter_service;
 /// Service which implements the echo protocol
 mod echo_service;
 /// Utilities for connecting to various exchange rate providers
@@ -13,6 +17,8 @@ mod exchange_rates_service;
 /// Service responsible for shortening the expiry time of packets,
 /// to take into account for network latency
 mod expiry_shortener_service;
+/// Service responsible for charging fees on forwarded packets
+mod fee_service;
 /// Service responsible for capping the amount an account can send in a packet
 mod max_packet_amount_service;
 /// Service responsible for capping the amount of packets and amount in packets an account can send
@@ -21,16 +27,27 @@ mod rate_limit_service;
 /// match the fulfillment inside the incoming fulfills
 mod validator_service;
 
-pub use self::balance_service::{BalanceService, BalanceStore};
+pub use self::account_status_service::{AccountStatusService, StatusAccount};
+pub use self::balance_service::{
+    journal_timestamp, BalanceChangeKind, BalanceHistoryPage, BalanceHistoryQuery,
+    BalanceJournalEntry, BalanceService, BalanceStore, PacketContext, BALANCE_JOURNAL_RETENTION,
+};
+pub use self::destination_filter_service::{
+    is_destination_allowed, DestinationFilterAccount, DestinationFilterService,
+};
 pub use self::echo_service::EchoService;
 pub use self::exchange_rates_service::{
-    ExchangeRateFetcher, ExchangeRateProvider, ExchangeRateService, ExchangeRateStore,
+    ExchangeRate, ExchangeRateFetcher, ExchangeRateHistoryPage, ExchangeRateHistoryQuery,
+    ExchangeRateHistoryStore, ExchangeRateProvider, ExchangeRateRecord, ExchangeRateService,
+    ExchangeRateStore, PairQuote, Spreads, EXCHANGE_RATE_HISTORY_LENGTH,
 };
 pub use self::expiry_shortener_service::{
     ExpiryShortenerService, RoundTripTimeAccount, DEFAULT_ROUND_TRIP_TIME,
 };
+pub use self::fee_service::{calculate_fee, FeeAccount, FeeService, FeeStore, FeesEarned};
 pub use self::max_packet_amount_service::{MaxPacketAmountAccount, MaxPacketAmountService};
 pub use self::rate_limit_service::{
-    RateLimitAccount, RateLimitError, RateLimitService, RateLimitStore,
+    AmountLimitPeriod, CumulativeAmounts, RateLimitAccount, RateLimitError, RateLimitService,
+    RateLimitStore,
 };
 pub use self::validator_service::ValidatorService;
@@ -1,7 +1,9 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use interledger_packet::{ErrorCode, RejectBuilder};
 use interledger_service::{Account, AddressStore, IlpResult, IncomingRequest, IncomingService};
 use log::{error, warn};
+use serde::Serialize;
 use std::marker::PhantomData;
 
 /// Extension trait for [`Account`](../interledger_service/trait.Account.html) with rate limiting related information
@@ -15,6 +17,105 @@ pub trait RateLimitAccount: Account {
     fn amount_per_minute_limit(&self) -> Option<u64> {
         None
     }
+
+    /// The maximum packets this account can send at once. The packets per minute
+    /// limit is the rate at which this allowance refills
+    fn packets_burst_limit(&self) -> Option<u32> {
+        self.packets_per_minute_limit()
+    }
+
+    /// The maximum units this account can send at once. The amount per minute
+    /// limit is the rate at which this allowance refills
+    fn amount_burst_limit(&self) -> Option<u64> {
+        self.amount_per_minute_limit()
+    }
+
+    /// The maximum units this account can send per calendar day (in UTC)
+    fn amount_per_day_limit(&self) -> Option<u64> {
+        None
+    }
+
+    /// The maximum units this account can send per calendar month (in UTC)
+    fn amount_per_month_limit(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the limit of the given period, if the account has one
+    fn amount_limit(&self, period: AmountLimitPeriod) -> Option<u64> {
+        match period {
+            AmountLimitPeriod::Day => self.amount_per_day_limit(),
+            AmountLimitPeriod::Month => self.amount_per_month_limit(),
+        }
+    }
+}
+
+/// Calendar periods over which the amounts sent by accounts are capped.
+/// Unlike the per minute limits, the amount an account may send is only
+/// reset when the next period starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AmountLimitPeriod {
+    Day,
+    Month,
+}
+
+impl AmountLimitPeriod {
+    pub const ALL: [AmountLimitPeriod; 2] = [AmountLimitPeriod::Day, AmountLimitPeriod::Month];
+
+    /// Identifies the period which includes the given time, such as `2020-01-31`
+    /// for a day or `2020-01` for a month. Amounts sent during a period are
+    /// stored under its id, so they do not count towards the next period
+    pub fn id_at(self, time: DateTime<Utc>) -> String {
+        match self {
+            AmountLimitPeriod::Day => time.format("%Y-%m-%d").to_string(),
+            AmountLimitPeriod::Month => time.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// Identifies the period which is currently ongoing
+    pub fn current_id(self) -> String {
+        self.id_at(Utc::now())
+    }
+
+    /// The error returned when a packet would make an account exceed its limit for this period
+    pub fn limit_exceeded(self) -> RateLimitError {
+        match self {
+            AmountLimitPeriod::Day => RateLimitError::DailyLimitExceeded,
+            AmountLimitPeriod::Month => RateLimitError::MonthlyLimitExceeded,
+        }
+    }
+}
+
+impl AsRef<str> for AmountLimitPeriod {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            AmountLimitPeriod::Day => "day",
+            AmountLimitPeriod::Month => "month",
+        }
+    }
+}
+
+/// Amounts an account sent during the current day and month, which count towards its
+/// daily and monthly limits. Denominated in the account's asset and scale
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CumulativeAmounts {
+    pub day: u64,
+    pub month: u64,
+}
+
+impl CumulativeAmounts {
+    pub fn get(&self, period: AmountLimitPeriod) -> u64 {
+        match period {
+            AmountLimitPeriod::Day => self.day,
+            AmountLimitPeriod::Month => self.month,
+        }
+    }
+
+    pub fn set(&mut self, period: AmountLimitPeriod, amount: u64) {
+        match period {
+            AmountLimitPeriod::Day => self.day = amount,
+            AmountLimitPeriod::Month => self.month = amount,
+        }
+    }
 }
 
 /// Rate limiting related errors
@@ -24,6 +125,10 @@ pub enum RateLimitError {
     PacketLimitExceeded,
     /// Account exceeded their amount limit
     ThroughputLimitExceeded,
+    /// Account exceeded the amount they can send in the current day
+    DailyLimitExceeded,
+    /// Account exceeded the amount they can send in the current month
+    MonthlyLimitExceeded,
     /// There was an internal error when trying to connect to the store
     StoreError,
 }
@@ -34,15 +139,18 @@ pub trait RateLimitStore {
     /// The provided account must implement [`RateLimitAccount`](./trait.RateLimitAccount.html)
     type Account: RateLimitAccount;
 
-    /// Apply rate limits based on the packets per minute and amount of per minute
-    /// limits set on the provided account
+    /// Apply rate limits based on the burst limits and the packets per minute and
+    /// amount per minute refill rates set on the provided account, then add the
+    /// amount to what the account sent in the current day and month unless that
+    /// would exceed its daily or monthly limits
     async fn apply_rate_limits(
         &self,
         account: Self::Account,
         prepare_amount: u64,
     ) -> Result<(), RateLimitError>;
 
-    /// Refunds the throughput limit which was charged to an account
+    /// Refunds the throughput limit and the daily and monthly amounts which were
+    /// charged to an account
     /// Called if the node receives a reject packet after trying to forward
     /// a packet to a peer, meaning that effectively reject packets do not
     /// count towards a node's throughput limits
@@ -51,6 +159,10 @@ pub trait RateLimitStore {
         account: Self::Account,
         prepare_amount: u64,
     ) -> Result<(), ()>;
+
+    /// Returns the amounts the account sent in the current day and month
+    async fn get_cumulative_amounts(&self, account: Self::Account)
+        -> Result<CumulativeAmounts, ()>;
 }
 
 /// # Rate Limit Service
@@ -60,6 +172,8 @@ pub trait RateLimitStore {
 /// Talks with the associated Store in order to figure out
 /// and set the rate limits per account.
 /// This service does packet based limiting and amount based limiting.
+/// Each limit is a token bucket: accounts can send up to their burst limit at once,
+/// after which they are limited to their per minute rate.
 ///
 /// Forwards everything else.
 /// Requires a `RateLimitAccount` and a `RateLimitStore`.
@@ -106,7 +220,9 @@ where
         let account = request.from.clone();
         let account_clone = account.clone();
         let prepare_amount = request.prepare.amount();
-        let has_throughput_limit = account.amount_per_minute_limit().is_some();
+        let has_throughput_limit = account.amount_per_minute_limit().is_some()
+            || account.amount_per_day_limit().is_some()
+            || account.amount_per_month_limit().is_some();
         // request.from and request.amount are used for apply_rate_limits, can't the previous service
         // always set the account to have None for both?
         match self
@@ -135,13 +251,25 @@ where
                 let code = match err {
                     RateLimitError::PacketLimitExceeded => {
                         if let Some(limit) = account.packets_per_minute_limit() {
-                            warn!("Account {} was rate limited for sending too many packets. Limit is: {} per minute", account.id(), limit);
+                            warn!("Account {} was rate limited for sending too many packets. Limit is: {} per minute with bursts of {}", account.id(), limit, account.packets_burst_limit().unwrap_or(limit));
                         }
                         ErrorCode::T05_RATE_LIMITED
                     }
                     RateLimitError::ThroughputLimitExceeded => {
                         if let Some(limit) = account.amount_per_minute_limit() {
-                            warn!("Account {} was throughput limited for trying to send too much money. Limit is: {} per minute", account.id(), limit);
+                            warn!("Account {} was throughput limited for trying to send too much money. Limit is: {} per minute with bursts of {}", account.id(), limit, account.amount_burst_limit().unwrap_or(limit));
+                        }
+                        ErrorCode::T04_INSUFFICIENT_LIQUIDITY
+                    }
+                    RateLimitError::DailyLimitExceeded => {
+                        if let Some(limit) = account.amount_per_day_limit() {
+                            warn!("Account {} reached the amount it can send today. Limit is: {} per day", account.id(), limit);
+                        }
+                        ErrorCode::T04_INSUFFICIENT_LIQUIDITY
+                    }
+                    RateLimitError::MonthlyLimitExceeded => {
+                        if let Some(limit) = account.amount_per_month_limit() {
+                            warn!("Account {} reached the amount it can send this month. Limit is: {} per month", account.id(), limit);
                         }
                         ErrorCode::T04_INSUFFICIENT_LIQUIDITY
                     }
@@ -161,3 +289,19 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn identifies_calendar_periods() {
+        let time = Utc.ymd(2020, 1, 31).and_hms(23, 59, 59);
+        assert_eq!(AmountLimitPeriod::Day.id_at(time), "2020-01-31");
+        assert_eq!(AmountLimitPeriod::Month.id_at(time), "2020-01");
+        let next_day = Utc.ymd(2020, 2, 1).and_hms(0, 0, 0);
+        assert_eq!(AmountLimitPeriod::Day.id_at(next_day), "2020-02-01");
+        assert_eq!(AmountLimitPeriod::Month.id_at(next_day), "2020-02");
+    }
+}
@@ -359,6 +359,7 @@ mod outgoing {
                     data: b"test data",
                 }
                 .build(),
+                applied_rate: None,
             })
             .await;
 
@@ -397,6 +398,7 @@ mod outgoing {
                     data: b"test data",
                 }
                 .build(),
+                applied_rate: None,
             })
             .await;
 
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Whether an account may send and receive packets. Accounts which are not
+/// active keep their balances and settlement data, unlike deleted accounts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    /// The account sends and receives packets normally
+    Active,
+    /// The account's packets are rejected and the routes to it are withdrawn
+    Suspended,
+    /// The routes to the account are withdrawn so that no more packets are sent
+    /// to it, but it may still send packets (e.g. to pay what it owes) and settle
+    Closing,
+}
+
+impl AccountStatus {
+    /// Indicates whether packets from the account are accepted
+    pub fn accepts_incoming_packets(self) -> bool {
+        self != AccountStatus::Suspended
+    }
+
+    /// Indicates whether packets may be sent to the account and routes to it advertised
+    pub fn accepts_outgoing_packets(self) -> bool {
+        self == AccountStatus::Active
+    }
+}
+
+impl Default for AccountStatus {
+    fn default() -> Self {
+        AccountStatus::Active
+    }
+}
+
+impl FromStr for AccountStatus {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string.to_lowercase().as_str() {
+            "active" => Ok(AccountStatus::Active),
+            "suspended" => Ok(AccountStatus::Suspended),
+            "closing" => Ok(AccountStatus::Closing),
+            _ => Err(()),
+        }
+    }
+}
+
+impl AsRef<str> for AccountStatus {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Closing => "closing",
+        }
+    }
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_status() {
+        for status in &[
+            AccountStatus::Active,
+            AccountStatus::Suspended,
+            AccountStatus::Closing,
+        ] {
+            assert_eq!(AccountStatus::from_str(&status.to_string()), Ok(*status));
+        }
+        assert_eq!(
+            AccountStatus::from_str("Suspended"),
+            Ok(AccountStatus::Suspended)
+        );
+        assert!(AccountStatus::from_str("deleted").is_err());
+    }
+
+    #[test]
+    fn closing_accounts_only_send_packets() {
+        assert!(AccountStatus::Closing.accepts_incoming_packets());
+        assert!(!AccountStatus::Closing.accepts_outgoing_packets());
+        assert!(!AccountStatus::Suspended.accepts_incoming_packets());
+        assert!(AccountStatus::Active.accepts_outgoing_packets());
+    }
+}
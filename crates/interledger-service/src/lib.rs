@@ -36,6 +36,8 @@ use std::{
 };
 use uuid::Uuid;
 
+mod account_status;
+pub use account_status::AccountStatus;
 mod username;
 pub use username::Username;
 #[cfg(feature = "trace")]
@@ -93,6 +95,9 @@ pub struct OutgoingRequest<A: Account> {
     pub original_amount: u64,
     /// The prepare packet attached to the request
     pub prepare: Prepare,
+    /// The exchange rate (including the spread) the amount was converted with,
+    /// once it was converted to the asset of the account it is sent to
+    pub applied_rate: Option<f64>,
 }
 
 // Use a custom debug implementation to specify the order of the fields
@@ -105,6 +110,7 @@ where
             .debug_struct("OutgoingRequest")
             .field("prepare", &self.prepare)
             .field("original_amount", &self.original_amount)
+            .field("applied_rate", &self.applied_rate)
             .field("to", &self.to)
             .field("from", &self.from)
             .finish()
@@ -122,6 +128,7 @@ where
             original_amount: self.prepare.amount(),
             prepare: self.prepare,
             to,
+            applied_rate: None,
         }
     }
 }
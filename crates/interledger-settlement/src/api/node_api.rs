@@ -348,6 +348,7 @@ where
                     execution_condition: &PEER_PROTOCOL_CONDITION,
                 }
                 .build(),
+                applied_rate: None,
             })
             .await
     };
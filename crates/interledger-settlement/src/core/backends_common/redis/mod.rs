@@ -243,6 +243,14 @@ impl LeftoversStore for EngineRedisStore {
         Ok((amount.num, amount.scale))
     }
 
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        account_id: Self::AccountId,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        // Getting the leftovers does not remove them from this store
+        self.get_uncredited_settlement_amount(account_id).await
+    }
+
     async fn save_uncredited_settlement_amount(
         &self,
         account_id: Self::AccountId,
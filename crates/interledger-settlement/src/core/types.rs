@@ -180,6 +180,13 @@ pub trait LeftoversStore {
         &self,
         account_id: Self::AccountId,
     ) -> Result<(Self::AssetType, u8), ()>;
+
+    /// Gets the current amount of leftovers like `get_uncredited_settlement_amount`,
+    /// but leaves them in the store (for example to export them)
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        account_id: Self::AccountId,
+    ) -> Result<(Self::AssetType, u8), ()>;
 }
 
 /// Helper struct for converting a quantity's amount from one asset scale to another
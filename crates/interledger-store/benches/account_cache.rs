@@ -0,0 +1,161 @@
+//! Benchmark loading accounts from the RedisStore for each packet,
+//! with and without the in-process account cache.
+//!
+//! Requires `redis-server` to be installed.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use interledger_api::{AccountDetails, NodeStore};
+use interledger_http::HttpStore;
+use interledger_service::{Account as AccountTrait, AccountStore, Username};
+use interledger_store::redis::{RedisStore, RedisStoreBuilder};
+use redis_crate::{Client, ConnectionAddr, ConnectionInfo};
+use secrecy::SecretString;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use uuid::Uuid;
+
+struct RedisServer {
+    process: Child,
+    socket: PathBuf,
+}
+
+impl RedisServer {
+    fn start() -> RedisServer {
+        let socket = PathBuf::from(format!(
+            "/tmp/interledger-store-bench-{}.sock",
+            std::process::id()
+        ));
+        let process = Command::new("redis-server")
+            .arg("--port")
+            .arg("0")
+            .arg("--unixsocket")
+            .arg(&socket)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("redis-server must be installed to run this benchmark");
+        let server = RedisServer { process, socket };
+
+        let client = Client::open(server.connection_info()).unwrap();
+        while client.get_connection().is_err() {
+            sleep(Duration::from_millis(1));
+        }
+        server
+    }
+
+    fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            addr: Box::new(ConnectionAddr::Unix(self.socket.clone())),
+            db: 0,
+            passwd: None,
+        }
+    }
+}
+
+impl Drop for RedisServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+fn account_details() -> AccountDetails {
+    AccountDetails {
+        ilp_address: None,
+        additional_prefixes: Vec::new(),
+        username: Username::from_str("alice").unwrap(),
+        asset_scale: 9,
+        asset_code: "XRP".to_string(),
+        max_packet_amount: 1000,
+        min_balance: Some(0),
+        ilp_over_http_url: Some("http://example.com/accounts/alice/ilp".to_string()),
+        ilp_over_http_incoming_token: Some(SecretString::new("incoming_token".to_string())),
+        ilp_over_http_outgoing_token: Some(SecretString::new("outgoing_token".to_string())),
+        ilp_over_btp_url: None,
+        ilp_over_btp_incoming_token: None,
+        ilp_over_btp_outgoing_token: None,
+        settle_threshold: None,
+        settle_to: None,
+        routing_relation: Some("Child".to_string()),
+        round_trip_time: None,
+        amount_per_minute_limit: None,
+        packets_per_minute_limit: None,
+        amount_burst_limit: None,
+        packets_burst_limit: None,
+        amount_per_day_limit: None,
+        amount_per_month_limit: None,
+        fixed_fee: None,
+        fee_basis_points: None,
+        allowed_destination_prefixes: Vec::new(),
+        denied_destination_prefixes: Vec::new(),
+        settlement_engine_url: None,
+    }
+}
+
+/// Connects a store with the cache enabled and one with it disabled to the same Redis
+fn setup(runtime: &mut Runtime, server: &RedisServer) -> (RedisStore, RedisStore, Uuid) {
+    runtime.block_on(async {
+        let cached = RedisStoreBuilder::new(server.connection_info(), [0; 32])
+            .connect()
+            .await
+            .unwrap();
+        let uncached = RedisStoreBuilder::new(server.connection_info(), [0; 32])
+            .account_cache_ttl(0)
+            .connect()
+            .await
+            .unwrap();
+        let account = cached.insert_account(account_details()).await.unwrap();
+        (cached, uncached, account.id())
+    })
+}
+
+fn benchmark_get_accounts(c: &mut Criterion) {
+    let server = RedisServer::start();
+    let mut runtime = Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (cached, uncached, id) = setup(&mut runtime, &server);
+
+    c.bench_function("get_accounts (cached)", |b| {
+        b.iter(|| runtime.block_on(cached.get_accounts(vec![id])).unwrap());
+    });
+    c.bench_function("get_accounts (uncached)", |b| {
+        b.iter(|| runtime.block_on(uncached.get_accounts(vec![id])).unwrap());
+    });
+}
+
+fn benchmark_http_auth(c: &mut Criterion) {
+    let server = RedisServer::start();
+    let mut runtime = Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (cached, uncached, _) = setup(&mut runtime, &server);
+    let username = Username::from_str("alice").unwrap();
+
+    c.bench_function("get_account_from_http_auth (cached)", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(cached.get_account_from_http_auth(&username, "incoming_token"))
+                .unwrap()
+        });
+    });
+    c.bench_function("get_account_from_http_auth (uncached)", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(uncached.get_account_from_http_auth(&username, "incoming_token"))
+                .unwrap()
+        });
+    });
+}
+
+criterion_group!(benches, benchmark_get_accounts, benchmark_http_auth);
+criterion_main!(benches);
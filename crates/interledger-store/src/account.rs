@@ -1,16 +1,17 @@
-use super::crypto::{decrypt_token, encrypt_token};
-use interledger_api::AccountDetails;
+use super::crypto::{hash_token, is_token_hash, StoreKeys, TOKEN_HASH_PREFIX};
+use bytes::Bytes;
+use interledger_api::{AccountDetails, ExportedAccount};
 use interledger_btp::BtpAccount;
 use interledger_ccp::{CcpRoutingAccount, RoutingRelation};
 use interledger_http::HttpAccount;
 use interledger_packet::Address;
-use interledger_service::{Account as AccountTrait, Username};
+use interledger_service::{Account as AccountTrait, AccountStatus, Username};
 use interledger_service_util::{
-    MaxPacketAmountAccount, RateLimitAccount, RoundTripTimeAccount, DEFAULT_ROUND_TRIP_TIME,
+    DestinationFilterAccount, FeeAccount, MaxPacketAmountAccount, RateLimitAccount,
+    RoundTripTimeAccount, StatusAccount, DEFAULT_ROUND_TRIP_TIME,
 };
 use interledger_settlement::core::types::{SettlementAccount, SettlementEngineDetails};
 use log::error;
-use ring::aead;
 use secrecy::{ExposeSecret, SecretBytesMut, SecretString};
 use serde::Serializer;
 use serde::{Deserialize, Serialize};
@@ -30,7 +31,9 @@ pub struct Account {
     #[serde(serialize_with = "address_to_string")]
     /// The account's Interledger Protocol address
     pub(crate) ilp_address: Address,
-    // TODO add additional routes
+    #[serde(serialize_with = "addresses_to_strings")]
+    /// Other address prefixes which are routed to the account
+    pub(crate) additional_prefixes: Vec<Address>,
     /// The account's currency
     pub(crate) asset_code: String,
     /// The account's asset scale
@@ -78,10 +81,30 @@ pub struct Account {
     pub(crate) packets_per_minute_limit: Option<u32>,
     /// The maximum amount the account can send per minute
     pub(crate) amount_per_minute_limit: Option<u64>,
+    /// The number of packets the account can send at once, if it differs
+    /// from its packets per minute limit
+    pub(crate) packets_burst_limit: Option<u32>,
+    /// The amount the account can send at once, if it differs from its
+    /// amount per minute limit
+    pub(crate) amount_burst_limit: Option<u64>,
+    /// The maximum amount the account can send per calendar day
+    pub(crate) amount_per_day_limit: Option<u64>,
+    /// The maximum amount the account can send per calendar month
+    pub(crate) amount_per_month_limit: Option<u64>,
+    /// The fixed fee deducted from every packet forwarded to the account
+    pub(crate) fixed_fee: Option<u64>,
+    /// The fee deducted from every packet forwarded to the account, in basis points
+    pub(crate) fee_basis_points: Option<u32>,
+    /// Address prefixes the account may send packets to (any if there are none)
+    pub(crate) allowed_destination_prefixes: Vec<String>,
+    /// Address prefixes the account may not send packets to
+    pub(crate) denied_destination_prefixes: Vec<String>,
     /// The account's settlement engine URL. If a global engine url is configured
     /// for the account's asset code,  that will be used instead (even if the account is
     /// configured with a specific one)
     pub(crate) settlement_engine_url: Option<Url>,
+    /// Whether the account is active, suspended or closing
+    pub(crate) status: AccountStatus,
 }
 
 fn address_to_string<S>(address: &Address, serializer: S) -> Result<S::Ok, S::Error>
@@ -91,6 +114,92 @@ where
     serializer.serialize_str(str::from_utf8(address.as_ref()).unwrap_or(""))
 }
 
+fn addresses_to_strings<S>(addresses: &[Address], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(
+        addresses
+            .iter()
+            .map(|address| str::from_utf8(address.as_ref()).unwrap_or("")),
+    )
+}
+
+/// Checks that the destination prefix is not empty and only contains the
+/// characters allowed in ILP addresses, so that it can be matched against them
+fn validate_destination_prefix(prefix: &str) -> Result<(), ()> {
+    let is_valid_char = |c: char| c.is_ascii_alphanumeric() || "._~-".contains(c);
+    if !prefix.is_empty() && prefix.chars().all(is_valid_char) {
+        Ok(())
+    } else {
+        error!("Invalid destination prefix: {:?}", prefix);
+        Err(())
+    }
+}
+
+/// Checks that none of the additional prefixes is the node's address or one of
+/// its prefixes, which would route the packets addressed to the node to the account
+fn validate_additional_prefixes(
+    prefixes: &[Address],
+    node_ilp_address: &Address,
+) -> Result<(), ()> {
+    let node_ilp_address: &str = node_ilp_address;
+    for prefix in prefixes {
+        let prefix: &str = prefix;
+        if node_ilp_address == prefix
+            || (node_ilp_address.starts_with(prefix)
+                && node_ilp_address[prefix.len()..].starts_with('.'))
+        {
+            error!(
+                "The additional prefix {} overlaps the node's address {}",
+                prefix, node_ilp_address
+            );
+            return Err(());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the per minute and burst limits are not zero and that the burst
+/// limit is not lower than the per minute limit it refills at
+fn validate_rate_limit(name: &str, per_minute: Option<u64>, burst: Option<u64>) -> Result<(), ()> {
+    if per_minute == Some(0) || burst == Some(0) {
+        error!("The {} rate limits must be greater than zero", name);
+        return Err(());
+    }
+    if let (Some(per_minute), Some(burst)) = (per_minute, burst) {
+        if burst < per_minute {
+            error!(
+                "The {} burst limit {} is lower than the per minute limit {}",
+                name, burst, per_minute
+            );
+            return Err(());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an incoming token does not look like a hash. Stored incoming tokens
+/// which start with the hash prefix are verified as hashes, so such a token
+/// would be stored without being hashed and could never be used
+pub(crate) fn validate_incoming_token(token: Option<&SecretString>) -> Result<(), ()> {
+    match token {
+        Some(token)
+            if token
+                .expose_secret()
+                .as_bytes()
+                .starts_with(TOKEN_HASH_PREFIX) =>
+        {
+            error!(
+                "Incoming tokens may not start with {}",
+                str::from_utf8(TOKEN_HASH_PREFIX).unwrap_or_default()
+            );
+            Err(())
+        }
+        _ => Ok(()),
+    }
+}
+
 fn optional_secret_bytes_to_utf8<S>(
     _bytes: &Option<SecretBytesMut>,
     serializer: S,
@@ -105,7 +214,7 @@ impl Account {
     /// Creates an account from the provided id and details. If there is no ILP Address
     /// in the provided details, then the account's ILP Address is generated by appending
     /// the `details.username` to the provided `node_ilp_address`.
-    /// The default RoutingRelation is `NonRoutingAccount`
+    /// The default RoutingRelation is `NonRoutingAccount` and the account is active
     pub fn try_from(
         id: Uuid,
         details: AccountDetails,
@@ -140,6 +249,26 @@ impl Account {
         } else {
             RoutingRelation::NonRoutingAccount
         };
+        validate_additional_prefixes(&details.additional_prefixes, &node_ilp_address)?;
+        for prefix in details
+            .allowed_destination_prefixes
+            .iter()
+            .chain(details.denied_destination_prefixes.iter())
+        {
+            validate_destination_prefix(prefix)?;
+        }
+        validate_rate_limit(
+            "packet",
+            details.packets_per_minute_limit.map(u64::from),
+            details.packets_burst_limit.map(u64::from),
+        )?;
+        validate_rate_limit(
+            "throughput",
+            details.amount_per_minute_limit,
+            details.amount_burst_limit,
+        )?;
+        validate_incoming_token(details.ilp_over_http_incoming_token.as_ref())?;
+        validate_incoming_token(details.ilp_over_btp_incoming_token.as_ref())?;
         let settlement_engine_url =
             if let Some(settlement_engine_url) = details.settlement_engine_url {
                 Url::parse(&settlement_engine_url).ok()
@@ -151,6 +280,7 @@ impl Account {
             id,
             username: details.username,
             ilp_address,
+            additional_prefixes: details.additional_prefixes,
             asset_code: details.asset_code.to_uppercase(),
             asset_scale: details.asset_scale,
             max_packet_amount: details.max_packet_amount,
@@ -175,43 +305,126 @@ impl Account {
             round_trip_time: details.round_trip_time.unwrap_or(DEFAULT_ROUND_TRIP_TIME),
             packets_per_minute_limit: details.packets_per_minute_limit,
             amount_per_minute_limit: details.amount_per_minute_limit,
+            packets_burst_limit: details.packets_burst_limit,
+            amount_burst_limit: details.amount_burst_limit,
+            amount_per_day_limit: details.amount_per_day_limit,
+            amount_per_month_limit: details.amount_per_month_limit,
+            fixed_fee: details.fixed_fee,
+            fee_basis_points: details.fee_basis_points,
+            allowed_destination_prefixes: details.allowed_destination_prefixes,
+            denied_destination_prefixes: details.denied_destination_prefixes,
             settlement_engine_url,
+            status: AccountStatus::Active,
         })
     }
 
-    /// Encrypts the account's incoming/outgoing BTP and HTTP keys with the provided encryption key
-    pub fn encrypt_tokens(
-        mut self,
-        encryption_key: &aead::LessSafeKey,
-    ) -> AccountWithEncryptedTokens {
+    /// Creates an account from one exported from another store. The incoming tokens
+    /// are kept as they were stored, so tokens which were hashed are not hashed again
+    pub fn try_from_exported(
+        exported: ExportedAccount,
+        node_ilp_address: Address,
+    ) -> Result<Account, ()> {
+        let mut account = Account::try_from(exported.id, exported.details, node_ilp_address)?;
+        account.status = exported.status;
+        account.ilp_over_http_incoming_token = exported
+            .ilp_over_http_incoming_token
+            .map(|token| SecretBytesMut::new(&token[..]));
+        account.ilp_over_btp_incoming_token = exported
+            .ilp_over_btp_incoming_token
+            .map(|token| SecretBytesMut::new(&token[..]));
+        Ok(account)
+    }
+
+    /// Exports the account's details and (decrypted) tokens
+    pub fn export(&self) -> ExportedAccount {
+        let outgoing_token = |token: &Option<SecretBytesMut>| {
+            token.as_ref().map(|token| {
+                SecretString::new(String::from_utf8_lossy(&token.expose_secret()).into_owned())
+            })
+        };
+        let incoming_token = |token: &Option<SecretBytesMut>| {
+            token
+                .as_ref()
+                .map(|token| Bytes::copy_from_slice(&token.expose_secret()))
+        };
+        ExportedAccount {
+            id: self.id,
+            details: AccountDetails {
+                ilp_address: Some(self.ilp_address.clone()),
+                additional_prefixes: self.additional_prefixes.clone(),
+                username: self.username.clone(),
+                asset_code: self.asset_code.clone(),
+                asset_scale: self.asset_scale,
+                max_packet_amount: self.max_packet_amount,
+                min_balance: self.min_balance,
+                ilp_over_http_url: self.ilp_over_http_url.as_ref().map(Url::to_string),
+                ilp_over_http_incoming_token: None,
+                ilp_over_http_outgoing_token: outgoing_token(&self.ilp_over_http_outgoing_token),
+                ilp_over_btp_url: self.ilp_over_btp_url.as_ref().map(Url::to_string),
+                ilp_over_btp_outgoing_token: outgoing_token(&self.ilp_over_btp_outgoing_token),
+                ilp_over_btp_incoming_token: None,
+                settle_threshold: self.settle_threshold,
+                settle_to: self.settle_to,
+                routing_relation: Some(self.routing_relation.to_string()),
+                round_trip_time: Some(self.round_trip_time),
+                amount_per_minute_limit: self.amount_per_minute_limit,
+                packets_per_minute_limit: self.packets_per_minute_limit,
+                amount_burst_limit: self.amount_burst_limit,
+                packets_burst_limit: self.packets_burst_limit,
+                amount_per_day_limit: self.amount_per_day_limit,
+                amount_per_month_limit: self.amount_per_month_limit,
+                fixed_fee: self.fixed_fee,
+                fee_basis_points: self.fee_basis_points,
+                allowed_destination_prefixes: self.allowed_destination_prefixes.clone(),
+                denied_destination_prefixes: self.denied_destination_prefixes.clone(),
+                settlement_engine_url: self.settlement_engine_url.as_ref().map(Url::to_string),
+            },
+            status: self.status,
+            ilp_over_http_incoming_token: incoming_token(&self.ilp_over_http_incoming_token),
+            ilp_over_btp_incoming_token: incoming_token(&self.ilp_over_btp_incoming_token),
+        }
+    }
+
+    /// Returns the address prefixes other than its ILP address which are routed to the account
+    pub fn additional_prefixes(&self) -> &[Address] {
+        &self.additional_prefixes
+    }
+
+    /// Returns the account's ILP address followed by its additional prefixes,
+    /// which are the local routes to the account
+    pub(crate) fn local_prefixes(&self) -> impl Iterator<Item = &Address> {
+        std::iter::once(&self.ilp_address).chain(self.additional_prefixes.iter())
+    }
+
+    /// Encrypts the account's outgoing BTP and HTTP keys with the provided store keys.
+    /// The incoming keys only need to be verified, so they are hashed instead.
+    pub fn encrypt_tokens(mut self, keys: &StoreKeys) -> AccountWithEncryptedTokens {
         if let Some(ref token) = self.ilp_over_btp_outgoing_token {
-            self.ilp_over_btp_outgoing_token = Some(SecretBytesMut::from(encrypt_token(
-                encryption_key,
-                &token.expose_secret(),
-            )));
+            self.ilp_over_btp_outgoing_token = Some(SecretBytesMut::from(
+                keys.encrypt_token(&token.expose_secret()),
+            ));
         }
         if let Some(ref token) = self.ilp_over_http_outgoing_token {
-            self.ilp_over_http_outgoing_token = Some(SecretBytesMut::from(encrypt_token(
-                encryption_key,
-                &token.expose_secret(),
-            )));
-        }
-        if let Some(ref token) = self.ilp_over_btp_incoming_token {
-            self.ilp_over_btp_incoming_token = Some(SecretBytesMut::from(encrypt_token(
-                encryption_key,
-                &token.expose_secret(),
-            )));
-        }
-        if let Some(ref token) = self.ilp_over_http_incoming_token {
-            self.ilp_over_http_incoming_token = Some(SecretBytesMut::from(encrypt_token(
-                encryption_key,
-                &token.expose_secret(),
-            )));
+            self.ilp_over_http_outgoing_token = Some(SecretBytesMut::from(
+                keys.encrypt_token(&token.expose_secret()),
+            ));
         }
+        let username = self.username.to_string();
+        hash_incoming_token(&username, &mut self.ilp_over_btp_incoming_token);
+        hash_incoming_token(&username, &mut self.ilp_over_http_incoming_token);
         AccountWithEncryptedTokens { account: self }
     }
 }
 
+/// Hashes the token unless it already is a hash (e.g. when it was loaded from the store)
+fn hash_incoming_token(username: &str, token: &mut Option<SecretBytesMut>) {
+    if let Some(t) = token {
+        if !is_token_hash(&t.expose_secret()) {
+            *t = SecretBytesMut::from(hash_token(username, &t.expose_secret()));
+        }
+    }
+}
+
 /// A wrapper over the [`Account`](./struct.Account.html) which contains their encrypt tokens.
 #[derive(Debug, Clone)]
 pub struct AccountWithEncryptedTokens {
@@ -219,51 +432,63 @@ pub struct AccountWithEncryptedTokens {
 }
 
 impl AccountWithEncryptedTokens {
-    /// Decrypts the account's incoming/outgoing BTP and HTTP keys with the provided decryption key
-    pub fn decrypt_tokens(mut self, decryption_key: &aead::LessSafeKey) -> Account {
+    /// Decrypts the account's outgoing BTP and HTTP keys with the provided store keys.
+    /// Hashed incoming keys are kept as they are, but the incoming keys of accounts
+    /// which were stored before keys were hashed are decrypted.
+    pub fn decrypt_tokens(mut self, keys: &StoreKeys) -> Account {
         if let Some(ref encrypted) = self.account.ilp_over_btp_outgoing_token {
-            self.account.ilp_over_btp_outgoing_token =
-                decrypt_token(decryption_key, &encrypted.expose_secret())
-                    .map_err(|_| {
-                        error!(
-                            "Unable to decrypt ilp_over_btp_outgoing_token for account {}",
-                            self.account.id
-                        )
-                    })
-                    .ok();
+            self.account.ilp_over_btp_outgoing_token = keys
+                .decrypt_token(&encrypted.expose_secret())
+                .map_err(|_| {
+                    error!(
+                        "Unable to decrypt ilp_over_btp_outgoing_token for account {}",
+                        self.account.id
+                    )
+                })
+                .ok();
         }
         if let Some(ref encrypted) = self.account.ilp_over_http_outgoing_token {
-            self.account.ilp_over_http_outgoing_token =
-                decrypt_token(decryption_key, &encrypted.expose_secret())
-                    .map_err(|_| {
-                        error!(
-                            "Unable to decrypt ilp_over_http_outgoing_token for account {}",
-                            self.account.id
-                        )
-                    })
-                    .ok();
+            self.account.ilp_over_http_outgoing_token = keys
+                .decrypt_token(&encrypted.expose_secret())
+                .map_err(|_| {
+                    error!(
+                        "Unable to decrypt ilp_over_http_outgoing_token for account {}",
+                        self.account.id
+                    )
+                })
+                .ok();
         }
-        if let Some(ref encrypted) = self.account.ilp_over_btp_incoming_token {
-            self.account.ilp_over_btp_incoming_token =
-                decrypt_token(decryption_key, &encrypted.expose_secret())
-                    .map_err(|_| {
-                        error!(
-                            "Unable to decrypt ilp_over_btp_incoming_token for account {}",
-                            self.account.id
-                        )
-                    })
-                    .ok();
+        if let Some(ref encrypted) = self
+            .account
+            .ilp_over_btp_incoming_token
+            .as_ref()
+            .filter(|token| !is_token_hash(&token.expose_secret()))
+        {
+            self.account.ilp_over_btp_incoming_token = keys
+                .decrypt_token(&encrypted.expose_secret())
+                .map_err(|_| {
+                    error!(
+                        "Unable to decrypt ilp_over_btp_incoming_token for account {}",
+                        self.account.id
+                    )
+                })
+                .ok();
         }
-        if let Some(ref encrypted) = self.account.ilp_over_http_incoming_token {
-            self.account.ilp_over_http_incoming_token =
-                decrypt_token(decryption_key, &encrypted.expose_secret())
-                    .map_err(|_| {
-                        error!(
-                            "Unable to decrypt ilp_over_http_incoming_token for account {}",
-                            self.account.id
-                        )
-                    })
-                    .ok();
+        if let Some(ref encrypted) = self
+            .account
+            .ilp_over_http_incoming_token
+            .as_ref()
+            .filter(|token| !is_token_hash(&token.expose_secret()))
+        {
+            self.account.ilp_over_http_incoming_token = keys
+                .decrypt_token(&encrypted.expose_secret())
+                .map_err(|_| {
+                    error!(
+                        "Unable to decrypt ilp_over_http_incoming_token for account {}",
+                        self.account.id
+                    )
+                })
+                .ok();
         }
 
         self.account
@@ -334,6 +559,16 @@ impl CcpRoutingAccount for Account {
     fn routing_relation(&self) -> RoutingRelation {
         self.routing_relation
     }
+
+    fn is_routable(&self) -> bool {
+        self.status.accepts_outgoing_packets()
+    }
+}
+
+impl StatusAccount for Account {
+    fn status(&self) -> AccountStatus {
+        self.status
+    }
 }
 
 impl RoundTripTimeAccount for Account {
@@ -350,6 +585,42 @@ impl RateLimitAccount for Account {
     fn packets_per_minute_limit(&self) -> Option<u32> {
         self.packets_per_minute_limit
     }
+
+    fn packets_burst_limit(&self) -> Option<u32> {
+        self.packets_burst_limit.or(self.packets_per_minute_limit)
+    }
+
+    fn amount_burst_limit(&self) -> Option<u64> {
+        self.amount_burst_limit.or(self.amount_per_minute_limit)
+    }
+
+    fn amount_per_day_limit(&self) -> Option<u64> {
+        self.amount_per_day_limit
+    }
+
+    fn amount_per_month_limit(&self) -> Option<u64> {
+        self.amount_per_month_limit
+    }
+}
+
+impl FeeAccount for Account {
+    fn fixed_fee(&self) -> u64 {
+        self.fixed_fee.unwrap_or(0)
+    }
+
+    fn fee_basis_points(&self) -> u32 {
+        self.fee_basis_points.unwrap_or(0)
+    }
+}
+
+impl DestinationFilterAccount for Account {
+    fn allowed_destination_prefixes(&self) -> &[String] {
+        &self.allowed_destination_prefixes
+    }
+
+    fn denied_destination_prefixes(&self) -> &[String] {
+        &self.denied_destination_prefixes
+    }
 }
 
 impl SettlementAccount for Account {
@@ -370,6 +641,7 @@ mod test {
     lazy_static! {
         static ref ACCOUNT_DETAILS: AccountDetails = AccountDetails {
             ilp_address: Some(Address::from_str("example.alice").unwrap()),
+            additional_prefixes: Vec::new(),
             username: Username::from_str("alice").unwrap(),
             asset_scale: 6,
             asset_code: "XYZ".to_string(),
@@ -388,6 +660,14 @@ mod test {
             round_trip_time: Some(600),
             amount_per_minute_limit: None,
             packets_per_minute_limit: None,
+            amount_burst_limit: None,
+            packets_burst_limit: None,
+            amount_per_day_limit: None,
+            amount_per_month_limit: None,
+            fixed_fee: None,
+            fee_basis_points: None,
+            allowed_destination_prefixes: Vec::new(),
+            denied_destination_prefixes: Vec::new(),
             settlement_engine_url: None,
         };
     }
@@ -421,4 +701,84 @@ mod test {
         );
         assert_eq!(account.routing_relation(), RoutingRelation::Peer);
     }
+
+    #[test]
+    fn rejects_invalid_rate_limits() {
+        let node_ilp_address = Address::from_str("example.account").unwrap();
+        let mut details = ACCOUNT_DETAILS.clone();
+        details.packets_per_minute_limit = Some(10);
+        details.packets_burst_limit = Some(10);
+        details.amount_per_minute_limit = Some(1000);
+        details.amount_burst_limit = Some(5000);
+        assert!(
+            Account::try_from(Uuid::new_v4(), details.clone(), node_ilp_address.clone()).is_ok()
+        );
+
+        let mut zero_limit = details.clone();
+        zero_limit.packets_per_minute_limit = Some(0);
+        let mut zero_burst = details.clone();
+        zero_burst.amount_burst_limit = Some(0);
+        let mut low_burst = details.clone();
+        low_burst.amount_burst_limit = Some(999);
+        for details in vec![zero_limit, zero_burst, low_burst] {
+            assert!(Account::try_from(Uuid::new_v4(), details, node_ilp_address.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_incoming_tokens_that_look_like_hashes() {
+        let node_ilp_address = Address::from_str("example.account").unwrap();
+        let mut http_token = ACCOUNT_DETAILS.clone();
+        http_token.ilp_over_http_incoming_token =
+            Some(SecretString::new("pbkdf2-sha256$token".to_string()));
+        let mut btp_token = ACCOUNT_DETAILS.clone();
+        btp_token.ilp_over_btp_incoming_token =
+            Some(SecretString::new("pbkdf2-sha256$token".to_string()));
+        for details in vec![http_token, btp_token] {
+            assert!(Account::try_from(Uuid::new_v4(), details, node_ilp_address.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_additional_prefixes_overlapping_the_node_address() {
+        let node_ilp_address = Address::from_str("example.parent.node").unwrap();
+        let mut details = ACCOUNT_DETAILS.clone();
+        for prefix in &[
+            "example.parent.nodes",
+            "example.parent.node.alice-other",
+            "test.alice",
+        ] {
+            details.additional_prefixes = vec![Address::from_str(prefix).unwrap()];
+            assert!(
+                Account::try_from(Uuid::new_v4(), details.clone(), node_ilp_address.clone())
+                    .is_ok()
+            );
+        }
+        for prefix in &["example.parent", "example.parent.node"] {
+            details.additional_prefixes = vec![Address::from_str(prefix).unwrap()];
+            assert!(
+                Account::try_from(Uuid::new_v4(), details.clone(), node_ilp_address.clone())
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_destination_prefixes() {
+        let node_ilp_address = Address::from_str("example.account").unwrap();
+        let mut details = ACCOUNT_DETAILS.clone();
+        details.allowed_destination_prefixes = vec!["example.".to_string()];
+        details.denied_destination_prefixes = vec!["test".to_string(), "g.a-b_c~d".to_string()];
+        let account =
+            Account::try_from(Uuid::new_v4(), details.clone(), node_ilp_address.clone()).unwrap();
+        assert_eq!(account.allowed_destination_prefixes(), &["example."]);
+
+        for prefix in &["", "g.a,b", "g.a b"] {
+            details.denied_destination_prefixes = vec![prefix.to_string()];
+            assert!(
+                Account::try_from(Uuid::new_v4(), details.clone(), node_ilp_address.clone())
+                    .is_err()
+            );
+        }
+    }
 }
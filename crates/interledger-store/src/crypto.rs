@@ -1,16 +1,35 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
+use log::error;
+use parking_lot::{Mutex, RwLock};
 use ring::{
-    aead, hmac,
+    aead, constant_time, digest, hmac, pbkdf2,
     rand::{SecureRandom, SystemRandom},
 };
 
 const NONCE_LENGTH: usize = 12;
 static ENCRYPTION_KEY_GENERATION_STRING: &[u8] = b"ilp_store_redis_encryption_key";
 
+/// Incoming tokens starting with this prefix are stored as PBKDF2 hashes,
+/// the others are encrypted (as they were before tokens were hashed)
+pub const TOKEN_HASH_PREFIX: &[u8] = b"pbkdf2-sha256$";
+const TOKEN_HASH_ITERATIONS: u32 = 100_000;
+const TOKEN_SALT_LENGTH: usize = 16;
+const TOKEN_HASH_LENGTH: usize = digest::SHA256_OUTPUT_LEN;
+/// Upper bound of the number of verified tokens remembered by a `TokenVerifier`
+const MAX_VERIFIED_TOKENS: usize = 10_000;
+/// Number of the latest wrong tokens tried against each hash which are remembered,
+/// so that they are rejected again without hashing them
+const MAX_FAILED_ATTEMPTS: usize = 10;
+/// Number of tokens a `TokenVerifier` hashes at the same time. The other tokens
+/// wait for their turn, so that a peer trying many tokens slows down the checks
+/// instead of using up the blocking thread pool
+const MAX_CONCURRENT_HASHES: usize = 4;
+
 use core::sync::atomic;
-use secrecy::{DebugSecret, Secret, SecretBytesMut};
-use std::ptr;
-use zeroize::Zeroize;
+use secrecy::{DebugSecret, ExposeSecret, Secret, SecretBytesMut};
+use std::{collections::HashMap, convert::TryInto, num::NonZeroU32, ptr, sync::Arc};
+use tokio::sync::Semaphore;
+use zeroize::{Zeroize, Zeroizing};
 
 #[derive(Debug)]
 pub struct EncryptionKey(pub(crate) aead::LessSafeKey);
@@ -156,6 +175,235 @@ pub fn decrypt_token(
     }
 }
 
+/// Hashes an incoming token with PBKDF2. The salt is made of the username
+/// and random bytes, and is stored in the returned bytes along with the
+/// number of iterations so that they can be changed later on.
+pub fn hash_token(username: &str, token: &[u8]) -> BytesMut {
+    let mut salt = [0; TOKEN_SALT_LENGTH];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .expect("Unable to get sufficient entropy for salt");
+    let mut hash = [0; TOKEN_HASH_LENGTH];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(TOKEN_HASH_ITERATIONS).unwrap(),
+        &token_salt(username, &salt),
+        token,
+        &mut hash,
+    );
+
+    let mut hashed = BytesMut::with_capacity(
+        TOKEN_HASH_PREFIX.len() + 4 + TOKEN_SALT_LENGTH + TOKEN_HASH_LENGTH,
+    );
+    hashed.put_slice(TOKEN_HASH_PREFIX);
+    hashed.put_u32(TOKEN_HASH_ITERATIONS);
+    hashed.put_slice(&salt);
+    hashed.put_slice(&hash);
+    hashed
+}
+
+/// Returns true if the stored incoming token is a hash rather than an encrypted token
+pub fn is_token_hash(stored: &[u8]) -> bool {
+    stored.starts_with(TOKEN_HASH_PREFIX)
+}
+
+/// Checks the token against a hash returned by `hash_token` in constant time
+pub fn verify_token_hash(username: &str, hashed: &[u8], token: &[u8]) -> bool {
+    if !is_token_hash(hashed)
+        || hashed.len() != TOKEN_HASH_PREFIX.len() + 4 + TOKEN_SALT_LENGTH + TOKEN_HASH_LENGTH
+    {
+        return false;
+    }
+    let hashed = &hashed[TOKEN_HASH_PREFIX.len()..];
+    let (iterations, hashed) = hashed.split_at(4);
+    let iterations = match NonZeroU32::new(u32::from_be_bytes(iterations.try_into().unwrap())) {
+        Some(iterations) => iterations,
+        None => return false,
+    };
+    let (salt, hash) = hashed.split_at(TOKEN_SALT_LENGTH);
+    pbkdf2::verify(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &token_salt(username, salt),
+        token,
+        hash,
+    )
+    .is_ok()
+}
+
+fn token_salt(username: &str, random: &[u8]) -> Vec<u8> {
+    let mut salt = Vec::with_capacity(username.len() + random.len());
+    salt.extend_from_slice(username.as_bytes());
+    salt.extend_from_slice(random);
+    salt
+}
+
+/// Verifies the tokens presented by peers against the stored incoming tokens.
+///
+/// Hashing a token is deliberately slow, which is too slow to do for every
+/// ILP over HTTP request, so once a token has been verified against a hash an
+/// HMAC of it (keyed with a random key which never leaves the process) is
+/// remembered and compared on the following requests instead.
+///
+/// Hashes are computed on the blocking thread pool so that they do not hold up
+/// the executor, and only a few of them at a time. The latest wrong tokens
+/// tried against each hash are remembered too, so that retrying them does not
+/// cost another hash. Every other token is checked against the hash, so peers
+/// presenting the right token are never locked out by others trying wrong ones.
+pub struct TokenVerifier {
+    key: hmac::Key,
+    verified: RwLock<HashMap<Vec<u8>, hmac::Tag>>,
+    failed: Mutex<HashMap<Vec<u8>, Vec<hmac::Tag>>>,
+    hashing: Semaphore,
+}
+
+impl TokenVerifier {
+    pub fn new() -> Self {
+        TokenVerifier {
+            key: hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .expect("Unable to get sufficient entropy for HMAC key"),
+            verified: RwLock::new(HashMap::new()),
+            failed: Mutex::new(HashMap::new()),
+            hashing: Semaphore::new(MAX_CONCURRENT_HASHES),
+        }
+    }
+
+    /// Checks the token against the account's stored incoming token, which is
+    /// either a hash or the decrypted token of an account that was not migrated yet
+    pub async fn verify(&self, username: &str, stored: &[u8], token: &[u8]) -> bool {
+        if !is_token_hash(stored) {
+            return constant_time::verify_slices_are_equal(stored, token).is_ok();
+        }
+        // Only the token which was verified can match the hash
+        if let Some(tag) = self.verified.read().get(stored) {
+            return hmac::verify(&self.key, token, tag.as_ref()).is_ok();
+        }
+        if let Some(tags) = self.failed.lock().get(stored) {
+            if tags
+                .iter()
+                .any(|tag| hmac::verify(&self.key, token, tag.as_ref()).is_ok())
+            {
+                return false;
+            }
+        }
+
+        let hash_username = username.to_owned();
+        let hashed = stored.to_vec();
+        let hash_token = Zeroizing::new(token.to_vec());
+        let matches = {
+            let _permit = self.hashing.acquire().await;
+            tokio::task::spawn_blocking(move || {
+                verify_token_hash(&hash_username, &hashed, &hash_token)
+            })
+            .await
+            .unwrap_or_else(|err| {
+                error!("Error verifying token for {}: {:?}", username, err);
+                false
+            })
+        };
+
+        if matches {
+            let mut verified = self.verified.write();
+            // Hashes of tokens which were changed are never looked up again
+            if verified.len() >= MAX_VERIFIED_TOKENS {
+                verified.clear();
+            }
+            verified.insert(stored.to_vec(), hmac::sign(&self.key, token));
+            self.failed.lock().remove(stored);
+        } else {
+            let mut failed = self.failed.lock();
+            if failed.len() >= MAX_VERIFIED_TOKENS {
+                failed.clear();
+            }
+            let tags = failed.entry(stored.to_vec()).or_insert_with(Vec::new);
+            if tags.len() >= MAX_FAILED_ATTEMPTS {
+                tags.remove(0);
+            }
+            tags.push(hmac::sign(&self.key, token));
+        }
+        matches
+    }
+}
+
+impl Default for TokenVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The keys used by a store to encrypt and decrypt account tokens.
+///
+/// When the store's secret is rotated, the decryption keys derived from the
+/// previous secrets are kept until every token was re-encrypted with the new
+/// secret, so that accounts can still be used in the meantime.
+#[derive(Clone)]
+pub struct StoreKeys {
+    pub(crate) encryption_key: Arc<Secret<EncryptionKey>>,
+    pub(crate) decryption_key: Arc<Secret<DecryptionKey>>,
+    previous_decryption_keys: Vec<Arc<Secret<DecryptionKey>>>,
+}
+
+impl StoreKeys {
+    pub fn new(server_secret: &[u8]) -> Self {
+        let (encryption_key, decryption_key) = generate_keys(server_secret);
+        StoreKeys {
+            encryption_key: Arc::new(encryption_key),
+            decryption_key: Arc::new(decryption_key),
+            previous_decryption_keys: Vec::new(),
+        }
+    }
+
+    /// Returns the keys for the new secret, which can also decrypt
+    /// everything that could be decrypted with these keys
+    pub fn rotate(&self, new_server_secret: &[u8]) -> Self {
+        let mut rotated = StoreKeys::new(new_server_secret);
+        rotated.previous_decryption_keys = self.previous_decryption_keys.clone();
+        rotated
+            .previous_decryption_keys
+            .push(self.decryption_key.clone());
+        rotated
+    }
+
+    /// Returns keys which still encrypt with the current key but can also
+    /// decrypt tokens encrypted with the key of the new secret. Every node
+    /// sharing the database switches to these before any of them encrypts
+    /// with the new key
+    pub fn prepare_rotation(&self, new_server_secret: &[u8]) -> Self {
+        let (_, decryption_key) = generate_keys(new_server_secret);
+        let mut prepared = self.clone();
+        prepared
+            .previous_decryption_keys
+            .push(Arc::new(decryption_key));
+        prepared
+    }
+
+    /// Drops the keys derived from the previous secrets. This must only be
+    /// done once every token has been re-encrypted with the current key
+    pub fn without_previous_keys(&self) -> Self {
+        StoreKeys {
+            encryption_key: self.encryption_key.clone(),
+            decryption_key: self.decryption_key.clone(),
+            previous_decryption_keys: Vec::new(),
+        }
+    }
+
+    pub fn encrypt_token(&self, token: &[u8]) -> BytesMut {
+        encrypt_token(&self.encryption_key.expose_secret().0, token)
+    }
+
+    /// Decrypts the token with the current key, falling back to the
+    /// keys of the previous secrets while a rotation is in progress
+    pub fn decrypt_token(&self, encrypted: &[u8]) -> Result<SecretBytesMut, ()> {
+        decrypt_token(&self.decryption_key.expose_secret().0, encrypted).or_else(|_| {
+            self.previous_decryption_keys
+                .iter()
+                .rev()
+                .find_map(|key| decrypt_token(&key.expose_secret().0, encrypted).ok())
+                .ok_or(())
+        })
+    }
+}
+
 #[cfg(test)]
 mod encryption {
     use super::*;
@@ -172,4 +420,70 @@ mod encryption {
             "test test"
         );
     }
+
+    #[test]
+    fn decrypts_with_previous_keys_after_rotation() {
+        let keys = StoreKeys::new(&[1; 32]);
+        let encrypted = keys.encrypt_token(b"test test");
+        let rotated = keys.rotate(&[2; 32]).rotate(&[3; 32]);
+        let decrypted = rotated.decrypt_token(encrypted.as_ref()).unwrap();
+        assert_eq!(decrypted.expose_secret().as_ref(), b"test test");
+
+        let reencrypted = rotated.encrypt_token(decrypted.expose_secret().as_ref());
+        let rotated = rotated.without_previous_keys();
+        assert!(rotated.decrypt_token(encrypted.as_ref()).is_err());
+        assert!(rotated.decrypt_token(reencrypted.as_ref()).is_ok());
+        assert!(keys.decrypt_token(reencrypted.as_ref()).is_err());
+    }
+
+    #[test]
+    fn decrypts_with_new_key_after_preparing_rotation() {
+        let keys = StoreKeys::new(&[1; 32]);
+        let prepared = keys.prepare_rotation(&[2; 32]);
+        let encrypted = prepared.encrypt_token(b"test test");
+        assert!(keys.decrypt_token(encrypted.as_ref()).is_ok());
+
+        let rotated = keys.rotate(&[2; 32]);
+        let reencrypted = rotated.encrypt_token(b"test test");
+        assert!(prepared.decrypt_token(reencrypted.as_ref()).is_ok());
+        assert!(rotated.decrypt_token(encrypted.as_ref()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn hashes_and_verifies_tokens() {
+        let hashed = hash_token("alice", b"test test");
+        assert!(is_token_hash(&hashed));
+        assert_ne!(hash_token("alice", b"test test"), hashed);
+        assert!(verify_token_hash("alice", &hashed, b"test test"));
+        assert!(!verify_token_hash("alice", &hashed, b"test"));
+        assert!(!verify_token_hash("bob", &hashed, b"test test"));
+
+        let verifier = TokenVerifier::new();
+        for _ in 0..2 {
+            assert!(verifier.verify("alice", &hashed, b"test test").await);
+            assert!(!verifier.verify("alice", &hashed, b"test").await);
+        }
+        // Tokens that were not migrated yet are compared with the decrypted token
+        assert!(verifier.verify("alice", b"test test", b"test test").await);
+        assert!(!verifier.verify("alice", b"test test", b"test").await);
+    }
+
+    #[tokio::test]
+    async fn verifies_right_token_after_many_failed_attempts() {
+        let hashed = hash_token("alice", b"test test");
+        let verifier = TokenVerifier::new();
+        for i in 0..MAX_FAILED_ATTEMPTS + 1 {
+            let token = format!("wrong {}", i);
+            assert!(!verifier.verify("alice", &hashed, token.as_bytes()).await);
+        }
+        assert_eq!(
+            verifier.failed.lock()[hashed.as_ref()].len(),
+            MAX_FAILED_ATTEMPTS
+        );
+
+        // The right token is still checked against the hash
+        assert!(verifier.verify("alice", &hashed, b"test test").await);
+        assert!(!verifier.failed.lock().contains_key(hashed.as_ref()));
+        assert!(!verifier.verify("alice", &hashed, b"wrong 0").await);
+    }
 }
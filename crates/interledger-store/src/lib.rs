@@ -6,6 +6,15 @@
 pub mod account;
 /// Cryptographic utilities for encrypting/decrypting data as well as clearing data from memory
 pub mod crypto;
+/// An in-memory backend which does not persist any data (useful for tests and demos)
+#[cfg(feature = "memory")]
+pub mod memory;
 /// A redis backend using [redis-rs](https://github.com/mitsuhiko/redis-rs/)
 #[cfg(feature = "redis")]
 pub mod redis;
+/// A SQLite backend using [rusqlite](https://github.com/jgallagher/rusqlite), for nodes which run on a single machine
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+/// GCRA rate limiting for the backends which cannot use redis-cell
+#[cfg(any(feature = "memory", feature = "sqlite"))]
+mod throttle;
@@ -0,0 +1,1702 @@
+// The in-memory store mirrors the data model of the Redis store:
+//   accounts           map         information and balances for each account
+//   usernames          map         username -> account id
+//   routes             map         dynamic routing table (routes:current)
+//   static_routes      map         static routing table (routes:static)
+//   default_route      option      account used for the "" prefix (routes:default)
+//   settlement_engines map         asset code -> settlement engine url
+//   uncredited_amounts map         leftovers which could not be credited yet
+//   idempotency_keys   map         cached settlement API responses
+// Nothing is persisted, so all of the node's state is lost when the
+// process exits. This backend is meant for tests, demos and CI.
+
+use super::account::{validate_incoming_token, Account};
+use super::crypto::TokenVerifier;
+use super::throttle::{AmountLimits, Throttle};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::channel::mpsc::UnboundedSender;
+use http::StatusCode;
+use interledger_api::{
+    AccountDetails, AccountSettings, AccountsPage, AccountsQuery, ApiKey, ExportedAccount,
+    NodeStore,
+};
+use interledger_btp::BtpStore;
+use interledger_ccp::{CcpRoutingAccount, RouteManagerStore, RoutingRelation};
+use interledger_http::HttpStore;
+use interledger_packet::Address;
+use interledger_router::RouterStore;
+use interledger_service::{AccountStatus, AccountStore, AddressStore, Username};
+use interledger_service_util::{
+    journal_timestamp, BalanceChangeKind, BalanceHistoryPage, BalanceHistoryQuery,
+    BalanceJournalEntry, BalanceStore, CumulativeAmounts, ExchangeRate, ExchangeRateHistoryPage,
+    ExchangeRateHistoryQuery, ExchangeRateHistoryStore, ExchangeRateRecord, ExchangeRateStore,
+    FeeStore, FeesEarned, PacketContext, PairQuote, RateLimitError, RateLimitStore, Spreads,
+    BALANCE_JOURNAL_RETENTION, EXCHANGE_RATE_HISTORY_LENGTH,
+};
+use interledger_settlement::core::{
+    idempotency::{IdempotentData, IdempotentStore},
+    scale_with_precision_loss,
+    types::{Convert, ConvertDetails, LeftoversStore, SettlementStore},
+};
+use interledger_stream::{PaymentNotification, StreamNotificationsStore};
+use lazy_static::lazy_static;
+use log::{debug, error, trace, warn};
+use num_bigint::BigUint;
+use parking_lot::RwLock;
+use secrecy::{ExposeSecret, SecretBytesMut, SecretString};
+use std::{
+    collections::{HashMap, VecDeque},
+    iter::FromIterator,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use url::Url;
+use uuid::Uuid;
+
+/// Idempotency keys are kept for 24 hours, same as in the Redis store
+const IDEMPOTENCY_KEY_EXPIRY: Duration = Duration::from_secs(86400);
+
+lazy_static! {
+    /// The node's default ILP Address
+    static ref DEFAULT_ILP_ADDRESS: Address = Address::from_str("local.host").unwrap();
+}
+
+/// Builder for the In-Memory Store
+pub struct InMemoryStoreBuilder {
+    /// Connector's ILP Address. Used to derive the addresses of `Child` accounts
+    node_ilp_address: Address,
+}
+
+impl Default for InMemoryStoreBuilder {
+    fn default() -> Self {
+        InMemoryStoreBuilder {
+            node_ilp_address: DEFAULT_ILP_ADDRESS.clone(),
+        }
+    }
+}
+
+impl InMemoryStoreBuilder {
+    /// Simple Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the ILP Address corresponding to the node
+    pub fn node_ilp_address(&mut self, node_ilp_address: Address) -> &mut Self {
+        self.node_ilp_address = node_ilp_address;
+        self
+    }
+
+    /// Creates an empty store
+    pub fn build(&self) -> InMemoryStore {
+        InMemoryStore {
+            ilp_address: Arc::new(RwLock::new(self.node_ilp_address.clone())),
+            state: Arc::new(RwLock::new(InMemoryState::default())),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rates: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rates_updated_at: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rate_history: Arc::new(RwLock::new(ExchangeRateHistory::default())),
+            spreads: Arc::new(RwLock::new(Spreads::default())),
+            spreads_set: Arc::new(AtomicBool::new(false)),
+            pair_quotes: Arc::new(RwLock::new(HashMap::new())),
+            routes: Arc::new(RwLock::new(Arc::new(HashMap::new()))),
+            token_verifier: Arc::new(TokenVerifier::new()),
+        }
+    }
+}
+
+/// An account along with its balances
+struct AccountEntry {
+    account: Account,
+    balance: i64,
+    prepaid_amount: i64,
+    /// Oldest first. Entries are removed from the front once they are older than the retention
+    journal: VecDeque<BalanceJournalEntry>,
+    /// Number of entries removed from the journal, so that an entry's position
+    /// in the whole journal can be used as the cursor of the next page
+    journal_offset: u64,
+    /// The secrets are kept as they are, like the incoming tokens
+    api_keys: Vec<(ApiKey, SecretString)>,
+}
+
+/// The last `EXCHANGE_RATE_HISTORY_LENGTH` rates set for each asset, oldest first
+#[derive(Default)]
+struct ExchangeRateHistory {
+    /// The records of each asset with their positions among all records ever
+    /// recorded, which are used as the cursors of the pages
+    records: HashMap<String, VecDeque<(u64, ExchangeRateRecord)>>,
+    next_position: u64,
+}
+
+impl ExchangeRateHistory {
+    fn record(&mut self, records: Vec<ExchangeRateRecord>) {
+        for record in records {
+            let asset_records = self.records.entry(record.asset_code.clone()).or_default();
+            if asset_records.len() >= EXCHANGE_RATE_HISTORY_LENGTH {
+                asset_records.pop_front();
+            }
+            asset_records.push_back((self.next_position, record));
+            self.next_position += 1;
+        }
+    }
+}
+
+impl AccountEntry {
+    /// Appends an entry for a balance change that was just applied
+    fn record(&mut self, kind: BalanceChangeKind, delta: i64, context: Option<PacketContext>) {
+        let balance = self.balance + self.prepaid_amount;
+        let entry = BalanceJournalEntry::new(kind, delta, balance, context);
+        let expired_before = entry.timestamp.saturating_sub(BALANCE_JOURNAL_RETENTION);
+        while self
+            .journal
+            .front()
+            .map_or(false, |oldest| oldest.timestamp < expired_before)
+        {
+            self.journal.pop_front();
+            self.journal_offset += 1;
+        }
+        self.journal.push_back(entry);
+    }
+}
+
+/// A cached settlement API response
+struct IdempotencyEntry {
+    data: IdempotentData,
+    saved_at: Instant,
+}
+
+/// Everything the store knows about. All of it is kept behind a single lock
+/// so that each operation is atomic, just like the Lua scripts in the Redis store.
+struct InMemoryState {
+    accounts: HashMap<Uuid, AccountEntry>,
+    usernames: HashMap<String, Uuid>,
+    /// The address we were assigned by our parent, if any
+    parent_ilp_address: Option<Address>,
+    routes: HashMap<String, Uuid>,
+    static_routes: HashMap<String, Uuid>,
+    default_route: Option<Uuid>,
+    settlement_engines: HashMap<String, Url>,
+    uncredited_amounts: HashMap<Uuid, Vec<(BigUint, u8)>>,
+    idempotency_keys: HashMap<String, IdempotencyEntry>,
+    /// Idempotency keys of incoming settlements which were already credited
+    settlement_idempotency_keys: HashMap<String, Instant>,
+    rate_limits: Throttle,
+    amount_limits: AmountLimits,
+    /// Fees earned on packets forwarded between each pair of accounts
+    fees_earned: HashMap<(Uuid, Uuid), u64>,
+}
+
+impl Default for InMemoryState {
+    fn default() -> Self {
+        InMemoryState {
+            accounts: HashMap::new(),
+            usernames: HashMap::new(),
+            parent_ilp_address: None,
+            routes: HashMap::new(),
+            static_routes: HashMap::new(),
+            default_route: None,
+            settlement_engines: HashMap::new(),
+            uncredited_amounts: HashMap::new(),
+            idempotency_keys: HashMap::new(),
+            settlement_idempotency_keys: HashMap::new(),
+            rate_limits: Throttle::default(),
+            amount_limits: AmountLimits::default(),
+            fees_earned: HashMap::new(),
+        }
+    }
+}
+
+impl InMemoryState {
+    /// Loads an account, falling back to the globally configured settlement
+    /// engine for the account's asset if it does not have one of its own
+    fn load_account(&self, id: Uuid) -> Option<Account> {
+        self.accounts.get(&id).map(|entry| {
+            let mut account = entry.account.clone();
+            if account.settlement_engine_url.is_none() {
+                account.settlement_engine_url =
+                    self.settlement_engines.get(&account.asset_code).cloned();
+            }
+            account
+        })
+    }
+
+    /// Returns an additional prefix of the account which is already routed to another account
+    fn prefix_routed_to_other_account<'a>(&self, account: &'a Account) -> Option<&'a Address> {
+        account.additional_prefixes.iter().find(|prefix| {
+            self.routes
+                .get(&prefix.to_string())
+                .map_or(false, |id| *id != account.id)
+        })
+    }
+
+    /// Removes the routes to the account's address and additional prefixes,
+    /// unless they were routed to another account since
+    fn remove_local_routes(&mut self, account: &Account) {
+        for prefix in account.local_prefixes() {
+            if self.routes.get(&prefix.to_string()) == Some(&account.id) {
+                self.routes.remove(&prefix.to_string());
+            }
+        }
+    }
+
+    fn load_all_accounts(&self) -> Vec<Account> {
+        self.accounts
+            .keys()
+            .filter_map(|id| self.load_account(*id))
+            .collect()
+    }
+
+    /// Combines the dynamic routes, the default route and the static routes
+    /// into a routing table. Static routes overwrite any dynamic routes with
+    /// the same prefix.
+    fn routing_table(&self) -> HashMap<String, Uuid> {
+        let default_route_iter = self.default_route.map(|id| (String::new(), id));
+        HashMap::from_iter(
+            self.routes
+                .iter()
+                .map(|(prefix, id)| (prefix.clone(), *id))
+                .chain(default_route_iter)
+                .chain(
+                    self.static_routes
+                        .iter()
+                        .map(|(prefix, id)| (prefix.clone(), *id)),
+                ),
+        )
+    }
+}
+
+/// Base asset code -> quote asset code -> quote and when it was set
+type PairQuotes = HashMap<String, HashMap<String, (PairQuote, SystemTime)>>;
+
+/// A Store that keeps all of its data in memory.
+///
+/// It implements the same traits with the same semantics as the RedisStore,
+/// but its data does not outlive the process and cannot be shared between nodes.
+#[derive(Clone)]
+pub struct InMemoryStore {
+    /// The Store's ILP Address
+    ilp_address: Arc<RwLock<Address>>,
+    /// Accounts, balances, routes and settlement data
+    state: Arc<RwLock<InMemoryState>>,
+    /// WebSocket sender which publishes incoming payment updates
+    subscriptions: Arc<RwLock<HashMap<Uuid, UnboundedSender<PaymentNotification>>>>,
+    exchange_rates: Arc<RwLock<HashMap<String, ExchangeRate>>>,
+    /// When each of the exchange rates was last set
+    exchange_rates_updated_at: Arc<RwLock<HashMap<String, SystemTime>>>,
+    exchange_rate_history: Arc<RwLock<ExchangeRateHistory>>,
+    spreads: Arc<RwLock<Spreads>>,
+    /// Whether the spreads were set with `set_spreads`, after which the default spreads no longer apply
+    spreads_set: Arc<AtomicBool>,
+    pair_quotes: Arc<RwLock<PairQuotes>>,
+    /// The routing table is computed whenever the routes change so that it
+    /// can be returned synchronously while the Router is processing packets.
+    routes: Arc<RwLock<Arc<HashMap<String, Uuid>>>>,
+    /// The incoming tokens are kept as they are, unless the account was
+    /// imported from a store which hashed them
+    token_verifier: Arc<TokenVerifier>,
+}
+
+impl InMemoryStore {
+    /// Inserts the account unless its id or username is already taken
+    fn insert(&self, account: Account) -> Result<Account, ()> {
+        let mut state = self.state.write();
+        // Check that there isn't already an account with the same id or username
+        if state.accounts.contains_key(&account.id)
+            || state.usernames.contains_key(&account.username.to_string())
+        {
+            warn!(
+                "An account already exists with the same {}. Cannot insert account: {:?}",
+                account.id, account
+            );
+            return Err(());
+        }
+        if let Some(prefix) = state.prefix_routed_to_other_account(&account) {
+            warn!(
+                "The prefix {} is already routed to another account. Cannot insert account: {:?}",
+                prefix, account
+            );
+            return Err(());
+        }
+        // Only one parent account is allowed
+        if account.routing_relation == RoutingRelation::Parent && state.parent_ilp_address.is_some()
+        {
+            warn!(
+                "A parent account already exists. Cannot insert account: {:?}",
+                account
+            );
+            return Err(());
+        }
+
+        state
+            .usernames
+            .insert(account.username.to_string(), account.id);
+        // Add routes to routing table
+        for prefix in account.local_prefixes() {
+            state.routes.insert(prefix.to_string(), account.id);
+        }
+        state.accounts.insert(
+            account.id,
+            AccountEntry {
+                account: account.clone(),
+                balance: 0,
+                prepaid_amount: 0,
+                journal: VecDeque::new(),
+                journal_offset: 0,
+                api_keys: Vec::new(),
+            },
+        );
+        self.update_routes(&state);
+
+        debug!(
+            "Inserted account {} (ILP address: {})",
+            account.id, account.ilp_address
+        );
+        Ok(account)
+    }
+
+    fn update_routes(&self, state: &InMemoryState) {
+        let routes = state.routing_table();
+        trace!("Routing table is: {:?}", routes);
+        *self.routes.write() = Arc::new(routes);
+    }
+}
+
+#[async_trait]
+impl AccountStore for InMemoryStore {
+    type Account = Account;
+
+    async fn get_accounts(&self, account_ids: Vec<Uuid>) -> Result<Vec<Account>, ()> {
+        let state = self.state.read();
+        let accounts: Vec<Account> = account_ids
+            .iter()
+            .filter_map(|id| state.load_account(*id))
+            .collect();
+        if accounts.len() == account_ids.len() {
+            Ok(accounts)
+        } else {
+            Err(())
+        }
+    }
+
+    async fn get_account_id_from_username(&self, username: &Username) -> Result<Uuid, ()> {
+        match self.state.read().usernames.get(&username.to_string()) {
+            Some(id) => Ok(*id),
+            None => {
+                debug!("Username not found: {}", username);
+                Err(())
+            }
+        }
+    }
+}
+
+impl StreamNotificationsStore for InMemoryStore {
+    type Account = Account;
+
+    fn add_payment_notification_subscription(
+        &self,
+        id: Uuid,
+        sender: UnboundedSender<PaymentNotification>,
+    ) {
+        trace!("Added payment notification listener for {}", id);
+        self.subscriptions.write().insert(id, sender);
+    }
+
+    fn publish_payment_notification(&self, payment: PaymentNotification) {
+        let account_id = match self
+            .state
+            .read()
+            .usernames
+            .get(&payment.to_username.to_string())
+        {
+            Some(id) => *id,
+            None => {
+                error!(
+                    "Failed to find account ID corresponding to username: {}",
+                    payment.to_username
+                );
+                return;
+            }
+        };
+
+        debug!(
+            "Publishing payment notification {:?} for account {}",
+            payment, account_id
+        );
+        match self.subscriptions.read().get(&account_id) {
+            Some(sender) => {
+                if let Err(err) = sender.unbounded_send(payment) {
+                    error!("Failed to send message: {}", err);
+                }
+            }
+            None => trace!(
+                "Ignoring message for account {} because there were no open subscriptions",
+                account_id
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl BalanceStore for InMemoryStore {
+    /// Returns the balance **from the account holder's perspective**, meaning the sum of
+    /// the Payable Balance and Pending Outgoing minus the Receivable Balance and the Pending Incoming.
+    async fn get_balance(&self, account: Account) -> Result<i64, ()> {
+        match self.state.read().accounts.get(&account.id) {
+            Some(entry) => Ok(entry.balance + entry.prepaid_amount),
+            None => {
+                error!("Error getting balance for account: {}", account.id);
+                Err(())
+            }
+        }
+    }
+
+    async fn set_balance(&self, account: Account, balance: i64) -> Result<(), ()> {
+        match self.state.write().accounts.get_mut(&account.id) {
+            Some(entry) => {
+                entry.balance = balance;
+                entry.prepaid_amount = 0;
+                Ok(())
+            }
+            None => {
+                error!("Error setting balance for account: {}", account.id);
+                Err(())
+            }
+        }
+    }
+
+    async fn get_balance_history(
+        &self,
+        account: Account,
+        query: BalanceHistoryQuery,
+    ) -> Result<BalanceHistoryPage, ()> {
+        // The cursor is the position of the last entry of the previous page
+        let start = match query.cursor {
+            Some(ref cursor) => u64::from_str(cursor)
+                .map_err(|_| error!("Invalid balance history cursor: {}", cursor))?
+                .saturating_add(1),
+            None => 0,
+        };
+        let state = self.state.read();
+        let entry = state
+            .accounts
+            .get(&account.id)
+            .ok_or_else(|| error!("Error getting balance history for account: {}", account.id))?;
+        let skip = start.saturating_sub(entry.journal_offset) as usize;
+        let mut entries: Vec<(u64, BalanceJournalEntry)> = entry
+            .journal
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .filter(|(_, e)| e.timestamp >= query.from && e.timestamp <= query.to)
+            .map(|(i, e)| (entry.journal_offset + i as u64, e.clone()))
+            .take(query.limit.saturating_add(1))
+            .collect();
+        let mut next_cursor = None;
+        if entries.len() > query.limit {
+            entries.truncate(query.limit);
+            next_cursor = entries.last().map(|(position, _)| position.to_string());
+        }
+        Ok(BalanceHistoryPage {
+            entries: entries.into_iter().map(|(_, e)| e).collect(),
+            next_cursor,
+        })
+    }
+
+    async fn update_balances_for_prepare(
+        &self,
+        from_account: Account,
+        incoming_amount: u64,
+        context: PacketContext,
+    ) -> Result<(), ()> {
+        // Don't do anything if the amount was 0
+        if incoming_amount == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&from_account.id).ok_or_else(|| {
+            error!(
+                "Error processing prepare for account that does not exist: {}",
+                from_account.id
+            )
+        })?;
+        let amount = incoming_amount as i64;
+
+        // Check that the prepare wouldn't go under the account's minimum balance
+        if let Some(min_balance) = entry.account.min_balance {
+            if entry.balance + entry.prepaid_amount - amount < min_balance {
+                warn!(
+                    "Incoming prepare of {} would bring account {} under its minimum balance. Current balance: {}, min balance: {}",
+                    amount, from_account.id, entry.balance + entry.prepaid_amount, min_balance
+                );
+                return Err(());
+            }
+        }
+
+        // Deduct the amount from the prepaid amount first and then from the balance
+        if entry.prepaid_amount >= amount {
+            entry.prepaid_amount -= amount;
+        } else {
+            entry.balance -= amount - entry.prepaid_amount;
+            entry.prepaid_amount = 0;
+        }
+        entry.record(BalanceChangeKind::Prepare, -amount, Some(context));
+
+        trace!(
+            "Processed prepare with incoming amount: {}. Account {} has balance (including prepaid amount): {} ",
+            incoming_amount, from_account.id, entry.balance + entry.prepaid_amount
+        );
+        Ok(())
+    }
+
+    async fn update_balances_for_fulfill(
+        &self,
+        to_account: Account,
+        outgoing_amount: u64,
+        context: PacketContext,
+    ) -> Result<(i64, u64), ()> {
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&to_account.id).ok_or_else(|| {
+            error!(
+                "Error processing fulfill for account that does not exist: {}",
+                to_account.id
+            )
+        })?;
+
+        entry.balance += outgoing_amount as i64;
+        entry.record(
+            BalanceChangeKind::Fulfill,
+            outgoing_amount as i64,
+            Some(context),
+        );
+
+        // Trigger a settlement if the balance went over the settle threshold
+        let mut settle_amount = 0;
+        if let (Some(settle_threshold), Some(settle_to)) =
+            (entry.account.settle_threshold, entry.account.settle_to)
+        {
+            if entry.balance >= settle_threshold && settle_threshold > settle_to {
+                settle_amount = (entry.balance - settle_to) as u64;
+                entry.balance = settle_to;
+                entry.record(
+                    BalanceChangeKind::OutgoingSettlement,
+                    -(settle_amount as i64),
+                    None,
+                );
+            }
+        }
+        let balance = entry.balance + entry.prepaid_amount;
+
+        trace!(
+            "Processed fulfill for account {} for outgoing amount {}. Fulfill call result: {} {}",
+            to_account.id,
+            outgoing_amount,
+            balance,
+            settle_amount,
+        );
+        Ok((balance, settle_amount))
+    }
+
+    async fn update_balances_for_reject(
+        &self,
+        from_account: Account,
+        incoming_amount: u64,
+        context: PacketContext,
+    ) -> Result<(), ()> {
+        if incoming_amount == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&from_account.id).ok_or_else(|| {
+            error!(
+                "Error processing reject for account that does not exist: {}",
+                from_account.id
+            )
+        })?;
+        entry.balance += incoming_amount as i64;
+        entry.record(
+            BalanceChangeKind::Reject,
+            incoming_amount as i64,
+            Some(context),
+        );
+
+        trace!(
+            "Processed reject for incoming amount: {}. Account {} has balance (including prepaid amount): {}",
+            incoming_amount, from_account.id, entry.balance + entry.prepaid_amount
+        );
+        Ok(())
+    }
+}
+
+impl ExchangeRateStore for InMemoryStore {
+    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()> {
+        let rates: Vec<ExchangeRate> = asset_codes
+            .iter()
+            .filter_map(|code| (*self.exchange_rates.read()).get(*code).cloned())
+            .collect();
+        if rates.len() == asset_codes.len() {
+            Ok(rates)
+        } else {
+            Err(())
+        }
+    }
+
+    fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
+        Ok((*self.exchange_rates.read()).clone())
+    }
+
+    fn set_exchange_rates(
+        &self,
+        rates: HashMap<String, ExchangeRate>,
+        source: &str,
+    ) -> Result<(), ()> {
+        let now = SystemTime::now();
+        let timestamp = journal_timestamp();
+        let mut records: Vec<ExchangeRateRecord> = rates
+            .iter()
+            .map(|(asset_code, rate)| ExchangeRateRecord {
+                timestamp,
+                asset_code: asset_code.clone(),
+                rate: rate.clone(),
+                source: source.to_string(),
+            })
+            .collect();
+        records.sort_by(|a, b| a.asset_code.cmp(&b.asset_code));
+        (*self.exchange_rates_updated_at.write()) =
+            rates.keys().map(|code| (code.clone(), now)).collect();
+        (*self.exchange_rates.write()) = rates;
+        self.exchange_rate_history.write().record(records);
+        Ok(())
+    }
+
+    fn get_exchange_rates_updated_at(&self, asset_codes: &[&str]) -> Result<Vec<SystemTime>, ()> {
+        let updated_at = self.exchange_rates_updated_at.read();
+        asset_codes
+            .iter()
+            .map(|code| updated_at.get(*code).cloned().ok_or(()))
+            .collect()
+    }
+
+    fn set_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let mut current = self.spreads.write();
+        self.spreads_set.store(true, Ordering::SeqCst);
+        *current = spreads;
+        Ok(())
+    }
+
+    fn set_default_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let mut current = self.spreads.write();
+        if !self.spreads_set.load(Ordering::SeqCst) {
+            *current = spreads;
+        }
+        Ok(())
+    }
+
+    fn get_spread(&self, from_asset_code: &str, to_asset_code: &str) -> f64 {
+        self.spreads.read().get(from_asset_code, to_asset_code)
+    }
+
+    fn get_all_spreads(&self) -> Result<Spreads, ()> {
+        Ok((*self.spreads.read()).clone())
+    }
+
+    fn set_pair_quotes(&self, quotes: Vec<PairQuote>) -> Result<(), ()> {
+        let now = SystemTime::now();
+        let mut pair_quotes: PairQuotes = HashMap::new();
+        for quote in quotes {
+            pair_quotes
+                .entry(quote.base.clone())
+                .or_default()
+                .insert(quote.quote.clone(), (quote, now));
+        }
+        (*self.pair_quotes.write()) = pair_quotes;
+        Ok(())
+    }
+
+    fn get_pair_quote(&self, base: &str, quote: &str) -> Option<(PairQuote, SystemTime)> {
+        self.pair_quotes
+            .read()
+            .get(base)
+            .and_then(|quotes| quotes.get(quote))
+            .cloned()
+    }
+
+    fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()> {
+        let mut quotes: Vec<PairQuote> = self
+            .pair_quotes
+            .read()
+            .values()
+            .flat_map(|quotes| quotes.values())
+            .map(|(quote, _)| quote.clone())
+            .collect();
+        quotes.sort_by(|a, b| (&a.base, &a.quote).cmp(&(&b.base, &b.quote)));
+        Ok(quotes)
+    }
+}
+
+#[async_trait]
+impl ExchangeRateHistoryStore for InMemoryStore {
+    async fn get_exchange_rate_history(
+        &self,
+        query: ExchangeRateHistoryQuery,
+    ) -> Result<ExchangeRateHistoryPage, ()> {
+        // The cursor is the position of the last record of the previous page
+        let start = match query.cursor {
+            Some(ref cursor) => u64::from_str(cursor)
+                .map_err(|_| error!("Invalid exchange rate history cursor: {}", cursor))?
+                .saturating_add(1),
+            None => 0,
+        };
+        let history = self.exchange_rate_history.read();
+        let mut records: Vec<&(u64, ExchangeRateRecord)> = history
+            .records
+            .iter()
+            .filter(|(code, _)| {
+                query
+                    .asset_code
+                    .as_ref()
+                    .map_or(true, |asset| asset == *code)
+            })
+            .flat_map(|(_, records)| records.iter())
+            .filter(|(position, record)| {
+                *position >= start && record.timestamp >= query.from && record.timestamp <= query.to
+            })
+            .collect();
+        records.sort_by_key(|(position, _)| *position);
+        records.truncate(query.limit.saturating_add(1));
+        let mut next_cursor = None;
+        if records.len() > query.limit {
+            records.truncate(query.limit);
+            next_cursor = records.last().map(|(position, _)| position.to_string());
+        }
+        Ok(ExchangeRateHistoryPage {
+            records: records.into_iter().map(|(_, r)| r.clone()).collect(),
+            next_cursor,
+        })
+    }
+}
+
+#[async_trait]
+impl BtpStore for InMemoryStore {
+    type Account = Account;
+
+    async fn get_account_from_btp_auth(
+        &self,
+        username: &Username,
+        token: &str,
+    ) -> Result<Self::Account, ()> {
+        // Verifying a hashed token takes a while, so the lock is released first
+        let account = {
+            let state = self.state.read();
+            state
+                .usernames
+                .get(&username.to_string())
+                .and_then(|id| state.load_account(*id))
+        };
+        match account {
+            Some(account) => {
+                let matches = match account.ilp_over_btp_incoming_token {
+                    Some(ref t) => {
+                        self.token_verifier
+                            .verify(username.as_ref(), &t.expose_secret(), token.as_bytes())
+                            .await
+                    }
+                    None => false,
+                };
+                if matches {
+                    Ok(account)
+                } else {
+                    debug!("Found account {} but BTP auth token was wrong", username);
+                    Err(())
+                }
+            }
+            None => {
+                warn!("No account found with BTP token");
+                Err(())
+            }
+        }
+    }
+
+    async fn get_btp_outgoing_accounts(&self) -> Result<Vec<Self::Account>, ()> {
+        let accounts: Vec<Account> = self
+            .state
+            .read()
+            .load_all_accounts()
+            .into_iter()
+            .filter(|account| account.ilp_over_btp_url.is_some())
+            .collect();
+        debug!(
+            "Loaded {} accounts with an outgoing BTP URL",
+            accounts.len()
+        );
+        Ok(accounts)
+    }
+}
+
+#[async_trait]
+impl HttpStore for InMemoryStore {
+    type Account = Account;
+
+    /// Checks if the stored token for the provided account id matches the
+    /// provided token, and if so, returns the account associated with that token
+    async fn get_account_from_http_auth(
+        &self,
+        username: &Username,
+        token: &str,
+    ) -> Result<Self::Account, ()> {
+        // Verifying a hashed token takes a while, so the lock is released first
+        let account = {
+            let state = self.state.read();
+            state
+                .usernames
+                .get(&username.to_string())
+                .and_then(|id| state.load_account(*id))
+        };
+        match account {
+            Some(account) => {
+                let matches = match account.ilp_over_http_incoming_token {
+                    Some(ref t) => {
+                        self.token_verifier
+                            .verify(username.as_ref(), &t.expose_secret(), token.as_bytes())
+                            .await
+                    }
+                    None => false,
+                };
+                if matches {
+                    Ok(account)
+                } else {
+                    Err(())
+                }
+            }
+            None => {
+                warn!(
+                    "No account found with given HTTP auth. Username: {}",
+                    username
+                );
+                Err(())
+            }
+        }
+    }
+}
+
+impl RouterStore for InMemoryStore {
+    fn routing_table(&self) -> Arc<HashMap<String, Uuid>> {
+        self.routes.read().clone()
+    }
+}
+
+#[async_trait]
+impl NodeStore for InMemoryStore {
+    type Account = Account;
+
+    async fn insert_account(&self, account: AccountDetails) -> Result<Account, ()> {
+        let id = Uuid::new_v4();
+        let account = Account::try_from(id, account, self.get_ilp_address())
+            .map_err(|_| error!("Invalid account details"))?;
+        debug!(
+            "Generated account id for {}: {}",
+            account.username.clone(),
+            id
+        );
+        self.insert(account)
+    }
+
+    async fn delete_account(&self, id: Uuid) -> Result<Account, ()> {
+        let mut state = self.state.write();
+        let account = state.load_account(id).ok_or_else(|| {
+            warn!("No account found with id: {}", id);
+        })?;
+
+        state.accounts.remove(&id);
+        state.usernames.remove(&account.username.to_string());
+        state.remove_local_routes(&account);
+        state.uncredited_amounts.remove(&id);
+        self.update_routes(&state);
+
+        debug!("Deleted account {}", account.id);
+        Ok(account)
+    }
+
+    async fn update_account(&self, id: Uuid, account: AccountDetails) -> Result<Self::Account, ()> {
+        let mut account = Account::try_from(id, account, self.get_ilp_address())
+            .map_err(|_| error!("Invalid account details"))?;
+
+        let mut state = self.state.write();
+        if let Some(other_id) = state.usernames.get(&account.username.to_string()) {
+            if *other_id != id {
+                warn!(
+                    "Username {} is already taken. Cannot update account: {}",
+                    account.username, id
+                );
+                return Err(());
+            }
+        }
+        if let Some(prefix) = state.prefix_routed_to_other_account(&account) {
+            warn!(
+                "The prefix {} is already routed to another account. Cannot update account: {}",
+                prefix, id
+            );
+            return Err(());
+        }
+        let previous = match state.accounts.get_mut(&id) {
+            Some(entry) => {
+                // The status is only changed by `set_account_status`
+                account.status = entry.account.status;
+                std::mem::replace(&mut entry.account, account.clone())
+            }
+            None => {
+                warn!(
+                    "No account exists with ID {}, cannot update account {:?}",
+                    id, account
+                );
+                return Err(());
+            }
+        };
+
+        state.usernames.remove(&previous.username.to_string());
+        state
+            .usernames
+            .insert(account.username.to_string(), account.id);
+        state.remove_local_routes(&previous);
+        for prefix in account.local_prefixes() {
+            state.routes.insert(prefix.to_string(), account.id);
+        }
+        self.update_routes(&state);
+
+        debug!("Updated account {}", id);
+        state.load_account(id).ok_or(())
+    }
+
+    async fn modify_account_settings(
+        &self,
+        id: Uuid,
+        settings: AccountSettings,
+    ) -> Result<Self::Account, ()> {
+        validate_incoming_token(settings.ilp_over_btp_incoming_token.as_ref())?;
+        validate_incoming_token(settings.ilp_over_http_incoming_token.as_ref())?;
+        // Parse the URLs before touching the account so that invalid settings
+        // do not leave it partially modified
+        let ilp_over_btp_url = match settings.ilp_over_btp_url {
+            Some(ref url) => {
+                Some(Url::parse(url).map_err(|err| error!("Invalid BTP URL {}: {:?}", url, err))?)
+            }
+            None => None,
+        };
+        let ilp_over_http_url = match settings.ilp_over_http_url {
+            Some(ref url) => {
+                Some(Url::parse(url).map_err(|err| error!("Invalid HTTP URL {}: {:?}", url, err))?)
+            }
+            None => None,
+        };
+
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&id).ok_or_else(|| {
+            warn!(
+                "No account exists with ID {}, cannot modify its settings",
+                id
+            )
+        })?;
+        let account = &mut entry.account;
+
+        if let Some(url) = ilp_over_btp_url {
+            account.ilp_over_btp_url = Some(url);
+        }
+        if let Some(url) = ilp_over_http_url {
+            account.ilp_over_http_url = Some(url);
+        }
+        if let Some(token) = settings.ilp_over_btp_outgoing_token {
+            account.ilp_over_btp_outgoing_token =
+                Some(SecretBytesMut::new(token.expose_secret().as_str()));
+        }
+        if let Some(token) = settings.ilp_over_http_outgoing_token {
+            account.ilp_over_http_outgoing_token =
+                Some(SecretBytesMut::new(token.expose_secret().as_str()));
+        }
+        if let Some(token) = settings.ilp_over_btp_incoming_token {
+            account.ilp_over_btp_incoming_token =
+                Some(SecretBytesMut::new(token.expose_secret().as_str()));
+        }
+        if let Some(token) = settings.ilp_over_http_incoming_token {
+            account.ilp_over_http_incoming_token =
+                Some(SecretBytesMut::new(token.expose_secret().as_str()));
+        }
+        if let Some(settle_threshold) = settings.settle_threshold {
+            account.settle_threshold = Some(settle_threshold);
+        }
+        if let Some(settle_to) = settings.settle_to {
+            if settle_to > std::i64::MAX as u64 {
+                // Balances are stored as i64 so settle_to cannot be larger than that
+                error!(
+                    "Provided settle_to value {} is greater than i64::MAX",
+                    settle_to
+                );
+                return Err(());
+            }
+            account.settle_to = Some(settle_to as i64);
+        }
+
+        debug!("Modified account {} settings", id);
+        state.load_account(id).ok_or(())
+    }
+
+    async fn set_account_status(
+        &self,
+        id: Uuid,
+        status: AccountStatus,
+    ) -> Result<Self::Account, ()> {
+        let mut state = self.state.write();
+        let entry = state
+            .accounts
+            .get_mut(&id)
+            .ok_or_else(|| warn!("No account exists with ID {}, cannot set its status", id))?;
+        entry.account.status = status;
+
+        debug!("Set the status of account {} to {}", id, status);
+        state.load_account(id).ok_or(())
+    }
+
+    async fn insert_api_key(&self, key: ApiKey, secret: SecretString) -> Result<ApiKey, ()> {
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&key.account_id).ok_or_else(|| {
+            warn!(
+                "No account exists with ID {}, cannot insert API key",
+                key.account_id
+            )
+        })?;
+        entry.api_keys.push((key.clone(), secret));
+        debug!("Inserted API key {} of account {}", key.id, key.account_id);
+        Ok(key)
+    }
+
+    async fn get_api_keys(&self, account_id: Uuid) -> Result<Vec<ApiKey>, ()> {
+        let state = self.state.read();
+        let entry = state
+            .accounts
+            .get(&account_id)
+            .ok_or_else(|| warn!("No account exists with ID {}", account_id))?;
+        Ok(entry.api_keys.iter().map(|(key, _)| key.clone()).collect())
+    }
+
+    async fn delete_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<ApiKey, ()> {
+        let mut state = self.state.write();
+        let entry = state
+            .accounts
+            .get_mut(&account_id)
+            .ok_or_else(|| warn!("No account exists with ID {}", account_id))?;
+        let index = entry
+            .api_keys
+            .iter()
+            .position(|(key, _)| key.id == key_id)
+            .ok_or_else(|| warn!("Account {} has no API key {}", account_id, key_id))?;
+        let (key, _) = entry.api_keys.remove(index);
+        debug!("Deleted API key {} of account {}", key_id, account_id);
+        Ok(key)
+    }
+
+    async fn get_api_key_from_auth(
+        &self,
+        account_id: Uuid,
+        key_id: Uuid,
+        secret: &str,
+    ) -> Result<ApiKey, ()> {
+        let (key, stored) = self
+            .state
+            .read()
+            .accounts
+            .get(&account_id)
+            .and_then(|entry| entry.api_keys.iter().find(|(key, _)| key.id == key_id))
+            .cloned()
+            .ok_or_else(|| warn!("Account {} has no API key {}", account_id, key_id))?;
+        if self
+            .token_verifier
+            .verify(
+                &key_id.to_string(),
+                stored.expose_secret().as_bytes(),
+                secret.as_bytes(),
+            )
+            .await
+        {
+            Ok(key)
+        } else {
+            Err(())
+        }
+    }
+
+    async fn get_all_accounts(&self) -> Result<Vec<Self::Account>, ()> {
+        let accounts = self.state.read().load_all_accounts();
+        trace!("Loaded {} accounts", accounts.len());
+        Ok(accounts)
+    }
+
+    async fn get_accounts_page(
+        &self,
+        query: AccountsQuery,
+    ) -> Result<AccountsPage<Self::Account>, ()> {
+        let mut accounts = self.state.read().load_all_accounts();
+        accounts.sort_by_key(|account| account.id);
+        Ok(AccountsPage::from_sorted_accounts(accounts, &query))
+    }
+
+    async fn set_static_routes<R>(&self, routes: R) -> Result<(), ()>
+    where
+        R: IntoIterator<Item = (String, Uuid)> + Send + 'async_trait,
+    {
+        let routes: Vec<(String, Uuid)> = routes.into_iter().collect();
+
+        let mut state = self.state.write();
+        // Check that all of the accounts exist
+        for (_, account_id) in routes.iter() {
+            if !state.accounts.contains_key(account_id) {
+                error!(
+                    "Error setting static routes because account {} does not exist",
+                    account_id
+                );
+                return Err(());
+            }
+        }
+
+        state.static_routes = HashMap::from_iter(routes);
+        self.update_routes(&state);
+        Ok(())
+    }
+
+    async fn get_static_routes(&self) -> Result<HashMap<String, Uuid>, ()> {
+        Ok(self.state.read().static_routes.clone())
+    }
+
+    async fn set_static_route(&self, prefix: String, account_id: Uuid) -> Result<(), ()> {
+        let mut state = self.state.write();
+        if !state.accounts.contains_key(&account_id) {
+            error!(
+                "Cannot set static route for prefix: {} because account {} does not exist",
+                prefix, account_id
+            );
+            return Err(());
+        }
+
+        state.static_routes.insert(prefix, account_id);
+        self.update_routes(&state);
+        Ok(())
+    }
+
+    async fn set_default_route(&self, account_id: Uuid) -> Result<(), ()> {
+        let mut state = self.state.write();
+        if !state.accounts.contains_key(&account_id) {
+            error!(
+                "Cannot set default route because account {} does not exist",
+                account_id
+            );
+            return Err(());
+        }
+
+        state.default_route = Some(account_id);
+        debug!("Set default route to account id: {}", account_id);
+        self.update_routes(&state);
+        Ok(())
+    }
+
+    async fn set_settlement_engines(
+        &self,
+        asset_to_url_map: impl IntoIterator<Item = (String, Url)> + Send + 'async_trait,
+    ) -> Result<(), ()> {
+        let asset_to_url_map: Vec<(String, Url)> = asset_to_url_map.into_iter().collect();
+        debug!("Setting settlement engines to {:?}", asset_to_url_map);
+        self.state
+            .write()
+            .settlement_engines
+            .extend(asset_to_url_map);
+        Ok(())
+    }
+
+    async fn get_asset_settlement_engine(&self, asset_code: &str) -> Result<Option<Url>, ()> {
+        Ok(self
+            .state
+            .read()
+            .settlement_engines
+            .get(asset_code)
+            .cloned())
+    }
+
+    async fn get_settlement_engines(&self) -> Result<HashMap<String, Url>, ()> {
+        Ok(self.state.read().settlement_engines.clone())
+    }
+
+    async fn export_accounts(&self) -> Result<Vec<ExportedAccount>, ()> {
+        let accounts = self.state.read().load_all_accounts();
+        Ok(accounts.iter().map(Account::export).collect())
+    }
+
+    async fn import_account(&self, account: ExportedAccount) -> Result<Account, ()> {
+        let account = Account::try_from_exported(account, self.get_ilp_address())
+            .map_err(|_| error!("Invalid account details"))?;
+        self.insert(account)
+    }
+
+    /// The tokens are never written anywhere, so they are not encrypted
+    /// and there is nothing to re-encrypt
+    async fn rotate_secret(&self, _new_secret: [u8; 32]) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AddressStore for InMemoryStore {
+    // Updates the ILP address of the store & iterates over all children and
+    // updates their ILP Address to match the new address.
+    async fn set_ilp_address(&self, ilp_address: Address) -> Result<(), ()> {
+        debug!("Setting ILP address to: {}", ilp_address);
+        let mut state = self.state.write();
+
+        // Set the ILP address we have in memory
+        (*self.ilp_address.write()) = ilp_address.clone();
+        state.parent_ilp_address = Some(ilp_address.clone());
+
+        let mut updated_routes = Vec::new();
+        for entry in state.accounts.values_mut() {
+            let account = &mut entry.account;
+            // Update the address and routes of all children and non-routing accounts.
+            if account.routing_relation != RoutingRelation::Parent
+                && account.routing_relation != RoutingRelation::Peer
+            {
+                // If the node's address ends with the account's username,
+                // this account represents the node's non routing account
+                // and keeps the node's address.
+                let new_ilp_address = if ilp_address.segments().rev().next().unwrap()
+                    == account.username.to_string()
+                {
+                    ilp_address.clone()
+                } else {
+                    ilp_address
+                        .with_suffix(account.username.as_bytes())
+                        .unwrap()
+                };
+                let old_ilp_address =
+                    std::mem::replace(&mut account.ilp_address, new_ilp_address.clone());
+                updated_routes.push((old_ilp_address, new_ilp_address, account.id));
+            }
+        }
+
+        for (old_ilp_address, new_ilp_address, id) in updated_routes {
+            state.routes.remove(&old_ilp_address.to_string());
+            state.routes.insert(new_ilp_address.to_string(), id);
+        }
+        self.update_routes(&state);
+        Ok(())
+    }
+
+    async fn clear_ilp_address(&self) -> Result<(), ()> {
+        self.state.write().parent_ilp_address = None;
+
+        // overwrite the ilp address with the default value
+        *(self.ilp_address.write()) = DEFAULT_ILP_ADDRESS.clone();
+        Ok(())
+    }
+
+    fn get_ilp_address(&self) -> Address {
+        // read consumes the Arc<RwLock<T>> so we cannot return a reference
+        self.ilp_address.read().clone()
+    }
+}
+
+type RoutingTable<A> = HashMap<String, A>;
+
+#[async_trait]
+impl RouteManagerStore for InMemoryStore {
+    type Account = Account;
+
+    async fn get_accounts_to_send_routes_to(
+        &self,
+        ignore_accounts: Vec<Uuid>,
+    ) -> Result<Vec<Account>, ()> {
+        Ok(self
+            .state
+            .read()
+            .load_all_accounts()
+            .into_iter()
+            .filter(|account| {
+                account.should_send_routes() && !ignore_accounts.contains(&account.id)
+            })
+            .collect())
+    }
+
+    async fn get_accounts_to_receive_routes_from(&self) -> Result<Vec<Account>, ()> {
+        Ok(self
+            .state
+            .read()
+            .load_all_accounts()
+            .into_iter()
+            .filter(|account| account.should_receive_routes())
+            .collect())
+    }
+
+    async fn get_local_and_configured_routes(
+        &self,
+    ) -> Result<(RoutingTable<Account>, RoutingTable<Account>), ()> {
+        let state = self.state.read();
+        let accounts = state.load_all_accounts();
+
+        let local_table = HashMap::from_iter(accounts.iter().flat_map(|account| {
+            account
+                .local_prefixes()
+                .map(move |prefix| (prefix.to_string(), account.clone()))
+        }));
+
+        let account_map: HashMap<Uuid, &Account> =
+            HashMap::from_iter(accounts.iter().map(|account| (account.id, account)));
+        let configured_table: HashMap<String, Account> = HashMap::from_iter(
+            state
+                .static_routes
+                .iter()
+                .filter_map(|(prefix, account_id)| {
+                    if let Some(account) = account_map.get(account_id) {
+                        Some((prefix.clone(), (*account).clone()))
+                    } else {
+                        warn!(
+                            "No account for ID: {}, ignoring configured route for prefix: {}",
+                            account_id, prefix
+                        );
+                        None
+                    }
+                }),
+        );
+
+        Ok((local_table, configured_table))
+    }
+
+    async fn set_routes(
+        &mut self,
+        routes: impl IntoIterator<Item = (String, Account)> + Send + 'async_trait,
+    ) -> Result<(), ()> {
+        let routes: HashMap<String, Uuid> = routes
+            .into_iter()
+            .map(|(prefix, account)| (prefix, account.id))
+            .collect();
+        let num_routes = routes.len();
+
+        let mut state = self.state.write();
+        state.routes = routes;
+        trace!("Saved {} routes", num_routes);
+        self.update_routes(&state);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryStore {
+    type Account = Account;
+
+    /// Apply rate limits for number of packets per minute and amount of money per minute,
+    /// then the daily and monthly amount limits
+    ///
+    /// This behaves like the redis-cell module used by the RedisStore
+    async fn apply_rate_limits(
+        &self,
+        account: Account,
+        prepare_amount: u64,
+    ) -> Result<(), RateLimitError> {
+        let mut state = self.state.write();
+        state
+            .rate_limits
+            .apply_rate_limits(&account, prepare_amount)?;
+        if let Err(err) = state.amount_limits.apply(&account, prepare_amount) {
+            // The packet will not be forwarded, so it does not count towards the throughput
+            state
+                .rate_limits
+                .refund_throughput_limit(&account, prepare_amount);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    async fn refund_throughput_limit(
+        &self,
+        account: Account,
+        prepare_amount: u64,
+    ) -> Result<(), ()> {
+        let mut state = self.state.write();
+        state
+            .rate_limits
+            .refund_throughput_limit(&account, prepare_amount);
+        state.amount_limits.refund(&account, prepare_amount);
+        Ok(())
+    }
+
+    async fn get_cumulative_amounts(&self, account: Account) -> Result<CumulativeAmounts, ()> {
+        Ok(self.state.read().amount_limits.get(account.id))
+    }
+}
+
+#[async_trait]
+impl FeeStore for InMemoryStore {
+    async fn record_fee(
+        &self,
+        from_account_id: Uuid,
+        to_account_id: Uuid,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let mut state = self.state.write();
+        let earned = state
+            .fees_earned
+            .entry((from_account_id, to_account_id))
+            .or_insert(0);
+        *earned = earned.saturating_add(amount);
+        Ok(())
+    }
+
+    async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()> {
+        Ok(self
+            .state
+            .read()
+            .fees_earned
+            .iter()
+            .map(|((from_account_id, to_account_id), amount)| FeesEarned {
+                from_account_id: *from_account_id,
+                to_account_id: *to_account_id,
+                amount: *amount,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl IdempotentStore for InMemoryStore {
+    async fn load_idempotent_data(
+        &self,
+        idempotency_key: String,
+    ) -> Result<Option<IdempotentData>, ()> {
+        let mut state = self.state.write();
+        let expired = match state.idempotency_keys.get(&idempotency_key) {
+            Some(entry) => entry.saved_at.elapsed() > IDEMPOTENCY_KEY_EXPIRY,
+            None => return Ok(None),
+        };
+        if expired {
+            state.idempotency_keys.remove(&idempotency_key);
+            return Ok(None);
+        }
+
+        let data = state
+            .idempotency_keys
+            .get(&idempotency_key)
+            .map(|entry| entry.data.clone());
+        trace!("Loaded idempotency key {:?} - {:?}", idempotency_key, data);
+        Ok(data)
+    }
+
+    async fn save_idempotent_data(
+        &self,
+        idempotency_key: String,
+        input_hash: [u8; 32],
+        status_code: StatusCode,
+        data: Bytes,
+    ) -> Result<(), ()> {
+        trace!(
+            "Cached {:?}: {:?}, {:?}",
+            idempotency_key,
+            status_code,
+            data,
+        );
+        self.state.write().idempotency_keys.insert(
+            idempotency_key,
+            IdempotencyEntry {
+                data: IdempotentData::new(status_code, data, input_hash),
+                saved_at: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SettlementStore for InMemoryStore {
+    type Account = Account;
+
+    async fn update_balance_for_incoming_settlement(
+        &self,
+        account_id: Uuid,
+        amount: u64,
+        idempotency_key: Option<String>,
+    ) -> Result<(), ()> {
+        let mut state = self.state.write();
+        let state = &mut *state;
+
+        // Make sure each settlement is only credited once
+        if let Some(ref idempotency_key) = idempotency_key {
+            if let Some(saved_at) = state.settlement_idempotency_keys.get(idempotency_key) {
+                if saved_at.elapsed() <= IDEMPOTENCY_KEY_EXPIRY {
+                    trace!(
+                        "Incoming settlement with idempotency key {} was already processed",
+                        idempotency_key
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        let entry = state.accounts.get_mut(&account_id).ok_or_else(|| {
+            error!(
+                "Error processing incoming settlement from account: {} for amount: {}: account does not exist",
+                account_id, amount
+            )
+        })?;
+        // The key is only recorded once the account is found, so that a
+        // settlement which failed can be retried
+        if let Some(idempotency_key) = idempotency_key {
+            state
+                .settlement_idempotency_keys
+                .insert(idempotency_key, Instant::now());
+        }
+        let amount = amount as i64;
+
+        // If the balance is negative, the settlement first pays off the debt
+        // and any remaining amount is added to the prepaid amount
+        if entry.balance >= 0 {
+            entry.prepaid_amount += amount;
+        } else if entry.balance.abs() >= amount {
+            entry.balance += amount;
+        } else {
+            entry.prepaid_amount += amount + entry.balance;
+            entry.balance = 0;
+        }
+        entry.record(BalanceChangeKind::IncomingSettlement, amount, None);
+
+        trace!(
+            "Processed incoming settlement from account: {} for amount: {}. Balance is now: {}",
+            account_id,
+            amount,
+            entry.balance + entry.prepaid_amount
+        );
+        Ok(())
+    }
+
+    async fn refund_settlement(&self, account_id: Uuid, settle_amount: u64) -> Result<(), ()> {
+        trace!(
+            "Refunding settlement for account: {} of amount: {}",
+            account_id,
+            settle_amount
+        );
+        let mut state = self.state.write();
+        let entry = state.accounts.get_mut(&account_id).ok_or_else(|| {
+            error!(
+                "Error refunding settlement for account: {} of amount: {}: account does not exist",
+                account_id, settle_amount
+            )
+        })?;
+        entry.balance += settle_amount as i64;
+        entry.record(
+            BalanceChangeKind::SettlementRefund,
+            settle_amount as i64,
+            None,
+        );
+
+        trace!(
+            "Refunded settlement for account: {} of amount: {}. Balance is now: {}",
+            account_id,
+            settle_amount,
+            entry.balance + entry.prepaid_amount
+        );
+        Ok(())
+    }
+}
+
+/// Sums up the amounts after normalizing them to the largest scale
+fn sum_uncredited_amounts(amounts: Vec<(BigUint, u8)>) -> Result<(BigUint, u8), ()> {
+    let max_scale = amounts.iter().map(|(_, scale)| *scale).max().unwrap_or(0);
+    let mut sum = BigUint::from(0u32);
+    for (num, scale) in amounts {
+        sum += num
+            .normalize_scale(ConvertDetails {
+                from: scale,
+                to: max_scale,
+            })
+            .map_err(|_| error!("Error normalizing uncredited_settlement_amount"))?;
+    }
+    Ok((sum, max_scale))
+}
+
+#[async_trait]
+impl LeftoversStore for InMemoryStore {
+    type AccountId = Uuid;
+    type AssetType = BigUint;
+
+    async fn get_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        // get the amounts and instantly delete them
+        let amounts = self
+            .state
+            .write()
+            .uncredited_amounts
+            .remove(&account_id)
+            .unwrap_or_default();
+        sum_uncredited_amounts(amounts)
+    }
+
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        let amounts = self
+            .state
+            .read()
+            .uncredited_amounts
+            .get(&account_id)
+            .cloned()
+            .unwrap_or_default();
+        sum_uncredited_amounts(amounts)
+    }
+
+    async fn save_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+        uncredited_settlement_amount: (Self::AssetType, u8),
+    ) -> Result<(), ()> {
+        trace!(
+            "Saving uncredited_settlement_amount {:?} {:?}",
+            account_id,
+            uncredited_settlement_amount
+        );
+        self.state
+            .write()
+            .uncredited_amounts
+            .entry(account_id)
+            .or_insert_with(Vec::new)
+            .push(uncredited_settlement_amount);
+        Ok(())
+    }
+
+    async fn load_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+        local_scale: u8,
+    ) -> Result<Self::AssetType, ()> {
+        trace!("Loading uncredited_settlement_amount {:?}", account_id);
+        let amount = self.get_uncredited_settlement_amount(account_id).await?;
+        // scale the amount from the max scale to the local scale, and then
+        // save any potential leftovers to the store
+        let (scaled_amount, precision_loss) =
+            scale_with_precision_loss(amount.0, local_scale, amount.1);
+
+        if precision_loss > BigUint::from(0u32) {
+            self.save_uncredited_settlement_amount(
+                account_id,
+                (precision_loss, std::cmp::max(local_scale, amount.1)),
+            )
+            .await?;
+        }
+
+        Ok(scaled_amount)
+    }
+
+    async fn clear_uncredited_settlement_amount(&self, account_id: Uuid) -> Result<(), ()> {
+        trace!("Clearing uncredited_settlement_amount {:?}", account_id);
+        self.state.write().uncredited_amounts.remove(&account_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::account::Account;
+use parking_lot::RwLock;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Decrypted accounts kept in the process' memory so that packets can be
+/// processed without loading and decrypting the account from Redis every time.
+///
+/// Entries are removed whenever an account is changed through this store or
+/// another store connected to the same Redis (which publishes the change on
+/// the account updates channel). They also expire after the TTL so that a
+/// missed notification cannot leave the cache stale indefinitely.
+pub(crate) struct AccountCache {
+    ttl: Duration,
+    inner: RwLock<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    accounts: HashMap<Uuid, (Account, Instant)>,
+    usernames: HashMap<String, Uuid>,
+    /// Incremented on every invalidation so that accounts which were being
+    /// loaded while they were changed are not put in the cache
+    generation: u64,
+}
+
+impl AccountCache {
+    /// Creates a cache whose entries expire after the provided TTL.
+    /// A TTL of zero disables the cache.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        AccountCache {
+            ttl,
+            inner: RwLock::new(CacheState::default()),
+        }
+    }
+
+    /// Must be read before loading accounts from Redis and passed to `insert`
+    pub(crate) fn generation(&self) -> u64 {
+        self.inner.read().generation
+    }
+
+    /// Returns the accounts in the order of the provided ids,
+    /// or `None` if any of them is not cached
+    pub(crate) fn get(&self, ids: &[Uuid]) -> Option<Vec<Account>> {
+        let state = self.inner.read();
+        ids.iter()
+            .map(|id| match state.accounts.get(id) {
+                Some((account, loaded_at)) if loaded_at.elapsed() < self.ttl => {
+                    Some(account.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_by_username(&self, username: &str) -> Option<Account> {
+        let id = *self.inner.read().usernames.get(username)?;
+        self.get(&[id]).and_then(|mut accounts| accounts.pop())
+    }
+
+    /// Caches the accounts unless the cache was invalidated since `generation` was read
+    pub(crate) fn insert(&self, generation: u64, accounts: impl IntoIterator<Item = Account>) {
+        if self.ttl == Duration::from_secs(0) {
+            return;
+        }
+        let mut state = self.inner.write();
+        if state.generation != generation {
+            return;
+        }
+        let now = Instant::now();
+        for account in accounts {
+            state
+                .usernames
+                .insert(account.username.to_string(), account.id);
+            state.accounts.insert(account.id, (account, now));
+        }
+    }
+
+    /// Removes the account from the cache
+    pub(crate) fn invalidate(&self, id: Uuid) {
+        let mut state = self.inner.write();
+        state.generation += 1;
+        if let Some((account, _)) = state.accounts.remove(&id) {
+            state.usernames.remove(&account.username.to_string());
+        }
+    }
+
+    /// Removes all accounts from the cache
+    pub(crate) fn clear(&self) {
+        let mut state = self.inner.write();
+        state.generation += 1;
+        state.accounts.clear();
+        state.usernames.clear();
+    }
+}
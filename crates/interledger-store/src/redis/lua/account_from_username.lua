@@ -1,7 +1,9 @@
+local usernames = KEYS[1]
 local username = ARGV[1]
-local id_from_username = redis.call('HGET', 'usernames', username)
+local accounts_prefix = ARGV[2]
+local id_from_username = redis.call('HGET', usernames, username)
 if id_from_username then
-    return redis.call('HGETALL', 'accounts:' .. id_from_username)
+    return redis.call('HGETALL', accounts_prefix .. id_from_username)
 else
     return nil
 end
@@ -0,0 +1,18 @@
+-- Adds the amount of a packet to the amounts an account sent in its current
+-- day and month, unless that would exceed one of its limits. ARGV holds the
+-- amount followed by the limit of each key and how many seconds to keep it for
+local amount = tonumber(ARGV[1])
+
+for i, key in ipairs(KEYS) do
+    local sent = tonumber(redis.call('GET', key) or 0)
+    if sent + amount > tonumber(ARGV[i * 2]) then
+        -- The index of the limit which would be exceeded
+        return i
+    end
+end
+
+for i, key in ipairs(KEYS) do
+    redis.call('INCRBY', key, amount)
+    redis.call('EXPIRE', key, ARGV[i * 2 + 1])
+end
+return 0
@@ -0,0 +1,7 @@
+-- Renames KEYS[1] to the tagged name KEYS[2], unless it does not
+-- exist or a key with the tagged name was already written
+if redis.call('EXISTS', KEYS[1]) == 1 and redis.call('EXISTS', KEYS[2]) == 0 then
+    redis.call('RENAME', KEYS[1], KEYS[2])
+    return 1
+end
+return 0
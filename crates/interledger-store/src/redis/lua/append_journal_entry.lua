@@ -0,0 +1,16 @@
+-- Prepended to the scripts which change balances, after the line which sets
+-- journal_retention to the number of milliseconds entries are kept for.
+-- Appends an entry to the balance journal stream and deletes the entries which
+-- are older than the retention. Only a few are deleted at a time, which keeps up
+-- with the journal because each call adds a single entry
+local function append_journal_entry(journal, ...)
+    redis.call('XADD', journal, '*', ...)
+    local now = redis.call('TIME')
+    local expired_before = tonumber(now[1]) * 1000 + math.floor(tonumber(now[2]) / 1000) - journal_retention
+    if expired_before > 0 then
+        local expired = redis.call('XRANGE', journal, '-', string.format('%d', expired_before - 1), 'COUNT', 10)
+        for _, entry in ipairs(expired) do
+            redis.call('XDEL', journal, entry[1])
+        end
+    end
+end
@@ -0,0 +1,12 @@
+local routes = KEYS[1]
+local account_id = ARGV[1]
+
+-- Only the prefixes which are still routed to the account are removed,
+-- the others were routed to another account since
+local deleted = 0
+for i = 2, #ARGV do
+    if redis.call('HGET', routes, ARGV[i]) == account_id then
+        deleted = deleted + redis.call('HDEL', routes, ARGV[i])
+    end
+end
+return deleted
@@ -7,7 +7,8 @@ local function into_dictionary(flat_map)
     return result
 end
 
-local settlement_engines = into_dictionary(redis.call('HGETALL', 'settlement_engines'))
+-- The first key is the settlement engines hash and the others are the accounts to load
+local settlement_engines = into_dictionary(redis.call('HGETALL', KEYS[1]))
 local accounts = {}
 
 -- TODO get rid of the two representations of account
@@ -21,10 +22,11 @@ local accounts = {}
 -- settlement_engine_url into the account we are going to return
 local account
 local account_dict
-for index, id in ipairs(ARGV) do
-    account = redis.call('HGETALL', 'accounts:' .. id)
+for index = 2, #KEYS do
+    account = redis.call('HGETALL', KEYS[index])
 
-    if account ~= nil then
+    -- Deleted accounts are skipped
+    if #account > 0 then
         account_dict = into_dictionary(account)
 
         -- If the account does not have a settlement_engine_url specified
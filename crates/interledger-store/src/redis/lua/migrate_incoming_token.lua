@@ -0,0 +1,12 @@
+local account = KEYS[1]
+local field = ARGV[1]
+local hash_prefix = ARGV[2]
+
+-- The token is only replaced if it is still encrypted, otherwise
+-- it was changed (and hashed) since the account was loaded
+local current = redis.call('HGET', account, field)
+if current and string.sub(current, 1, string.len(hash_prefix)) ~= hash_prefix then
+    redis.call('HSET', account, field, ARGV[3])
+    return 1
+end
+return 0
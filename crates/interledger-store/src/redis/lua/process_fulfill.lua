@@ -1,8 +1,19 @@
-local to_account = 'accounts:' .. ARGV[1]
-local to_amount = tonumber(ARGV[2])
+-- XADD assigns the entry id from the server's clock, so the script
+-- has to be replicated by its effects rather than verbatim
+redis.replicate_commands()
+
+local to_account = KEYS[1]
+local journal = KEYS[2]
+local to_amount = tonumber(ARGV[1])
 
 local balance = redis.call('HINCRBY', to_account, 'balance', to_amount)
 local prepaid_amount, settle_threshold, settle_to = unpack(redis.call('HMGET', to_account, 'prepaid_amount', 'settle_threshold', 'settle_to'))
+append_journal_entry(journal,
+    'kind', 'fulfill',
+    'delta', to_amount,
+    'balance', balance + prepaid_amount,
+    'execution_condition', ARGV[2],
+    'counterparty', ARGV[3])
 
 -- The logic for trigerring settlement is as follows:
 --  1. settle_threshold must be non-nil (if it's nil, then settlement was perhaps disabled on the account).
@@ -17,6 +28,10 @@ if (settle_threshold and settle_to) and (balance >= tonumber(settle_threshold))
     -- the balance change by re-adding the amount back to the balance
     balance = settle_to
     redis.call('HSET', to_account, 'balance', balance)
+    append_journal_entry(journal,
+        'kind', 'outgoing_settlement',
+        'delta', 0 - settle_amount,
+        'balance', balance + prepaid_amount)
 end
 
 return {balance + prepaid_amount, settle_amount}
@@ -1,6 +1,11 @@
-local account = 'accounts:' .. ARGV[1]
-local amount = tonumber(ARGV[2])
-local idempotency_key = ARGV[3]
+-- XADD assigns the entry id from the server's clock, so the script
+-- has to be replicated by its effects rather than verbatim
+redis.replicate_commands()
+
+local account = KEYS[1]
+local journal = KEYS[2]
+local idempotency_key = KEYS[3]
+local amount = tonumber(ARGV[1])
 
 local balance, prepaid_amount = unpack(redis.call('HMGET', account, 'balance', 'prepaid_amount'))
 
@@ -24,4 +29,9 @@ else
     redis.call('HSET', account, 'balance', 0)
 end
 
+append_journal_entry(journal,
+    'kind', 'incoming_settlement',
+    'delta', amount,
+    'balance', balance + prepaid_amount)
+
 return balance + prepaid_amount
@@ -1,6 +1,12 @@
-local from_id = ARGV[1]
-local from_account = 'accounts:' .. ARGV[1]
-local from_amount = tonumber(ARGV[2])
+-- XADD assigns the entry id from the server's clock, so the script
+-- has to be replicated by its effects rather than verbatim
+redis.replicate_commands()
+
+local from_account = KEYS[1]
+local journal = KEYS[2]
+local from_amount = tonumber(ARGV[1])
+local execution_condition = ARGV[2]
+local counterparty = ARGV[3]
 local min_balance, balance, prepaid_amount = unpack(redis.call('HMGET', from_account, 'min_balance', 'balance', 'prepaid_amount'))
 balance = tonumber(balance)
 prepaid_amount = tonumber(prepaid_amount)
@@ -9,7 +15,7 @@ prepaid_amount = tonumber(prepaid_amount)
 if min_balance then
     min_balance = tonumber(min_balance)
     if balance + prepaid_amount - from_amount < min_balance then
-        error('Incoming prepare of ' .. from_amount .. ' would bring account ' .. from_id .. ' under its minimum balance. Current balance: ' .. balance .. ', min balance: ' .. min_balance)
+        error('Incoming prepare of ' .. from_amount .. ' would bring account ' .. from_account .. ' under its minimum balance. Current balance: ' .. balance .. ', min balance: ' .. min_balance)
     end
 end
 
@@ -25,4 +31,11 @@ else
     balance = redis.call('HINCRBY', from_account, 'balance', 0 - from_amount)
 end
 
+append_journal_entry(journal,
+    'kind', 'prepare',
+    'delta', 0 - from_amount,
+    'balance', balance + prepaid_amount,
+    'execution_condition', execution_condition,
+    'counterparty', counterparty)
+
 return balance + prepaid_amount
@@ -1,6 +1,17 @@
-local from_account = 'accounts:' .. ARGV[1]
-local from_amount = tonumber(ARGV[2])
+-- XADD assigns the entry id from the server's clock, so the script
+-- has to be replicated by its effects rather than verbatim
+redis.replicate_commands()
+
+local from_account = KEYS[1]
+local journal = KEYS[2]
+local from_amount = tonumber(ARGV[1])
 
 local prepaid_amount = redis.call('HGET', from_account, 'prepaid_amount')
 local balance = redis.call('HINCRBY', from_account, 'balance', from_amount)
+append_journal_entry(journal,
+    'kind', 'reject',
+    'delta', from_amount,
+    'balance', balance + prepaid_amount,
+    'execution_condition', ARGV[2],
+    'counterparty', ARGV[3])
 return balance + prepaid_amount
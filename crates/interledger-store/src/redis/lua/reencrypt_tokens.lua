@@ -0,0 +1,13 @@
+local account = KEYS[1]
+
+-- The arguments are (field, previous value, new value) triples.
+-- A token is only replaced if it was not changed since it was loaded,
+-- otherwise it was already encrypted with the new key
+local replaced = 0
+for i = 1, #ARGV, 3 do
+    if redis.call('HGET', account, ARGV[i]) == ARGV[i + 1] then
+        redis.call('HSET', account, ARGV[i], ARGV[i + 2])
+        replaced = replaced + 1
+    end
+end
+return replaced
@@ -0,0 +1,13 @@
+-- Gives the amount of a rejected packet back to the amounts an account sent
+-- in its current day and month, without letting them go below zero
+local amount = tonumber(ARGV[1])
+
+for _, key in ipairs(KEYS) do
+    if redis.call('EXISTS', key) == 1 then
+        local sent = redis.call('DECRBY', key, amount)
+        if sent < 0 then
+            redis.call('INCRBY', key, -sent)
+        end
+    end
+end
+return 0
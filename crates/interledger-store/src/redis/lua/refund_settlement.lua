@@ -1,5 +1,15 @@
-local account = 'accounts:' .. ARGV[1]
-local settle_amount = tonumber(ARGV[2])
+-- XADD assigns the entry id from the server's clock, so the script
+-- has to be replicated by its effects rather than verbatim
+redis.replicate_commands()
+
+local account = KEYS[1]
+local journal = KEYS[2]
+local settle_amount = tonumber(ARGV[1])
 
 local balance = redis.call('HINCRBY', account, 'balance', settle_amount)
+local prepaid_amount = redis.call('HGET', account, 'prepaid_amount')
+append_journal_entry(journal,
+    'kind', 'settlement_refund',
+    'delta', settle_amount,
+    'balance', balance + prepaid_amount)
 return balance
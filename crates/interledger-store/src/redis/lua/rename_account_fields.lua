@@ -0,0 +1,17 @@
+-- The arguments are (previous name, new name) pairs. The fields of every
+-- account are renamed, unless the account already has a field with the new name
+local renamed = 0
+for _, id in ipairs(redis.call('SMEMBERS', 'accounts')) do
+    local account = 'accounts:' .. id
+    for i = 1, #ARGV, 2 do
+        local value = redis.call('HGET', account, ARGV[i])
+        if value then
+            if redis.call('HEXISTS', account, ARGV[i + 1]) == 0 then
+                redis.call('HSET', account, ARGV[i + 1], value)
+                renamed = renamed + 1
+            end
+            redis.call('HDEL', account, ARGV[i])
+        end
+    end
+end
+return renamed
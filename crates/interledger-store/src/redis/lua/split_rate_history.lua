@@ -0,0 +1,17 @@
+-- Moves the entries of the exchange rate history stream (KEYS[1]) to one stream
+-- per asset, named ARGV[1] followed by the asset code, keeping the entries' ids.
+-- The asset codes are added to the set KEYS[2] and only the last ARGV[2] entries
+-- of each asset are kept
+local moved = 0
+for _, entry in ipairs(redis.call('XRANGE', KEYS[1], '-', '+')) do
+    local fields = entry[2]
+    for i = 1, #fields, 2 do
+        if fields[i] == 'asset_code' then
+            redis.call('XADD', ARGV[1] .. fields[i + 1], 'MAXLEN', ARGV[2], entry[1], unpack(fields))
+            redis.call('SADD', KEYS[2], fields[i + 1])
+            moved = moved + 1
+        end
+    end
+end
+redis.call('DEL', KEYS[1])
+return moved
@@ -0,0 +1,22 @@
+local rotation_key = KEYS[1]
+local confirmations_key = KEYS[2]
+local rotation = ARGV[1]
+local origin = ARGV[2]
+local instance_key_prefix = ARGV[3]
+local channel = ARGV[4]
+
+-- Another store's rotation which is not done yet is only replaced
+-- if that store is no longer connected
+local current = redis.call('GET', rotation_key)
+if current then
+    current = cjson.decode(current)
+    if current.stage ~= 'done' and current.origin ~= origin
+        and redis.call('EXISTS', instance_key_prefix .. current.origin) == 1 then
+        return 0
+    end
+end
+
+redis.call('SET', rotation_key, rotation)
+redis.call('DEL', confirmations_key)
+redis.call('PUBLISH', channel, rotation)
+return 1
@@ -0,0 +1,252 @@
+use super::namespace::Namespace;
+use futures::TryFutureExt;
+use interledger_service_util::EXCHANGE_RATE_HISTORY_LENGTH;
+use lazy_static::lazy_static;
+use log::{debug, error, info};
+use redis_crate::{aio::ConnectionLike, cmd, AsyncCommands, Script};
+
+/// The version of the layout of the data which is stored in Redis.
+/// It must be incremented whenever a migration is added to `MIGRATIONS`
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// Prefixed by the namespace like the other keys
+static SCHEMA_VERSION_KEY: &str = "schema_version";
+/// Where the schema version was saved before the keys had a hash tag
+static UNTAGGED_SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Description of the migration which upgrades the data to each version.
+/// Data stored without a version has version 0.
+static MIGRATIONS: [&str; SCHEMA_VERSION as usize] = [
+    "Rename the HTTP and BTP account fields to their ilp_over_http_* and ilp_over_btp_* names",
+    "Add the {ilp} Redis Cluster hash tag to the name of every key",
+    "Index the account IDs in a sorted set so that they can be listed in order",
+    "Split the exchange rate history into a stream per asset",
+];
+
+/// The account fields of older versions and the names they were replaced with
+static RENAMED_ACCOUNT_FIELDS: &[(&str, &str)] = &[
+    ("http_endpoint", "ilp_over_http_url"),
+    ("http_incoming_token", "ilp_over_http_incoming_token"),
+    ("http_outgoing_token", "ilp_over_http_outgoing_token"),
+    ("btp_uri", "ilp_over_btp_url"),
+    ("btp_incoming_token", "ilp_over_btp_incoming_token"),
+    ("btp_outgoing_token", "ilp_over_btp_outgoing_token"),
+];
+
+/// The names of the keys which are not per account, before they had a hash tag.
+/// The accounts set comes last because the other keys are found from it
+static UNTAGGED_KEYS: &[&str] = &[
+    "parent_node_account_address",
+    "routes:current",
+    "rates:current",
+    "routes:static",
+    "routes:default",
+    "settlement_engines",
+    "usernames",
+    "send_routes_to",
+    "receive_routes_from",
+    "btp_outgoing",
+    "accounts",
+];
+
+lazy_static! {
+    /// Lua script which renames fields of every account
+    static ref RENAME_ACCOUNT_FIELDS: Script = Script::new(include_str!("lua/rename_account_fields.lua"));
+
+    /// Lua script which adds the hash tag to the name of a key
+    static ref ADD_KEY_TAG: Script = Script::new(include_str!("lua/add_key_tag.lua"));
+
+    /// Lua script which moves the exchange rate history to the streams of the assets
+    static ref SPLIT_RATE_HISTORY: Script = Script::new(include_str!("lua/split_rate_history.lua"));
+}
+
+/// Upgrades the data stored in Redis to the current `SCHEMA_VERSION` by
+/// running the migrations which were not run yet, in order. The version is
+/// saved after each migration, so that a failed upgrade resumes where it stopped.
+///
+/// Migrations must be idempotent because several nodes sharing the same
+/// Redis may run them at the same time when they are started.
+///
+/// Only stores without a key prefix have data from the versions before
+/// the prefix could be set, so the data of the other namespaces starts
+/// with the current version.
+pub async fn migrate<C>(connection: &mut C, namespace: &Namespace) -> Result<u32, ()>
+where
+    C: ConnectionLike + Send,
+{
+    let version_key = namespace.key(SCHEMA_VERSION_KEY);
+    let version: Option<u32> = connection
+        .get(&version_key)
+        .map_err(|err| error!("Error loading the schema version: {:?}", err))
+        .await?;
+    if version.is_none() && !namespace.is_default() {
+        connection
+            .set(&version_key, SCHEMA_VERSION)
+            .map_err(|err| error!("Error saving the schema version: {:?}", err))
+            .await?;
+        debug!("The data in Redis has schema version {}", SCHEMA_VERSION);
+        return Ok(SCHEMA_VERSION);
+    }
+    // The keys are in different hash slots, so they can't be loaded together on Redis Cluster
+    let untagged_version: Option<u32> = if version.is_none() {
+        connection
+            .get(UNTAGGED_SCHEMA_VERSION_KEY)
+            .map_err(|err| error!("Error loading the untagged schema version: {:?}", err))
+            .await?
+    } else {
+        None
+    };
+    let version = version.or(untagged_version).unwrap_or(0);
+    if version > SCHEMA_VERSION {
+        error!(
+            "The data in Redis has schema version {}, which is newer than the version supported by this node ({}). Please upgrade the node",
+            version, SCHEMA_VERSION
+        );
+        return Err(());
+    }
+
+    for next_version in version + 1..=SCHEMA_VERSION {
+        info!(
+            "Migrating the data in Redis to schema version {}: {}",
+            next_version,
+            MIGRATIONS[next_version as usize - 1]
+        );
+        run_migration(connection, namespace, next_version).await?;
+        connection
+            .set(&version_key, next_version)
+            .map_err(|err| error!("Error saving the schema version: {:?}", err))
+            .await?;
+    }
+    if untagged_version.is_some() {
+        connection
+            .del(UNTAGGED_SCHEMA_VERSION_KEY)
+            .map_err(|err| error!("Error deleting the untagged schema version: {:?}", err))
+            .await?;
+    }
+    debug!("The data in Redis has schema version {}", SCHEMA_VERSION);
+    Ok(SCHEMA_VERSION)
+}
+
+async fn run_migration<C>(connection: &mut C, namespace: &Namespace, version: u32) -> Result<(), ()>
+where
+    C: ConnectionLike + Send,
+{
+    match version {
+        1 => {
+            let mut script = RENAME_ACCOUNT_FIELDS.prepare_invoke();
+            for (previous, new) in RENAMED_ACCOUNT_FIELDS {
+                script.arg(*previous).arg(*new);
+            }
+            let renamed: u32 = script
+                .invoke_async(connection)
+                .map_err(|err| error!("Error renaming account fields: {:?}", err))
+                .await?;
+            debug!("Renamed {} account fields", renamed);
+            Ok(())
+        }
+        2 => {
+            let renamed = add_key_tag(connection, &namespace.key("")).await?;
+            debug!("Added the hash tag to {} keys", renamed);
+            Ok(())
+        }
+        3 => {
+            let account_ids: Vec<String> = connection
+                .smembers(namespace.key("accounts"))
+                .map_err(|err| error!("Error loading the account IDs: {:?}", err))
+                .await?;
+            for ids in account_ids.chunks(100) {
+                let items: Vec<(u8, &String)> = ids.iter().map(|id| (0, id)).collect();
+                let _: () = connection
+                    .zadd_multiple(namespace.key("account_ids"), &items)
+                    .map_err(|err| error!("Error indexing the account IDs: {:?}", err))
+                    .await?;
+            }
+            debug!("Indexed {} account IDs", account_ids.len());
+            Ok(())
+        }
+        4 => {
+            let moved: u32 = SPLIT_RATE_HISTORY
+                .key(namespace.key("rates:history"))
+                .key(namespace.key("rates:history_assets"))
+                .arg(namespace.rate_history_key(""))
+                .arg(EXCHANGE_RATE_HISTORY_LENGTH)
+                .invoke_async(connection)
+                .map_err(|err| error!("Error splitting the exchange rate history: {:?}", err))
+                .await?;
+            debug!(
+                "Moved {} exchange rates to the history of their assets",
+                moved
+            );
+            Ok(())
+        }
+        _ => unreachable!("There is no migration to schema version {}", version),
+    }
+}
+
+/// Adds the tag in front of the name of every key. The keys which can't be found
+/// from the accounts are looked up with SCAN from here, since scripts may only
+/// access the keys they are given
+async fn add_key_tag<C>(connection: &mut C, tag: &str) -> Result<u32, ()>
+where
+    C: ConnectionLike + Send,
+{
+    let mut renamed = 0;
+    let account_ids: Vec<String> = connection
+        .smembers("accounts")
+        .map_err(|err| error!("Error loading the account IDs: {:?}", err))
+        .await?;
+    for id in account_ids {
+        for prefix in &["accounts:", "uncredited-amount:", "balance_journal:"] {
+            let key = format!("{}{}", prefix, id);
+            renamed += rename_key(connection, &key, &format!("{}{}", tag, key)).await?;
+        }
+    }
+
+    // Incoming settlements were marked as processed with the bare idempotency
+    // key, which is only known from the idempotency key of the settlement request
+    let mut cursor: u64 = 0;
+    loop {
+        let (next_cursor, keys): (u64, Vec<String>) = cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg("idempotency-key:*")
+            .query_async(connection)
+            .map_err(|err| error!("Error scanning the idempotency keys: {:?}", err))
+            .await?;
+        for key in keys {
+            renamed += rename_key(connection, &key, &format!("{}{}", tag, key)).await?;
+            let idempotency_key = &key["idempotency-key:".len()..];
+            let key_type: String = cmd("TYPE")
+                .arg(idempotency_key)
+                .query_async(connection)
+                .map_err(|err| error!("Error checking the idempotency key: {:?}", err))
+                .await?;
+            if key_type == "string" {
+                let new_key = format!("{}settlement-idempotency-key:{}", tag, idempotency_key);
+                renamed += rename_key(connection, idempotency_key, &new_key).await?;
+            }
+        }
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    for key in UNTAGGED_KEYS {
+        renamed += rename_key(connection, key, &format!("{}{}", tag, key)).await?;
+    }
+    Ok(renamed)
+}
+
+/// Returns 1 if the key was renamed
+async fn rename_key<C>(connection: &mut C, key: &str, new_key: &str) -> Result<u32, ()>
+where
+    C: ConnectionLike + Send,
+{
+    ADD_KEY_TAG
+        .key(key)
+        .key(new_key)
+        .invoke_async(connection)
+        .map_err(|err| error!("Error adding the hash tag to {}: {:?}", key, err))
+        .await
+}
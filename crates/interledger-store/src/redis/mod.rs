@@ -2,11 +2,31 @@
 //   send_routes_to         set         used for CCP routing
 //   receive_routes_from    set         used for CCP routing
 //   next_account_id        string      unique ID for each new account
-//   rates:current          hash        exchange rates
+//   rates:current          hash        exchange rates, as decimal strings
+//   rates:updated_at       hash        when each exchange rate was set (ms since epoch)
+//   rates:history:<asset>  stream      the last exchange rates set for an asset, with their sources
+//   rates:history_assets   set         asset codes which have a rate history
+//   rates:pairs            string      JSON of the quotes of pairs of assets
+//   rates:spreads          string      JSON of the spreads set via the API
 //   routes:current         hash        dynamic routing table
 //   routes:static          hash        static routing table
 //   accounts:<id>          hash        information for each account
+//   fees_earned            hash        fees earned per pair of accounts
 //   btp_outgoing
+//   instances              set         ids of the stores which announced that they are connected
+//   instances:<id>         string      set while the store is connected, expires otherwise
+//   secret_rotation        string      JSON of the last secret rotation and its stage
+//   secret_rotation:confirmations hash store id -> stage of the rotation it switched to
+// Every key name starts with a hash tag containing the store's key prefix
+// ({ilp}: by default), see the `namespace` module.
+// Changes to accounts are published on the account_updates channel so that
+// every store connected to the same Redis can drop them from its account cache.
+// Likewise, changes to the routing table are announced on routes_updates
+// and new exchange rates are published on exchange_rates (pair quotes on pair_quotes
+// and spreads on spreads).
+// When the secret is rotated, each stage of the rotation is published on
+// secret_rotation and the rotating store waits until every connected store
+// confirmed that it switched its keys before going on.
 // For interactive exploration of the store,
 // use the redis-cli tool included with your redis install.
 // Within redis-cli:
@@ -14,24 +34,42 @@
 //    smembers <key>        list the members of a set
 //    get <key>             get the value of a key
 //    hgetall <key>         the flattened list of every key/value entry within a hash
+mod account_cache;
+mod migrations;
+mod namespace;
 mod reconnect;
-use reconnect::RedisReconnect;
+use account_cache::AccountCache;
+pub use migrations::SCHEMA_VERSION;
+use namespace::{Namespace, DEFAULT_KEY_PREFIX};
+use reconnect::{RedisReconnect, RedisTarget};
 
-use super::account::{Account, AccountWithEncryptedTokens};
-use super::crypto::{encrypt_token, generate_keys, DecryptionKey, EncryptionKey};
+use super::account::{validate_incoming_token, Account, AccountWithEncryptedTokens};
+use super::crypto::{hash_token, is_token_hash, StoreKeys, TokenVerifier, TOKEN_HASH_PREFIX};
 use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
-use futures::channel::mpsc::UnboundedSender;
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::future::{select, Either};
+use futures::StreamExt;
 use http::StatusCode;
-use interledger_api::{AccountDetails, AccountSettings, EncryptedAccountSettings, NodeStore};
+use interledger_api::{
+    AccountDetails, AccountSettings, AccountsPage, AccountsQuery, ApiKey, EncryptedAccountSettings,
+    ExportedAccount, NodeStore,
+};
 use interledger_btp::BtpStore;
 use interledger_ccp::{CcpRoutingAccount, RouteManagerStore, RoutingRelation};
 use interledger_http::HttpStore;
 use interledger_packet::Address;
 use interledger_router::RouterStore;
-use interledger_service::{Account as AccountTrait, AccountStore, AddressStore, Username};
+use interledger_service::{
+    Account as AccountTrait, AccountStatus, AccountStore, AddressStore, Username,
+};
 use interledger_service_util::{
-    BalanceStore, ExchangeRateStore, RateLimitError, RateLimitStore, DEFAULT_ROUND_TRIP_TIME,
+    AmountLimitPeriod, BalanceChangeKind, BalanceHistoryPage, BalanceHistoryQuery,
+    BalanceJournalEntry, BalanceStore, CumulativeAmounts, ExchangeRate, ExchangeRateHistoryPage,
+    ExchangeRateHistoryQuery, ExchangeRateHistoryStore, ExchangeRateRecord, ExchangeRateStore,
+    FeeStore, FeesEarned, PacketContext, PairQuote, RateLimitAccount, RateLimitError,
+    RateLimitStore, Spreads, BALANCE_JOURNAL_RETENTION, DEFAULT_ROUND_TRIP_TIME,
+    EXCHANGE_RATE_HISTORY_LENGTH,
 };
 use interledger_settlement::core::{
     idempotency::{IdempotentData, IdempotentStore},
@@ -42,13 +80,14 @@ use interledger_stream::{PaymentNotification, StreamNotificationsStore};
 use lazy_static::lazy_static;
 use log::{debug, error, trace, warn};
 use num_bigint::BigUint;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use redis_crate::AsyncCommands;
 use redis_crate::{
-    self, cmd, from_redis_value, Client, ConnectionInfo, ControlFlow, ErrorKind, FromRedisValue,
-    PubSubCommands, RedisError, RedisWrite, Script, ToRedisArgs, Value,
+    self, cmd, from_redis_value, Client, Connection, ConnectionInfo, ControlFlow, ErrorKind,
+    FromRedisValue, Msg, PubSubCommands, RedisError, RedisWrite, Script, ScriptInvocation,
+    ToRedisArgs, Value,
 };
-use secrecy::{ExposeSecret, Secret, SecretBytesMut};
+use secrecy::{ExposeSecret, SecretBytesMut, SecretString};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::{
@@ -56,39 +95,236 @@ use std::{
     fmt::Display,
 };
 use std::{
+    convert::TryFrom,
     iter::{self, FromIterator},
     str,
     str::FromStr,
-    sync::Arc,
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc as std_mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tokio::runtime::Handle;
+use tokio::sync::RwLock as AsyncRwLock;
 use url::Url;
 use uuid::Uuid;
 use zeroize::Zeroize;
 
 const DEFAULT_POLL_INTERVAL: u64 = 30000; // 30 seconds
-const ACCOUNT_DETAILS_FIELDS: usize = 21;
-
+const DEFAULT_ACCOUNT_CACHE_TTL: u64 = 60000; // 1 minute
+/// Bounds of the delay before resubscribing after the pub/sub connection was closed
+const MIN_RESUBSCRIBE_DELAY: Duration = Duration::from_millis(100);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(10);
+const ACCOUNT_DETAILS_FIELDS: usize = 31;
+/// Seconds to keep the amount an account sent in a day or month for. The
+/// amounts are stored under the id of their period, so they can outlive it
+const AMOUNT_SENT_IN_DAY_EXPIRY: u64 = 2 * 86400;
+const AMOUNT_SENT_IN_MONTH_EXPIRY: u64 = 32 * 86400;
+/// Number of accounts loaded at a time while filling a page of accounts
+const ACCOUNTS_PAGE_BATCH_SIZE: usize = 100;
+/// How often a store announces that it is connected, and after how long without
+/// an announcement the other stores no longer wait for it while rotating the secret
+const INSTANCE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const INSTANCE_TIMEOUT: u64 = 15000; // 15 seconds
+/// How long the store rotating the secret waits for the others to switch keys
+const SECRET_ROTATION_TIMEOUT: Duration = Duration::from_secs(60);
+const SECRET_ROTATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// The names of the keys and channels, which are prefixed by the store's `Namespace`
 static PARENT_ILP_KEY: &str = "parent_node_account_address";
 static ROUTES_KEY: &str = "routes:current";
+static RATES_KEY: &str = "rates:current";
+static RATES_UPDATED_AT_KEY: &str = "rates:updated_at";
+/// Set of the asset codes which have a stream of the rates that were set
+static RATE_HISTORY_ASSETS_KEY: &str = "rates:history_assets";
+static PAIR_QUOTES_KEY: &str = "rates:pairs";
+static SPREADS_KEY: &str = "rates:spreads";
 static STATIC_ROUTES_KEY: &str = "routes:static";
 static DEFAULT_ROUTE_KEY: &str = "routes:default";
+static ACCOUNTS_KEY: &str = "accounts";
+/// Sorted set of the account ids, which all have the score 0 so that they are ordered by id
+static ACCOUNT_IDS_KEY: &str = "account_ids";
+static USERNAMES_KEY: &str = "usernames";
+static SEND_ROUTES_TO_KEY: &str = "send_routes_to";
+static RECEIVE_ROUTES_FROM_KEY: &str = "receive_routes_from";
+static BTP_OUTGOING_KEY: &str = "btp_outgoing";
 static STREAM_NOTIFICATIONS_PREFIX: &str = "stream_notifications:";
 static SETTLEMENT_ENGINES_KEY: &str = "settlement_engines";
+/// Hash of `{from account id}:{to account id}` -> total fees earned
+static FEES_EARNED_KEY: &str = "fees_earned";
+static ACCOUNT_UPDATES_CHANNEL: &str = "account_updates";
+/// Published on the account updates channel when every account may have changed
+static ALL_ACCOUNTS_UPDATED: &str = "*";
+static ROUTES_UPDATES_CHANNEL: &str = "routes_updates";
+static EXCHANGE_RATES_CHANNEL: &str = "exchange_rates";
+static PAIR_QUOTES_CHANNEL: &str = "pair_quotes";
+static SPREADS_CHANNEL: &str = "spreads";
+/// Set of the ids of the stores which announced that they are connected
+static INSTANCES_KEY: &str = "instances";
+static SECRET_ROTATION_KEY: &str = "secret_rotation";
+/// Hash of store id -> the stage of the secret rotation it switched its keys to
+static SECRET_ROTATION_CONFIRMATIONS_KEY: &str = "secret_rotation:confirmations";
+static SECRET_ROTATION_CHANNEL: &str = "secret_rotation";
+
+/// Prepares a script which changes the amounts the account sent in the current day and
+/// month, passing the limit of each period and how long to keep its amount after the amount
+fn amount_sent_script(
+    script: &'static Script,
+    namespace: &Namespace,
+    account: &Account,
+    amount: u64,
+) -> ScriptInvocation<'static> {
+    let mut script = script.prepare_invoke();
+    script.arg(amount);
+    for period in AmountLimitPeriod::ALL.iter() {
+        if let Some(limit) = account.amount_limit(*period) {
+            let expiry = match period {
+                AmountLimitPeriod::Day => AMOUNT_SENT_IN_DAY_EXPIRY,
+                AmountLimitPeriod::Month => AMOUNT_SENT_IN_MONTH_EXPIRY,
+            };
+            script
+                .key(namespace.amount_sent_key(period.as_ref(), &period.current_id(), account.id))
+                .arg(limit)
+                .arg(expiry);
+        }
+    }
+    script
+}
 
-/// Domain separator for leftover amounts
-fn uncredited_amount_key(account_id: impl ToString) -> String {
-    format!("uncredited-amount:{}", account_id.to_string())
+/// Prepares the command which gives the amount of a packet back to the account's
+/// throughput limit, if it has one
+fn refund_throughput_cmd(
+    namespace: &Namespace,
+    account: &Account,
+    amount: u64,
+) -> Option<redis_crate::Cmd> {
+    let limit = account.amount_per_minute_limit?;
+    let burst = account.amount_burst_limit.unwrap_or(limit);
+    let amount = i64::try_from(amount)
+        .map_err(|_| error!("Cannot refund {} to the throughput limit", amount))
+        .ok()?;
+    let mut refund = cmd("CL.THROTTLE");
+    refund
+        .arg(namespace.rate_limit_key("throughput", account.id))
+        .arg(burst.saturating_sub(1))
+        .arg(limit)
+        .arg(60)
+        .arg(-amount);
+    Some(refund)
 }
 
-/// Domain separator for idempotency keys
-fn prefixed_idempotency_key(idempotency_key: String) -> String {
-    format!("idempotency-key:{}", idempotency_key)
+/// Prepares the LOAD_ACCOUNTS script to load the accounts with the given ids
+fn load_accounts_script<'a>(
+    namespace: &Namespace,
+    account_ids: impl IntoIterator<Item = &'a Uuid>,
+) -> ScriptInvocation<'static> {
+    let mut script = LOAD_ACCOUNTS.prepare_invoke();
+    script.key(namespace.key(SETTLEMENT_ENGINES_KEY));
+    for id in account_ids {
+        script.key(namespace.accounts_key(*id));
+    }
+    script
+}
+
+/// Creates a script which changes balances, prepending the function which it uses
+/// to append entries to the balance journal and delete the expired ones
+fn journal_script(script: &str) -> Script {
+    Script::new(&format!(
+        "local journal_retention = {}\n{}\n{}",
+        BALANCE_JOURNAL_RETENTION,
+        include_str!("lua/append_journal_entry.lua"),
+        script
+    ))
 }
 
-/// Domain separator for accounts
-fn accounts_key(account_id: Uuid) -> String {
-    format!("accounts:{}", account_id)
+/// Returns true if both incoming tokens are the same (or both are not set)
+fn same_token(a: &Option<SecretBytesMut>, b: &Option<SecretBytesMut>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.expose_secret() == b.expose_secret(),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Parses the `{milliseconds}-{sequence number}` id of a stream entry
+fn parse_stream_id(id: &str) -> Option<(u64, u64)> {
+    let mut parts = id.splitn(2, '-');
+    let ms = u64::from_str(parts.next()?).ok()?;
+    let seq = u64::from_str(parts.next()?).ok()?;
+    Some((ms, seq))
+}
+
+/// Parses an entry read from the balance journal stream
+fn parse_journal_entry((id, fields): (String, Vec<String>)) -> Result<BalanceJournalEntry, ()> {
+    let (timestamp, _) =
+        parse_stream_id(&id).ok_or_else(|| error!("Invalid balance journal entry id: {}", id))?;
+    let fields: HashMap<String, String> = fields
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .ok_or_else(|| error!("Balance journal entry {} is missing {}", id, name))
+    };
+    let kind = field("kind")?;
+    Ok(BalanceJournalEntry {
+        timestamp,
+        kind: BalanceChangeKind::from_str(kind)
+            .map_err(|_| error!("Invalid balance journal entry kind: {}", kind))?,
+        delta: i64::from_str(field("delta")?)
+            .map_err(|err| error!("Invalid balance journal delta: {:?}", err))?,
+        balance: i64::from_str(field("balance")?)
+            .map_err(|err| error!("Invalid balance journal balance: {:?}", err))?,
+        execution_condition: fields.get("execution_condition").cloned(),
+        counterparty: fields
+            .get("counterparty")
+            .map(|id| Uuid::from_str(id))
+            .transpose()
+            .map_err(|err| error!("Invalid account id in balance journal: {:?}", err))?,
+    })
+}
+
+/// Returns the smallest id of a stream entry which comes after the given one
+fn next_stream_id((ms, seq): (u64, u64)) -> (u64, u64) {
+    match seq.checked_add(1) {
+        Some(seq) => (ms, seq),
+        None => (ms.saturating_add(1), 0),
+    }
+}
+
+/// Parses an entry read from the stream of an asset's exchange rate history,
+/// along with the id of the entry
+fn parse_rate_record(
+    (id, fields): (String, Vec<String>),
+) -> Result<((u64, u64), ExchangeRateRecord), ()> {
+    let id_parts = parse_stream_id(&id)
+        .ok_or_else(|| error!("Invalid exchange rate history entry id: {}", id))?;
+    let fields: HashMap<String, String> = fields
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .cloned()
+            .ok_or_else(|| error!("Exchange rate history entry {} is missing {}", id, name))
+    };
+    let rate = field("rate")?;
+    Ok((
+        id_parts,
+        ExchangeRateRecord {
+            timestamp: id_parts.0,
+            asset_code: field("asset_code")?,
+            rate: ExchangeRate::from_str(&rate)
+                .map_err(|err| error!("Invalid rate in exchange rate history: {}", err))?,
+            source: field("source")?,
+        },
+    ))
 }
 
 // The following are Lua scripts that are used to atomically execute the given logic
@@ -111,26 +347,49 @@ lazy_static! {
     static ref LOAD_ACCOUNTS: Script = Script::new(include_str!("lua/load_accounts.lua"));
 
     /// Lua script which reduces the provided account's balance before sending a Prepare packet
-    static ref PROCESS_PREPARE: Script = Script::new(include_str!("lua/process_prepare.lua"));
+    static ref PROCESS_PREPARE: Script = journal_script(include_str!("lua/process_prepare.lua"));
 
     /// Lua script which increases the provided account's balance after receiving a Fulfill packet
-    static ref PROCESS_FULFILL: Script = Script::new(include_str!("lua/process_fulfill.lua"));
+    static ref PROCESS_FULFILL: Script = journal_script(include_str!("lua/process_fulfill.lua"));
 
     /// Lua script which increases the provided account's balance after receiving a Reject packet
-    static ref PROCESS_REJECT: Script = Script::new(include_str!("lua/process_reject.lua"));
+    static ref PROCESS_REJECT: Script = journal_script(include_str!("lua/process_reject.lua"));
 
     /// Lua script which increases the provided account's balance after a settlement attempt failed
-    static ref REFUND_SETTLEMENT: Script = Script::new(include_str!("lua/refund_settlement.lua"));
+    static ref REFUND_SETTLEMENT: Script = journal_script(include_str!("lua/refund_settlement.lua"));
 
     /// Lua script which increases the provided account's balance after an incoming settlement succeeded
-    static ref PROCESS_INCOMING_SETTLEMENT: Script = Script::new(include_str!("lua/process_incoming_settlement.lua"));
+    static ref PROCESS_INCOMING_SETTLEMENT: Script = journal_script(include_str!("lua/process_incoming_settlement.lua"));
+
+    /// Lua script which replaces an account's encrypted tokens after the store's secret was rotated
+    static ref REENCRYPT_TOKENS: Script = Script::new(include_str!("lua/reencrypt_tokens.lua"));
+
+    /// Lua script which replaces an incoming token that is still encrypted with its hash
+    static ref MIGRATE_INCOMING_TOKEN: Script = Script::new(include_str!("lua/migrate_incoming_token.lua"));
+
+    /// Lua script which adds a packet's amount to what an account sent in the current day and month
+    static ref ADD_AMOUNT_SENT: Script = Script::new(include_str!("lua/add_amount_sent.lua"));
+
+    /// Lua script which gives a rejected packet's amount back to what an account sent
+    static ref REFUND_AMOUNT_SENT: Script = Script::new(include_str!("lua/refund_amount_sent.lua"));
+
+    /// Lua script which removes the routes to an account's prefixes which are still routed to it
+    static ref DELETE_ROUTES: Script = Script::new(include_str!("lua/delete_routes.lua"));
+
+    /// Lua script which saves and publishes a new secret rotation unless another
+    /// store which is still connected is rotating the secret
+    static ref START_SECRET_ROTATION: Script = Script::new(include_str!("lua/start_secret_rotation.lua"));
 }
 
 /// Builder for the Redis Store
 pub struct RedisStoreBuilder {
     redis_url: ConnectionInfo,
+    /// The master name and the sentinels, if the master is found with Redis Sentinel
+    sentinel: Option<(String, Vec<ConnectionInfo>)>,
+    key_prefix: String,
     secret: [u8; 32],
     poll_interval: u64,
+    account_cache_ttl: u64,
     /// Connector's ILP Address. Used to insert `Child` accounts as
     node_ilp_address: Address,
 }
@@ -140,8 +399,11 @@ impl RedisStoreBuilder {
     pub fn new(redis_url: ConnectionInfo, secret: [u8; 32]) -> Self {
         RedisStoreBuilder {
             redis_url,
+            sentinel: None,
+            key_prefix: DEFAULT_KEY_PREFIX.to_string(),
             secret,
             poll_interval: DEFAULT_POLL_INTERVAL,
+            account_cache_ttl: DEFAULT_ACCOUNT_CACHE_TTL,
             node_ilp_address: DEFAULT_ILP_ADDRESS.clone(),
         }
     }
@@ -158,36 +420,85 @@ impl RedisStoreBuilder {
         self
     }
 
+    /// Sets how long (in milliseconds) accounts are kept in the in-process account cache.
+    /// Accounts are removed from the cache as soon as they are changed, so this only
+    /// bounds how stale the cache can get if a change notification is missed.
+    /// Setting it to 0 disables the cache.
+    pub fn account_cache_ttl(&mut self, account_cache_ttl: u64) -> &mut Self {
+        self.account_cache_ttl = account_cache_ttl;
+        self
+    }
+
+    /// Connects to the master that Redis Sentinel monitors under `master_name`
+    /// instead of the server of the Redis URL, whose database and password are
+    /// used for the master. The sentinels are asked for the master's address in
+    /// order, and again after a failover.
+    pub fn sentinel(&mut self, master_name: String, sentinels: Vec<ConnectionInfo>) -> &mut Self {
+        self.sentinel = Some((master_name, sentinels));
+        self
+    }
+
+    /// Sets the prefix of the keys and pub/sub channels (defaults to "ilp"), which lets
+    /// several nodes share the same Redis database. It may only contain ASCII letters,
+    /// digits, '-', '_' and '.', and must not be changed once the store has data
+    /// because the data is not moved to the new prefix.
+    pub fn key_prefix(&mut self, key_prefix: &str) -> &mut Self {
+        self.key_prefix = key_prefix.to_string();
+        self
+    }
+
+    fn target(&self) -> RedisTarget {
+        match &self.sentinel {
+            Some((master_name, sentinels)) => RedisTarget::Sentinel {
+                master_name: master_name.clone(),
+                sentinels: sentinels.clone(),
+                master_info: self.redis_url.clone(),
+            },
+            None => RedisTarget::Direct(self.redis_url.clone()),
+        }
+    }
+
+    /// Upgrades the data stored in Redis to the current [`SCHEMA_VERSION`](./constant.SCHEMA_VERSION.html)
+    /// without connecting the store. This is also done by `connect`.
+    pub async fn migrate(&self) -> Result<u32, ()> {
+        let namespace = Namespace::new(&self.key_prefix)?;
+        let mut connection = RedisReconnect::connect(self.target())
+            .map_err(|_| ())
+            .await?;
+        migrations::migrate(&mut connection, &namespace).await
+    }
+
     /// Connects to the Redis Store
     ///
     /// Specifically
     /// 1. Generates encryption and decryption keys
     /// 1. Connects to the redis store (ensuring that it reconnects in case of drop)
+    /// 1. Migrates the stored data to the current schema version
     /// 1. Gets the Node address assigned to us by our parent (if it exists)
-    /// 1. Starts polling for routing table updates
+    /// 1. Loads the spreads saved by any store
+    /// 1. Starts polling for routing table and exchange rate updates
+    /// 1. Spawns a task to save and publish the exchange rates set on this store
     /// 1. Spawns a thread to notify incoming payments over WebSockets
+    ///    and to apply the account, route and rate changes published by other stores
     pub async fn connect(&mut self) -> Result<RedisStore, ()> {
-        let redis_info = self.redis_url.clone();
-        let (encryption_key, decryption_key) = generate_keys(&self.secret[..]);
+        let target = self.target();
+        let namespace = Arc::new(Namespace::new(&self.key_prefix)?);
+        let keys = StoreKeys::new(&self.secret[..]);
         self.secret.zeroize(); // clear the secret after it has been used for key generation
         let poll_interval = self.poll_interval;
         let ilp_address = self.node_ilp_address.clone();
 
-        let client = Client::open(redis_info.clone())
-            .map_err(|err| error!("Error creating subscription Redis client: {:?}", err))?;
-        debug!("Connected subscription client to redis: {:?}", client);
-        let mut connection = RedisReconnect::connect(redis_info.clone())
-            .map_err(|_| ())
-            .await?;
-        let mut sub_connection = client
-            .get_connection()
-            .map_err(|err| error!("Error connecting subscription client to Redis: {:?}", err))?;
+        let mut connection = RedisReconnect::connect(target).map_err(|_| ()).await?;
+        // Subscribe on the server the connection was opened to, which is the master
+        // if Redis Sentinel is used
+        let sub_connection = subscription_connection(connection.redis_info())?;
+        migrations::migrate(&mut connection, &namespace).await?;
         // Before initializing the store, check if we have an address
         // that was configured due to adding a parent. If no parent was
         // found, use the builder's provided address (local.host) or the
         // one we decided to override it with
         let address: Option<String> = connection
-            .get(PARENT_ILP_KEY)
+            .get(namespace.key(PARENT_ILP_KEY))
             .map_err(|err| {
                 error!(
                     "Error checking whether we have a parent configured: {:?}",
@@ -201,36 +512,112 @@ impl RedisStoreBuilder {
             ilp_address
         };
 
+        // Used to ignore the exchange rates this store published itself
+        // and to confirm the stages of secret rotations
+        let instance_id = Uuid::new_v4();
+        let (exchange_rate_updates, mut rates_to_publish) = mpsc::unbounded();
+
         let store = RedisStore {
             ilp_address: Arc::new(RwLock::new(node_ilp_address)),
             connection,
+            namespace: namespace.clone(),
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
             exchange_rates: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rates_updated_at: Arc::new(RwLock::new(HashMap::new())),
+            spreads: Arc::new(RwLock::new(Spreads::default())),
+            spreads_set: Arc::new(AtomicBool::new(false)),
+            pair_quotes: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rate_updates,
             routes: Arc::new(RwLock::new(Arc::new(HashMap::new()))),
-            encryption_key: Arc::new(encryption_key),
-            decryption_key: Arc::new(decryption_key),
+            account_cache: Arc::new(AccountCache::new(Duration::from_millis(
+                self.account_cache_ttl,
+            ))),
+            keys: Arc::new(AsyncRwLock::new(keys)),
+            applied_secret_rotation: Arc::new(Mutex::new(None)),
+            secret_rotation_in_progress: Arc::new(AtomicBool::new(false)),
+            instance_id,
+            token_verifier: Arc::new(TokenVerifier::new()),
         };
+        // Load the saved spreads so they apply before the node sets its default spreads
+        update_spreads(
+            store.connection.clone(),
+            &namespace,
+            store.spreads.clone(),
+            store.spreads_set.clone(),
+        )
+        .await?;
+        // Switch to the keys of a secret rotation before any token is encrypted
+        // with the secret this store was started with
+        let _ = update_secret_rotation(
+            store.connection.clone(),
+            &namespace,
+            instance_id,
+            &store.keys,
+            &store.applied_secret_rotation,
+        )
+        .await;
 
-        // Poll for routing table updates
+        // Reload the routing table whenever another store announces that it changed.
+        // Both the routes and the exchange rates are also polled in case an
+        // announcement was missed (for example while reconnecting to Redis)
         // Note: if this behavior changes, make sure to update the Drop implementation
         let connection_clone = Arc::downgrade(&store.connection.conn);
+        let target = store.connection.target.clone();
         let redis_info = store.connection.redis_info.clone();
         let routing_table = store.routes.clone();
+        let exchange_rates = store.exchange_rates.clone();
+        let exchange_rates_updated_at = store.exchange_rates_updated_at.clone();
+        let pair_quotes = store.pair_quotes.clone();
+        let spreads = store.spreads.clone();
+        let spreads_set = store.spreads_set.clone();
+        let poll_namespace = namespace.clone();
+        let (routes_updated, mut route_updates) = mpsc::unbounded::<()>();
 
         let poll_routes = async move {
             let mut interval = tokio::time::interval(Duration::from_millis(poll_interval));
-            // Irrefutable while pattern, can we do something here?
             loop {
-                interval.tick().await;
+                // Only the routes are reloaded when another store announces a change
+                let poll_rates = match select(Box::pin(interval.tick()), route_updates.next()).await
+                {
+                    Either::Left(_) => true,
+                    Either::Right((Some(()), _)) => false,
+                    // Without announcements, fall back to polling everything
+                    Either::Right((None, tick)) => {
+                        tick.await;
+                        true
+                    }
+                };
                 if let Some(conn) = connection_clone.upgrade() {
-                    let _ = update_routes(
-                        RedisReconnect {
-                            conn,
-                            redis_info: redis_info.clone(),
-                        },
-                        routing_table.clone(),
-                    )
-                    .await;
+                    let connection = RedisReconnect {
+                        target: target.clone(),
+                        redis_info: redis_info.clone(),
+                        conn,
+                    };
+                    let _ =
+                        update_routes(connection.clone(), &poll_namespace, routing_table.clone())
+                            .await;
+                    if poll_rates {
+                        let _ = update_exchange_rates(
+                            connection.clone(),
+                            &poll_namespace,
+                            exchange_rates.clone(),
+                            exchange_rates_updated_at.clone(),
+                        )
+                        .await;
+                        let _ = update_pair_quotes(
+                            connection.clone(),
+                            &poll_namespace,
+                            pair_quotes.clone(),
+                        )
+                        .await;
+                        let _ = update_spreads(
+                            connection,
+                            &poll_namespace,
+                            spreads.clone(),
+                            spreads_set.clone(),
+                        )
+                        .await;
+                    }
                 } else {
                     debug!("Not polling routes anymore because connection was closed");
                     break;
@@ -240,26 +627,146 @@ impl RedisStoreBuilder {
         };
         tokio::spawn(poll_routes);
 
+        // Announce that this store is connected, so that the secret is not rotated
+        // without it, and switch keys whenever a secret rotation is published.
+        // The rotation is also polled in case an announcement was missed
+        let connection_clone = Arc::downgrade(&store.connection.conn);
+        let target = store.connection.target.clone();
+        let redis_info = store.connection.redis_info.clone();
+        let keys = store.keys.clone();
+        let applied_secret_rotation = store.applied_secret_rotation.clone();
+        let rotation_namespace = namespace.clone();
+        let (secret_rotation_updated, mut secret_rotation_updates) = mpsc::unbounded::<()>();
+
+        let poll_secret_rotation = async move {
+            let mut interval = tokio::time::interval(INSTANCE_HEARTBEAT_INTERVAL);
+            loop {
+                if let Either::Right((None, tick)) =
+                    select(Box::pin(interval.tick()), secret_rotation_updates.next()).await
+                {
+                    tick.await;
+                }
+                if let Some(conn) = connection_clone.upgrade() {
+                    let connection = RedisReconnect {
+                        target: target.clone(),
+                        redis_info: redis_info.clone(),
+                        conn,
+                    };
+                    let _ = update_secret_rotation(
+                        connection,
+                        &rotation_namespace,
+                        instance_id,
+                        &keys,
+                        &applied_secret_rotation,
+                    )
+                    .await;
+                } else {
+                    debug!("Not polling secret rotations anymore because connection was closed");
+                    break;
+                }
+            }
+        };
+        tokio::spawn(poll_secret_rotation);
+
+        // Exchange rates are set synchronously, so they are written to Redis
+        // in order by this task. It stops once every clone of the store is dropped
+        let mut connection = store.connection.clone();
+        let publish_namespace = namespace.clone();
+        let publish_rates = async move {
+            while let Some(update) = rates_to_publish.next().await {
+                let _ = match update {
+                    RatesUpdate::Rates(rates, updated_at, source) => {
+                        save_exchange_rates(
+                            &mut connection,
+                            &publish_namespace,
+                            instance_id,
+                            rates,
+                            updated_at,
+                            &source,
+                        )
+                        .await
+                    }
+                    RatesUpdate::PairQuotes(quotes, updated_at) => {
+                        save_pair_quotes(
+                            &mut connection,
+                            &publish_namespace,
+                            instance_id,
+                            quotes,
+                            updated_at,
+                        )
+                        .await
+                    }
+                    RatesUpdate::Spreads(spreads) => {
+                        save_spreads(&mut connection, &publish_namespace, instance_id, spreads)
+                            .await
+                    }
+                };
+            }
+        };
+        tokio::spawn(publish_rates);
+
         // Here we spawn a worker thread to listen for incoming messages on Redis pub/sub,
         // running a callback for each message received.
         // This currently must be a thread rather than a task due to the redis-rs driver
         // not yet supporting asynchronous subscriptions (see https://github.com/mitsuhiko/redis-rs/issues/183).
+        // If the subscription is closed (for example because the master failed over),
+        // the thread looks up the server again and resubscribes. It stops once the
+        // store is dropped, which it notices when the next message arrives
+        let runtime = Handle::current();
+        let target = store.connection.target.clone();
+        let store_connection = Arc::downgrade(&store.connection.conn);
+        let store_dropped = store_connection.clone();
+        let resync_account_cache = store.account_cache.clone();
+        let resync_routes = routes_updated.clone();
+        let resync_secret_rotation = secret_rotation_updated.clone();
         let subscriptions_clone = store.subscriptions.clone();
-        std::thread::spawn(move || {
-            let sub_status =
-                sub_connection.psubscribe::<_, _, Vec<String>>(&["*"], move |msg| {
-                    let channel_name = msg.get_channel_name();
-                    if channel_name.starts_with(STREAM_NOTIFICATIONS_PREFIX) {
-                        if let Ok(account_id) = Uuid::from_str(&channel_name[STREAM_NOTIFICATIONS_PREFIX.len()..]) {
-                            let message: PaymentNotification = match serde_json::from_slice(msg.get_payload_bytes()) {
-                                Ok(s) => s,
-                                Err(e) => {
-                                    error!("Failed to get payload from subscription: {}", e);
-                                    return ControlFlow::Continue;
-                                }
-                            };
-                            trace!("Subscribed message received for account {}: {:?}", account_id, message);
-                            match subscriptions_clone.read().get(&account_id) {
+        let account_cache = store.account_cache.clone();
+        let exchange_rates = store.exchange_rates.clone();
+        let exchange_rates_updated_at = store.exchange_rates_updated_at.clone();
+        let pair_quotes = store.pair_quotes.clone();
+        let spreads = store.spreads.clone();
+        let spreads_set = store.spreads_set.clone();
+        // Only the channels of this store's namespace are subscribed to. Channel
+        // names without wildcards are patterns which only match themselves
+        let stream_notifications_prefix = namespace.channel(STREAM_NOTIFICATIONS_PREFIX);
+        let account_updates_channel = namespace.channel(ACCOUNT_UPDATES_CHANNEL);
+        let routes_updates_channel = namespace.channel(ROUTES_UPDATES_CHANNEL);
+        let exchange_rates_channel = namespace.channel(EXCHANGE_RATES_CHANNEL);
+        let pair_quotes_channel = namespace.channel(PAIR_QUOTES_CHANNEL);
+        let spreads_channel = namespace.channel(SPREADS_CHANNEL);
+        let secret_rotation_channel = namespace.channel(SECRET_ROTATION_CHANNEL);
+        let patterns = vec![
+            format!("{}*", stream_notifications_prefix),
+            account_updates_channel.clone(),
+            routes_updates_channel.clone(),
+            exchange_rates_channel.clone(),
+            pair_quotes_channel.clone(),
+            spreads_channel.clone(),
+            secret_rotation_channel.clone(),
+        ];
+        let mut on_message = move |msg: Msg| {
+            if store_dropped.upgrade().is_none() {
+                return ControlFlow::Break(());
+            }
+            let channel_name = msg.get_channel_name();
+            if channel_name.starts_with(&stream_notifications_prefix) {
+                if let Ok(account_id) =
+                    Uuid::from_str(&channel_name[stream_notifications_prefix.len()..])
+                {
+                    let message: PaymentNotification =
+                        match serde_json::from_slice(msg.get_payload_bytes()) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                error!("Failed to get payload from subscription: {}", e);
+                                return ControlFlow::Continue;
+                            }
+                        };
+                    trace!(
+                        "Subscribed message received for account {}: {:?}",
+                        account_id,
+                        message
+                    );
+                    match subscriptions_clone.read().get(&account_id) {
                                 Some(sender) => {
                                     if let Err(err) = sender.unbounded_send(message) {
                                         error!("Failed to send message: {}", err);
@@ -267,39 +774,190 @@ impl RedisStoreBuilder {
                                 }
                                 None => trace!("Ignoring message for account {} because there were no open subscriptions", account_id),
                             }
-                        } else {
-                            error!("Invalid Uuid in channel name: {}", channel_name);
+                } else {
+                    error!("Invalid Uuid in channel name: {}", channel_name);
+                }
+            } else if channel_name == account_updates_channel {
+                match str::from_utf8(msg.get_payload_bytes()) {
+                    Ok(payload) if payload == ALL_ACCOUNTS_UPDATED => account_cache.clear(),
+                    Ok(payload) => match Uuid::from_str(payload) {
+                        Ok(account_id) => {
+                            trace!(
+                                "Removing account {} from the cache because it was updated",
+                                account_id
+                            );
+                            account_cache.invalidate(account_id);
                         }
-                    } else {
-                        warn!("Ignoring unexpected message from Redis subscription for channel: {}", channel_name);
+                        Err(_) => error!("Invalid Uuid in account update: {}", payload),
+                    },
+                    Err(_) => error!("Account update is not valid UTF-8"),
+                }
+            } else if channel_name == routes_updates_channel {
+                // The routing table is reloaded by the polling task
+                let _ = routes_updated.unbounded_send(());
+            } else if channel_name == exchange_rates_channel {
+                match serde_json::from_slice::<ExchangeRatesUpdate>(msg.get_payload_bytes()) {
+                    Ok(update) if update.origin == instance_id => {}
+                    Ok(update) => {
+                        trace!(
+                            "Received exchange rates from another store: {:?}",
+                            update.rates
+                        );
+                        let updated_at = UNIX_EPOCH + Duration::from_millis(update.updated_at);
+                        *exchange_rates_updated_at.write() = update
+                            .rates
+                            .keys()
+                            .map(|code| (code.clone(), updated_at))
+                            .collect();
+                        *exchange_rates.write() = update.rates;
+                    }
+                    Err(err) => error!("Invalid exchange rates update: {}", err),
+                }
+            } else if channel_name == pair_quotes_channel {
+                match serde_json::from_slice::<PairQuotesUpdate>(msg.get_payload_bytes()) {
+                    Ok(update) if update.origin == instance_id => {}
+                    Ok(update) => {
+                        trace!(
+                            "Received pair quotes from another store: {:?}",
+                            update.quotes
+                        );
+                        *pair_quotes.write() = index_pair_quotes(
+                            update.quotes,
+                            UNIX_EPOCH + Duration::from_millis(update.updated_at),
+                        );
                     }
-                    ControlFlow::Continue
-                });
-            match sub_status {
-                Err(e) => warn!("Could not issue psubscribe to Redis: {}", e),
-                Ok(_) => debug!("Successfully subscribed to Redis pubsub"),
+                    Err(err) => error!("Invalid pair quotes update: {}", err),
+                }
+            } else if channel_name == spreads_channel {
+                match serde_json::from_slice::<SpreadsUpdate>(msg.get_payload_bytes()) {
+                    Ok(update) if update.origin == instance_id => {}
+                    Ok(update) => {
+                        trace!("Received spreads from another store: {:?}", update.spreads);
+                        let mut current = spreads.write();
+                        spreads_set.store(true, Ordering::SeqCst);
+                        *current = update.spreads;
+                    }
+                    Err(err) => error!("Invalid spreads update: {}", err),
+                }
+            } else if channel_name == secret_rotation_channel {
+                // The keys are switched by the polling task, which loads the rotation
+                let _ = secret_rotation_updated.unbounded_send(());
+            } else {
+                warn!(
+                    "Ignoring unexpected message from Redis subscription for channel: {}",
+                    channel_name
+                );
             }
+            ControlFlow::Continue
+        };
+        std::thread::spawn(move || {
+            let mut sub_connection = Some(sub_connection);
+            let mut delay = MIN_RESUBSCRIBE_DELAY;
+            loop {
+                let mut connection = match sub_connection.take() {
+                    Some(connection) => connection,
+                    None => {
+                        thread::sleep(delay);
+                        if store_connection.upgrade().is_none() {
+                            break;
+                        }
+                        match resolve_blocking(&runtime, target.clone())
+                            .and_then(subscription_connection)
+                        {
+                            Ok(connection) => {
+                                // Messages published while the subscription was
+                                // closed were missed, so everything they would have
+                                // changed is reloaded
+                                resync_account_cache.clear();
+                                let _ = resync_routes.unbounded_send(());
+                                let _ = resync_secret_rotation.unbounded_send(());
+                                connection
+                            }
+                            Err(()) => {
+                                delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                let subscribed_at = Instant::now();
+                match connection.psubscribe(patterns.clone(), &mut on_message) {
+                    Ok(()) => break,
+                    Err(err) => warn!("Redis subscription was closed, resubscribing: {}", err),
+                }
+                // Only back off further if the last subscription did not last
+                delay = if subscribed_at.elapsed() > MAX_RESUBSCRIBE_DELAY {
+                    MIN_RESUBSCRIBE_DELAY
+                } else {
+                    (delay * 2).min(MAX_RESUBSCRIBE_DELAY)
+                };
+            }
+            debug!("Stopped listening to Redis pub/sub because the store was dropped");
         });
 
         Ok(store)
     }
 }
 
+/// Opens the connection that the pub/sub thread subscribes on
+fn subscription_connection(redis_info: ConnectionInfo) -> Result<Connection, ()> {
+    let client = Client::open(redis_info)
+        .map_err(|err| error!("Error creating subscription Redis client: {:?}", err))?;
+    debug!("Connected subscription client to redis: {:?}", client);
+    client
+        .get_connection()
+        .map_err(|err| error!("Error connecting subscription client to Redis: {:?}", err))
+}
+
+/// Looks up the server to connect to from the pub/sub thread. Connecting to Redis
+/// Sentinel needs the runtime, which that thread is not part of
+fn resolve_blocking(runtime: &Handle, target: Arc<RedisTarget>) -> Result<ConnectionInfo, ()> {
+    let (sender, receiver) = std_mpsc::sync_channel(1);
+    runtime.spawn(async move {
+        let _ = sender.send(target.resolve().await);
+    });
+    receiver
+        .recv()
+        .map_err(|_| error!("Runtime stopped while looking up the Redis server"))?
+        .map_err(|err| {
+            error!(
+                "Error looking up the Redis server to subscribe to: {:?}",
+                err
+            )
+        })
+}
+
+/// Base asset code -> quote asset code -> quote and when it was set
+type PairQuotes = HashMap<String, HashMap<String, (PairQuote, SystemTime)>>;
+
 /// A Store that uses Redis as its underlying database.
 ///
 /// This store leverages atomic Redis transactions to do operations such as balance updates.
 ///
-/// Currently the RedisStore polls the database for the routing table and rate updates, but
-/// future versions of it will use PubSub to subscribe to updates.
+/// Changes to the routing table and exchange rates are propagated to every RedisStore
+/// connected to the same database using PubSub. The stores also poll the database
+/// for them in case a message is missed.
 #[derive(Clone)]
 pub struct RedisStore {
     /// The Store's ILP Address
     ilp_address: Arc<RwLock<Address>>,
     /// A connection which reconnects if dropped by accident
     connection: RedisReconnect,
+    /// Names of the keys and channels, which start with the store's key prefix
+    namespace: Arc<Namespace>,
     /// WebSocket sender which publishes incoming payment updates
     subscriptions: Arc<RwLock<HashMap<Uuid, UnboundedSender<PaymentNotification>>>>,
-    exchange_rates: Arc<RwLock<HashMap<String, f64>>>,
+    exchange_rates: Arc<RwLock<HashMap<String, ExchangeRate>>>,
+    /// When each of the exchange rates was last set
+    exchange_rates_updated_at: Arc<RwLock<HashMap<String, SystemTime>>>,
+    spreads: Arc<RwLock<Spreads>>,
+    /// Whether the spreads were set with `set_spreads` by this or another store,
+    /// after which the default spreads no longer apply
+    spreads_set: Arc<AtomicBool>,
+    pair_quotes: Arc<RwLock<PairQuotes>>,
+    /// Rates, pair quotes and spreads to be saved in Redis and published to the other stores
+    exchange_rate_updates: UnboundedSender<RatesUpdate>,
     /// The store keeps the routing table in memory so that it can be returned
     /// synchronously while the Router is processing packets.
     /// The outer `Arc<RwLock>` is used so that we can update the stored routing
@@ -307,23 +965,353 @@ pub struct RedisStore {
     /// The inner `Arc<HashMap>` is used so that the `routing_table` method can
     /// return a reference to the routing table without cloning the underlying data.
     routes: Arc<RwLock<Arc<HashMap<String, Uuid>>>>,
-    /// Encryption Key so that the no cleartext data are stored
-    encryption_key: Arc<Secret<EncryptionKey>>,
-    /// Decryption Key to provide cleartext data to users
-    decryption_key: Arc<Secret<DecryptionKey>>,
+    /// Decrypted accounts used while processing packets
+    account_cache: Arc<AccountCache>,
+    /// Keys used to encrypt the account tokens so that no cleartext data are stored
+    /// and to decrypt them when they are provided to users. They are replaced when
+    /// the secret is rotated, which waits until the tokens being encrypted or
+    /// decrypted with the current keys were written or returned
+    keys: Arc<AsyncRwLock<StoreKeys>>,
+    /// The last secret rotation, and its stage, which the keys were switched to
+    applied_secret_rotation: Arc<Mutex<Option<(Uuid, SecretRotationStage)>>>,
+    secret_rotation_in_progress: Arc<AtomicBool>,
+    /// Identifies the store in the messages it publishes
+    instance_id: Uuid,
+    /// Verifies the tokens presented by peers against the hashed incoming tokens
+    token_verifier: Arc<TokenVerifier>,
 }
 
 impl RedisStore {
+    /// Rotates the secret of every store connected to the same database in stages,
+    /// each of which waits until all of them switched their keys:
+    /// 1. the stores can decrypt the tokens encrypted with the key of the new secret
+    /// 1. they encrypt the tokens with it
+    /// 1. every account's tokens are re-encrypted with it and the keys of the
+    ///    previous secret are dropped
+    ///
+    /// The new secret is published encrypted with the key of the previous one
+    async fn rotate_secret_of_every_store(&self, mut new_secret: [u8; 32]) -> Result<(), ()> {
+        let id = Uuid::new_v4();
+        let mut rotation = SecretRotation {
+            id,
+            origin: self.instance_id,
+            encrypted_secret: hex::encode(self.keys.read().await.encrypt_token(&new_secret[..])),
+            check: hex::encode(StoreKeys::new(&new_secret[..]).encrypt_token(id.as_bytes())),
+            stage: SecretRotationStage::Prepare,
+        };
+        new_secret.zeroize();
+
+        let serialized = serde_json::to_string(&rotation)
+            .map_err(|err| error!("Error serializing secret rotation: {:?}", err))?;
+        let started: bool = START_SECRET_ROTATION
+            .key(self.namespace.key(SECRET_ROTATION_KEY))
+            .key(self.namespace.key(SECRET_ROTATION_CONFIRMATIONS_KEY))
+            .arg(serialized)
+            .arg(self.instance_id.to_string())
+            .arg(self.namespace.instance_key_prefix())
+            .arg(self.namespace.channel(SECRET_ROTATION_CHANNEL))
+            .invoke_async(&mut self.connection.clone())
+            .map_err(|err| error!("Error starting secret rotation: {:?}", err))
+            .await?;
+        if !started {
+            error!("Cannot rotate the secret while another store is rotating it");
+            return Err(());
+        }
+        self.apply_secret_rotation(&rotation).await?;
+        if self.wait_for_secret_rotation(&rotation).await.is_err() {
+            // No store encrypts with the new key yet, so the rotation can be abandoned
+            let _: Result<(), _> = self
+                .connection
+                .clone()
+                .del(self.namespace.key(SECRET_ROTATION_KEY))
+                .await;
+            return Err(());
+        }
+
+        rotation.stage = SecretRotationStage::Commit;
+        save_secret_rotation(&mut self.connection.clone(), &self.namespace, &rotation).await?;
+        self.apply_secret_rotation(&rotation).await?;
+        self.wait_for_secret_rotation(&rotation).await?;
+
+        self.reencrypt_tokens().await?;
+        // The other stores drop the keys of the previous secret when they get the
+        // last stage. It is not waited for since no token needs them anymore
+        rotation.stage = SecretRotationStage::Done;
+        save_secret_rotation(&mut self.connection.clone(), &self.namespace, &rotation).await?;
+        self.apply_secret_rotation(&rotation).await
+    }
+
+    /// Switches this store's keys to the stage of the secret rotation
+    async fn apply_secret_rotation(&self, rotation: &SecretRotation) -> Result<(), ()> {
+        apply_secret_rotation(
+            self.connection.clone(),
+            &self.namespace,
+            self.instance_id,
+            &self.keys,
+            &self.applied_secret_rotation,
+            rotation,
+        )
+        .await
+    }
+
+    /// Waits until every connected store confirmed that it switched its keys
+    /// to the stage of the secret rotation
+    async fn wait_for_secret_rotation(&self, rotation: &SecretRotation) -> Result<(), ()> {
+        let mut connection = self.connection.clone();
+        let started_at = Instant::now();
+        loop {
+            let mut pipe = redis_crate::pipe();
+            pipe.smembers(self.namespace.key(INSTANCES_KEY))
+                .hgetall(self.namespace.key(SECRET_ROTATION_CONFIRMATIONS_KEY));
+            let (instances, confirmations): (Vec<String>, HashMap<String, String>) = pipe
+                .query_async(&mut connection)
+                .map_err(|err| error!("Error loading secret rotation confirmations: {:?}", err))
+                .await?;
+            let unconfirmed: Vec<Uuid> = instances
+                .iter()
+                .filter_map(|instance| Uuid::from_str(instance).ok())
+                .filter(|instance| {
+                    confirmations
+                        .get(&instance.to_string())
+                        .and_then(|confirmation| {
+                            serde_json::from_str::<SecretRotationConfirmation>(confirmation).ok()
+                        })
+                        .filter(|confirmation| {
+                            confirmation.id == rotation.id && confirmation.stage >= rotation.stage
+                        })
+                        .is_none()
+                })
+                .collect();
+
+            // The stores which are no longer connected are not waited for
+            let mut pending = Vec::new();
+            for instance in unconfirmed {
+                let connected: bool = connection
+                    .exists(self.namespace.instance_key(instance))
+                    .map_err(|err| error!("Error checking whether store is connected: {:?}", err))
+                    .await?;
+                if connected {
+                    pending.push(instance);
+                } else {
+                    let _: () = connection
+                        .srem(self.namespace.key(INSTANCES_KEY), instance.to_string())
+                        .map_err(|err| error!("Error removing disconnected store: {:?}", err))
+                        .await?;
+                }
+            }
+            if pending.is_empty() {
+                return Ok(());
+            }
+            if started_at.elapsed() > SECRET_ROTATION_TIMEOUT {
+                error!(
+                    "Stores {:?} did not switch their keys to the {:?} stage of secret rotation {} in time",
+                    pending, rotation.stage, rotation.id
+                );
+                return Err(());
+            }
+            tokio::time::delay_for(SECRET_ROTATION_CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Re-encrypts every account's tokens with the current key once every store
+    /// encrypts with it. Accounts can be used in the meantime because the keys
+    /// of the previous secret are kept until all tokens were re-encrypted
+    async fn reencrypt_tokens(&self) -> Result<(), ()> {
+        let keys = self.keys.read().await.clone();
+        let mut connection = self.connection.clone();
+        let account_ids = self.get_all_accounts_ids().await?;
+        for ids in account_ids.chunks(ACCOUNTS_PAGE_BATCH_SIZE) {
+            let accounts: Vec<AccountWithEncryptedTokens> =
+                load_accounts_script(&self.namespace, ids)
+                    .invoke_async(&mut connection)
+                    .map_err(|err| error!("Error loading accounts to re-encrypt: {:?}", err))
+                    .await?;
+
+            for AccountWithEncryptedTokens { account } in accounts {
+                let mut script = REENCRYPT_TOKENS.prepare_invoke();
+                script.key(self.namespace.accounts_key(account.id));
+                let tokens = vec![
+                    (
+                        "ilp_over_btp_outgoing_token",
+                        &account.ilp_over_btp_outgoing_token,
+                    ),
+                    (
+                        "ilp_over_http_outgoing_token",
+                        &account.ilp_over_http_outgoing_token,
+                    ),
+                    (
+                        "ilp_over_btp_incoming_token",
+                        &account.ilp_over_btp_incoming_token,
+                    ),
+                    (
+                        "ilp_over_http_incoming_token",
+                        &account.ilp_over_http_incoming_token,
+                    ),
+                ];
+                for (field, encrypted) in tokens {
+                    let encrypted = match encrypted {
+                        // Hashed incoming tokens do not depend on the secret
+                        Some(encrypted) if !is_token_hash(encrypted.expose_secret()) => {
+                            encrypted.expose_secret()
+                        }
+                        _ => continue,
+                    };
+                    match keys.decrypt_token(encrypted) {
+                        Ok(token) => {
+                            script
+                                .arg(field)
+                                .arg(encrypted.as_ref())
+                                .arg(keys.encrypt_token(&token.expose_secret()).as_ref());
+                        }
+                        // It could not be used before the rotation either
+                        Err(_) => warn!(
+                            "Not re-encrypting {} for account {} because it could not be decrypted",
+                            field, account.id
+                        ),
+                    }
+                }
+                let _: u32 = script
+                    .invoke_async(&mut connection)
+                    .map_err(|err| {
+                        error!(
+                            "Error re-encrypting tokens for account {}: {:?}",
+                            account.id, err
+                        )
+                    })
+                    .await?;
+            }
+        }
+
+        debug!("Re-encrypted the tokens of {} accounts", account_ids.len());
+        Ok(())
+    }
+
+    /// Checks the token provided by a peer against the account's incoming token
+    async fn token_matches(
+        &self,
+        username: &Username,
+        incoming_token: &Option<SecretBytesMut>,
+        token: &str,
+    ) -> bool {
+        match incoming_token {
+            Some(t) => {
+                self.token_verifier
+                    .verify(username.as_ref(), &t.expose_secret(), token.as_bytes())
+                    .await
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces an incoming token which was stored encrypted (before incoming tokens
+    /// were hashed) with its hash, once the peer has shown that it knows the token.
+    /// Errors are only logged because the token is migrated again on the next attempt
+    async fn migrate_incoming_token(
+        &self,
+        account: &Account,
+        field: &str,
+        incoming_token: &Option<SecretBytesMut>,
+    ) {
+        let token = match incoming_token {
+            Some(token) if !is_token_hash(token.expose_secret()) => token.expose_secret(),
+            _ => return,
+        };
+        let hashed = hash_token(account.username.as_ref(), token);
+        let migrated: Result<u32, ()> = MIGRATE_INCOMING_TOKEN
+            .key(self.namespace.accounts_key(account.id))
+            .arg(field)
+            .arg(TOKEN_HASH_PREFIX)
+            .arg(hashed.as_ref())
+            .invoke_async(&mut self.connection.clone())
+            .map_err(|err| {
+                error!(
+                    "Error replacing {} of account {} with its hash: {:?}",
+                    field, account.id, err
+                )
+            })
+            .await;
+        if let Ok(1) = migrated {
+            debug!("Replaced {} of account {} with its hash", field, account.id);
+            self.invalidate_cached_account(account.id).await;
+        }
+    }
+
+    /// Removes the account from this store's cache and tells the other
+    /// stores connected to the same Redis to do the same
+    async fn invalidate_cached_account(&self, id: Uuid) {
+        self.account_cache.invalidate(id);
+        self.publish_account_update(id.to_string()).await;
+    }
+
+    /// Clears the cache of every store, for changes that may affect all accounts
+    async fn invalidate_cached_accounts(&self) {
+        self.account_cache.clear();
+        self.publish_account_update(ALL_ACCOUNTS_UPDATED.to_string())
+            .await;
+    }
+
+    async fn publish_account_update(&self, message: String) {
+        let mut connection = self.connection.clone();
+        // The change was already saved, so failing to publish it only means
+        // that other stores keep the cached account until it expires
+        let result: Result<(), RedisError> = redis_crate::cmd("PUBLISH")
+            .arg(self.namespace.channel(ACCOUNT_UPDATES_CHANNEL))
+            .arg(message)
+            .query_async(&mut connection)
+            .await;
+        if let Err(err) = result {
+            error!("Error publishing account update: {:?}", err);
+        }
+    }
+
     /// Gets all the account ids from Redis
     async fn get_all_accounts_ids(&self) -> Result<Vec<Uuid>, ()> {
         let mut connection = self.connection.clone();
         let account_ids: Vec<RedisAccountId> = connection
-            .smembers("accounts")
+            .smembers(self.namespace.key(ACCOUNTS_KEY))
+            .map_err(|err| error!("Error getting account IDs: {:?}", err))
+            .await?;
+        Ok(account_ids.iter().map(|rid| rid.0).collect())
+    }
+
+    /// Gets at most `count` account ids, in order, starting after the given id
+    async fn get_account_ids_after(
+        &self,
+        after: Option<Uuid>,
+        count: usize,
+    ) -> Result<Vec<Uuid>, ()> {
+        let mut connection = self.connection.clone();
+        let min = match after {
+            Some(id) => format!("({}", id.to_hyphenated()),
+            None => "-".to_string(),
+        };
+        let account_ids: Vec<RedisAccountId> = connection
+            .zrangebylex_limit(
+                self.namespace.key(ACCOUNT_IDS_KEY),
+                min,
+                "+",
+                0,
+                count as isize,
+            )
             .map_err(|err| error!("Error getting account IDs: {:?}", err))
             .await?;
         Ok(account_ids.iter().map(|rid| rid.0).collect())
     }
 
+    /// Loads the accounts which still exist, skipping the ids of the deleted ones
+    async fn load_existing_accounts(&self, account_ids: &[Uuid]) -> Result<Vec<Account>, ()> {
+        let keys = self.keys.read().await;
+        let accounts: Vec<AccountWithEncryptedTokens> =
+            load_accounts_script(&self.namespace, account_ids)
+                .invoke_async(&mut self.connection.clone())
+                .map_err(|err| error!("Error loading accounts: {:?}", err))
+                .await?;
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.decrypt_tokens(&keys))
+            .collect())
+    }
+
     /// Inserts the account corresponding to the provided `AccountWithEncryptedtokens`
     /// in Redis. Returns the provided account (tokens remain encrypted)
     async fn redis_insert_account(
@@ -336,10 +1324,13 @@ impl RedisStore {
         let routing_table = self.routes.clone();
         // Check that there isn't already an account with values that MUST be unique
         let mut pipe = redis_crate::pipe();
-        pipe.exists(accounts_key(account.id));
-        pipe.hexists("usernames", account.username().as_ref());
+        pipe.exists(self.namespace.accounts_key(account.id));
+        pipe.hexists(
+            self.namespace.key(USERNAMES_KEY),
+            account.username().as_ref(),
+        );
         if account.routing_relation == RoutingRelation::Parent {
-            pipe.exists(PARENT_ILP_KEY);
+            pipe.exists(self.namespace.key(PARENT_ILP_KEY));
         }
 
         let results: Vec<bool> = pipe
@@ -358,16 +1349,24 @@ impl RedisStore {
             );
             return Err(());
         }
+        self.check_prefixes_are_free(&account).await?;
 
         let mut pipe = redis_crate::pipe();
         pipe.atomic();
 
         // Add the account key to the list of accounts
-        pipe.sadd("accounts", RedisAccountId(account.id)).ignore();
+        pipe.sadd(self.namespace.key(ACCOUNTS_KEY), RedisAccountId(account.id))
+            .ignore();
+        pipe.zadd(
+            self.namespace.key(ACCOUNT_IDS_KEY),
+            RedisAccountId(account.id),
+            0,
+        )
+        .ignore();
 
         // Save map for Username -> Account ID
         pipe.hset(
-            "usernames",
+            self.namespace.key(USERNAMES_KEY),
             account.username().as_ref(),
             RedisAccountId(account.id),
         )
@@ -375,39 +1374,50 @@ impl RedisStore {
 
         // Set account details
         pipe.cmd("HMSET")
-            .arg(accounts_key(account.id))
+            .arg(self.namespace.accounts_key(account.id))
             .arg(encrypted)
             .ignore();
 
         // Set balance-related details
         pipe.hset_multiple(
-            accounts_key(account.id),
+            self.namespace.accounts_key(account.id),
             &[("balance", 0), ("prepaid_amount", 0)],
         )
         .ignore();
 
         if account.should_send_routes() {
-            pipe.sadd("send_routes_to", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(SEND_ROUTES_TO_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.should_receive_routes() {
-            pipe.sadd("receive_routes_from", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(RECEIVE_ROUTES_FROM_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.ilp_over_btp_url.is_some() {
-            pipe.sadd("btp_outgoing", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(BTP_OUTGOING_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
-        // Add route to routing table
-        pipe.hset(
-            ROUTES_KEY,
-            account.ilp_address.to_bytes().to_vec(),
-            RedisAccountId(account.id),
-        )
-        .ignore();
+        // Add routes to routing table
+        for prefix in account.local_prefixes() {
+            pipe.hset(
+                self.namespace.key(ROUTES_KEY),
+                prefix.to_bytes().to_vec(),
+                RedisAccountId(account.id),
+            )
+            .ignore();
+        }
 
         // The parent account settings are done via the API. We just
         // had to check for the existence of a parent
@@ -415,7 +1425,7 @@ impl RedisStore {
             .map_err(|err| error!("Error inserting account into DB: {:?}", err))
             .await?;
 
-        update_routes(connection, routing_table).await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         debug!(
             "Inserted account {} (ILP address: {})",
             account.id, account.ilp_address
@@ -427,7 +1437,7 @@ impl RedisStore {
     /// in Redis. Returns the provided account (tokens remain encrypted)
     async fn redis_update_account(
         &self,
-        encrypted: AccountWithEncryptedTokens,
+        mut encrypted: AccountWithEncryptedTokens,
     ) -> Result<AccountWithEncryptedTokens, ()> {
         let account = encrypted.account.clone();
         let mut connection = self.connection.clone();
@@ -440,7 +1450,7 @@ impl RedisStore {
         // AccountDetails.RoutingRelation == Parent and parent is
         // already set
         let exists: bool = connection
-            .exists(accounts_key(account.id))
+            .exists(self.namespace.accounts_key(account.id))
             .map_err(|err| error!("Error checking whether ID exists: {:?}", err))
             .await?;
 
@@ -451,45 +1461,96 @@ impl RedisStore {
             );
             return Err(());
         }
+        self.check_prefixes_are_free(&account).await?;
+        // The routes to the prefixes which were removed from the account are deleted
+        let previous = self.clone().redis_get_account(account.id).await?.account;
+        // The status is only changed by `set_account_status`
+        encrypted.account.status = previous.status;
+        let account = encrypted.account.clone();
         let mut pipe = redis_crate::pipe();
         pipe.atomic();
 
         // Add the account key to the list of accounts
-        pipe.sadd("accounts", RedisAccountId(account.id)).ignore();
+        pipe.sadd(self.namespace.key(ACCOUNTS_KEY), RedisAccountId(account.id))
+            .ignore();
+        pipe.zadd(
+            self.namespace.key(ACCOUNT_IDS_KEY),
+            RedisAccountId(account.id),
+            0,
+        )
+        .ignore();
+
+        // HMSET does not remove the fields which are no longer set, such as
+        // the additional prefixes, the burst, daily and monthly limits, the fees
+        // or the destination prefixes
+        pipe.hdel(
+            self.namespace.accounts_key(account.id),
+            &[
+                "additional_prefixes",
+                "packets_burst_limit",
+                "amount_burst_limit",
+                "amount_per_day_limit",
+                "amount_per_month_limit",
+                "fixed_fee",
+                "fee_basis_points",
+                "allowed_destination_prefixes",
+                "denied_destination_prefixes",
+            ][..],
+        )
+        .ignore();
 
         // Set account details
         pipe.cmd("HMSET")
-            .arg(accounts_key(account.id))
+            .arg(self.namespace.accounts_key(account.id))
             .arg(encrypted.clone())
             .ignore();
 
         if account.should_send_routes() {
-            pipe.sadd("send_routes_to", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(SEND_ROUTES_TO_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.should_receive_routes() {
-            pipe.sadd("receive_routes_from", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(RECEIVE_ROUTES_FROM_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.ilp_over_btp_url.is_some() {
-            pipe.sadd("btp_outgoing", RedisAccountId(account.id))
-                .ignore();
+            pipe.sadd(
+                self.namespace.key(BTP_OUTGOING_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
-        // Add route to routing table
-        pipe.hset(
-            ROUTES_KEY,
-            account.ilp_address.to_bytes().to_vec(),
-            RedisAccountId(account.id),
-        )
-        .ignore();
+        // Add routes to routing table
+        for prefix in account.local_prefixes() {
+            pipe.hset(
+                self.namespace.key(ROUTES_KEY),
+                prefix.to_bytes().to_vec(),
+                RedisAccountId(account.id),
+            )
+            .ignore();
+        }
 
         pipe.query_async(&mut connection)
             .map_err(|err| error!("Error inserting account into DB: {:?}", err))
             .await?;
-        update_routes(connection, routing_table).await?;
+        self.delete_routes(
+            account.id,
+            previous
+                .additional_prefixes
+                .iter()
+                .filter(|prefix| !account.additional_prefixes.contains(*prefix)),
+        )
+        .await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         debug!(
             "Inserted account {} (id: {}, ILP address: {})",
             account.username, account.id, account.ilp_address
@@ -511,16 +1572,24 @@ impl RedisStore {
         pipe.atomic();
 
         if let Some(ref endpoint) = settings.ilp_over_btp_url {
-            pipe.hset(accounts_key(id), "ilp_over_btp_url", endpoint);
+            pipe.hset(
+                self.namespace.accounts_key(id),
+                "ilp_over_btp_url",
+                endpoint,
+            );
         }
 
         if let Some(ref endpoint) = settings.ilp_over_http_url {
-            pipe.hset(accounts_key(id), "ilp_over_http_url", endpoint);
+            pipe.hset(
+                self.namespace.accounts_key(id),
+                "ilp_over_http_url",
+                endpoint,
+            );
         }
 
         if let Some(ref token) = settings.ilp_over_btp_outgoing_token {
             pipe.hset(
-                accounts_key(id),
+                self.namespace.accounts_key(id),
                 "ilp_over_btp_outgoing_token",
                 token.as_ref(),
             );
@@ -528,7 +1597,7 @@ impl RedisStore {
 
         if let Some(ref token) = settings.ilp_over_http_outgoing_token {
             pipe.hset(
-                accounts_key(id),
+                self.namespace.accounts_key(id),
                 "ilp_over_http_outgoing_token",
                 token.as_ref(),
             );
@@ -536,7 +1605,7 @@ impl RedisStore {
 
         if let Some(ref token) = settings.ilp_over_btp_incoming_token {
             pipe.hset(
-                accounts_key(id),
+                self.namespace.accounts_key(id),
                 "ilp_over_btp_incoming_token",
                 token.as_ref(),
             );
@@ -544,18 +1613,22 @@ impl RedisStore {
 
         if let Some(ref token) = settings.ilp_over_http_incoming_token {
             pipe.hset(
-                accounts_key(id),
+                self.namespace.accounts_key(id),
                 "ilp_over_http_incoming_token",
                 token.as_ref(),
             );
         }
 
         if let Some(settle_threshold) = settings.settle_threshold {
-            pipe.hset(accounts_key(id), "settle_threshold", settle_threshold);
+            pipe.hset(
+                self.namespace.accounts_key(id),
+                "settle_threshold",
+                settle_threshold,
+            );
         }
 
         if let Some(settle_to) = settings.settle_to {
-            pipe.hset(accounts_key(id), "settle_to", settle_to);
+            pipe.hset(self.namespace.accounts_key(id), "settle_to", settle_to);
         }
 
         pipe.query_async(&mut connection.clone())
@@ -566,14 +1639,78 @@ impl RedisStore {
         self_clone.redis_get_account(id).await
     }
 
+    /// Gets the account's API key along with the hash of its secret
+    async fn get_redis_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<RedisApiKey, ()> {
+        let json: Option<String> = self
+            .connection
+            .clone()
+            .hget(self.namespace.api_keys_key(account_id), key_id.to_string())
+            .map_err(|err| error!("Error getting API key: {:?}", err))
+            .await?;
+        let json = json.ok_or_else(|| warn!("Account {} has no API key {}", account_id, key_id))?;
+        serde_json::from_str(&json).map_err(|err| error!("Error parsing API key: {:?}", err))
+    }
+
     /// Gets the account (tokens remain encrypted) corresponding to the provided `id` from Redis.
     async fn redis_get_account(&mut self, id: Uuid) -> Result<AccountWithEncryptedTokens, ()> {
-        let mut accounts: Vec<AccountWithEncryptedTokens> = LOAD_ACCOUNTS
-            .arg(id.to_string())
+        let mut accounts: Vec<AccountWithEncryptedTokens> =
+            load_accounts_script(&self.namespace, &[id])
+                .invoke_async(&mut self.connection.clone())
+                .map_err(|err| error!("Error loading accounts: {:?}", err))
+                .await?;
+        accounts.pop().ok_or(())
+    }
+
+    /// Checks that none of the account's additional prefixes is routed to another account
+    async fn check_prefixes_are_free(&self, account: &Account) -> Result<(), ()> {
+        if account.additional_prefixes.is_empty() {
+            return Ok(());
+        }
+        let routed: Vec<Option<RedisAccountId>> = cmd("HMGET")
+            .arg(self.namespace.key(ROUTES_KEY))
+            .arg(
+                account
+                    .additional_prefixes
+                    .iter()
+                    .map(|prefix| prefix.to_bytes().to_vec())
+                    .collect::<Vec<_>>(),
+            )
+            .query_async(&mut self.connection.clone())
+            .map_err(|err| error!("Error checking the routes to the prefixes: {:?}", err))
+            .await?;
+        for (prefix, routed) in account.additional_prefixes.iter().zip(routed) {
+            if let Some(RedisAccountId(id)) = routed {
+                if id != account.id {
+                    warn!(
+                        "The prefix {} is already routed to another account. Cannot save account: {}",
+                        prefix, account.id
+                    );
+                    return Err(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the routes to the prefixes, unless they were routed to another account since
+    async fn delete_routes<'a>(
+        &self,
+        id: Uuid,
+        prefixes: impl Iterator<Item = &'a Address>,
+    ) -> Result<(), ()> {
+        let mut script = DELETE_ROUTES.prepare_invoke();
+        script
+            .key(self.namespace.key(ROUTES_KEY))
+            .arg(RedisAccountId(id));
+        for prefix in prefixes {
+            script.arg(prefix.to_bytes().to_vec());
+        }
+        let deleted: u32 = script
             .invoke_async(&mut self.connection.clone())
-            .map_err(|err| error!("Error loading accounts: {:?}", err))
+            .map_err(|err| error!("Error deleting the routes of account {}: {:?}", id, err))
             .await?;
-        accounts.pop().ok_or(())
+        trace!("Deleted {} routes of account {}", deleted, id);
+        Ok(())
     }
 
     /// Deletes the account corresponding to the provided `id` from Redis.
@@ -586,36 +1723,56 @@ impl RedisStore {
         let mut pipe = redis_crate::pipe();
         pipe.atomic();
 
-        pipe.srem("accounts", RedisAccountId(account.id)).ignore();
+        pipe.srem(self.namespace.key(ACCOUNTS_KEY), RedisAccountId(account.id))
+            .ignore();
+        pipe.zrem(
+            self.namespace.key(ACCOUNT_IDS_KEY),
+            RedisAccountId(account.id),
+        )
+        .ignore();
 
-        pipe.del(accounts_key(account.id)).ignore();
-        pipe.hdel("usernames", account.username().as_ref()).ignore();
+        pipe.del(self.namespace.accounts_key(account.id)).ignore();
+        pipe.hdel(
+            self.namespace.key(USERNAMES_KEY),
+            account.username().as_ref(),
+        )
+        .ignore();
 
         if account.should_send_routes() {
-            pipe.srem("send_routes_to", RedisAccountId(account.id))
-                .ignore();
+            pipe.srem(
+                self.namespace.key(SEND_ROUTES_TO_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.should_receive_routes() {
-            pipe.srem("receive_routes_from", RedisAccountId(account.id))
-                .ignore();
+            pipe.srem(
+                self.namespace.key(RECEIVE_ROUTES_FROM_KEY),
+                RedisAccountId(account.id),
+            )
+            .ignore();
         }
 
         if account.ilp_over_btp_url.is_some() {
-            pipe.srem("btp_outgoing", RedisAccountId(account.id))
-                .ignore();
-        }
-
-        pipe.hdel(ROUTES_KEY, account.ilp_address.to_bytes().to_vec())
+            pipe.srem(
+                self.namespace.key(BTP_OUTGOING_KEY),
+                RedisAccountId(account.id),
+            )
             .ignore();
+        }
 
-        pipe.del(uncredited_amount_key(id));
+        pipe.del(self.namespace.uncredited_amount_key(id));
+        pipe.del(self.namespace.balance_journal_key(id)).ignore();
+        pipe.del(self.namespace.api_keys_key(id)).ignore();
 
         pipe.query_async(&mut connection)
             .map_err(|err| error!("Error deleting account from DB: {:?}", err))
             .await?;
+        self.delete_routes(account.id, account.local_prefixes())
+            .await?;
 
-        update_routes(connection, routing_table).await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         debug!("Deleted account {}", account.id);
         Ok(encrypted)
     }
@@ -625,14 +1782,14 @@ impl RedisStore {
 impl AccountStore for RedisStore {
     type Account = Account;
 
-    // TODO cache results to avoid hitting Redis for each packet
     async fn get_accounts(&self, account_ids: Vec<Uuid>) -> Result<Vec<Account>, ()> {
-        let decryption_key = self.decryption_key.clone();
-        let num_accounts = account_ids.len();
-        let mut script = LOAD_ACCOUNTS.prepare_invoke();
-        for id in account_ids.iter() {
-            script.arg(id.to_string());
+        if let Some(accounts) = self.account_cache.get(&account_ids) {
+            return Ok(accounts);
         }
+        let generation = self.account_cache.generation();
+        let keys = self.keys.read().await;
+        let num_accounts = account_ids.len();
+        let script = load_accounts_script(&self.namespace, &account_ids);
 
         // Need to clone the connection here to avoid lifetime errors
         let connection = self.connection.clone();
@@ -644,10 +1801,11 @@ impl AccountStore for RedisStore {
         // Decrypt the accounts. TODO: This functionality should be
         // decoupled from redis so that it gets reused by the other backends
         if accounts.len() == num_accounts {
-            let accounts = accounts
+            let accounts: Vec<Account> = accounts
                 .into_iter()
-                .map(|account| account.decrypt_tokens(&decryption_key.expose_secret().0))
+                .map(|account| account.decrypt_tokens(&keys))
                 .collect();
+            self.account_cache.insert(generation, accounts.clone());
             Ok(accounts)
         } else {
             Err(())
@@ -658,7 +1816,7 @@ impl AccountStore for RedisStore {
         let username = username.clone();
         let mut connection = self.connection.clone();
         let id: Option<RedisAccountId> = connection
-            .hget("usernames", username.as_ref())
+            .hget(self.namespace.key(USERNAMES_KEY), username.as_ref())
             .map_err(move |err| error!("Error getting account id: {:?}", err))
             .await?;
         match id {
@@ -688,6 +1846,7 @@ impl StreamNotificationsStore for RedisStore {
         let message = serde_json::to_string(&payment).unwrap();
         let mut connection = self.connection.clone();
         let self_clone = self.clone();
+        let stream_notifications_prefix = self.namespace.channel(STREAM_NOTIFICATIONS_PREFIX);
         tokio::spawn(async move {
             let account_id = self_clone
                 .get_account_id_from_username(&username)
@@ -704,7 +1863,7 @@ impl StreamNotificationsStore for RedisStore {
                 message, account_id
             );
             // https://github.com/rust-lang/rust/issues/64960#issuecomment-544219926
-            let published_args = format!("{}{}", STREAM_NOTIFICATIONS_PREFIX, account_id.clone());
+            let published_args = format!("{}{}", stream_notifications_prefix, account_id.clone());
             redis_crate::cmd("PUBLISH")
                 .arg(published_args)
                 .arg(message)
@@ -724,7 +1883,10 @@ impl BalanceStore for RedisStore {
     async fn get_balance(&self, account: Account) -> Result<i64, ()> {
         let mut connection = self.connection.clone();
         let values: Vec<i64> = connection
-            .hget(accounts_key(account.id), &["balance", "prepaid_amount"])
+            .hget(
+                self.namespace.accounts_key(account.id),
+                &["balance", "prepaid_amount"],
+            )
             .map_err(move |err| {
                 error!(
                     "Error getting balance for account: {} {:?}",
@@ -738,10 +1900,71 @@ impl BalanceStore for RedisStore {
         Ok(balance + prepaid_amount)
     }
 
+    async fn set_balance(&self, account: Account, balance: i64) -> Result<(), ()> {
+        let mut connection = self.connection.clone();
+        connection
+            .hset_multiple(
+                self.namespace.accounts_key(account.id),
+                &[("balance", balance), ("prepaid_amount", 0)],
+            )
+            .map_err(move |err| {
+                error!(
+                    "Error setting balance for account: {} {:?}",
+                    account.id, err
+                )
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn get_balance_history(
+        &self,
+        account: Account,
+        query: BalanceHistoryQuery,
+    ) -> Result<BalanceHistoryPage, ()> {
+        // The cursor is the id of the last entry of the previous page, and
+        // the page starts at the id after it unless that is before `from`
+        let mut start = (query.from, 0);
+        if let Some(ref cursor) = query.cursor {
+            let id = parse_stream_id(cursor)
+                .ok_or_else(|| error!("Invalid balance history cursor: {}", cursor))?;
+            start = start.max(next_stream_id(id));
+        }
+        let mut connection = self.connection.clone();
+        // Take one more entry than needed to know whether there is a next page
+        let mut entries: Vec<(String, Vec<String>)> = cmd("XRANGE")
+            .arg(self.namespace.balance_journal_key(account.id))
+            .arg(format!("{}-{}", start.0, start.1))
+            .arg(query.to)
+            .arg("COUNT")
+            .arg(query.limit.saturating_add(1))
+            .query_async(&mut connection)
+            .map_err(move |err| {
+                error!(
+                    "Error getting balance history for account: {} {:?}",
+                    account.id, err
+                )
+            })
+            .await?;
+        let mut next_cursor = None;
+        if entries.len() > query.limit {
+            entries.truncate(query.limit);
+            next_cursor = entries.last().map(|(id, _)| id.clone());
+        }
+        Ok(BalanceHistoryPage {
+            entries: entries
+                .into_iter()
+                .map(parse_journal_entry)
+                .collect::<Result<_, ()>>()?,
+            next_cursor,
+        })
+    }
+
     async fn update_balances_for_prepare(
         &self,
         from_account: Account, // TODO: Make this take only the id
         incoming_amount: u64,
+        context: PacketContext,
     ) -> Result<(), ()> {
         // Don't do anything if the amount was 0
         if incoming_amount == 0 {
@@ -750,8 +1973,11 @@ impl BalanceStore for RedisStore {
 
         let from_account_id = from_account.id;
         let balance: i64 = PROCESS_PREPARE
-            .arg(RedisAccountId(from_account_id))
+            .key(self.namespace.accounts_key(from_account_id))
+            .key(self.namespace.balance_journal_key(from_account_id))
             .arg(incoming_amount)
+            .arg(hex::encode(&context.execution_condition))
+            .arg(RedisAccountId(context.counterparty))
             .invoke_async(&mut self.connection.clone())
             .map_err(move |err| {
                 warn!(
@@ -772,14 +1998,18 @@ impl BalanceStore for RedisStore {
         &self,
         to_account: Account, // TODO: Make this take only the id
         outgoing_amount: u64,
+        context: PacketContext,
     ) -> Result<(i64, u64), ()> {
         if outgoing_amount == 0 {
             return Ok((0, 0));
         }
         let to_account_id = to_account.id;
         let (balance, amount_to_settle): (i64, u64) = PROCESS_FULFILL
-            .arg(RedisAccountId(to_account_id))
+            .key(self.namespace.accounts_key(to_account_id))
+            .key(self.namespace.balance_journal_key(to_account_id))
             .arg(outgoing_amount)
+            .arg(hex::encode(&context.execution_condition))
+            .arg(RedisAccountId(context.counterparty))
             .invoke_async(&mut self.connection.clone())
             .map_err(move |err| {
                 error!(
@@ -803,6 +2033,7 @@ impl BalanceStore for RedisStore {
         &self,
         from_account: Account, // TODO: Make this take only the id
         incoming_amount: u64,
+        context: PacketContext,
     ) -> Result<(), ()> {
         if incoming_amount == 0 {
             return Ok(());
@@ -810,8 +2041,11 @@ impl BalanceStore for RedisStore {
 
         let from_account_id = from_account.id;
         let balance: i64 = PROCESS_REJECT
-            .arg(RedisAccountId(from_account_id))
+            .key(self.namespace.accounts_key(from_account_id))
+            .key(self.namespace.balance_journal_key(from_account_id))
             .arg(incoming_amount)
+            .arg(hex::encode(&context.execution_condition))
+            .arg(RedisAccountId(context.counterparty))
             .invoke_async(&mut self.connection.clone())
             .map_err(move |err| {
                 warn!(
@@ -830,8 +2064,8 @@ impl BalanceStore for RedisStore {
 }
 
 impl ExchangeRateStore for RedisStore {
-    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<f64>, ()> {
-        let rates: Vec<f64> = asset_codes
+    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()> {
+        let rates: Vec<ExchangeRate> = asset_codes
             .iter()
             .filter_map(|code| (*self.exchange_rates.read()).get(*code).cloned())
             .collect();
@@ -842,15 +2076,174 @@ impl ExchangeRateStore for RedisStore {
         }
     }
 
-    fn get_all_exchange_rates(&self) -> Result<HashMap<String, f64>, ()> {
+    fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
         Ok((*self.exchange_rates.read()).clone())
     }
 
-    fn set_exchange_rates(&self, rates: HashMap<String, f64>) -> Result<(), ()> {
-        // TODO publish rate updates through a pubsub mechanism to support horizontally scaling nodes
-        (*self.exchange_rates.write()) = rates;
+    fn set_exchange_rates(
+        &self,
+        rates: HashMap<String, ExchangeRate>,
+        source: &str,
+    ) -> Result<(), ()> {
+        let now = SystemTime::now();
+        (*self.exchange_rates_updated_at.write()) =
+            rates.keys().map(|code| (code.clone(), now)).collect();
+        (*self.exchange_rates.write()) = rates.clone();
+        self.exchange_rate_updates
+            .unbounded_send(RatesUpdate::Rates(rates, now, source.to_string()))
+            .map_err(|err| error!("Error queueing exchange rates to be published: {:?}", err))?;
+        Ok(())
+    }
+
+    fn get_exchange_rates_updated_at(&self, asset_codes: &[&str]) -> Result<Vec<SystemTime>, ()> {
+        let updated_at = self.exchange_rates_updated_at.read();
+        asset_codes
+            .iter()
+            .map(|code| updated_at.get(*code).cloned().ok_or(()))
+            .collect()
+    }
+
+    fn set_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let mut current = self.spreads.write();
+        self.spreads_set.store(true, Ordering::SeqCst);
+        *current = spreads.clone();
+        self.exchange_rate_updates
+            .unbounded_send(RatesUpdate::Spreads(spreads))
+            .map_err(|err| error!("Error queueing spreads to be published: {:?}", err))?;
+        Ok(())
+    }
+
+    fn set_default_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let mut current = self.spreads.write();
+        if !self.spreads_set.load(Ordering::SeqCst) {
+            *current = spreads;
+        }
         Ok(())
     }
+
+    fn get_spread(&self, from_asset_code: &str, to_asset_code: &str) -> f64 {
+        self.spreads.read().get(from_asset_code, to_asset_code)
+    }
+
+    fn get_all_spreads(&self) -> Result<Spreads, ()> {
+        Ok((*self.spreads.read()).clone())
+    }
+
+    fn set_pair_quotes(&self, quotes: Vec<PairQuote>) -> Result<(), ()> {
+        let now = SystemTime::now();
+        (*self.pair_quotes.write()) = index_pair_quotes(quotes.clone(), now);
+        self.exchange_rate_updates
+            .unbounded_send(RatesUpdate::PairQuotes(quotes, now))
+            .map_err(|err| error!("Error queueing pair quotes to be published: {:?}", err))?;
+        Ok(())
+    }
+
+    fn get_pair_quote(&self, base: &str, quote: &str) -> Option<(PairQuote, SystemTime)> {
+        self.pair_quotes
+            .read()
+            .get(base)
+            .and_then(|quotes| quotes.get(quote))
+            .cloned()
+    }
+
+    fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()> {
+        let mut quotes: Vec<PairQuote> = self
+            .pair_quotes
+            .read()
+            .values()
+            .flat_map(|quotes| quotes.values())
+            .map(|(quote, _)| quote.clone())
+            .collect();
+        quotes.sort_by(|a, b| (&a.base, &a.quote).cmp(&(&b.base, &b.quote)));
+        Ok(quotes)
+    }
+}
+
+#[async_trait]
+impl ExchangeRateHistoryStore for RedisStore {
+    async fn get_exchange_rate_history(
+        &self,
+        query: ExchangeRateHistoryQuery,
+    ) -> Result<ExchangeRateHistoryPage, ()> {
+        // The cursor is the id of the last record of the previous page, followed by
+        // its asset code because each asset has its own stream. Records with the
+        // same id are ordered by asset code, so the page continues at that id in
+        // the streams of the assets which come after the cursor's asset
+        let cursor = match query.cursor {
+            Some(ref cursor) => {
+                let separator = cursor.find(':');
+                let id = separator.and_then(|index| parse_stream_id(&cursor[..index]));
+                match (id, separator) {
+                    (Some(id), Some(index)) => Some((id, cursor[index + 1..].to_string())),
+                    _ => {
+                        error!("Invalid exchange rate history cursor: {}", cursor);
+                        return Err(());
+                    }
+                }
+            }
+            None => None,
+        };
+        let mut connection = self.connection.clone();
+        let mut asset_codes: Vec<String> = match query.asset_code {
+            Some(asset_code) => vec![asset_code],
+            None => {
+                connection
+                    .smembers(self.namespace.key(RATE_HISTORY_ASSETS_KEY))
+                    .map_err(|err| error!("Error loading exchange rate history assets: {:?}", err))
+                    .await?
+            }
+        };
+        if asset_codes.is_empty() {
+            return Ok(ExchangeRateHistoryPage {
+                records: Vec::new(),
+                next_cursor: None,
+            });
+        }
+        asset_codes.sort();
+
+        // Take one more record than needed from each stream to know whether there is a next page
+        let mut pipe = redis_crate::pipe();
+        for asset_code in asset_codes.iter() {
+            let mut start = (query.from, 0);
+            if let Some((id, ref cursor_asset_code)) = cursor {
+                if asset_code > cursor_asset_code {
+                    start = start.max(id);
+                } else {
+                    start = start.max(next_stream_id(id));
+                }
+            }
+            pipe.cmd("XRANGE")
+                .arg(self.namespace.rate_history_key(asset_code))
+                .arg(format!("{}-{}", start.0, start.1))
+                .arg(query.to)
+                .arg("COUNT")
+                .arg(query.limit.saturating_add(1));
+        }
+        let streams: Vec<Vec<(String, Vec<String>)>> = pipe
+            .query_async(&mut connection)
+            .map_err(|err| error!("Error getting exchange rate history: {:?}", err))
+            .await?;
+        let mut records = streams
+            .into_iter()
+            .flatten()
+            .map(parse_rate_record)
+            .collect::<Result<Vec<_>, ()>>()?;
+        records.sort_by(|(a_id, a), (b_id, b)| {
+            a_id.cmp(b_id).then_with(|| a.asset_code.cmp(&b.asset_code))
+        });
+        records.truncate(query.limit.saturating_add(1));
+        let mut next_cursor = None;
+        if records.len() > query.limit {
+            records.truncate(query.limit);
+            next_cursor = records
+                .last()
+                .map(|((ms, seq), record)| format!("{}-{}:{}", ms, seq, record.asset_code));
+        }
+        Ok(ExchangeRateHistoryPage {
+            records: records.into_iter().map(|(_, record)| record).collect(),
+            next_cursor,
+        })
+    }
 }
 
 #[async_trait]
@@ -862,25 +2255,57 @@ impl BtpStore for RedisStore {
         username: &Username,
         token: &str,
     ) -> Result<Self::Account, ()> {
+        let cached = self.account_cache.get_by_username(username.as_ref());
+        if let Some(ref account) = cached {
+            if self
+                .token_matches(username, &account.ilp_over_btp_incoming_token, token)
+                .await
+            {
+                return Ok(account.clone());
+            }
+        }
+
         // TODO make sure it can't do script injection!
-        // TODO cache the result so we don't hit redis for every packet (is that
-        // necessary if redis is often used as a cache?)
-        let decryption_key = self.decryption_key.clone();
+        let generation = self.account_cache.generation();
+        let keys = self.keys.read().await;
         let mut connection = self.connection.clone();
         let token = token.to_owned();
         let username = username.to_owned(); // TODO: Can we avoid taking ownership?
 
         let account: Option<AccountWithEncryptedTokens> = ACCOUNT_FROM_USERNAME
+            .key(self.namespace.key(USERNAMES_KEY))
             .arg(username.as_ref())
+            .arg(self.namespace.accounts_key_prefix())
             .invoke_async(&mut connection)
             .map_err(|err| error!("Error getting account from BTP token: {:?}", err))
             .await?;
 
         if let Some(account) = account {
-            let account = account.decrypt_tokens(&decryption_key.expose_secret().0);
-            if let Some(t) = account.ilp_over_btp_incoming_token.clone() {
-                let t = t.expose_secret().clone();
-                if t == Bytes::from(token) {
+            let account = account.decrypt_tokens(&keys);
+            // Checking the token does not need the keys, so they can be rotated meanwhile
+            drop(keys);
+            // The token was already checked against the cached account, so it is only
+            // checked again if the account's token was changed since it was cached
+            let already_failed = cached.map_or(false, |cached| {
+                same_token(
+                    &cached.ilp_over_btp_incoming_token,
+                    &account.ilp_over_btp_incoming_token,
+                )
+            });
+            if account.ilp_over_btp_incoming_token.is_some() {
+                if !already_failed
+                    && self
+                        .token_matches(&username, &account.ilp_over_btp_incoming_token, &token)
+                        .await
+                {
+                    self.migrate_incoming_token(
+                        &account,
+                        "ilp_over_btp_incoming_token",
+                        &account.ilp_over_btp_incoming_token,
+                    )
+                    .await;
+                    self.account_cache
+                        .insert(generation, iter::once(account.clone()));
                     Ok(account)
                 } else {
                     debug!(
@@ -906,7 +2331,7 @@ impl BtpStore for RedisStore {
         let mut connection = self.connection.clone();
 
         let account_ids: Vec<RedisAccountId> = connection
-            .smembers("btp_outgoing")
+            .smembers(self.namespace.key(BTP_OUTGOING_KEY))
             .map_err(|err| error!("Error getting members of set btp_outgoing: {:?}", err))
             .await?;
         let account_ids: Vec<Uuid> = account_ids.into_iter().map(|id| id.0).collect();
@@ -931,20 +2356,54 @@ impl HttpStore for RedisStore {
         username: &Username,
         token: &str,
     ) -> Result<Self::Account, ()> {
+        let cached = self.account_cache.get_by_username(username.as_ref());
+        if let Some(ref account) = cached {
+            if self
+                .token_matches(username, &account.ilp_over_http_incoming_token, token)
+                .await
+            {
+                return Ok(account.clone());
+            }
+        }
+
         // TODO make sure it can't do script injection!
-        let decryption_key = self.decryption_key.clone();
+        let generation = self.account_cache.generation();
+        let keys = self.keys.read().await;
         let token = token.to_owned();
         let account: Option<AccountWithEncryptedTokens> = ACCOUNT_FROM_USERNAME
+            .key(self.namespace.key(USERNAMES_KEY))
             .arg(username.as_ref())
+            .arg(self.namespace.accounts_key_prefix())
             .invoke_async(&mut self.connection.clone())
             .map_err(|err| error!("Error getting account from HTTP auth: {:?}", err))
             .await?;
 
         if let Some(account) = account {
-            let account = account.decrypt_tokens(&decryption_key.expose_secret().0);
-            if let Some(t) = account.ilp_over_http_incoming_token.clone() {
-                let t = t.expose_secret().clone();
-                if t == Bytes::from(token) {
+            let account = account.decrypt_tokens(&keys);
+            // Checking the token does not need the keys, so they can be rotated meanwhile
+            drop(keys);
+            // The token was already checked against the cached account, so it is only
+            // checked again if the account's token was changed since it was cached
+            let already_failed = cached.map_or(false, |cached| {
+                same_token(
+                    &cached.ilp_over_http_incoming_token,
+                    &account.ilp_over_http_incoming_token,
+                )
+            });
+            if account.ilp_over_http_incoming_token.is_some() {
+                if !already_failed
+                    && self
+                        .token_matches(username, &account.ilp_over_http_incoming_token, &token)
+                        .await
+                {
+                    self.migrate_incoming_token(
+                        &account,
+                        "ilp_over_http_incoming_token",
+                        &account.ilp_over_http_incoming_token,
+                    )
+                    .await;
+                    self.account_cache
+                        .insert(generation, iter::once(account.clone()));
                     Ok(account)
                 } else {
                     Err(())
@@ -970,7 +2429,7 @@ impl NodeStore for RedisStore {
     type Account = Account;
 
     async fn insert_account(&self, account: AccountDetails) -> Result<Account, ()> {
-        let encryption_key = self.encryption_key.clone();
+        let keys = self.keys.read().await;
         let id = Uuid::new_v4();
         let account = Account::try_from(id, account, self.get_ilp_address())?;
         debug!(
@@ -978,9 +2437,7 @@ impl NodeStore for RedisStore {
             account.username.clone(),
             account.id
         );
-        let encrypted = account
-            .clone()
-            .encrypt_tokens(&encryption_key.expose_secret().0);
+        let encrypted = account.clone().encrypt_tokens(&keys);
         let mut self_clone = self.clone();
 
         self_clone.redis_insert_account(encrypted).await?;
@@ -988,15 +2445,15 @@ impl NodeStore for RedisStore {
     }
 
     async fn delete_account(&self, id: Uuid) -> Result<Account, ()> {
-        let decryption_key = self.decryption_key.clone();
+        let keys = self.keys.read().await;
         let mut self_clone = self.clone();
         let account = self_clone.redis_delete_account(id).await?;
-        Ok(account.decrypt_tokens(&decryption_key.expose_secret().0))
+        self.invalidate_cached_account(id).await;
+        Ok(account.decrypt_tokens(&keys))
     }
 
     async fn update_account(&self, id: Uuid, account: AccountDetails) -> Result<Self::Account, ()> {
-        let encryption_key = self.encryption_key.clone();
-        let decryption_key = self.decryption_key.clone();
+        let keys = self.keys.read().await;
         let account = Account::try_from(id, account, self.get_ilp_address())?;
 
         debug!(
@@ -1004,12 +2461,11 @@ impl NodeStore for RedisStore {
             account.username.clone(),
             account.id
         );
-        let encrypted = account
-            .clone()
-            .encrypt_tokens(&encryption_key.expose_secret().0);
+        let encrypted = account.clone().encrypt_tokens(&keys);
 
         let account = self.redis_update_account(encrypted).await?;
-        Ok(account.decrypt_tokens(&decryption_key.expose_secret().0))
+        self.invalidate_cached_account(id).await;
+        Ok(account.decrypt_tokens(&keys))
     }
 
     async fn modify_account_settings(
@@ -1017,73 +2473,211 @@ impl NodeStore for RedisStore {
         id: Uuid,
         settings: AccountSettings,
     ) -> Result<Self::Account, ()> {
-        let encryption_key = self.encryption_key.clone();
-        let decryption_key = self.decryption_key.clone();
+        validate_incoming_token(settings.ilp_over_btp_incoming_token.as_ref())?;
+        validate_incoming_token(settings.ilp_over_http_incoming_token.as_ref())?;
+        let keys = self.keys.read().await;
+        // The incoming tokens are hashed with the username as part of the salt
+        let username = if settings.ilp_over_btp_incoming_token.is_some()
+            || settings.ilp_over_http_incoming_token.is_some()
+        {
+            let username: Option<String> = self
+                .connection
+                .clone()
+                .hget(self.namespace.accounts_key(id), "username")
+                .map_err(|err| error!("Error loading username of account {}: {:?}", id, err))
+                .await?;
+            username.ok_or_else(|| warn!("No account exists with ID {}", id))?
+        } else {
+            String::new()
+        };
         let settings = EncryptedAccountSettings {
             settle_to: settings.settle_to,
             settle_threshold: settings.settle_threshold,
             ilp_over_btp_url: settings.ilp_over_btp_url,
             ilp_over_http_url: settings.ilp_over_http_url,
-            ilp_over_btp_incoming_token: settings.ilp_over_btp_incoming_token.map(|token| {
-                encrypt_token(
-                    &encryption_key.expose_secret().0,
-                    token.expose_secret().as_bytes(),
-                )
-                .freeze()
-            }),
-            ilp_over_http_incoming_token: settings.ilp_over_http_incoming_token.map(|token| {
-                encrypt_token(
-                    &encryption_key.expose_secret().0,
-                    token.expose_secret().as_bytes(),
-                )
-                .freeze()
-            }),
+            ilp_over_btp_incoming_token: settings
+                .ilp_over_btp_incoming_token
+                .map(|token| hash_token(&username, token.expose_secret().as_bytes()).freeze()),
+            ilp_over_http_incoming_token: settings
+                .ilp_over_http_incoming_token
+                .map(|token| hash_token(&username, token.expose_secret().as_bytes()).freeze()),
             ilp_over_btp_outgoing_token: settings.ilp_over_btp_outgoing_token.map(|token| {
-                encrypt_token(
-                    &encryption_key.expose_secret().0,
-                    token.expose_secret().as_bytes(),
-                )
-                .freeze()
+                keys.encrypt_token(token.expose_secret().as_bytes())
+                    .freeze()
             }),
             ilp_over_http_outgoing_token: settings.ilp_over_http_outgoing_token.map(|token| {
-                encrypt_token(
-                    &encryption_key.expose_secret().0,
-                    token.expose_secret().as_bytes(),
-                )
-                .freeze()
+                keys.encrypt_token(token.expose_secret().as_bytes())
+                    .freeze()
             }),
         };
 
         let account = self.redis_modify_account(id, settings).await?;
-        Ok(account.decrypt_tokens(&decryption_key.expose_secret().0))
+        self.invalidate_cached_account(id).await;
+        Ok(account.decrypt_tokens(&keys))
+    }
+
+    async fn set_account_status(
+        &self,
+        id: Uuid,
+        status: AccountStatus,
+    ) -> Result<Self::Account, ()> {
+        let keys = self.keys.read().await;
+        let mut connection = self.connection.clone();
+        let exists: bool = connection
+            .exists(self.namespace.accounts_key(id))
+            .map_err(|err| error!("Error checking whether ID exists: {:?}", err))
+            .await?;
+        if !exists {
+            warn!("No account exists with ID {}, cannot set its status", id);
+            return Err(());
+        }
+
+        // The routes to the account are withdrawn by the CCP server the next
+        // time it checks its best routes, since it skips accounts which are not active
+        let _: () = connection
+            .hset(self.namespace.accounts_key(id), "status", status.as_ref())
+            .map_err(|err| error!("Error setting the status of account {}: {:?}", id, err))
+            .await?;
+        debug!("Set the status of account {} to {}", id, status);
+
+        let account = self.clone().redis_get_account(id).await?;
+        self.invalidate_cached_account(id).await;
+        Ok(account.decrypt_tokens(&keys))
+    }
+
+    async fn insert_api_key(&self, key: ApiKey, secret: SecretString) -> Result<ApiKey, ()> {
+        let mut connection = self.connection.clone();
+        let exists: bool = connection
+            .exists(self.namespace.accounts_key(key.account_id))
+            .map_err(|err| error!("Error checking whether ID exists: {:?}", err))
+            .await?;
+        if !exists {
+            warn!(
+                "No account exists with ID {}, cannot insert API key",
+                key.account_id
+            );
+            return Err(());
+        }
+
+        let secret_hash = hash_token(&key.id.to_string(), secret.expose_secret().as_bytes());
+        let stored = RedisApiKey {
+            key,
+            secret_hash: hex::encode(secret_hash),
+        };
+        let json = serde_json::to_string(&stored)
+            .map_err(|err| error!("Error serializing API key: {:?}", err))?;
+        let _: () = connection
+            .hset(
+                self.namespace.api_keys_key(stored.key.account_id),
+                stored.key.id.to_string(),
+                json,
+            )
+            .map_err(|err| error!("Error inserting API key: {:?}", err))
+            .await?;
+
+        debug!(
+            "Inserted API key {} of account {}",
+            stored.key.id, stored.key.account_id
+        );
+        Ok(stored.key)
+    }
+
+    async fn get_api_keys(&self, account_id: Uuid) -> Result<Vec<ApiKey>, ()> {
+        let keys: HashMap<String, String> = self
+            .connection
+            .clone()
+            .hgetall(self.namespace.api_keys_key(account_id))
+            .map_err(|err| error!("Error getting API keys: {:?}", err))
+            .await?;
+        let mut keys = keys
+            .values()
+            .map(|json| serde_json::from_str::<RedisApiKey>(json).map(|stored| stored.key))
+            .collect::<Result<Vec<ApiKey>, _>>()
+            .map_err(|err| error!("Error parsing API keys: {:?}", err))?;
+        keys.sort_by_key(|key| key.id);
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<ApiKey, ()> {
+        let stored = self.get_redis_api_key(account_id, key_id).await?;
+        let _: () = self
+            .connection
+            .clone()
+            .hdel(self.namespace.api_keys_key(account_id), key_id.to_string())
+            .map_err(|err| error!("Error deleting API key: {:?}", err))
+            .await?;
+
+        debug!("Deleted API key {} of account {}", key_id, account_id);
+        Ok(stored.key)
+    }
+
+    async fn get_api_key_from_auth(
+        &self,
+        account_id: Uuid,
+        key_id: Uuid,
+        secret: &str,
+    ) -> Result<ApiKey, ()> {
+        let stored = self.get_redis_api_key(account_id, key_id).await?;
+        let secret_hash = hex::decode(&stored.secret_hash)
+            .map_err(|err| error!("Invalid hash of API key {}: {:?}", key_id, err))?;
+        if self
+            .token_verifier
+            .verify(&key_id.to_string(), &secret_hash, secret.as_bytes())
+            .await
+        {
+            Ok(stored.key)
+        } else {
+            Err(())
+        }
     }
 
-    // TODO limit the number of results and page through them
     async fn get_all_accounts(&self) -> Result<Vec<Self::Account>, ()> {
-        let decryption_key = self.decryption_key.clone();
+        let keys = self.keys.read().await;
         let mut connection = self.connection.clone();
 
         let account_ids = self.get_all_accounts_ids().await?;
 
-        let mut script = LOAD_ACCOUNTS.prepare_invoke();
-        for id in account_ids.iter() {
-            script.arg(id.to_string());
-        }
-
-        let accounts: Vec<AccountWithEncryptedTokens> = script
-            .invoke_async(&mut connection)
-            .map_err(|err| error!("Error getting account ids: {:?}", err))
-            .await?;
+        let accounts: Vec<AccountWithEncryptedTokens> =
+            load_accounts_script(&self.namespace, &account_ids)
+                .invoke_async(&mut connection)
+                .map_err(|err| error!("Error getting account ids: {:?}", err))
+                .await?;
 
         // TODO this should be refactored so that it gets reused in multiple backends
         let accounts: Vec<Account> = accounts
             .into_iter()
-            .map(|account| account.decrypt_tokens(&decryption_key.expose_secret().0))
+            .map(|account| account.decrypt_tokens(&keys))
             .collect();
 
         Ok(accounts)
     }
 
+    async fn get_accounts_page(
+        &self,
+        query: AccountsQuery,
+    ) -> Result<AccountsPage<Self::Account>, ()> {
+        // Load the accounts in batches so that requesting the first
+        // page does not require loading every account on the node.
+        // Accounts deleted while the page is loaded are left out
+        let limit = query.limit.unwrap_or_else(usize::max_value);
+        let mut accounts = Vec::new();
+        let mut after = query.cursor;
+        loop {
+            let account_ids = self
+                .get_account_ids_after(after, ACCOUNTS_PAGE_BATCH_SIZE)
+                .await?;
+            let batch = self.load_existing_accounts(&account_ids).await?;
+            accounts.extend(batch.into_iter().filter(|account| query.matches(account)));
+            // Having more accounts than the limit means there is a next page
+            if accounts.len() > limit || account_ids.len() < ACCOUNTS_PAGE_BATCH_SIZE {
+                break;
+            }
+            after = account_ids.last().cloned();
+        }
+        trace!("Loaded {} accounts for page", accounts.len());
+        Ok(AccountsPage::from_sorted_accounts(accounts, &query))
+    }
+
     async fn set_static_routes<R>(&self, routes: R) -> Result<(), ()>
     where
         R: IntoIterator<Item = (String, Uuid)> + Send + 'async_trait,
@@ -1097,7 +2691,7 @@ impl NodeStore for RedisStore {
             HashSet::from_iter(routes.iter().map(|(_prefix, account_id)| account_id));
         let mut pipe = redis_crate::pipe();
         for account_id in accounts {
-            pipe.exists(accounts_key((*account_id).0));
+            pipe.exists(self.namespace.accounts_key((*account_id).0));
         }
 
         let routing_table = self.routes.clone();
@@ -1119,26 +2713,38 @@ impl NodeStore for RedisStore {
 
         let mut pipe = redis_crate::pipe();
         pipe.atomic()
-            .del(STATIC_ROUTES_KEY)
+            .del(self.namespace.key(STATIC_ROUTES_KEY))
             .ignore()
-            .hset_multiple(STATIC_ROUTES_KEY, &routes)
+            .hset_multiple(self.namespace.key(STATIC_ROUTES_KEY), &routes)
             .ignore();
 
         pipe.query_async(&mut connection)
             .map_err(|err| error!("Error setting static routes: {:?}", err))
             .await?;
 
-        update_routes(connection, routing_table).await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         Ok(())
     }
 
+    async fn get_static_routes(&self) -> Result<HashMap<String, Uuid>, ()> {
+        let mut connection = self.connection.clone();
+        let routes: HashMap<String, RedisAccountId> = connection
+            .hgetall(self.namespace.key(STATIC_ROUTES_KEY))
+            .map_err(|err| error!("Error getting static routes: {:?}", err))
+            .await?;
+        Ok(routes
+            .into_iter()
+            .map(|(prefix, account_id)| (prefix, account_id.0))
+            .collect())
+    }
+
     async fn set_static_route(&self, prefix: String, account_id: Uuid) -> Result<(), ()> {
         let routing_table = self.routes.clone();
         let prefix_clone = prefix.clone();
         let mut connection = self.connection.clone();
 
         let exists: bool = connection
-            .exists(accounts_key(account_id))
+            .exists(self.namespace.accounts_key(account_id))
             .map_err(|err| {
                 error!(
                     "Error checking if account exists before setting static route: {:?}",
@@ -1155,11 +2761,15 @@ impl NodeStore for RedisStore {
         }
 
         connection
-            .hset(STATIC_ROUTES_KEY, prefix, RedisAccountId(account_id))
+            .hset(
+                self.namespace.key(STATIC_ROUTES_KEY),
+                prefix,
+                RedisAccountId(account_id),
+            )
             .map_err(|err| error!("Error setting static route: {:?}", err))
             .await?;
 
-        update_routes(connection, routing_table).await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
 
         Ok(())
     }
@@ -1169,7 +2779,7 @@ impl NodeStore for RedisStore {
         // TODO replace this with a lua script to do both calls at once
         let mut connection = self.connection.clone();
         let exists: bool = connection
-            .exists(accounts_key(account_id))
+            .exists(self.namespace.accounts_key(account_id))
             .map_err(|err| {
                 error!(
                     "Error checking if account exists before setting default route: {:?}",
@@ -1186,11 +2796,14 @@ impl NodeStore for RedisStore {
         }
 
         connection
-            .set(DEFAULT_ROUTE_KEY, RedisAccountId(account_id))
+            .set(
+                self.namespace.key(DEFAULT_ROUTE_KEY),
+                RedisAccountId(account_id),
+            )
             .map_err(|err| error!("Error setting default route: {:?}", err))
             .await?;
         debug!("Set default route to account id: {}", account_id);
-        update_routes(connection, routing_table).await?;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         Ok(())
     }
 
@@ -1205,9 +2818,14 @@ impl NodeStore for RedisStore {
             .collect();
         debug!("Setting settlement engines to {:?}", asset_to_url_map);
         connection
-            .hset_multiple(SETTLEMENT_ENGINES_KEY, &asset_to_url_map)
+            .hset_multiple(
+                self.namespace.key(SETTLEMENT_ENGINES_KEY),
+                &asset_to_url_map,
+            )
             .map_err(|err| error!("Error setting settlement engines: {:?}", err))
             .await?;
+        // Accounts without their own engine use the engine configured for their asset
+        self.invalidate_cached_accounts().await;
         Ok(())
     }
 
@@ -1216,7 +2834,7 @@ impl NodeStore for RedisStore {
         let asset_code = asset_code.to_owned();
 
         let url: Option<String> = connection
-            .hget(SETTLEMENT_ENGINES_KEY, asset_code)
+            .hget(self.namespace.key(SETTLEMENT_ENGINES_KEY), asset_code)
             .map_err(|err| error!("Error getting settlement engine: {:?}", err))
             .await?;
         if let Some(url) = url {
@@ -1234,6 +2852,56 @@ impl NodeStore for RedisStore {
             Ok(None)
         }
     }
+
+    async fn get_settlement_engines(&self) -> Result<HashMap<String, Url>, ()> {
+        let mut connection = self.connection.clone();
+        let engines: HashMap<String, String> = connection
+            .hgetall(self.namespace.key(SETTLEMENT_ENGINES_KEY))
+            .map_err(|err| error!("Error getting settlement engines: {:?}", err))
+            .await?;
+        engines
+            .into_iter()
+            .map(|(asset_code, url)| {
+                let url = Url::parse(&url).map_err(|err| {
+                    error!(
+                        "Settlement engine URL loaded from Redis was not a valid URL: {:?}",
+                        err
+                    )
+                })?;
+                Ok((asset_code, url))
+            })
+            .collect()
+    }
+
+    async fn export_accounts(&self) -> Result<Vec<ExportedAccount>, ()> {
+        let accounts = self.get_all_accounts().await?;
+        Ok(accounts.iter().map(Account::export).collect())
+    }
+
+    async fn import_account(&self, account: ExportedAccount) -> Result<Account, ()> {
+        let account = Account::try_from_exported(account, self.get_ilp_address())?;
+        let keys = self.keys.read().await;
+        let encrypted = account.clone().encrypt_tokens(&keys);
+        let mut self_clone = self.clone();
+
+        self_clone.redis_insert_account(encrypted).await?;
+        Ok(account)
+    }
+
+    async fn rotate_secret(&self, new_secret: [u8; 32]) -> Result<(), ()> {
+        if self
+            .secret_rotation_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            error!("Cannot rotate the secret while it is already being rotated");
+            return Err(());
+        }
+        let result = self.rotate_secret_of_every_store(new_secret).await;
+        self.secret_rotation_in_progress
+            .store(false, Ordering::SeqCst);
+        result
+    }
 }
 
 #[async_trait]
@@ -1251,7 +2919,7 @@ impl AddressStore for RedisStore {
 
         // Save it to Redis
         connection
-            .set(PARENT_ILP_KEY, ilp_address.as_bytes())
+            .set(self.namespace.key(PARENT_ILP_KEY), ilp_address.as_bytes())
             .map_err(|err| error!("Error setting ILP address {:?}", err))
             .await?;
 
@@ -1271,7 +2939,8 @@ impl AddressStore for RedisStore {
                 && account.routing_relation() != RoutingRelation::Peer
             {
                 // remove the old route
-                pipe.hdel(ROUTES_KEY, &account.ilp_address as &str).ignore();
+                pipe.hdel(self.namespace.key(ROUTES_KEY), &account.ilp_address as &str)
+                    .ignore();
 
                 // if the username of the account ends with the
                 // node's address, we're already configured so no
@@ -1291,14 +2960,14 @@ impl AddressStore for RedisStore {
                         .unwrap()
                 };
                 pipe.hset(
-                    accounts_key(account.id()),
+                    self.namespace.accounts_key(account.id()),
                     "ilp_address",
                     new_ilp_address.as_bytes(),
                 )
                 .ignore();
 
                 pipe.hset(
-                    ROUTES_KEY,
+                    self.namespace.key(ROUTES_KEY),
                     new_ilp_address.as_bytes(),
                     RedisAccountId(account.id()),
                 )
@@ -1309,14 +2978,15 @@ impl AddressStore for RedisStore {
         pipe.query_async(&mut connection.clone())
             .map_err(|err| error!("Error updating children: {:?}", err))
             .await?;
-        update_routes(connection, routing_table).await?;
+        self.invalidate_cached_accounts().await;
+        update_and_publish_routes(connection, &self.namespace, routing_table).await?;
         Ok(())
     }
 
     async fn clear_ilp_address(&self) -> Result<(), ()> {
         let mut connection = self.connection.clone();
         connection
-            .del(PARENT_ILP_KEY)
+            .del(self.namespace.key(PARENT_ILP_KEY))
             .map_err(|err| error!("Error removing parent address: {:?}", err))
             .await?;
 
@@ -1344,7 +3014,7 @@ impl RouteManagerStore for RedisStore {
         let mut connection = self.connection.clone();
 
         let account_ids: Vec<RedisAccountId> = connection
-            .smembers("send_routes_to")
+            .smembers(self.namespace.key(SEND_ROUTES_TO_KEY))
             .map_err(|err| error!("Error getting members of set send_routes_to: {:?}", err))
             .await?;
         let account_ids: Vec<Uuid> = account_ids
@@ -1363,7 +3033,7 @@ impl RouteManagerStore for RedisStore {
     async fn get_accounts_to_receive_routes_from(&self) -> Result<Vec<Account>, ()> {
         let mut connection = self.connection.clone();
         let account_ids: Vec<RedisAccountId> = connection
-            .smembers("receive_routes_from")
+            .smembers(self.namespace.key(RECEIVE_ROUTES_FROM_KEY))
             .map_err(|err| {
                 error!(
                     "Error getting members of set receive_routes_from: {:?}",
@@ -1386,17 +3056,17 @@ impl RouteManagerStore for RedisStore {
     ) -> Result<(RoutingTable<Account>, RoutingTable<Account>), ()> {
         let mut connection = self.connection.clone();
         let static_routes: Vec<(String, RedisAccountId)> = connection
-            .hgetall(STATIC_ROUTES_KEY)
+            .hgetall(self.namespace.key(STATIC_ROUTES_KEY))
             .map_err(|err| error!("Error getting static routes: {:?}", err))
             .await?;
 
         let accounts = self.get_all_accounts().await?;
 
-        let local_table = HashMap::from_iter(
-            accounts
-                .iter()
-                .map(|account| (account.ilp_address.to_string(), account.clone())),
-        );
+        let local_table = HashMap::from_iter(accounts.iter().flat_map(|account| {
+            account
+                .local_prefixes()
+                .map(move |prefix| (prefix.to_string(), account.clone()))
+        }));
 
         let account_map: HashMap<Uuid, &Account> =
             HashMap::from_iter(accounts.iter().map(|account| (account.id, account)));
@@ -1434,9 +3104,9 @@ impl RouteManagerStore for RedisStore {
         let routing_tale = self.routes.clone();
         let mut pipe = redis_crate::pipe();
         pipe.atomic()
-            .del(ROUTES_KEY)
+            .del(self.namespace.key(ROUTES_KEY))
             .ignore()
-            .hset_multiple(ROUTES_KEY, &routes)
+            .hset_multiple(self.namespace.key(ROUTES_KEY), &routes)
             .ignore();
 
         pipe.query_async(&mut connection)
@@ -1444,7 +3114,7 @@ impl RouteManagerStore for RedisStore {
             .await?;
         trace!("Saved {} routes to Redis", num_routes);
 
-        update_routes(connection, routing_tale).await
+        update_and_publish_routes(connection, &self.namespace, routing_tale).await
     }
 }
 
@@ -1452,7 +3122,8 @@ impl RouteManagerStore for RedisStore {
 impl RateLimitStore for RedisStore {
     type Account = Account;
 
-    /// Apply rate limits for number of packets per minute and amount of money per minute
+    /// Apply rate limits for number of packets and amount of money. Accounts can send up to
+    /// their burst limits at once, which refill at their per minute limits
     ///
     /// This uses https://github.com/brandur/redis-cell so the redis-cell module MUST be loaded into redis before this is run
     async fn apply_rate_limits(
@@ -1460,35 +3131,36 @@ impl RateLimitStore for RedisStore {
         account: Account,
         prepare_amount: u64,
     ) -> Result<(), RateLimitError> {
-        if account.amount_per_minute_limit.is_some() || account.packets_per_minute_limit.is_some() {
+        let mut connection = self.connection.clone();
+        let per_minute_limits = if account.amount_per_minute_limit.is_some()
+            || account.packets_per_minute_limit.is_some()
+        {
             let mut pipe = redis_crate::pipe();
             let packet_limit = account.packets_per_minute_limit.is_some();
             let amount_limit = account.amount_per_minute_limit.is_some();
 
             if let Some(limit) = account.packets_per_minute_limit {
-                let limit = limit - 1;
-                let packets_limit = format!("limit:packets:{}", account.id);
+                let burst = account.packets_burst_limit.unwrap_or(limit);
+                let packets_limit = self.namespace.rate_limit_key("packets", account.id);
                 pipe.cmd("CL.THROTTLE")
                     .arg(packets_limit)
-                    .arg(limit)
+                    .arg(burst.saturating_sub(1))
                     .arg(limit)
                     .arg(60)
                     .arg(1);
             }
 
             if let Some(limit) = account.amount_per_minute_limit {
-                let limit = limit - 1;
-                let throughput_limit = format!("limit:throughput:{}", account.id);
+                let burst = account.amount_burst_limit.unwrap_or(limit);
+                let throughput_limit = self.namespace.rate_limit_key("throughput", account.id);
                 pipe.cmd("CL.THROTTLE")
                     .arg(throughput_limit)
-                    // TODO allow separate configuration for burst limit
-                    .arg(limit)
+                    .arg(burst.saturating_sub(1))
                     .arg(limit)
                     .arg(60)
                     .arg(prepare_amount);
             }
 
-            let mut connection = self.connection.clone();
             let results: Vec<Vec<i64>> = pipe
                 .query_async(&mut connection)
                 .map_err(|err| {
@@ -1514,7 +3186,41 @@ impl RateLimitStore for RedisStore {
             }
         } else {
             Ok(())
+        };
+        per_minute_limits?;
+
+        if account.amount_per_day_limit.is_none() && account.amount_per_month_limit.is_none() {
+            return Ok(());
+        }
+        // The index (starting from 1) of the limit which would be exceeded, if any
+        let exceeded: usize =
+            amount_sent_script(&ADD_AMOUNT_SENT, &self.namespace, &account, prepare_amount)
+                .invoke_async(&mut connection)
+                .map_err(|err| {
+                    error!(
+                        "Error applying the daily and monthly amount limits: {:?}",
+                        err
+                    );
+                    RateLimitError::StoreError
+                })
+                .await?;
+        if exceeded == 0 {
+            return Ok(());
+        }
+
+        // The packet will not be forwarded, so it does not count towards the throughput
+        if let Some(refund) = refund_throughput_cmd(&self.namespace, &account, prepare_amount) {
+            let refunded: Result<Value, _> = refund.query_async(&mut connection).await;
+            if let Err(err) = refunded {
+                error!("Error refunding throughput limit: {:?}", err);
+            }
         }
+        let period = AmountLimitPeriod::ALL
+            .iter()
+            .filter(|period| account.amount_limit(**period).is_some())
+            .nth(exceeded - 1)
+            .ok_or(RateLimitError::StoreError)?;
+        Err(period.limit_exceeded())
     }
 
     async fn refund_throughput_limit(
@@ -1522,24 +3228,93 @@ impl RateLimitStore for RedisStore {
         account: Account,
         prepare_amount: u64,
     ) -> Result<(), ()> {
-        if let Some(limit) = account.amount_per_minute_limit {
-            let mut connection = self.connection.clone();
-            let limit = limit - 1;
-            let throughput_limit = format!("limit:throughput:{}", account.id);
-            cmd("CL.THROTTLE")
-                .arg(throughput_limit)
-                .arg(limit)
-                .arg(limit)
-                .arg(60)
-                // TODO make sure this doesn't overflow
-                .arg(0i64 - (prepare_amount as i64))
+        let mut connection = self.connection.clone();
+        if let Some(refund) = refund_throughput_cmd(&self.namespace, &account, prepare_amount) {
+            refund
                 .query_async(&mut connection)
                 .map_err(|err| error!("Error refunding throughput limit: {:?}", err))
                 .await?;
         }
+        if account.amount_per_day_limit.is_some() || account.amount_per_month_limit.is_some() {
+            let _: () = amount_sent_script(
+                &REFUND_AMOUNT_SENT,
+                &self.namespace,
+                &account,
+                prepare_amount,
+            )
+            .invoke_async(&mut connection)
+            .map_err(|err| error!("Error refunding the amount sent: {:?}", err))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_cumulative_amounts(&self, account: Account) -> Result<CumulativeAmounts, ()> {
+        let mut connection = self.connection.clone();
+        let mut amounts = CumulativeAmounts::default();
+        for period in AmountLimitPeriod::ALL.iter() {
+            let key =
+                self.namespace
+                    .amount_sent_key(period.as_ref(), &period.current_id(), account.id);
+            let sent: Option<u64> = connection
+                .get(key)
+                .map_err(|err| {
+                    error!(
+                        "Error getting the amounts sent by account {}: {:?}",
+                        account.id, err
+                    )
+                })
+                .await?;
+            amounts.set(*period, sent.unwrap_or(0));
+        }
+        Ok(amounts)
+    }
+}
 
+#[async_trait]
+impl FeeStore for RedisStore {
+    async fn record_fee(
+        &self,
+        from_account_id: Uuid,
+        to_account_id: Uuid,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let mut connection = self.connection.clone();
+        let _: i64 = connection
+            .hincr(
+                self.namespace.key(FEES_EARNED_KEY),
+                format!("{}:{}", from_account_id, to_account_id),
+                amount as i64,
+            )
+            .map_err(|err| error!("Error recording fee: {:?}", err))
+            .await?;
         Ok(())
     }
+
+    async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()> {
+        let mut connection = self.connection.clone();
+        let fees: HashMap<String, u64> = connection
+            .hgetall(self.namespace.key(FEES_EARNED_KEY))
+            .map_err(|err| error!("Error getting fees earned: {:?}", err))
+            .await?;
+        fees.into_iter()
+            .map(|(accounts, amount)| {
+                let mut ids = accounts.split(':').map(Uuid::from_str);
+                match (ids.next(), ids.next(), ids.next()) {
+                    (Some(Ok(from_account_id)), Some(Ok(to_account_id)), None) => Ok(FeesEarned {
+                        from_account_id,
+                        to_account_id,
+                        amount,
+                    }),
+                    _ => {
+                        error!("Invalid account ids in fees earned: {}", accounts);
+                        Err(())
+                    }
+                }
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -1551,7 +3326,7 @@ impl IdempotentStore for RedisStore {
         let idempotency_key_clone = idempotency_key.clone();
         let mut connection = self.connection.clone();
         let ret: HashMap<String, String> = connection
-            .hgetall(prefixed_idempotency_key(idempotency_key.clone()))
+            .hgetall(self.namespace.prefixed_idempotency_key(&idempotency_key))
             .map_err(move |err| {
                 error!(
                     "Error loading idempotency key {}: {:?}",
@@ -1589,7 +3364,7 @@ impl IdempotentStore for RedisStore {
         let mut connection = self.connection.clone();
         pipe.atomic()
             .cmd("HMSET") // cannot use hset_multiple since data and status_code have different types
-            .arg(&prefixed_idempotency_key(idempotency_key.clone()))
+            .arg(&self.namespace.prefixed_idempotency_key(&idempotency_key))
             .arg("status_code")
             .arg(status_code.as_u16())
             .arg("data")
@@ -1597,7 +3372,10 @@ impl IdempotentStore for RedisStore {
             .arg("input_hash")
             .arg(&input_hash)
             .ignore()
-            .expire(&prefixed_idempotency_key(idempotency_key.clone()), 86400)
+            .expire(
+                &self.namespace.prefixed_idempotency_key(&idempotency_key),
+                86400,
+            )
             .ignore();
         pipe.query_async(&mut connection)
             .map_err(|err| error!("Error caching: {:?}", err))
@@ -1625,9 +3403,10 @@ impl SettlementStore for RedisStore {
     ) -> Result<(), ()> {
         let idempotency_key = idempotency_key.unwrap();
         let balance: i64 = PROCESS_INCOMING_SETTLEMENT
-            .arg(RedisAccountId(account_id))
+            .key(self.namespace.accounts_key(account_id))
+            .key(self.namespace.balance_journal_key(account_id))
+            .key(self.namespace.settlement_idempotency_key(&idempotency_key))
             .arg(amount)
-            .arg(idempotency_key)
             .invoke_async(&mut self.connection.clone())
             .map_err(move |err| {
                 error!(
@@ -1652,7 +3431,8 @@ impl SettlementStore for RedisStore {
             settle_amount
         );
         let balance: i64 = REFUND_SETTLEMENT
-            .arg(RedisAccountId(account_id))
+            .key(self.namespace.accounts_key(account_id))
+            .key(self.namespace.balance_journal_key(account_id))
             .arg(settle_amount)
             .invoke_async(&mut self.connection.clone())
             .map_err(move |err| {
@@ -1774,8 +3554,12 @@ impl LeftoversStore for RedisStore {
         let mut pipe = redis_crate::pipe();
         pipe.atomic();
         // get the amounts and instantly delete them
-        pipe.lrange(uncredited_amount_key(account_id.to_string()), 0, -1);
-        pipe.del(uncredited_amount_key(account_id.to_string()))
+        pipe.lrange(
+            self.namespace.uncredited_amount_key(account_id.to_string()),
+            0,
+            -1,
+        );
+        pipe.del(self.namespace.uncredited_amount_key(account_id.to_string()))
             .ignore();
 
         let amounts: Vec<AmountWithScale> = pipe
@@ -1788,6 +3572,23 @@ impl LeftoversStore for RedisStore {
         Ok((amount.num, amount.scale))
     }
 
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        let amount: AmountWithScale = self
+            .connection
+            .clone()
+            .lrange(
+                self.namespace.uncredited_amount_key(account_id.to_string()),
+                0,
+                -1,
+            )
+            .map_err(move |err| error!("Error getting uncredited_settlement_amount {:?}", err))
+            .await?;
+        Ok((amount.num, amount.scale))
+    }
+
     async fn save_uncredited_settlement_amount(
         &self,
         account_id: Uuid,
@@ -1805,7 +3606,7 @@ impl LeftoversStore for RedisStore {
         let mut connection = self.connection.clone();
         connection
             .rpush(
-                uncredited_amount_key(account_id),
+                self.namespace.uncredited_amount_key(account_id),
                 AmountWithScale {
                     num: uncredited_settlement_amount.0,
                     scale: uncredited_settlement_amount.1,
@@ -1833,7 +3634,7 @@ impl LeftoversStore for RedisStore {
         if precision_loss > BigUint::from(0u32) {
             connection
                 .rpush(
-                    uncredited_amount_key(account_id),
+                    self.namespace.uncredited_amount_key(account_id),
                     AmountWithScale {
                         num: precision_loss,
                         scale: std::cmp::max(local_scale, amount.1),
@@ -1850,7 +3651,7 @@ impl LeftoversStore for RedisStore {
         trace!("Clearing uncredited_settlement_amount {:?}", account_id);
         let mut connection = self.connection.clone();
         connection
-            .del(uncredited_amount_key(account_id))
+            .del(self.namespace.uncredited_amount_key(account_id))
             .map_err(move |err| error!("Error clearing uncredited_settlement_amount: {:?}", err))
             .await?;
         Ok(())
@@ -1861,15 +3662,35 @@ type RouteVec = Vec<(String, RedisAccountId)>;
 
 use futures::future::TryFutureExt;
 
-// TODO replace this with pubsub when async pubsub is added upstream: https://github.com/mitsuhiko/redis-rs/issues/183
+/// Reloads the routing table and announces the change to the other stores connected to the same Redis
+async fn update_and_publish_routes(
+    connection: RedisReconnect,
+    namespace: &Namespace,
+    routing_table: Arc<RwLock<Arc<HashMap<String, Uuid>>>>,
+) -> Result<(), ()> {
+    update_routes(connection.clone(), namespace, routing_table).await?;
+    // The other stores will still pick up the change when they next poll
+    // for routes if it cannot be published
+    let result: Result<(), RedisError> = redis_crate::cmd("PUBLISH")
+        .arg(namespace.channel(ROUTES_UPDATES_CHANNEL))
+        .arg("")
+        .query_async(&mut connection.clone())
+        .await;
+    if let Err(err) = result {
+        error!("Error publishing routing table update: {:?}", err);
+    }
+    Ok(())
+}
+
 async fn update_routes(
     mut connection: RedisReconnect,
+    namespace: &Namespace,
     routing_table: Arc<RwLock<Arc<HashMap<String, Uuid>>>>,
 ) -> Result<(), ()> {
     let mut pipe = redis_crate::pipe();
-    pipe.hgetall(ROUTES_KEY)
-        .hgetall(STATIC_ROUTES_KEY)
-        .get(DEFAULT_ROUTE_KEY);
+    pipe.hgetall(namespace.key(ROUTES_KEY))
+        .hgetall(namespace.key(STATIC_ROUTES_KEY))
+        .get(namespace.key(DEFAULT_ROUTE_KEY));
     let (routes, static_routes, default_route): (RouteVec, RouteVec, Option<RedisAccountId>) = pipe
         .query_async(&mut connection)
         .map_err(|err| error!("Error polling for routing table updates: {:?}", err))
@@ -1902,10 +3723,432 @@ async fn update_routes(
     Ok(())
 }
 
+/// Rates (with their source) or pair quotes set by this store, with the time they were set,
+/// or spreads set by this store
+enum RatesUpdate {
+    Rates(HashMap<String, ExchangeRate>, SystemTime, String),
+    PairQuotes(Vec<PairQuote>, SystemTime),
+    Spreads(Spreads),
+}
+
+/// Published on the exchange rates channel whenever a store sets the rates
+#[derive(Serialize, Deserialize)]
+struct ExchangeRatesUpdate {
+    /// Identifies the store that set the rates
+    origin: Uuid,
+    /// The rates are published as decimal strings. Rates published as numbers by
+    /// stores of previous versions are accepted too
+    rates: HashMap<String, ExchangeRate>,
+    /// Milliseconds since the UNIX epoch at which the rates were set
+    updated_at: u64,
+}
+
+/// Replaces the exchange rates saved in Redis, appends them to the
+/// rate history and publishes them to the other stores
+async fn save_exchange_rates(
+    connection: &mut RedisReconnect,
+    namespace: &Namespace,
+    origin: Uuid,
+    rates: HashMap<String, ExchangeRate>,
+    updated_at: SystemTime,
+    source: &str,
+) -> Result<(), ()> {
+    let updated_at = updated_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let update = serde_json::to_string(&ExchangeRatesUpdate {
+        origin,
+        rates: rates.clone(),
+        updated_at,
+    })
+    .map_err(|err| error!("Error serializing exchange rates: {:?}", err))?;
+    let rates_updated_at: Vec<(String, u64)> = rates
+        .keys()
+        .map(|code| (code.clone(), updated_at))
+        .collect();
+    // The rates are saved as decimal strings, so they are kept exactly
+    let mut rates: Vec<(String, String)> = rates
+        .into_iter()
+        .map(|(code, rate)| (code, rate.to_string()))
+        .collect();
+    rates.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut pipe = redis_crate::pipe();
+    pipe.atomic()
+        .del(namespace.key(RATES_KEY))
+        .ignore()
+        .del(namespace.key(RATES_UPDATED_AT_KEY))
+        .ignore();
+    if !rates.is_empty() {
+        pipe.hset_multiple(namespace.key(RATES_KEY), &rates)
+            .ignore();
+        pipe.hset_multiple(namespace.key(RATES_UPDATED_AT_KEY), &rates_updated_at)
+            .ignore();
+    }
+    for (asset_code, rate) in rates.iter() {
+        pipe.sadd(namespace.key(RATE_HISTORY_ASSETS_KEY), asset_code)
+            .ignore();
+        pipe.cmd("XADD")
+            .arg(namespace.rate_history_key(asset_code))
+            .arg("MAXLEN")
+            .arg(EXCHANGE_RATE_HISTORY_LENGTH)
+            .arg("*")
+            .arg("asset_code")
+            .arg(asset_code)
+            .arg("rate")
+            .arg(rate)
+            .arg("source")
+            .arg(source)
+            .ignore();
+    }
+    pipe.cmd("PUBLISH")
+        .arg(namespace.channel(EXCHANGE_RATES_CHANNEL))
+        .arg(update)
+        .ignore();
+    pipe.query_async(connection)
+        .map_err(|err| error!("Error saving exchange rates: {:?}", err))
+        .await
+}
+
+/// Saved in Redis and published on the pair quotes channel whenever a store sets the pair quotes
+#[derive(Serialize, Deserialize)]
+struct PairQuotesUpdate {
+    /// Identifies the store that set the quotes
+    origin: Uuid,
+    quotes: Vec<PairQuote>,
+    /// Milliseconds since the UNIX epoch at which the quotes were set
+    updated_at: u64,
+}
+
+/// Indexes the quotes by their base and quote asset codes
+fn index_pair_quotes(quotes: Vec<PairQuote>, updated_at: SystemTime) -> PairQuotes {
+    let mut pair_quotes: PairQuotes = HashMap::new();
+    for quote in quotes {
+        pair_quotes
+            .entry(quote.base.clone())
+            .or_default()
+            .insert(quote.quote.clone(), (quote, updated_at));
+    }
+    pair_quotes
+}
+
+/// Replaces the pair quotes saved in Redis and publishes them to the other stores
+async fn save_pair_quotes(
+    connection: &mut RedisReconnect,
+    namespace: &Namespace,
+    origin: Uuid,
+    quotes: Vec<PairQuote>,
+    updated_at: SystemTime,
+) -> Result<(), ()> {
+    let update = serde_json::to_string(&PairQuotesUpdate {
+        origin,
+        quotes,
+        updated_at: updated_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    })
+    .map_err(|err| error!("Error serializing pair quotes: {:?}", err))?;
+
+    let mut pipe = redis_crate::pipe();
+    pipe.atomic()
+        .set(namespace.key(PAIR_QUOTES_KEY), &update)
+        .ignore();
+    pipe.cmd("PUBLISH")
+        .arg(namespace.channel(PAIR_QUOTES_CHANNEL))
+        .arg(update)
+        .ignore();
+    pipe.query_async(connection)
+        .map_err(|err| error!("Error saving pair quotes: {:?}", err))
+        .await
+}
+
+async fn update_pair_quotes(
+    mut connection: RedisReconnect,
+    namespace: &Namespace,
+    pair_quotes: Arc<RwLock<PairQuotes>>,
+) -> Result<(), ()> {
+    let update: Option<String> = connection
+        .get(namespace.key(PAIR_QUOTES_KEY))
+        .map_err(|err| error!("Error polling for pair quote updates: {:?}", err))
+        .await?;
+    // The key is not set until one of the stores sets the pair quotes
+    if let Some(update) = update {
+        let update: PairQuotesUpdate = serde_json::from_str(&update)
+            .map_err(|err| error!("Invalid pair quotes saved in Redis: {:?}", err))?;
+        *pair_quotes.write() = index_pair_quotes(
+            update.quotes,
+            UNIX_EPOCH + Duration::from_millis(update.updated_at),
+        );
+    }
+    Ok(())
+}
+
+/// Saved in Redis and published on the spreads channel whenever a store sets the spreads
+#[derive(Serialize, Deserialize)]
+struct SpreadsUpdate {
+    /// Identifies the store that set the spreads
+    origin: Uuid,
+    spreads: Spreads,
+}
+
+/// Replaces the spreads saved in Redis and publishes them to the other stores
+async fn save_spreads(
+    connection: &mut RedisReconnect,
+    namespace: &Namespace,
+    origin: Uuid,
+    spreads: Spreads,
+) -> Result<(), ()> {
+    let update = serde_json::to_string(&SpreadsUpdate { origin, spreads })
+        .map_err(|err| error!("Error serializing spreads: {:?}", err))?;
+
+    let mut pipe = redis_crate::pipe();
+    pipe.atomic()
+        .set(namespace.key(SPREADS_KEY), &update)
+        .ignore();
+    pipe.cmd("PUBLISH")
+        .arg(namespace.channel(SPREADS_CHANNEL))
+        .arg(update)
+        .ignore();
+    pipe.query_async(connection)
+        .map_err(|err| error!("Error saving spreads: {:?}", err))
+        .await
+}
+
+async fn update_spreads(
+    mut connection: RedisReconnect,
+    namespace: &Namespace,
+    spreads: Arc<RwLock<Spreads>>,
+    spreads_set: Arc<AtomicBool>,
+) -> Result<(), ()> {
+    let update: Option<String> = connection
+        .get(namespace.key(SPREADS_KEY))
+        .map_err(|err| error!("Error polling for spread updates: {:?}", err))
+        .await?;
+    // The key is not set until one of the stores sets the spreads,
+    // until then the default spreads apply
+    if let Some(update) = update {
+        let update: SpreadsUpdate = serde_json::from_str(&update)
+            .map_err(|err| error!("Invalid spreads saved in Redis: {:?}", err))?;
+        let mut current = spreads.write();
+        spreads_set.store(true, Ordering::SeqCst);
+        *current = update.spreads;
+    }
+    Ok(())
+}
+
+/// The stages of a secret rotation, each of which every connected store
+/// switches its keys to before the next one starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SecretRotationStage {
+    /// Decrypt with the key of the new secret too, but still encrypt with the previous one
+    Prepare,
+    /// Encrypt with the key of the new secret, and still decrypt with the previous one
+    Commit,
+    /// The tokens were re-encrypted, so the keys of the previous secret are dropped
+    Done,
+}
+
+/// Saved in Redis and published on the secret rotation channel at each stage of a rotation
+#[derive(Serialize, Deserialize)]
+struct SecretRotation {
+    id: Uuid,
+    /// Identifies the store that rotates the secret
+    origin: Uuid,
+    /// The new secret, encrypted with the key of the previous one (hex-encoded)
+    encrypted_secret: String,
+    /// The rotation's id encrypted with the key of the new secret (hex-encoded),
+    /// so that stores started with the new secret can tell they already use it
+    check: String,
+    stage: SecretRotationStage,
+}
+
+/// Saved by each store in the hash of confirmations once it switched its keys
+#[derive(Serialize, Deserialize)]
+struct SecretRotationConfirmation {
+    id: Uuid,
+    stage: SecretRotationStage,
+}
+
+/// Replaces the secret rotation saved in Redis and publishes it to the other stores
+async fn save_secret_rotation(
+    connection: &mut RedisReconnect,
+    namespace: &Namespace,
+    rotation: &SecretRotation,
+) -> Result<(), ()> {
+    let rotation = serde_json::to_string(rotation)
+        .map_err(|err| error!("Error serializing secret rotation: {:?}", err))?;
+
+    let mut pipe = redis_crate::pipe();
+    pipe.atomic()
+        .set(namespace.key(SECRET_ROTATION_KEY), &rotation)
+        .ignore();
+    pipe.cmd("PUBLISH")
+        .arg(namespace.channel(SECRET_ROTATION_CHANNEL))
+        .arg(rotation)
+        .ignore();
+    pipe.query_async(connection)
+        .map_err(|err| error!("Error saving secret rotation: {:?}", err))
+        .await
+}
+
+/// Announces that the store is connected and switches its keys to the stage of
+/// the secret rotation saved in Redis, in case its announcement was missed
+async fn update_secret_rotation(
+    mut connection: RedisReconnect,
+    namespace: &Namespace,
+    instance_id: Uuid,
+    keys: &AsyncRwLock<StoreKeys>,
+    applied: &Mutex<Option<(Uuid, SecretRotationStage)>>,
+) -> Result<(), ()> {
+    // The store is announced before the rotation is loaded, so that a rotation
+    // whose next stage is saved after this either waits for the store or is
+    // loaded with that stage
+    let mut pipe = redis_crate::pipe();
+    pipe.sadd(namespace.key(INSTANCES_KEY), instance_id.to_string())
+        .ignore()
+        .cmd("SET")
+        .arg(namespace.instance_key(instance_id))
+        .arg("")
+        .arg("PX")
+        .arg(INSTANCE_TIMEOUT)
+        .ignore()
+        .get(namespace.key(SECRET_ROTATION_KEY));
+    let (rotation,): (Option<String>,) = pipe
+        .query_async(&mut connection)
+        .map_err(|err| error!("Error polling for secret rotations: {:?}", err))
+        .await?;
+    // The key is not set until the secret is rotated
+    if let Some(rotation) = rotation {
+        let rotation: SecretRotation = serde_json::from_str(&rotation)
+            .map_err(|err| error!("Invalid secret rotation saved in Redis: {:?}", err))?;
+        apply_secret_rotation(connection, namespace, instance_id, keys, applied, &rotation).await?;
+    }
+    Ok(())
+}
+
+/// Switches the keys to the stage of the secret rotation, unless they already
+/// were, and confirms it to the store rotating the secret
+async fn apply_secret_rotation(
+    mut connection: RedisReconnect,
+    namespace: &Namespace,
+    instance_id: Uuid,
+    keys: &AsyncRwLock<StoreKeys>,
+    applied: &Mutex<Option<(Uuid, SecretRotationStage)>>,
+    rotation: &SecretRotation,
+) -> Result<(), ()> {
+    {
+        // Waits until the tokens which are being encrypted with the current
+        // keys were written, so that none is written after the confirmation
+        let mut keys = keys.write().await;
+        let current = match *applied.lock() {
+            Some((id, stage)) if id == rotation.id => Some(stage),
+            _ => None,
+        };
+        if current < Some(rotation.stage) {
+            let new_secret = hex::decode(&rotation.encrypted_secret)
+                .map_err(|_| ())
+                .and_then(|encrypted| keys.decrypt_token(&encrypted));
+            let already_rotated = || {
+                hex::decode(&rotation.check)
+                    .map_err(|_| ())
+                    .and_then(|check| keys.decrypt_token(&check))
+                    .is_ok()
+            };
+            let rotated = match (current, new_secret) {
+                (Some(SecretRotationStage::Commit), _) => keys.without_previous_keys(),
+                (_, Ok(secret)) => {
+                    let secret = secret.expose_secret();
+                    match rotation.stage {
+                        SecretRotationStage::Prepare => keys.prepare_rotation(&secret[..]),
+                        SecretRotationStage::Commit => keys.rotate(&secret[..]),
+                        SecretRotationStage::Done => {
+                            keys.rotate(&secret[..]).without_previous_keys()
+                        }
+                    }
+                }
+                // The store was started with the new secret
+                (_, Err(())) if already_rotated() => keys.clone(),
+                (_, Err(())) => {
+                    error!(
+                        "Cannot switch to the secret of rotation {} because it was not encrypted with this store's secret",
+                        rotation.id
+                    );
+                    return Err(());
+                }
+            };
+            *keys = rotated;
+            *applied.lock() = Some((rotation.id, rotation.stage));
+            debug!(
+                "Switched keys to the {:?} stage of secret rotation {}",
+                rotation.stage, rotation.id
+            );
+        }
+    }
+
+    // Nothing waits for the last stage
+    if rotation.stage == SecretRotationStage::Done {
+        return Ok(());
+    }
+    let confirmation = serde_json::to_string(&SecretRotationConfirmation {
+        id: rotation.id,
+        stage: rotation.stage,
+    })
+    .map_err(|err| error!("Error serializing secret rotation confirmation: {:?}", err))?;
+    connection
+        .hset(
+            namespace.key(SECRET_ROTATION_CONFIRMATIONS_KEY),
+            instance_id.to_string(),
+            confirmation,
+        )
+        .map_err(|err| error!("Error confirming secret rotation: {:?}", err))
+        .await
+}
+
+async fn update_exchange_rates(
+    mut connection: RedisReconnect,
+    namespace: &Namespace,
+    exchange_rates: Arc<RwLock<HashMap<String, ExchangeRate>>>,
+    exchange_rates_updated_at: Arc<RwLock<HashMap<String, SystemTime>>>,
+) -> Result<(), ()> {
+    let mut pipe = redis_crate::pipe();
+    pipe.hgetall(namespace.key(RATES_KEY))
+        .hgetall(namespace.key(RATES_UPDATED_AT_KEY));
+    let (rates, updated_at): (HashMap<String, String>, HashMap<String, u64>) = pipe
+        .query_async(&mut connection)
+        .map_err(|err| error!("Error polling for exchange rate updates: {:?}", err))
+        .await?;
+    let rates = rates
+        .into_iter()
+        .map(|(code, rate)| Ok((code, ExchangeRate::from_str(&rate)?)))
+        .collect::<Result<HashMap<_, _>, String>>()
+        .map_err(|err| error!("Invalid exchange rate saved in Redis: {}", err))?;
+    // The hash is empty until one of the stores sets the rates
+    if !rates.is_empty() {
+        *exchange_rates_updated_at.write() = updated_at
+            .into_iter()
+            .map(|(code, updated_at)| (code, UNIX_EPOCH + Duration::from_millis(updated_at)))
+            .collect();
+        *exchange_rates.write() = rates;
+    }
+    Ok(())
+}
+
 // Uuid does not implement ToRedisArgs and FromRedisValue.
 // Rust does not allow implementing foreign traits on foreign data types.
 // As a result, we wrap Uuid in a local data type, and implement the necessary
 // traits for that.
+/// How an API key is saved in the hash of the account's API keys
+#[derive(Serialize, Deserialize)]
+struct RedisApiKey {
+    #[serde(flatten)]
+    key: ApiKey,
+    /// Hex encoded hash of the key's secret
+    secret_hash: String,
+}
+
 #[derive(Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize, Copy, Clone)]
 struct RedisAccountId(Uuid);
 
@@ -1956,6 +4199,17 @@ impl ToRedisArgs for AccountWithEncryptedTokens {
             "ilp_address".write_redis_args(&mut rv);
             rv.push(account.ilp_address.to_bytes().to_vec());
         }
+        if !account.additional_prefixes.is_empty() {
+            // Addresses can't contain commas
+            "additional_prefixes".write_redis_args(&mut rv);
+            account
+                .additional_prefixes
+                .iter()
+                .map(|prefix| prefix.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+                .write_redis_args(&mut rv);
+        }
         if !account.asset_code.is_empty() {
             "asset_code".write_redis_args(&mut rv);
             account.asset_code.write_redis_args(&mut rv);
@@ -2025,6 +4279,45 @@ impl ToRedisArgs for AccountWithEncryptedTokens {
             "amount_per_minute_limit".write_redis_args(&mut rv);
             limit.write_redis_args(&mut rv);
         }
+        if let Some(limit) = account.packets_burst_limit {
+            "packets_burst_limit".write_redis_args(&mut rv);
+            limit.write_redis_args(&mut rv);
+        }
+        if let Some(limit) = account.amount_burst_limit {
+            "amount_burst_limit".write_redis_args(&mut rv);
+            limit.write_redis_args(&mut rv);
+        }
+        if let Some(limit) = account.amount_per_day_limit {
+            "amount_per_day_limit".write_redis_args(&mut rv);
+            limit.write_redis_args(&mut rv);
+        }
+        if let Some(limit) = account.amount_per_month_limit {
+            "amount_per_month_limit".write_redis_args(&mut rv);
+            limit.write_redis_args(&mut rv);
+        }
+        if let Some(fee) = account.fixed_fee {
+            "fixed_fee".write_redis_args(&mut rv);
+            fee.write_redis_args(&mut rv);
+        }
+        if let Some(fee) = account.fee_basis_points {
+            "fee_basis_points".write_redis_args(&mut rv);
+            fee.write_redis_args(&mut rv);
+        }
+        // Destination prefixes are validated to not contain commas
+        if !account.allowed_destination_prefixes.is_empty() {
+            "allowed_destination_prefixes".write_redis_args(&mut rv);
+            account
+                .allowed_destination_prefixes
+                .join(",")
+                .write_redis_args(&mut rv);
+        }
+        if !account.denied_destination_prefixes.is_empty() {
+            "denied_destination_prefixes".write_redis_args(&mut rv);
+            account
+                .denied_destination_prefixes
+                .join(",")
+                .write_redis_args(&mut rv);
+        }
         if let Some(min_balance) = account.min_balance {
             "min_balance".write_redis_args(&mut rv);
             min_balance.write_redis_args(&mut rv);
@@ -2033,6 +4326,8 @@ impl ToRedisArgs for AccountWithEncryptedTokens {
             "settlement_engine_url".write_redis_args(&mut rv);
             settlement_engine_url.as_str().write_redis_args(&mut rv);
         }
+        "status".write_redis_args(&mut rv);
+        account.status.as_ref().write_redis_args(&mut rv);
 
         debug_assert!(rv.len() <= ACCOUNT_DETAILS_FIELDS * 2);
         debug_assert!((rv.len() % 2) == 0);
@@ -2047,6 +4342,13 @@ impl FromRedisValue for AccountWithEncryptedTokens {
         let ilp_address: String = get_value("ilp_address", &hash)?;
         let ilp_address = Address::from_str(&ilp_address)
             .map_err(|_| RedisError::from((ErrorKind::TypeError, "Invalid ILP address")))?;
+        let additional_prefixes: Option<String> = get_value_option("additional_prefixes", &hash)?;
+        let additional_prefixes = additional_prefixes
+            .iter()
+            .flat_map(|prefixes| prefixes.split(','))
+            .map(Address::from_str)
+            .collect::<Result<Vec<Address>, _>>()
+            .map_err(|_| RedisError::from((ErrorKind::TypeError, "Invalid address prefix")))?;
         let username: String = get_value("username", &hash)?;
         let username = Username::from_str(&username)
             .map_err(|_| RedisError::from((ErrorKind::TypeError, "Invalid username")))?;
@@ -2059,6 +4361,13 @@ impl FromRedisValue for AccountWithEncryptedTokens {
         };
         let round_trip_time: Option<u32> = get_value_option("round_trip_time", &hash)?;
         let round_trip_time: u32 = round_trip_time.unwrap_or(DEFAULT_ROUND_TRIP_TIME);
+        let status: Option<String> = get_value_option("status", &hash)?;
+        let status = if let Some(status) = status {
+            AccountStatus::from_str(status.as_str())
+                .map_err(|_| RedisError::from((ErrorKind::TypeError, "Invalid account status")))?
+        } else {
+            AccountStatus::Active
+        };
 
         let rid: RedisAccountId = get_value("id", &hash)?;
 
@@ -2067,6 +4376,7 @@ impl FromRedisValue for AccountWithEncryptedTokens {
                 id: rid.0,
                 username,
                 ilp_address,
+                additional_prefixes,
                 asset_code: get_value("asset_code", &hash)?,
                 asset_scale: get_value("asset_scale", &hash)?,
                 ilp_over_http_url: get_url_option("ilp_over_http_url", &hash)?,
@@ -2099,12 +4409,31 @@ impl FromRedisValue for AccountWithEncryptedTokens {
                 round_trip_time,
                 packets_per_minute_limit: get_value_option("packets_per_minute_limit", &hash)?,
                 amount_per_minute_limit: get_value_option("amount_per_minute_limit", &hash)?,
+                packets_burst_limit: get_value_option("packets_burst_limit", &hash)?,
+                amount_burst_limit: get_value_option("amount_burst_limit", &hash)?,
+                amount_per_day_limit: get_value_option("amount_per_day_limit", &hash)?,
+                amount_per_month_limit: get_value_option("amount_per_month_limit", &hash)?,
+                fixed_fee: get_value_option("fixed_fee", &hash)?,
+                fee_basis_points: get_value_option("fee_basis_points", &hash)?,
+                allowed_destination_prefixes: get_prefixes("allowed_destination_prefixes", &hash)?,
+                denied_destination_prefixes: get_prefixes("denied_destination_prefixes", &hash)?,
                 settlement_engine_url: get_url_option("settlement_engine_url", &hash)?,
+                status,
             },
         })
     }
 }
 
+/// Gets a comma separated list of prefixes, which is empty if the field is not set
+fn get_prefixes(key: &str, map: &HashMap<String, Value>) -> Result<Vec<String>, RedisError> {
+    let prefixes: Option<String> = get_value_option(key, map)?;
+    Ok(prefixes
+        .iter()
+        .flat_map(|prefixes| prefixes.split(','))
+        .map(String::from)
+        .collect())
+}
+
 fn get_value<V>(key: &str, map: &HashMap<String, Value>) -> Result<V, RedisError>
 where
     V: FromRedisValue,
@@ -0,0 +1,126 @@
+use log::error;
+use uuid::Uuid;
+
+/// The key prefix of stores which were not given one
+pub static DEFAULT_KEY_PREFIX: &str = "ilp";
+
+/// Names of the keys and pub/sub channels of a store.
+///
+/// Every key starts with the Redis Cluster hash tag `{<prefix>}:`, which puts all
+/// of the store's keys in the same hash slot. This is needed on Redis Cluster
+/// because the Lua scripts and transactions access several keys at once.
+/// Stores with different prefixes can share the same Redis database: they do not
+/// see each other's keys, and the channels of a store with a prefix other than
+/// the default start with `<prefix>:` so they do not get each other's messages.
+#[derive(Clone, Debug)]
+pub struct Namespace {
+    prefix: String,
+    key_prefix: String,
+    channel_prefix: String,
+}
+
+impl Namespace {
+    /// Returns an error if the prefix is empty or contains characters other than
+    /// ASCII letters, digits, `-`, `_` and `.`, which could be mistaken for part
+    /// of the hash tag or of a pub/sub pattern
+    pub fn new(prefix: &str) -> Result<Self, ()> {
+        if prefix.is_empty()
+            || !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            error!(
+                "Invalid Redis key prefix {:?}: it may only contain letters, digits, '-', '_' and '.'",
+                prefix
+            );
+            return Err(());
+        }
+        let channel_prefix = if prefix == DEFAULT_KEY_PREFIX {
+            // The channels of stores without a prefix did not have one either
+            String::new()
+        } else {
+            format!("{}:", prefix)
+        };
+        Ok(Namespace {
+            prefix: prefix.to_string(),
+            key_prefix: format!("{{{}}}:", prefix),
+            channel_prefix,
+        })
+    }
+
+    /// Returns true if this is the namespace of stores which were not given a prefix
+    pub fn is_default(&self) -> bool {
+        self.prefix == DEFAULT_KEY_PREFIX
+    }
+
+    /// Returns the key prefix followed by `name`
+    pub fn key(&self, name: &str) -> String {
+        format!("{}{}", self.key_prefix, name)
+    }
+
+    /// Returns the channel prefix followed by `name`
+    pub fn channel(&self, name: &str) -> String {
+        format!("{}{}", self.channel_prefix, name)
+    }
+
+    /// Prefix of the account hashes, which are followed by the account id
+    pub fn accounts_key_prefix(&self) -> String {
+        self.key("accounts:")
+    }
+
+    /// Domain separator for accounts
+    pub fn accounts_key(&self, account_id: Uuid) -> String {
+        format!("{}{}", self.accounts_key_prefix(), account_id)
+    }
+
+    /// The stream of balance journal entries, whose ids carry the entries' timestamps
+    pub fn balance_journal_key(&self, account_id: Uuid) -> String {
+        self.key(&format!("balance_journal:{}", account_id))
+    }
+
+    /// The stream of the rates set for an asset, whose ids carry the times they were saved
+    pub fn rate_history_key(&self, asset_code: &str) -> String {
+        self.key(&format!("rates:history:{}", asset_code))
+    }
+
+    /// The hash of the account's API keys, mapping their ids to `RedisApiKey`s
+    pub fn api_keys_key(&self, account_id: Uuid) -> String {
+        self.key(&format!("api_keys:{}", account_id))
+    }
+
+    /// Prefix of the keys which expire unless the connected stores refresh them,
+    /// which are followed by the store's id
+    pub fn instance_key_prefix(&self) -> String {
+        self.key("instances:")
+    }
+
+    /// Set while the store with the given id is connected
+    pub fn instance_key(&self, instance_id: Uuid) -> String {
+        format!("{}{}", self.instance_key_prefix(), instance_id)
+    }
+
+    /// Domain separator for leftover amounts
+    pub fn uncredited_amount_key(&self, account_id: impl ToString) -> String {
+        self.key(&format!("uncredited-amount:{}", account_id.to_string()))
+    }
+
+    /// Domain separator for idempotency keys
+    pub fn prefixed_idempotency_key(&self, idempotency_key: &str) -> String {
+        self.key(&format!("idempotency-key:{}", idempotency_key))
+    }
+
+    /// Marks an incoming settlement as processed
+    pub fn settlement_idempotency_key(&self, idempotency_key: &str) -> String {
+        self.key(&format!("settlement-idempotency-key:{}", idempotency_key))
+    }
+
+    /// The state of one of an account's rate limits, which redis-cell keeps
+    pub fn rate_limit_key(&self, limit: &str, account_id: Uuid) -> String {
+        self.key(&format!("limit:{}:{}", limit, account_id))
+    }
+
+    /// The amount an account sent during a day or month, such as `sent:day:2020-01-31:<id>`
+    pub fn amount_sent_key(&self, period: &str, period_id: &str, account_id: Uuid) -> String {
+        self.key(&format!("sent:{}:{}:{}", period, period_id, account_id))
+    }
+}
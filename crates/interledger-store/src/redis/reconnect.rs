@@ -1,24 +1,134 @@
 use futures::future::{FutureExt, TryFutureExt};
-use log::{debug, error};
+use log::{debug, error, warn};
 use parking_lot::RwLock;
 use redis_crate::{
     aio::{ConnectionLike, MultiplexedConnection},
-    Client, Cmd, ConnectionInfo, Pipeline, RedisError, RedisFuture, Value,
+    cmd, Client, Cmd, ConnectionAddr, ConnectionInfo, ErrorKind, Pipeline, RedisError, RedisFuture,
+    Value,
 };
 use std::sync::Arc;
 
 type Result<T> = std::result::Result<T, RedisError>;
 
+/// The Redis server(s) that the store connects to
+#[derive(Clone, Debug)]
+pub enum RedisTarget {
+    /// A single Redis server (or any node of a Redis Cluster)
+    Direct(ConnectionInfo),
+    /// The master that Redis Sentinel monitors under the given name
+    Sentinel {
+        master_name: String,
+        /// The sentinels, which are asked for the address of the master in order
+        sentinels: Vec<ConnectionInfo>,
+        /// Provides the database and password used for the master
+        master_info: ConnectionInfo,
+    },
+}
+
+impl RedisTarget {
+    /// Finds the server to connect to
+    pub(crate) async fn resolve(&self) -> Result<ConnectionInfo> {
+        match self {
+            RedisTarget::Direct(redis_info) => Ok(redis_info.clone()),
+            RedisTarget::Sentinel {
+                master_name,
+                sentinels,
+                master_info,
+            } => {
+                let mut last_error = None;
+                for sentinel in sentinels {
+                    match get_master_info(sentinel, master_name, master_info).await {
+                        Ok(redis_info) => return Ok(redis_info),
+                        Err(err) => {
+                            warn!(
+                                "Could not get the address of master {} from Redis Sentinel {:?}: {:?}",
+                                master_name, sentinel.addr, err
+                            );
+                            last_error = Some(err);
+                        }
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    RedisError::from((ErrorKind::InvalidClientConfig, "No Redis Sentinel given"))
+                }))
+            }
+        }
+    }
+}
+
+/// Asks a sentinel for the address of the master and checks that it
+/// is still the master, since the sentinel may not have noticed a failover yet
+async fn get_master_info(
+    sentinel: &ConnectionInfo,
+    master_name: &str,
+    master_info: &ConnectionInfo,
+) -> Result<ConnectionInfo> {
+    let mut connection = Client::open(sentinel.clone())?
+        .get_async_connection()
+        .await?;
+    let address: Option<(String, u16)> = cmd("SENTINEL")
+        .arg("get-master-addr-by-name")
+        .arg(master_name)
+        .query_async(&mut connection)
+        .await?;
+    let (host, port) = address.ok_or_else(|| {
+        RedisError::from((
+            ErrorKind::ResponseError,
+            "Sentinel does not monitor the master",
+        ))
+    })?;
+    let redis_info = ConnectionInfo {
+        addr: Box::new(ConnectionAddr::Tcp(host, port)),
+        db: master_info.db,
+        passwd: master_info.passwd.clone(),
+    };
+
+    let mut connection = Client::open(redis_info.clone())?
+        .get_async_connection()
+        .await?;
+    let role: Vec<Value> = cmd("ROLE").query_async(&mut connection).await?;
+    match role.first() {
+        Some(Value::Data(role)) if role.as_slice() == b"master" => {
+            debug!("Redis Sentinel returned master {:?}", redis_info.addr);
+            Ok(redis_info)
+        }
+        _ => Err(RedisError::from((
+            ErrorKind::ResponseError,
+            "Server returned by Sentinel is not a master",
+        ))),
+    }
+}
+
+/// Returns the node which a Redis Cluster redirected a command to
+fn moved_to(error: &RedisError) -> Option<ConnectionAddr> {
+    if error.kind() != ErrorKind::Moved {
+        return None;
+    }
+    // The detail is "<slot> <host>:<port>"
+    let node = error.detail()?.split_whitespace().nth(1)?;
+    let separator = node.rfind(':')?;
+    let port = node[separator + 1..].parse().ok()?;
+    Some(ConnectionAddr::Tcp(node[..separator].to_string(), port))
+}
+
 /// Wrapper around a Redis MultiplexedConnection that automatically
-/// attempts to reconnect to the DB if the connection is dropped
+/// attempts to reconnect to the DB if the connection is dropped.
+///
+/// It also follows the master when it fails over: the master is looked up
+/// again (from Redis Sentinel, if it is used) whenever the server it is
+/// connected to was demoted to a read-only replica. On Redis Cluster, it
+/// connects to the node that the keys were moved to. In both cases the
+/// failed command is retried once.
 #[derive(Clone)]
 pub struct RedisReconnect {
-    pub(crate) redis_info: Arc<ConnectionInfo>,
+    pub(crate) target: Arc<RedisTarget>,
+    /// The server the connection is currently open to
+    pub(crate) redis_info: Arc<RwLock<ConnectionInfo>>,
     pub(crate) conn: Arc<RwLock<MultiplexedConnection>>,
 }
 
-async fn get_shared_connection(redis_info: Arc<ConnectionInfo>) -> Result<MultiplexedConnection> {
-    let client = Client::open((*redis_info).clone())?;
+async fn get_shared_connection(redis_info: &ConnectionInfo) -> Result<MultiplexedConnection> {
+    let client = Client::open(redis_info.clone())?;
     client
         .get_multiplexed_tokio_connection()
         .map_err(|e| {
@@ -29,27 +139,64 @@ async fn get_shared_connection(redis_info: Arc<ConnectionInfo>) -> Result<Multip
 }
 
 impl RedisReconnect {
-    /// Connects to redis with the provided [`ConnectionInfo`](redis_crate::ConnectionInfo)
-    pub async fn connect(redis_info: ConnectionInfo) -> Result<RedisReconnect> {
-        let redis_info = Arc::new(redis_info);
-        let conn = get_shared_connection(redis_info.clone()).await?;
+    /// Connects to the server described by the [`RedisTarget`](./enum.RedisTarget.html)
+    pub async fn connect(target: RedisTarget) -> Result<RedisReconnect> {
+        let redis_info = target.resolve().await?;
+        let conn = get_shared_connection(&redis_info).await?;
         Ok(RedisReconnect {
+            target: Arc::new(target),
+            redis_info: Arc::new(RwLock::new(redis_info)),
             conn: Arc::new(RwLock::new(conn)),
-            redis_info,
         })
     }
 
     /// Reconnects to redis
     pub async fn reconnect(self) -> Result<Self> {
-        let shared_connection = get_shared_connection(self.redis_info.clone()).await?;
+        let redis_info = self.target.resolve().await?;
+        self.reconnect_to(redis_info).await
+    }
+
+    /// Replaces the connection with one to the given server
+    async fn reconnect_to(self, redis_info: ConnectionInfo) -> Result<Self> {
+        let shared_connection = get_shared_connection(&redis_info).await?;
         (*self.conn.write()) = shared_connection;
-        debug!("Reconnected to Redis");
+        debug!("Reconnected to Redis at {:?}", redis_info.addr);
+        (*self.redis_info.write()) = redis_info;
         Ok(self)
     }
 
+    /// Returns the server the connection is currently open to
+    pub fn redis_info(&self) -> ConnectionInfo {
+        self.redis_info.read().clone()
+    }
+
     fn get_shared_connection(&self) -> MultiplexedConnection {
         self.conn.read().clone()
     }
+
+    /// Reconnects if the error shows that the connection can't be used anymore.
+    /// Returns true if the command failed without being executed and can be
+    /// retried on the new connection
+    async fn recover(self, error: &RedisError) -> bool {
+        if error.is_connection_dropped() {
+            debug!("Redis connection was dropped, attempting to reconnect");
+            // The command may have been executed before the connection was dropped
+            let _ = self.reconnect().await;
+            false
+        } else if error.code() == Some("READONLY") {
+            warn!("Redis server is a read-only replica, reconnecting to the master");
+            self.reconnect().await.is_ok()
+        } else if let Some(addr) = moved_to(error) {
+            debug!("Redis Cluster moved the keys to {:?}, reconnecting", addr);
+            let redis_info = ConnectionInfo {
+                addr: Box::new(addr),
+                ..self.redis_info()
+            };
+            self.reconnect_to(redis_info).await.is_ok()
+        } else {
+            false
+        }
+    }
 }
 
 impl ConnectionLike for RedisReconnect {
@@ -64,12 +211,12 @@ impl ConnectionLike for RedisReconnect {
             match connection.req_packed_command(cmd).await {
                 Ok(res) => Ok(res),
                 Err(error) => {
-                    if error.is_connection_dropped() {
-                        debug!("Redis connection was dropped, attempting to reconnect");
-                        // TODO: Is this correct syntax? Otherwise we get an unused result warning
-                        let _ = self.clone().reconnect().await;
+                    if self.clone().recover(&error).await {
+                        let mut connection = self.get_shared_connection();
+                        connection.req_packed_command(cmd).await
+                    } else {
+                        Err(error)
                     }
-                    Err(error)
                 }
             }
         })
@@ -88,12 +235,12 @@ impl ConnectionLike for RedisReconnect {
             match connection.req_packed_commands(cmd, offset, count).await {
                 Ok(res) => Ok(res),
                 Err(error) => {
-                    if error.is_connection_dropped() {
-                        debug!("Redis connection was dropped, attempting to reconnect");
-                        // TODO: Is this correct syntax? Otherwise we get an unused result warning
-                        let _ = self.clone().reconnect().await;
+                    if self.clone().recover(&error).await {
+                        let mut connection = self.get_shared_connection();
+                        connection.req_packed_commands(cmd, offset, count).await
+                    } else {
+                        Err(error)
                     }
-                    Err(error)
                 }
             }
         })
@@ -0,0 +1,270 @@
+use log::{debug, error, info};
+use rusqlite::{Connection, TransactionBehavior, NO_PARAMS};
+
+/// The version of the layout of the data which is stored in SQLite, which is
+/// saved as the database's `user_version`. It must be incremented whenever a
+/// migration is added to `MIGRATIONS`
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// Description and statements of the migration which upgrades the data to each
+/// version. New databases, and databases written before the version was saved,
+/// have version 0.
+static MIGRATIONS: [(&str, &str); SCHEMA_VERSION as usize] = [
+    ("Create the accounts, routes and settlement tables", MIGRATION_1),
+    (
+        "Move the status, burst limits, amount limits and fees of the accounts into the accounts table and create the tables of the other account details",
+        MIGRATION_2,
+    ),
+    (
+        "Index the exchange rate history by asset code",
+        MIGRATION_3,
+    ),
+    (
+        "Save the rates of the exchange rate history as decimal strings",
+        MIGRATION_4,
+    ),
+];
+
+static MIGRATION_1: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        id TEXT PRIMARY KEY,
+        username TEXT NOT NULL UNIQUE,
+        ilp_address TEXT NOT NULL,
+        asset_code TEXT NOT NULL,
+        asset_scale INTEGER NOT NULL,
+        max_packet_amount INTEGER NOT NULL,
+        min_balance INTEGER,
+        ilp_over_http_url TEXT,
+        ilp_over_http_incoming_token BLOB,
+        ilp_over_http_outgoing_token BLOB,
+        ilp_over_btp_url TEXT,
+        ilp_over_btp_incoming_token BLOB,
+        ilp_over_btp_outgoing_token BLOB,
+        settle_threshold INTEGER,
+        settle_to INTEGER,
+        routing_relation TEXT NOT NULL,
+        round_trip_time INTEGER NOT NULL,
+        packets_per_minute_limit INTEGER,
+        amount_per_minute_limit INTEGER,
+        settlement_engine_url TEXT,
+        balance INTEGER NOT NULL DEFAULT 0,
+        prepaid_amount INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS routes (
+        prefix TEXT PRIMARY KEY,
+        account_id TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS static_routes (
+        prefix TEXT PRIMARY KEY,
+        account_id TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS settlement_engines (
+        asset_code TEXT PRIMARY KEY,
+        url TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS uncredited_settlement_amounts (
+        account_id TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        scale INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS uncredited_settlement_amounts_account_id
+        ON uncredited_settlement_amounts (account_id);
+    CREATE TABLE IF NOT EXISTS idempotency_keys (
+        key TEXT PRIMARY KEY,
+        status_code INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        input_hash BLOB NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS settlement_idempotency_keys (
+        key TEXT PRIMARY KEY,
+        expires_at INTEGER NOT NULL
+    );
+";
+
+// Databases written before the version was saved may already have some of these
+// tables. The ones which only held columns of the accounts are created (if needed)
+// so that their rows can be copied, and then dropped
+static MIGRATION_2: &str = "
+    CREATE TABLE IF NOT EXISTS account_statuses (
+        account_id TEXT PRIMARY KEY,
+        status TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS account_burst_limits (
+        account_id TEXT PRIMARY KEY,
+        packets_burst_limit INTEGER,
+        amount_burst_limit INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS account_amount_limits (
+        account_id TEXT PRIMARY KEY,
+        amount_per_day_limit INTEGER,
+        amount_per_month_limit INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS account_fees (
+        account_id TEXT PRIMARY KEY,
+        fixed_fee INTEGER,
+        fee_basis_points INTEGER
+    );
+
+    ALTER TABLE accounts ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+    ALTER TABLE accounts ADD COLUMN packets_burst_limit INTEGER;
+    ALTER TABLE accounts ADD COLUMN amount_burst_limit INTEGER;
+    ALTER TABLE accounts ADD COLUMN amount_per_day_limit INTEGER;
+    ALTER TABLE accounts ADD COLUMN amount_per_month_limit INTEGER;
+    ALTER TABLE accounts ADD COLUMN fixed_fee INTEGER;
+    ALTER TABLE accounts ADD COLUMN fee_basis_points INTEGER;
+
+    UPDATE accounts SET status = COALESCE(
+        (SELECT s.status FROM account_statuses s WHERE s.account_id = accounts.id),
+        'active'
+    );
+    UPDATE accounts SET
+        packets_burst_limit = (SELECT b.packets_burst_limit FROM account_burst_limits b
+            WHERE b.account_id = accounts.id),
+        amount_burst_limit = (SELECT b.amount_burst_limit FROM account_burst_limits b
+            WHERE b.account_id = accounts.id),
+        amount_per_day_limit = (SELECT l.amount_per_day_limit FROM account_amount_limits l
+            WHERE l.account_id = accounts.id),
+        amount_per_month_limit = (SELECT l.amount_per_month_limit FROM account_amount_limits l
+            WHERE l.account_id = accounts.id),
+        fixed_fee = (SELECT f.fixed_fee FROM account_fees f WHERE f.account_id = accounts.id),
+        fee_basis_points = (SELECT f.fee_basis_points FROM account_fees f
+            WHERE f.account_id = accounts.id);
+
+    DROP TABLE account_statuses;
+    DROP TABLE account_burst_limits;
+    DROP TABLE account_amount_limits;
+    DROP TABLE account_fees;
+
+    CREATE TABLE IF NOT EXISTS account_prefixes (
+        prefix TEXT NOT NULL,
+        account_id TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS account_prefixes_account_id
+        ON account_prefixes (account_id);
+    CREATE TABLE IF NOT EXISTS amounts_sent (
+        account_id TEXT NOT NULL,
+        period TEXT NOT NULL,
+        period_id TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        PRIMARY KEY (account_id, period)
+    );
+    CREATE TABLE IF NOT EXISTS account_destination_prefixes (
+        account_id TEXT NOT NULL,
+        prefix TEXT NOT NULL,
+        allowed INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS account_destination_prefixes_account_id
+        ON account_destination_prefixes (account_id);
+    CREATE TABLE IF NOT EXISTS fees_earned (
+        from_account_id TEXT NOT NULL,
+        to_account_id TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        PRIMARY KEY (from_account_id, to_account_id)
+    );
+    CREATE TABLE IF NOT EXISTS api_keys (
+        id TEXT PRIMARY KEY,
+        account_id TEXT NOT NULL,
+        secret_hash BLOB NOT NULL,
+        scopes TEXT NOT NULL,
+        description TEXT
+    );
+    CREATE INDEX IF NOT EXISTS api_keys_account_id
+        ON api_keys (account_id);
+    CREATE TABLE IF NOT EXISTS balance_journal (
+        account_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        delta INTEGER NOT NULL,
+        balance INTEGER NOT NULL,
+        execution_condition TEXT,
+        counterparty TEXT
+    );
+    CREATE INDEX IF NOT EXISTS balance_journal_account_id_timestamp
+        ON balance_journal (account_id, timestamp);
+    CREATE TABLE IF NOT EXISTS exchange_rate_history (
+        timestamp INTEGER NOT NULL,
+        asset_code TEXT NOT NULL,
+        rate REAL NOT NULL,
+        source TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS exchange_rate_history_timestamp
+        ON exchange_rate_history (timestamp);
+";
+
+static MIGRATION_3: &str = "
+    CREATE INDEX IF NOT EXISTS exchange_rate_history_asset_code
+        ON exchange_rate_history (asset_code);
+";
+
+// SQLite can't change the type of a column, so the history is copied into a new table.
+// A column with REAL affinity would convert the decimal strings back to floats.
+// The rowids are kept, because they are the cursors of the pages of the history
+static MIGRATION_4: &str = "
+    ALTER TABLE exchange_rate_history RENAME TO exchange_rate_history_old;
+    CREATE TABLE exchange_rate_history (
+        timestamp INTEGER NOT NULL,
+        asset_code TEXT NOT NULL,
+        rate TEXT NOT NULL,
+        source TEXT NOT NULL
+    );
+    INSERT INTO exchange_rate_history (rowid, timestamp, asset_code, rate, source)
+        SELECT rowid, timestamp, asset_code, CAST(rate AS TEXT), source
+        FROM exchange_rate_history_old;
+    DROP TABLE exchange_rate_history_old;
+    CREATE INDEX exchange_rate_history_timestamp
+        ON exchange_rate_history (timestamp);
+    CREATE INDEX exchange_rate_history_asset_code
+        ON exchange_rate_history (asset_code);
+";
+
+/// Upgrades the data stored in SQLite to the current `SCHEMA_VERSION` by running
+/// the migrations which were not run yet, in order.
+///
+/// The migrations and the new version are committed in a single transaction, so
+/// a failed upgrade leaves the data as it was. Other nodes opening the same
+/// database wait until it is committed and then find the data up to date.
+pub fn migrate(connection: &mut Connection) -> Result<u32, ()> {
+    let transaction = connection
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|err| error!("Error starting the SQLite migration: {:?}", err))?;
+    let version: u32 = transaction
+        .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+        .map_err(|err| error!("Error loading the schema version: {:?}", err))?;
+    if version > SCHEMA_VERSION {
+        error!(
+            "The data in SQLite has schema version {}, which is newer than the version supported by this node ({}). Please upgrade the node",
+            version, SCHEMA_VERSION
+        );
+        return Err(());
+    }
+
+    for next_version in version + 1..=SCHEMA_VERSION {
+        let (description, statements) = MIGRATIONS[next_version as usize - 1];
+        info!(
+            "Migrating the data in SQLite to schema version {}: {}",
+            next_version, description
+        );
+        transaction
+            .execute_batch(statements)
+            .and_then(|_| {
+                // PRAGMA statements can't have parameters
+                transaction.execute_batch(&format!("PRAGMA user_version = {}", next_version))
+            })
+            .map_err(|err| {
+                error!(
+                    "Error migrating the data in SQLite to schema version {}: {:?}",
+                    next_version, err
+                )
+            })?;
+    }
+    transaction
+        .commit()
+        .map_err(|err| error!("Error committing the SQLite migration: {:?}", err))?;
+    debug!("The data in SQLite has schema version {}", SCHEMA_VERSION);
+    Ok(SCHEMA_VERSION)
+}
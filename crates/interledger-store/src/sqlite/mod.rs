@@ -0,0 +1,3044 @@
+// The schema of our data in SQLite:
+//   accounts                     information and balances for each account
+//   account_prefixes             additional address prefixes of the accounts
+//   amounts_sent                 amounts sent by the accounts in their current day and month
+//   fees_earned                  total fees charged per pair of accounts
+//   account_destination_prefixes address prefixes the accounts may or may not send to
+//   api_keys                     hashed API keys of the accounts and their scopes
+//   routes                       dynamic routing table
+//   static_routes                static routing table
+//   settings                     node-wide values (parent address, default route, spreads)
+//   settlement_engines           asset code -> settlement engine url
+//   uncredited_settlement_amounts leftovers which could not be credited yet
+//   idempotency_keys             cached settlement API responses
+//   settlement_idempotency_keys  incoming settlements which were already credited
+//   balance_journal              balance changes of the accounts
+//   exchange_rate_history        the last exchange rates set for each asset and their sources
+// The version of this layout is saved as the database's user_version (see migrations.rs).
+// For interactive exploration of the store, open the database file with the
+// sqlite3 command line tool and use `.tables` and `.schema <table>`.
+
+mod migrations;
+
+pub use migrations::SCHEMA_VERSION;
+
+use super::account::{validate_incoming_token, Account, AccountWithEncryptedTokens};
+use super::crypto::{hash_token, is_token_hash, StoreKeys, TokenVerifier, TOKEN_HASH_PREFIX};
+use super::throttle::{add_amount_sent, Throttle};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc::UnboundedSender;
+use http::StatusCode;
+use interledger_api::{
+    AccountDetails, AccountSettings, AccountsPage, AccountsQuery, ApiKey, ApiKeyScope,
+    ExportedAccount, NodeStore,
+};
+use interledger_btp::BtpStore;
+use interledger_ccp::{RouteManagerStore, RoutingRelation};
+use interledger_http::HttpStore;
+use interledger_packet::Address;
+use interledger_router::RouterStore;
+use interledger_service::{AccountStatus, AccountStore, AddressStore, Username};
+use interledger_service_util::{
+    journal_timestamp, AmountLimitPeriod, BalanceChangeKind, BalanceHistoryPage,
+    BalanceHistoryQuery, BalanceJournalEntry, BalanceStore, CumulativeAmounts, ExchangeRate,
+    ExchangeRateHistoryPage, ExchangeRateHistoryQuery, ExchangeRateHistoryStore,
+    ExchangeRateRecord, ExchangeRateStore, FeeStore, FeesEarned, PacketContext, PairQuote,
+    RateLimitError, RateLimitStore, Spreads, BALANCE_JOURNAL_RETENTION,
+    EXCHANGE_RATE_HISTORY_LENGTH,
+};
+use interledger_settlement::core::{
+    idempotency::{IdempotentData, IdempotentStore},
+    scale_with_precision_loss,
+    types::{Convert, ConvertDetails, LeftoversStore, SettlementStore},
+};
+use interledger_stream::{PaymentNotification, StreamNotificationsStore};
+use lazy_static::lazy_static;
+use log::{debug, error, trace, warn};
+use num_bigint::BigUint;
+use parking_lot::{Mutex, RwLock};
+use rusqlite::{
+    params, types::Type, Connection, OptionalExtension, Row, TransactionBehavior, NO_PARAMS,
+};
+use secrecy::{ExposeSecret, SecretBytesMut, SecretString};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    iter::FromIterator,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+use uuid::Uuid;
+use zeroize::Zeroize;
+
+/// Idempotency keys expire after 24 hours, same as in the Redis store
+const IDEMPOTENCY_KEY_EXPIRY: u64 = 86400;
+/// How long to wait for another connection to release its lock on the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Columns containing encrypted tokens
+const TOKEN_COLUMNS: [&str; 4] = [
+    "ilp_over_http_incoming_token",
+    "ilp_over_http_outgoing_token",
+    "ilp_over_btp_incoming_token",
+    "ilp_over_btp_outgoing_token",
+];
+
+static PARENT_ILP_KEY: &str = "parent_node_account_address";
+static DEFAULT_ROUTE_KEY: &str = "default_route";
+/// The spreads set with `set_spreads`, as JSON
+static SPREADS_KEY: &str = "spreads";
+
+/// Loads accounts, using the globally configured settlement engine
+/// for the account's currency if it does not have one set
+static SELECT_ACCOUNTS: &str = "
+    SELECT a.id, a.username, a.ilp_address, a.asset_code, a.asset_scale,
+        a.max_packet_amount, a.min_balance,
+        a.ilp_over_http_url, a.ilp_over_http_incoming_token, a.ilp_over_http_outgoing_token,
+        a.ilp_over_btp_url, a.ilp_over_btp_incoming_token, a.ilp_over_btp_outgoing_token,
+        a.settle_threshold, a.settle_to, a.routing_relation, a.round_trip_time,
+        a.packets_per_minute_limit, a.amount_per_minute_limit,
+        COALESCE(a.settlement_engine_url, e.url),
+        (SELECT group_concat(p.prefix, ',') FROM account_prefixes p WHERE p.account_id = a.id),
+        a.status, a.packets_burst_limit, a.amount_burst_limit,
+        a.amount_per_day_limit, a.amount_per_month_limit,
+        a.fixed_fee, a.fee_basis_points,
+        (SELECT group_concat(d.prefix, ',') FROM account_destination_prefixes d
+            WHERE d.account_id = a.id AND d.allowed = 1),
+        (SELECT group_concat(d.prefix, ',') FROM account_destination_prefixes d
+            WHERE d.account_id = a.id AND d.allowed = 0)
+    FROM accounts a
+        LEFT JOIN settlement_engines e ON a.asset_code = e.asset_code";
+
+lazy_static! {
+    /// The node's default ILP Address
+    static ref DEFAULT_ILP_ADDRESS: Address = Address::from_str("local.host").unwrap();
+}
+
+/// Builder for the SQLite Store
+pub struct SqliteStoreBuilder {
+    path: PathBuf,
+    secret: [u8; 32],
+    /// Connector's ILP Address. Used to derive the addresses of `Child` accounts
+    node_ilp_address: Address,
+}
+
+impl SqliteStoreBuilder {
+    /// Simple Constructor. The path may be `:memory:` to use a database
+    /// which is not persisted to disk.
+    pub fn new(path: impl Into<PathBuf>, secret: [u8; 32]) -> Self {
+        SqliteStoreBuilder {
+            path: path.into(),
+            secret,
+            node_ilp_address: DEFAULT_ILP_ADDRESS.clone(),
+        }
+    }
+
+    /// Sets the ILP Address corresponding to the node
+    pub fn node_ilp_address(&mut self, node_ilp_address: Address) -> &mut Self {
+        self.node_ilp_address = node_ilp_address;
+        self
+    }
+
+    /// Opens (or creates) the database file and migrates it to the current schema
+    /// version on the blocking thread pool
+    async fn open(&self) -> Result<(Connection, u32), ()> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = Connection::open(&path)
+                .map_err(|err| error!("Error opening SQLite database at {:?}: {:?}", path, err))?;
+            connection
+                .busy_timeout(BUSY_TIMEOUT)
+                .map_err(|err| error!("Error setting the SQLite busy timeout: {:?}", err))?;
+            debug!("Opened SQLite database at {:?}", path);
+            let version = migrations::migrate(&mut connection)?;
+            Ok((connection, version))
+        })
+        .await
+        .map_err(|err| error!("Error opening SQLite database: {:?}", err))?
+    }
+
+    /// Creates the tables or upgrades them to the current [`SCHEMA_VERSION`](./constant.SCHEMA_VERSION.html)
+    /// without connecting the store. This is also done by `connect`.
+    pub async fn migrate(&self) -> Result<u32, ()> {
+        self.open().await.map(|(_, version)| version)
+    }
+
+    /// Opens the SQLite database
+    ///
+    /// Specifically
+    /// 1. Generates encryption and decryption keys
+    /// 1. Opens (or creates) the database file
+    /// 1. Creates the tables or migrates them to the current schema version
+    /// 1. Gets the Node address assigned to us by our parent (if it exists)
+    /// 1. Loads the routing table into memory
+    pub async fn connect(&mut self) -> Result<SqliteStore, ()> {
+        let keys = StoreKeys::new(&self.secret[..]);
+        self.secret.zeroize(); // clear the secret after it has been used for key generation
+
+        let (connection, _) = self.open().await?;
+
+        // Before initializing the store, check if we have an address
+        // that was configured due to adding a parent. If no parent was
+        // found, use the builder's provided address (local.host) or the
+        // one we decided to override it with
+        let address: Option<String> = get_setting(&connection, PARENT_ILP_KEY).map_err(|err| {
+            error!(
+                "Error checking whether we have a parent configured: {:?}",
+                err
+            )
+        })?;
+        let node_ilp_address = match address {
+            Some(address) => Address::from_str(&address)
+                .map_err(|err| error!("Invalid parent address stored in SQLite: {:?}", err))?,
+            None => self.node_ilp_address.clone(),
+        };
+        let spreads: Option<Spreads> = get_setting(&connection, SPREADS_KEY)
+            .map_err(|err| error!("Error loading the spreads: {:?}", err))?
+            .map(|spreads| serde_json::from_str(&spreads))
+            .transpose()
+            .map_err(|err| error!("Invalid spreads stored in SQLite: {:?}", err))?;
+
+        let store = SqliteStore {
+            ilp_address: Arc::new(RwLock::new(node_ilp_address)),
+            connection: Arc::new(Mutex::new(connection)),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rates: Arc::new(RwLock::new(HashMap::new())),
+            exchange_rates_updated_at: Arc::new(RwLock::new(HashMap::new())),
+            spreads_set: Arc::new(AtomicBool::new(spreads.is_some())),
+            spreads: Arc::new(RwLock::new(spreads.unwrap_or_default())),
+            pair_quotes: Arc::new(RwLock::new(HashMap::new())),
+            routes: Arc::new(RwLock::new(Arc::new(HashMap::new()))),
+            rate_limits: Arc::new(Mutex::new(Throttle::default())),
+            keys: Arc::new(RwLock::new(keys)),
+            secret_rotation_in_progress: Arc::new(AtomicBool::new(false)),
+            token_verifier: Arc::new(TokenVerifier::new()),
+        };
+        store.update_routes(&store.connection.lock())?;
+        Ok(store)
+    }
+}
+
+/// Base asset code -> quote asset code -> quote and when it was set
+type PairQuotes = HashMap<String, HashMap<String, (PairQuote, SystemTime)>>;
+
+/// A Store that persists its data in a SQLite database.
+///
+/// Balance updates are done in transactions which are equivalent to the Lua
+/// scripts used by the RedisStore. Queries are executed on the blocking thread pool
+/// and share a single connection, which is intended for nodes running on one machine
+/// with modest load. Only the exchange rate history is written synchronously, because
+/// `ExchangeRateStore::set_exchange_rates` is not async.
+#[derive(Clone)]
+pub struct SqliteStore {
+    /// The Store's ILP Address
+    ilp_address: Arc<RwLock<Address>>,
+    connection: Arc<Mutex<Connection>>,
+    /// WebSocket sender which publishes incoming payment updates
+    subscriptions: Arc<RwLock<HashMap<Uuid, UnboundedSender<PaymentNotification>>>>,
+    exchange_rates: Arc<RwLock<HashMap<String, ExchangeRate>>>,
+    /// When each of the exchange rates was last set
+    exchange_rates_updated_at: Arc<RwLock<HashMap<String, SystemTime>>>,
+    spreads: Arc<RwLock<Spreads>>,
+    /// Whether the spreads were set with `set_spreads` (and saved), after
+    /// which the default spreads no longer apply
+    spreads_set: Arc<AtomicBool>,
+    pair_quotes: Arc<RwLock<PairQuotes>>,
+    /// The routing table is loaded into memory whenever the routes change so that
+    /// it can be returned synchronously while the Router is processing packets.
+    routes: Arc<RwLock<Arc<HashMap<String, Uuid>>>>,
+    /// Packet and throughput limits are only tracked in memory
+    rate_limits: Arc<Mutex<Throttle>>,
+    /// Keys used to encrypt and decrypt the account tokens,
+    /// which are replaced when the secret is rotated
+    keys: Arc<RwLock<StoreKeys>>,
+    secret_rotation_in_progress: Arc<AtomicBool>,
+    /// Verifies the tokens presented by peers against the hashed incoming tokens
+    token_verifier: Arc<TokenVerifier>,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn get_setting(connection: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    connection
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+}
+
+/// Reads a column which is stored as text and parses it into `T`
+fn parse_column<T>(row: &Row<'_>, idx: usize) -> rusqlite::Result<T>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    let value: String = row.get(idx)?;
+    T::from_str(&value).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, format!("{:?}", err).into())
+    })
+}
+
+fn parse_optional_column<T>(row: &Row<'_>, idx: usize) -> rusqlite::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    let value: Option<String> = row.get(idx)?;
+    match value {
+        Some(value) => T::from_str(&value).map(Some).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, format!("{:?}", err).into())
+        }),
+        None => Ok(None),
+    }
+}
+
+fn get_token(row: &Row<'_>, idx: usize) -> rusqlite::Result<Option<SecretBytesMut>> {
+    let token: Option<Vec<u8>> = row.get(idx)?;
+    Ok(token.map(|token| SecretBytesMut::from(BytesMut::from(token.as_slice()))))
+}
+
+fn token_to_sql(token: &Option<SecretBytesMut>) -> Option<Vec<u8>> {
+    token.as_ref().map(|token| token.expose_secret().to_vec())
+}
+
+/// Reads a comma separated list of prefixes, which is empty if the column is null
+fn get_prefixes(row: &Row<'_>, idx: usize) -> rusqlite::Result<Vec<String>> {
+    let prefixes: Option<String> = row.get(idx)?;
+    Ok(prefixes
+        .iter()
+        .flat_map(|prefixes| prefixes.split(','))
+        .map(String::from)
+        .collect())
+}
+
+/// Reads an account from a row returned by `SELECT_ACCOUNTS`
+fn account_from_row(row: &Row<'_>) -> rusqlite::Result<AccountWithEncryptedTokens> {
+    let max_packet_amount: i64 = row.get(5)?;
+    let amount_per_minute_limit: Option<i64> = row.get(18)?;
+    let amount_burst_limit: Option<i64> = row.get(23)?;
+    let amount_per_day_limit: Option<i64> = row.get(24)?;
+    let amount_per_month_limit: Option<i64> = row.get(25)?;
+    let fixed_fee: Option<i64> = row.get(26)?;
+    let additional_prefixes: Option<String> = row.get(20)?;
+    let additional_prefixes = additional_prefixes
+        .iter()
+        .flat_map(|prefixes| prefixes.split(','))
+        .map(Address::from_str)
+        .collect::<Result<Vec<Address>, _>>()
+        .map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(20, Type::Text, format!("{:?}", err).into())
+        })?;
+    Ok(AccountWithEncryptedTokens {
+        account: Account {
+            id: parse_column(row, 0)?,
+            username: parse_column(row, 1)?,
+            ilp_address: parse_column(row, 2)?,
+            additional_prefixes,
+            asset_code: row.get(3)?,
+            asset_scale: row.get(4)?,
+            // u64 values are stored as i64 with the same bits
+            max_packet_amount: max_packet_amount as u64,
+            min_balance: row.get(6)?,
+            ilp_over_http_url: parse_optional_column(row, 7)?,
+            ilp_over_http_incoming_token: get_token(row, 8)?,
+            ilp_over_http_outgoing_token: get_token(row, 9)?,
+            ilp_over_btp_url: parse_optional_column(row, 10)?,
+            ilp_over_btp_incoming_token: get_token(row, 11)?,
+            ilp_over_btp_outgoing_token: get_token(row, 12)?,
+            settle_threshold: row.get(13)?,
+            settle_to: row.get(14)?,
+            routing_relation: parse_column(row, 15)?,
+            round_trip_time: row.get(16)?,
+            packets_per_minute_limit: row.get(17)?,
+            amount_per_minute_limit: amount_per_minute_limit.map(|limit| limit as u64),
+            settlement_engine_url: parse_optional_column(row, 19)?,
+            status: parse_column(row, 21)?,
+            packets_burst_limit: row.get(22)?,
+            amount_burst_limit: amount_burst_limit.map(|limit| limit as u64),
+            amount_per_day_limit: amount_per_day_limit.map(|limit| limit as u64),
+            amount_per_month_limit: amount_per_month_limit.map(|limit| limit as u64),
+            fixed_fee: fixed_fee.map(|fee| fee as u64),
+            fee_basis_points: row.get(27)?,
+            allowed_destination_prefixes: get_prefixes(row, 28)?,
+            denied_destination_prefixes: get_prefixes(row, 29)?,
+        },
+    })
+}
+
+/// Reads an API key from a row with its id, account id, scopes and description
+fn api_key_from_row(row: &Row<'_>) -> rusqlite::Result<ApiKey> {
+    let scopes: String = row.get(2)?;
+    let scopes = scopes
+        .split(',')
+        .map(ApiKeyScope::from_str)
+        .collect::<Result<Vec<ApiKeyScope>, _>>()
+        .map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(2, Type::Text, format!("{:?}", err).into())
+        })?;
+    Ok(ApiKey {
+        id: parse_column(row, 0)?,
+        account_id: parse_column(row, 1)?,
+        scopes,
+        description: row.get(3)?,
+    })
+}
+
+fn select_accounts(
+    connection: &Connection,
+    condition: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> rusqlite::Result<Vec<AccountWithEncryptedTokens>> {
+    let mut statement = connection.prepare(&format!("{} {}", SELECT_ACCOUNTS, condition))?;
+    let accounts = statement
+        .query_map(params, account_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(accounts)
+}
+
+fn account_exists(connection: &Connection, id: Uuid) -> rusqlite::Result<bool> {
+    connection.query_row(
+        "SELECT EXISTS(SELECT 1 FROM accounts WHERE id = ?1)",
+        params![id.to_string()],
+        |row| row.get(0),
+    )
+}
+
+/// Loads the balance and prepaid amount of an account
+fn get_balances(connection: &Connection, id: Uuid) -> rusqlite::Result<(i64, i64)> {
+    connection.query_row(
+        "SELECT balance, prepaid_amount FROM accounts WHERE id = ?1",
+        params![id.to_string()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+fn set_balances(
+    connection: &Connection,
+    id: Uuid,
+    balance: i64,
+    prepaid_amount: i64,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        "UPDATE accounts SET balance = ?1, prepaid_amount = ?2 WHERE id = ?3",
+        params![balance, prepaid_amount, id.to_string()],
+    )
+}
+
+/// Appends a journal entry for a balance change that was just applied to the account,
+/// and deletes the account's entries which are older than the retention
+fn append_journal_entry(
+    connection: &Connection,
+    id: Uuid,
+    kind: BalanceChangeKind,
+    delta: i64,
+    context: Option<PacketContext>,
+) -> rusqlite::Result<()> {
+    let (balance, prepaid_amount) = get_balances(connection, id)?;
+    let entry = BalanceJournalEntry::new(kind, delta, balance + prepaid_amount, context);
+    connection.execute(
+        "INSERT INTO balance_journal
+        (account_id, timestamp, kind, delta, balance, execution_condition, counterparty)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id.to_string(),
+            entry.timestamp as i64,
+            entry.kind.as_str(),
+            entry.delta,
+            entry.balance,
+            entry.execution_condition,
+            entry.counterparty.map(|id| id.to_string()),
+        ],
+    )?;
+    let expired_before = entry.timestamp.saturating_sub(BALANCE_JOURNAL_RETENTION);
+    connection.execute(
+        "DELETE FROM balance_journal WHERE account_id = ?1 AND timestamp < ?2",
+        params![id.to_string(), expired_before as i64],
+    )?;
+    Ok(())
+}
+
+impl SqliteStore {
+    /// Runs `f` with a handle to the store on the blocking thread pool, so that
+    /// waiting for the connection or the disk does not hold up the other tasks
+    async fn blocking<F, T>(&self, f: F) -> Result<T, ()>
+    where
+        F: FnOnce(SqliteStore) -> Result<T, ()> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(store))
+            .await
+            .map_err(|err| error!("Error running SQLite query: {:?}", err))?
+    }
+
+    fn keys(&self) -> StoreKeys {
+        self.keys.read().clone()
+    }
+
+    /// Switches to the keys derived from the new secret and re-encrypts every
+    /// account's tokens with them. The connection is locked separately for each
+    /// account so that packets can still be processed in the meantime. Since the
+    /// tokens are encrypted while the connection is locked, every token written
+    /// with the previous key is re-encrypted before its key is dropped
+    fn reencrypt_tokens(&self, mut new_secret: [u8; 32]) -> Result<(), ()> {
+        let keys = {
+            let mut keys = self.keys.write();
+            *keys = keys.rotate(&new_secret[..]);
+            keys.clone()
+        };
+        new_secret.zeroize();
+
+        let account_ids: Vec<String> = {
+            let connection = self.connection.lock();
+            let mut statement = connection
+                .prepare("SELECT id FROM accounts")
+                .map_err(|err| error!("Error loading account ids: {:?}", err))?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| row.get(0))
+                .map_err(|err| error!("Error loading account ids: {:?}", err))?;
+            rows.collect::<rusqlite::Result<_>>()
+                .map_err(|err| error!("Error loading account ids: {:?}", err))?
+        };
+
+        for id in account_ids.iter() {
+            let connection = self.connection.lock();
+            for column in TOKEN_COLUMNS.iter() {
+                let encrypted: Option<Vec<u8>> = connection
+                    .query_row(
+                        &format!("SELECT {} FROM accounts WHERE id = ?1", column),
+                        params![id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|err| error!("Error loading {} of account {}: {:?}", column, id, err))?
+                    // the account was deleted in the meantime
+                    .unwrap_or(None);
+                let encrypted = match encrypted {
+                    // Hashed incoming tokens do not depend on the secret
+                    Some(encrypted) if !is_token_hash(&encrypted) => encrypted,
+                    _ => continue,
+                };
+                let token = match keys.decrypt_token(&encrypted) {
+                    Ok(token) => token,
+                    // It could not be used before the rotation either
+                    Err(_) => {
+                        warn!(
+                            "Not re-encrypting {} for account {} because it could not be decrypted",
+                            column, id
+                        );
+                        continue;
+                    }
+                };
+                connection
+                    .execute(
+                        &format!("UPDATE accounts SET {} = ?1 WHERE id = ?2", column),
+                        params![keys.encrypt_token(&token.expose_secret()).to_vec(), id],
+                    )
+                    .map_err(|err| {
+                        error!(
+                            "Error re-encrypting {} of account {}: {:?}",
+                            column, id, err
+                        )
+                    })?;
+            }
+        }
+
+        *self.keys.write() = keys.without_previous_keys();
+        debug!("Re-encrypted the tokens of {} accounts", account_ids.len());
+        Ok(())
+    }
+
+    fn load_accounts(&self, account_ids: &[Uuid]) -> Result<Vec<Account>, ()> {
+        let connection = self.connection.lock();
+        let mut statement = connection
+            .prepare(&format!("{} WHERE a.id = ?1", SELECT_ACCOUNTS))
+            .map_err(|err| error!("Error preparing statement: {:?}", err))?;
+        let keys = self.keys();
+        let mut accounts = Vec::with_capacity(account_ids.len());
+        for id in account_ids {
+            let account = statement
+                .query_row(params![id.to_string()], account_from_row)
+                .optional()
+                .map_err(|err| error!("Error loading account {}: {:?}", id, err))?;
+            match account {
+                Some(account) => accounts.push(account.decrypt_tokens(&keys)),
+                None => {
+                    debug!("Account not found: {}", id);
+                    return Err(());
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn load_accounts_where(
+        &self,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<Account>, ()> {
+        let connection = self.connection.lock();
+        let accounts = select_accounts(&connection, condition, params)
+            .map_err(|err| error!("Error loading accounts: {:?}", err))?;
+        // Taken before the connection is unlocked, so that the tokens cannot
+        // be re-encrypted and the previous keys dropped in the meantime
+        let keys = self.keys();
+        drop(connection);
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.decrypt_tokens(&keys))
+            .collect())
+    }
+
+    /// Loads the account with the given username and checks that the provided
+    /// token matches the incoming token stored in `column`, which is selected by `incoming_token`
+    async fn account_from_auth<F>(
+        &self,
+        username: &Username,
+        token: &str,
+        column: &str,
+        incoming_token: F,
+    ) -> Option<Account>
+    where
+        F: Fn(&Account) -> &Option<SecretBytesMut>,
+    {
+        let name = username.to_string();
+        let accounts = self
+            .blocking(move |store| store.load_accounts_where("WHERE a.username = ?1", &[&name]))
+            .await
+            .ok()?;
+        let account = accounts.into_iter().next()?;
+        let stored = incoming_token(&account).as_ref()?.expose_secret();
+        if !self
+            .token_verifier
+            .verify(username.as_ref(), stored, token.as_bytes())
+            .await
+        {
+            return None;
+        }
+        if !is_token_hash(stored) {
+            let (id, username, column, token) = (
+                account.id,
+                username.clone(),
+                column.to_string(),
+                token.to_string(),
+            );
+            let _ = self
+                .blocking(move |store| {
+                    store.migrate_incoming_token(id, &username, &column, &token);
+                    Ok(())
+                })
+                .await;
+        }
+        Some(account)
+    }
+
+    /// Replaces an incoming token which was stored encrypted (before incoming tokens
+    /// were hashed) with its hash, once the peer has shown that it knows the token.
+    /// Errors are only logged because the token is migrated again on the next attempt
+    fn migrate_incoming_token(&self, id: Uuid, username: &Username, column: &str, token: &str) {
+        let hashed = hash_token(username.as_ref(), token.as_bytes());
+        // The token is only replaced if it was not changed (and hashed) in the meantime
+        let result = self.connection.lock().execute(
+            &format!(
+                "UPDATE accounts SET {0} = ?1 WHERE id = ?2 AND substr({0}, 1, ?3) != ?4",
+                column
+            ),
+            params![
+                hashed.to_vec(),
+                id.to_string(),
+                TOKEN_HASH_PREFIX.len() as i64,
+                TOKEN_HASH_PREFIX
+            ],
+        );
+        match result {
+            Ok(1) => debug!("Replaced {} of account {} with its hash", column, id),
+            Ok(_) => {}
+            Err(err) => error!(
+                "Error replacing {} of account {} with its hash: {:?}",
+                column, id, err
+            ),
+        }
+    }
+
+    /// Inserts the account unless its id or username is already taken
+    fn insert(&self, account: Account) -> Result<Account, ()> {
+        let mut connection = self.connection.lock();
+        // Tokens encrypted while the connection is locked are written before a
+        // secret rotation re-encrypts the tokens with the new keys
+        let encrypted = account.clone().encrypt_tokens(&self.keys());
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+
+        // Check that there isn't already an account with values that MUST be unique
+        let exists: bool = transaction
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM accounts WHERE id = ?1 OR username = ?2)",
+                params![account.id.to_string(), account.username.to_string()],
+                |row| row.get(0),
+            )
+            .map_err(|err| {
+                error!(
+                    "Error checking whether account details already exist: {:?}",
+                    err
+                )
+            })?;
+        let parent_exists = account.routing_relation == RoutingRelation::Parent
+            && get_setting(&transaction, PARENT_ILP_KEY)
+                .map_err(|err| error!("Error checking for a parent account: {:?}", err))?
+                .is_some();
+        if exists {
+            warn!(
+                "An account already exists with the same {}. Cannot insert account: {:?}",
+                account.id, account
+            );
+            return Err(());
+        }
+        if parent_exists {
+            warn!(
+                "A parent account already exists. Cannot insert account: {:?}",
+                account
+            );
+            return Err(());
+        }
+        let routed_prefix = prefix_routed_to_other_account(&transaction, &account)
+            .map_err(|err| error!("Error checking the routes to the prefixes: {:?}", err))?;
+        if let Some(prefix) = routed_prefix {
+            warn!(
+                "The prefix {} is already routed to another account. Cannot insert account: {:?}",
+                prefix, account
+            );
+            return Err(());
+        }
+
+        upsert_account(&transaction, &encrypted)
+            .and_then(|_| set_account_prefixes(&transaction, &account))
+            .and_then(|_| set_destination_prefixes(&transaction, &account))
+            // Add routes to routing table
+            .and_then(|_| set_local_routes(&transaction, &account))
+            .and_then(|_| transaction.commit())
+            .map_err(|err| error!("Error inserting account into DB: {:?}", err))?;
+        self.update_routes(&connection)?;
+
+        debug!(
+            "Inserted account {} (ILP address: {})",
+            account.id, account.ilp_address
+        );
+        Ok(account)
+    }
+
+    /// Reloads the routing table from the database. The static routes are
+    /// applied last so that they overwrite any routes with the same prefix.
+    fn update_routes(&self, connection: &Connection) -> Result<(), ()> {
+        let load = || -> rusqlite::Result<HashMap<String, Uuid>> {
+            let mut routes = HashMap::new();
+            for table in &["routes", "static_routes"] {
+                let mut statement =
+                    connection.prepare(&format!("SELECT prefix, account_id FROM {}", table))?;
+                let rows = statement.query_map(NO_PARAMS, |row| {
+                    Ok((row.get::<_, String>(0)?, parse_column::<Uuid>(row, 1)?))
+                })?;
+                for row in rows {
+                    let (prefix, account_id) = row?;
+                    routes.insert(prefix, account_id);
+                }
+                // If there is a default route set in the db,
+                // set the entry for "" in the routing table to route to that account
+                if *table == "routes" {
+                    if let Some(default_route) = get_setting(connection, DEFAULT_ROUTE_KEY)? {
+                        let account_id = Uuid::from_str(&default_route).map_err(|err| {
+                            rusqlite::Error::FromSqlConversionFailure(
+                                0,
+                                Type::Text,
+                                format!("{:?}", err).into(),
+                            )
+                        })?;
+                        routes.insert(String::new(), account_id);
+                    }
+                }
+            }
+            Ok(routes)
+        };
+        let routes = load().map_err(|err| error!("Error loading routing table: {:?}", err))?;
+        trace!("Routing table is: {:?}", routes);
+        *self.routes.write() = Arc::new(routes);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AccountStore for SqliteStore {
+    type Account = Account;
+
+    // TODO cache results to avoid hitting the db every time
+    async fn get_accounts(&self, account_ids: Vec<Uuid>) -> Result<Vec<Account>, ()> {
+        self.blocking(move |store| store.load_accounts(&account_ids))
+            .await
+    }
+
+    async fn get_account_id_from_username(&self, username: &Username) -> Result<Uuid, ()> {
+        let username = username.clone();
+        self.blocking(move |store| {
+            let id: Option<String> = store
+                .connection
+                .lock()
+                .query_row(
+                    "SELECT id FROM accounts WHERE username = ?1",
+                    params![username.to_string()],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|err| error!("Error getting account id for {}: {:?}", username, err))?;
+            match id {
+                Some(id) => {
+                    Uuid::from_str(&id).map_err(|err| error!("Invalid account id: {:?}", err))
+                }
+                None => {
+                    debug!("Username not found: {}", username);
+                    Err(())
+                }
+            }
+        })
+        .await
+    }
+}
+
+impl StreamNotificationsStore for SqliteStore {
+    type Account = Account;
+
+    fn add_payment_notification_subscription(
+        &self,
+        id: Uuid,
+        sender: UnboundedSender<PaymentNotification>,
+    ) {
+        trace!("Added payment notification listener for {}", id);
+        self.subscriptions.write().insert(id, sender);
+    }
+
+    fn publish_payment_notification(&self, payment: PaymentNotification) {
+        // Like the Redis store, the notification is published in a separate task
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let account_id: rusqlite::Result<String> = store.connection.lock().query_row(
+                "SELECT id FROM accounts WHERE username = ?1",
+                params![payment.to_username.to_string()],
+                |row| row.get(0),
+            );
+            let account_id = match account_id.ok().and_then(|id| Uuid::from_str(&id).ok()) {
+                Some(account_id) => account_id,
+                None => {
+                    error!(
+                        "Failed to find account ID corresponding to username: {}",
+                        payment.to_username
+                    );
+                    return;
+                }
+            };
+
+            debug!(
+                "Publishing payment notification {:?} for account {}",
+                payment, account_id
+            );
+            match store.subscriptions.read().get(&account_id) {
+                Some(sender) => {
+                    if let Err(err) = sender.unbounded_send(payment) {
+                        error!("Failed to send message: {}", err);
+                    }
+                }
+                None => trace!(
+                    "Ignoring message for account {} because there were no open subscriptions",
+                    account_id
+                ),
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl BalanceStore for SqliteStore {
+    /// Returns the balance **from the account holder's perspective**, meaning the sum of
+    /// the Payable Balance and Pending Outgoing minus the Receivable Balance and the Pending Incoming.
+    async fn get_balance(&self, account: Account) -> Result<i64, ()> {
+        self.blocking(move |store| {
+            let (balance, prepaid_amount) = get_balances(&store.connection.lock(), account.id)
+                .map_err(|err| {
+                    error!(
+                        "Error getting balance for account: {} {:?}",
+                        account.id, err
+                    )
+                })?;
+            Ok(balance + prepaid_amount)
+        })
+        .await
+    }
+
+    async fn set_balance(&self, account: Account, balance: i64) -> Result<(), ()> {
+        self.blocking(move |store| {
+            set_balances(&store.connection.lock(), account.id, balance, 0)
+                .map_err(|err| {
+                    error!(
+                        "Error setting balance for account: {} {:?}",
+                        account.id, err
+                    )
+                })
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn get_balance_history(
+        &self,
+        account: Account,
+        query: BalanceHistoryQuery,
+    ) -> Result<BalanceHistoryPage, ()> {
+        // The cursor is the rowid of the last entry of the previous page
+        let after = match query.cursor {
+            Some(ref cursor) => i64::from_str(cursor)
+                .map_err(|_| error!("Invalid balance history cursor: {}", cursor))?,
+            None => 0,
+        };
+        // Take one more entry than needed to know whether there is a next page
+        let limit = query.limit.saturating_add(1).min(std::i64::MAX as usize) as i64;
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare(
+                    "SELECT rowid, timestamp, kind, delta, balance, execution_condition, counterparty
+                    FROM balance_journal
+                    WHERE account_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3 AND rowid > ?4
+                    ORDER BY rowid
+                    LIMIT ?5",
+                )
+                .map_err(|err| error!("Error preparing statement: {:?}", err))?;
+            let mut rows = statement
+                .query_map(
+                    params![
+                        account.id.to_string(),
+                        query.from.min(std::i64::MAX as u64) as i64,
+                        query.to.min(std::i64::MAX as u64) as i64,
+                        after,
+                        limit
+                    ],
+                    |row| {
+                        let rowid: i64 = row.get(0)?;
+                        let timestamp: i64 = row.get(1)?;
+                        let kind: String = row.get(2)?;
+                        let delta: i64 = row.get(3)?;
+                        let balance: i64 = row.get(4)?;
+                        let execution_condition: Option<String> = row.get(5)?;
+                        let counterparty: Option<String> = row.get(6)?;
+                        Ok((
+                            rowid,
+                            timestamp as u64,
+                            kind,
+                            delta,
+                            balance,
+                            execution_condition,
+                            counterparty,
+                        ))
+                    },
+                )
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| {
+                    error!(
+                        "Error getting balance history for account: {} {:?}",
+                        account.id, err
+                    )
+                })?;
+            let mut next_cursor = None;
+            if rows.len() > query.limit {
+                rows.truncate(query.limit);
+                next_cursor = rows.last().map(|row| row.0.to_string());
+            }
+            let entries = rows
+                .into_iter()
+                .map(
+                    |(_, timestamp, kind, delta, balance, execution_condition, counterparty)| {
+                        Ok(BalanceJournalEntry {
+                            timestamp,
+                            kind: BalanceChangeKind::from_str(&kind).map_err(|_| {
+                                error!("Invalid balance journal entry kind: {}", kind)
+                            })?,
+                            delta,
+                            balance,
+                            execution_condition,
+                            counterparty: counterparty
+                                .map(|id| Uuid::from_str(&id))
+                                .transpose()
+                                .map_err(|err| {
+                                    error!("Invalid account id in balance journal: {:?}", err)
+                                })?,
+                        })
+                    },
+                )
+                .collect::<Result<Vec<_>, ()>>()?;
+            Ok(BalanceHistoryPage {
+                entries,
+                next_cursor,
+            })
+        })
+        .await
+    }
+
+    async fn update_balances_for_prepare(
+        &self,
+        from_account: Account,
+        incoming_amount: u64,
+        context: PacketContext,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            // Don't do anything if the amount was 0
+            if incoming_amount == 0 {
+                return Ok(());
+            }
+
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let (balance, prepaid_amount, min_balance): (i64, i64, Option<i64>) = transaction
+                .query_row(
+                    "SELECT balance, prepaid_amount, min_balance FROM accounts WHERE id = ?1",
+                    params![from_account.id.to_string()],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .map_err(|err| {
+                    error!(
+                        "Error processing prepare for account {}: {:?}",
+                        from_account.id, err
+                    )
+                })?;
+            let amount = incoming_amount as i64;
+
+            // Check that the prepare wouldn't go under the account's minimum balance
+            if let Some(min_balance) = min_balance {
+                if balance + prepaid_amount - amount < min_balance {
+                    warn!(
+                        "Incoming prepare of {} would bring account {} under its minimum balance. Current balance: {}, min balance: {}",
+                        amount, from_account.id, balance + prepaid_amount, min_balance
+                    );
+                    return Err(());
+                }
+            }
+
+            // Deduct the amount from the prepaid amount first and then from the balance
+            let (balance, prepaid_amount) = if prepaid_amount >= amount {
+                (balance, prepaid_amount - amount)
+            } else {
+                (balance - (amount - prepaid_amount), 0)
+            };
+            set_balances(&transaction, from_account.id, balance, prepaid_amount)
+                .and_then(|_| {
+                    append_journal_entry(
+                        &transaction,
+                        from_account.id,
+                        BalanceChangeKind::Prepare,
+                        -amount,
+                        Some(context),
+                    )
+                })
+                .and_then(|_| transaction.commit())
+                .map_err(|err| {
+                    error!(
+                        "Error processing prepare for account {}: {:?}",
+                        from_account.id, err
+                    )
+                })?;
+
+            trace!(
+                "Processed prepare with incoming amount: {}. Account {} has balance (including prepaid amount): {} ",
+                incoming_amount, from_account.id, balance + prepaid_amount
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_balances_for_fulfill(
+        &self,
+        to_account: Account,
+        outgoing_amount: u64,
+        context: PacketContext,
+    ) -> Result<(i64, u64), ()> {
+        self.blocking(move |store| {
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let (balance, prepaid_amount, settle_threshold, settle_to): (
+                i64,
+                i64,
+                Option<i64>,
+                Option<i64>,
+            ) = transaction
+                .query_row(
+                    "SELECT balance, prepaid_amount, settle_threshold, settle_to FROM accounts WHERE id = ?1",
+                    params![to_account.id.to_string()],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .map_err(|err| {
+                    error!(
+                        "Error processing fulfill for account {}: {:?}",
+                        to_account.id, err
+                    )
+                })?;
+
+            let balance = balance + outgoing_amount as i64;
+
+            // Trigger a settlement if the balance went over the settle threshold
+            let mut settle_amount = 0;
+            if let (Some(settle_threshold), Some(settle_to)) = (settle_threshold, settle_to) {
+                if balance >= settle_threshold && settle_threshold > settle_to {
+                    settle_amount = (balance - settle_to) as u64;
+                }
+            }
+            let settled_balance = balance - settle_amount as i64;
+            set_balances(&transaction, to_account.id, balance, prepaid_amount)
+                .and_then(|_| {
+                    append_journal_entry(
+                        &transaction,
+                        to_account.id,
+                        BalanceChangeKind::Fulfill,
+                        outgoing_amount as i64,
+                        Some(context),
+                    )
+                })
+                .and_then(|_| {
+                    if settle_amount == 0 {
+                        return Ok(());
+                    }
+                    set_balances(&transaction, to_account.id, settled_balance, prepaid_amount).and_then(
+                        |_| {
+                            append_journal_entry(
+                                &transaction,
+                                to_account.id,
+                                BalanceChangeKind::OutgoingSettlement,
+                                -(settle_amount as i64),
+                                None,
+                            )
+                        },
+                    )
+                })
+                .and_then(|_| transaction.commit())
+                .map_err(|err| {
+                    error!(
+                        "Error processing fulfill for account {}: {:?}",
+                        to_account.id, err
+                    )
+                })?;
+
+            trace!(
+                "Processed fulfill for account {} for outgoing amount {}. Fulfill call result: {} {}",
+                to_account.id,
+                outgoing_amount,
+                settled_balance + prepaid_amount,
+                settle_amount,
+            );
+            Ok((settled_balance + prepaid_amount, settle_amount))
+        })
+        .await
+    }
+
+    async fn update_balances_for_reject(
+        &self,
+        from_account: Account,
+        incoming_amount: u64,
+        context: PacketContext,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            if incoming_amount == 0 {
+                return Ok(());
+            }
+
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let updated = transaction
+                .execute(
+                    "UPDATE accounts SET balance = balance + ?1 WHERE id = ?2",
+                    params![incoming_amount as i64, from_account.id.to_string()],
+                )
+                .and_then(|updated| {
+                    if updated > 0 {
+                        append_journal_entry(
+                            &transaction,
+                            from_account.id,
+                            BalanceChangeKind::Reject,
+                            incoming_amount as i64,
+                            Some(context),
+                        )?;
+                    }
+                    Ok(updated)
+                })
+                .and_then(|updated| transaction.commit().map(|_| updated))
+                .map_err(|err| {
+                    error!(
+                        "Error processing reject for account {}: {:?}",
+                        from_account.id, err
+                    )
+                })?;
+            if updated == 0 {
+                error!(
+                    "Error processing reject for account that does not exist: {}",
+                    from_account.id
+                );
+                return Err(());
+            }
+
+            trace!(
+                "Processed reject for incoming amount: {} for account {}",
+                incoming_amount,
+                from_account.id
+            );
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl ExchangeRateStore for SqliteStore {
+    fn get_exchange_rates(&self, asset_codes: &[&str]) -> Result<Vec<ExchangeRate>, ()> {
+        let rates: Vec<ExchangeRate> = asset_codes
+            .iter()
+            .filter_map(|code| (*self.exchange_rates.read()).get(*code).cloned())
+            .collect();
+        if rates.len() == asset_codes.len() {
+            Ok(rates)
+        } else {
+            Err(())
+        }
+    }
+
+    fn get_all_exchange_rates(&self) -> Result<HashMap<String, ExchangeRate>, ()> {
+        Ok((*self.exchange_rates.read()).clone())
+    }
+
+    fn set_exchange_rates(
+        &self,
+        rates: HashMap<String, ExchangeRate>,
+        source: &str,
+    ) -> Result<(), ()> {
+        // The rates are only kept in memory, but the rates that were set are
+        // saved in the history so that the rates applied to packets can be audited
+        let now = SystemTime::now();
+        let timestamp = journal_timestamp();
+        let mut asset_codes: Vec<&String> = rates.keys().collect();
+        asset_codes.sort();
+        let mut connection = self.connection.lock();
+        let transaction = connection
+            .transaction()
+            .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+        asset_codes
+            .into_iter()
+            .try_for_each(|asset_code| {
+                transaction.execute(
+                    "INSERT INTO exchange_rate_history (timestamp, asset_code, rate, source)
+                    VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        timestamp as i64,
+                        asset_code,
+                        rates[asset_code].as_ref(),
+                        source
+                    ],
+                )?;
+                // Keep only the last EXCHANGE_RATE_HISTORY_LENGTH rates of the asset
+                transaction
+                    .execute(
+                        "DELETE FROM exchange_rate_history
+                        WHERE asset_code = ?1 AND rowid <= (
+                            SELECT rowid FROM exchange_rate_history
+                            WHERE asset_code = ?1
+                            ORDER BY rowid DESC
+                            LIMIT 1 OFFSET ?2
+                        )",
+                        params![asset_code, EXCHANGE_RATE_HISTORY_LENGTH as i64],
+                    )
+                    .map(|_| ())
+            })
+            .and_then(|_| transaction.commit())
+            .map_err(|err| error!("Error saving exchange rate history: {:?}", err))?;
+
+        (*self.exchange_rates_updated_at.write()) =
+            rates.keys().map(|code| (code.clone(), now)).collect();
+        (*self.exchange_rates.write()) = rates;
+        Ok(())
+    }
+
+    fn get_exchange_rates_updated_at(&self, asset_codes: &[&str]) -> Result<Vec<SystemTime>, ()> {
+        let updated_at = self.exchange_rates_updated_at.read();
+        asset_codes
+            .iter()
+            .map(|code| updated_at.get(*code).cloned().ok_or(()))
+            .collect()
+    }
+
+    fn set_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let saved = serde_json::to_string(&spreads)
+            .map_err(|err| error!("Error serializing spreads: {:?}", err))?;
+        let mut current = self.spreads.write();
+        set_setting(&self.connection.lock(), SPREADS_KEY, &saved)
+            .map_err(|err| error!("Error saving spreads: {:?}", err))?;
+        self.spreads_set.store(true, Ordering::SeqCst);
+        *current = spreads;
+        Ok(())
+    }
+
+    fn set_default_spreads(&self, spreads: Spreads) -> Result<(), ()> {
+        let mut current = self.spreads.write();
+        if !self.spreads_set.load(Ordering::SeqCst) {
+            *current = spreads;
+        }
+        Ok(())
+    }
+
+    fn get_spread(&self, from_asset_code: &str, to_asset_code: &str) -> f64 {
+        self.spreads.read().get(from_asset_code, to_asset_code)
+    }
+
+    fn get_all_spreads(&self) -> Result<Spreads, ()> {
+        Ok((*self.spreads.read()).clone())
+    }
+
+    fn set_pair_quotes(&self, quotes: Vec<PairQuote>) -> Result<(), ()> {
+        let now = SystemTime::now();
+        let mut pair_quotes: PairQuotes = HashMap::new();
+        for quote in quotes {
+            pair_quotes
+                .entry(quote.base.clone())
+                .or_default()
+                .insert(quote.quote.clone(), (quote, now));
+        }
+        (*self.pair_quotes.write()) = pair_quotes;
+        Ok(())
+    }
+
+    fn get_pair_quote(&self, base: &str, quote: &str) -> Option<(PairQuote, SystemTime)> {
+        self.pair_quotes
+            .read()
+            .get(base)
+            .and_then(|quotes| quotes.get(quote))
+            .cloned()
+    }
+
+    fn get_all_pair_quotes(&self) -> Result<Vec<PairQuote>, ()> {
+        let mut quotes: Vec<PairQuote> = self
+            .pair_quotes
+            .read()
+            .values()
+            .flat_map(|quotes| quotes.values())
+            .map(|(quote, _)| quote.clone())
+            .collect();
+        quotes.sort_by(|a, b| (&a.base, &a.quote).cmp(&(&b.base, &b.quote)));
+        Ok(quotes)
+    }
+}
+
+#[async_trait]
+impl ExchangeRateHistoryStore for SqliteStore {
+    async fn get_exchange_rate_history(
+        &self,
+        query: ExchangeRateHistoryQuery,
+    ) -> Result<ExchangeRateHistoryPage, ()> {
+        // The cursor is the rowid of the last record of the previous page
+        let after = match query.cursor {
+            Some(ref cursor) => i64::from_str(cursor)
+                .map_err(|_| error!("Invalid exchange rate history cursor: {}", cursor))?,
+            None => 0,
+        };
+        // Take one more record than needed to know whether there is a next page
+        let limit = query.limit.saturating_add(1).min(std::i64::MAX as usize) as i64;
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare(
+                    "SELECT rowid, timestamp, asset_code, rate, source
+                    FROM exchange_rate_history
+                    WHERE timestamp >= ?1 AND timestamp <= ?2
+                    AND (?3 IS NULL OR asset_code = ?3) AND rowid > ?4
+                    ORDER BY rowid
+                    LIMIT ?5",
+                )
+                .map_err(|err| error!("Error preparing statement: {:?}", err))?;
+            let mut rows = statement
+                .query_map(
+                    params![
+                        query.from.min(std::i64::MAX as u64) as i64,
+                        query.to.min(std::i64::MAX as u64) as i64,
+                        query.asset_code,
+                        after,
+                        limit
+                    ],
+                    |row| {
+                        let rowid: i64 = row.get(0)?;
+                        let timestamp: i64 = row.get(1)?;
+                        let rate: String = row.get(3)?;
+                        Ok((rowid, timestamp as u64, row.get(2)?, rate, row.get(4)?))
+                    },
+                )
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| error!("Error getting exchange rate history: {:?}", err))?
+                .into_iter()
+                .map(|(rowid, timestamp, asset_code, rate, source)| {
+                    let rate = ExchangeRate::from_str(&rate)
+                        .map_err(|err| error!("Invalid rate in exchange rate history: {}", err))?;
+                    Ok((
+                        rowid,
+                        ExchangeRateRecord {
+                            timestamp,
+                            asset_code,
+                            rate,
+                            source,
+                        },
+                    ))
+                })
+                .collect::<Result<Vec<_>, ()>>()?;
+            let mut next_cursor = None;
+            if rows.len() > query.limit {
+                rows.truncate(query.limit);
+                next_cursor = rows.last().map(|(rowid, _)| rowid.to_string());
+            }
+            Ok(ExchangeRateHistoryPage {
+                records: rows.into_iter().map(|(_, record)| record).collect(),
+                next_cursor,
+            })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl BtpStore for SqliteStore {
+    type Account = Account;
+
+    async fn get_account_from_btp_auth(
+        &self,
+        username: &Username,
+        token: &str,
+    ) -> Result<Self::Account, ()> {
+        self.account_from_auth(username, token, "ilp_over_btp_incoming_token", |account| {
+            &account.ilp_over_btp_incoming_token
+        })
+        .await
+        .ok_or_else(|| warn!("No account found with BTP token"))
+    }
+
+    async fn get_btp_outgoing_accounts(&self) -> Result<Vec<Self::Account>, ()> {
+        self.blocking(move |store| {
+            let accounts =
+                store.load_accounts_where("WHERE a.ilp_over_btp_url IS NOT NULL", &[])?;
+            debug!(
+                "Loaded {} accounts with an outgoing BTP URL",
+                accounts.len()
+            );
+            Ok(accounts)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl HttpStore for SqliteStore {
+    type Account = Account;
+
+    /// Checks if the stored token for the provided account id matches the
+    /// provided token, and if so, returns the account associated with that token
+    async fn get_account_from_http_auth(
+        &self,
+        username: &Username,
+        token: &str,
+    ) -> Result<Self::Account, ()> {
+        self.account_from_auth(username, token, "ilp_over_http_incoming_token", |account| {
+            &account.ilp_over_http_incoming_token
+        })
+        .await
+        .ok_or_else(|| {
+            warn!(
+                "No account found with given HTTP auth. Username: {}",
+                username
+            )
+        })
+    }
+}
+
+impl RouterStore for SqliteStore {
+    fn routing_table(&self) -> Arc<HashMap<String, Uuid>> {
+        self.routes.read().clone()
+    }
+}
+
+/// Writes all of the account's details, keeping its balances if it already exists
+fn upsert_account(
+    connection: &Connection,
+    encrypted: &AccountWithEncryptedTokens,
+) -> rusqlite::Result<usize> {
+    let account = &encrypted.account;
+    connection.execute(
+        "INSERT INTO accounts (id, username, ilp_address, asset_code, asset_scale,
+            max_packet_amount, min_balance,
+            ilp_over_http_url, ilp_over_http_incoming_token, ilp_over_http_outgoing_token,
+            ilp_over_btp_url, ilp_over_btp_incoming_token, ilp_over_btp_outgoing_token,
+            settle_threshold, settle_to, routing_relation, round_trip_time,
+            packets_per_minute_limit, amount_per_minute_limit, settlement_engine_url,
+            status, packets_burst_limit, amount_burst_limit,
+            amount_per_day_limit, amount_per_month_limit, fixed_fee, fee_basis_points)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10,
+            ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+            ?21, ?22, ?23, ?24, ?25, ?26, ?27)
+        ON CONFLICT(id) DO UPDATE SET
+            username = excluded.username,
+            ilp_address = excluded.ilp_address,
+            asset_code = excluded.asset_code,
+            asset_scale = excluded.asset_scale,
+            max_packet_amount = excluded.max_packet_amount,
+            min_balance = excluded.min_balance,
+            ilp_over_http_url = excluded.ilp_over_http_url,
+            ilp_over_http_incoming_token = excluded.ilp_over_http_incoming_token,
+            ilp_over_http_outgoing_token = excluded.ilp_over_http_outgoing_token,
+            ilp_over_btp_url = excluded.ilp_over_btp_url,
+            ilp_over_btp_incoming_token = excluded.ilp_over_btp_incoming_token,
+            ilp_over_btp_outgoing_token = excluded.ilp_over_btp_outgoing_token,
+            settle_threshold = excluded.settle_threshold,
+            settle_to = excluded.settle_to,
+            routing_relation = excluded.routing_relation,
+            round_trip_time = excluded.round_trip_time,
+            packets_per_minute_limit = excluded.packets_per_minute_limit,
+            amount_per_minute_limit = excluded.amount_per_minute_limit,
+            settlement_engine_url = excluded.settlement_engine_url,
+            packets_burst_limit = excluded.packets_burst_limit,
+            amount_burst_limit = excluded.amount_burst_limit,
+            amount_per_day_limit = excluded.amount_per_day_limit,
+            amount_per_month_limit = excluded.amount_per_month_limit,
+            fixed_fee = excluded.fixed_fee,
+            fee_basis_points = excluded.fee_basis_points",
+        params![
+            account.id.to_string(),
+            account.username.to_string(),
+            account.ilp_address.to_string(),
+            account.asset_code,
+            account.asset_scale,
+            account.max_packet_amount as i64,
+            account.min_balance,
+            account
+                .ilp_over_http_url
+                .as_ref()
+                .map(|url| url.to_string()),
+            token_to_sql(&account.ilp_over_http_incoming_token),
+            token_to_sql(&account.ilp_over_http_outgoing_token),
+            account.ilp_over_btp_url.as_ref().map(|url| url.to_string()),
+            token_to_sql(&account.ilp_over_btp_incoming_token),
+            token_to_sql(&account.ilp_over_btp_outgoing_token),
+            account.settle_threshold,
+            account.settle_to,
+            account.routing_relation.to_string(),
+            account.round_trip_time,
+            account.packets_per_minute_limit,
+            account.amount_per_minute_limit.map(|limit| limit as i64),
+            account
+                .settlement_engine_url
+                .as_ref()
+                .map(|url| url.to_string()),
+            account.status.as_ref(),
+            account.packets_burst_limit,
+            account.amount_burst_limit.map(|limit| limit as i64),
+            account.amount_per_day_limit.map(|limit| limit as i64),
+            account.amount_per_month_limit.map(|limit| limit as i64),
+            account.fixed_fee.map(|fee| fee as i64),
+            account.fee_basis_points,
+        ],
+    )
+}
+
+/// Replaces the additional prefixes of the account
+fn set_account_prefixes(connection: &Connection, account: &Account) -> rusqlite::Result<()> {
+    connection.execute(
+        "DELETE FROM account_prefixes WHERE account_id = ?1",
+        params![account.id.to_string()],
+    )?;
+    for prefix in account.additional_prefixes.iter() {
+        connection.execute(
+            "INSERT INTO account_prefixes (prefix, account_id) VALUES (?1, ?2)",
+            params![prefix.to_string(), account.id.to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Replaces the prefixes the account may and may not send packets to
+fn set_destination_prefixes(connection: &Connection, account: &Account) -> rusqlite::Result<()> {
+    connection.execute(
+        "DELETE FROM account_destination_prefixes WHERE account_id = ?1",
+        params![account.id.to_string()],
+    )?;
+    let prefixes = account
+        .allowed_destination_prefixes
+        .iter()
+        .map(|prefix| (prefix, true))
+        .chain(
+            account
+                .denied_destination_prefixes
+                .iter()
+                .map(|prefix| (prefix, false)),
+        );
+    for (prefix, allowed) in prefixes {
+        connection.execute(
+            "INSERT INTO account_destination_prefixes (account_id, prefix, allowed)
+            VALUES (?1, ?2, ?3)",
+            params![account.id.to_string(), prefix, allowed],
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the amounts the account sent in the current day and month. The rows
+/// of previous periods are ignored and replaced when the account next sends
+fn get_amounts_sent(
+    connection: &Connection,
+    account_id: Uuid,
+) -> rusqlite::Result<CumulativeAmounts> {
+    let mut amounts = CumulativeAmounts::default();
+    for period in AmountLimitPeriod::ALL.iter() {
+        let sent: Option<i64> = connection
+            .query_row(
+                "SELECT amount FROM amounts_sent
+                WHERE account_id = ?1 AND period = ?2 AND period_id = ?3",
+                params![account_id.to_string(), period.as_ref(), period.current_id()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        amounts.set(*period, sent.unwrap_or(0) as u64);
+    }
+    Ok(amounts)
+}
+
+/// Adds the amount to what the account sent in the current day and month,
+/// unless that would exceed one of its limits
+fn add_to_amounts_sent(
+    connection: &mut Connection,
+    account: &Account,
+    amount: u64,
+) -> rusqlite::Result<Result<(), RateLimitError>> {
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let sent = get_amounts_sent(&transaction, account.id)?;
+    let amounts = match add_amount_sent(account, sent, amount) {
+        Ok(amounts) => amounts,
+        Err(err) => return Ok(Err(err)),
+    };
+    for (period, amount) in amounts {
+        transaction.execute(
+            "INSERT OR REPLACE INTO amounts_sent (account_id, period, period_id, amount)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                account.id.to_string(),
+                period.as_ref(),
+                period.current_id(),
+                amount as i64
+            ],
+        )?;
+    }
+    transaction.commit()?;
+    Ok(Ok(()))
+}
+
+fn set_account_status(
+    connection: &Connection,
+    id: Uuid,
+    status: AccountStatus,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        "UPDATE accounts SET status = ?1 WHERE id = ?2",
+        params![status.as_ref(), id.to_string()],
+    )
+}
+
+/// Routes the account's address and additional prefixes to it
+fn set_local_routes(connection: &Connection, account: &Account) -> rusqlite::Result<()> {
+    for prefix in account.local_prefixes() {
+        set_route(connection, "routes", &prefix.to_string(), account.id)?;
+    }
+    Ok(())
+}
+
+/// Returns an additional prefix of the account which is already routed to another account
+fn prefix_routed_to_other_account(
+    connection: &Connection,
+    account: &Account,
+) -> rusqlite::Result<Option<Address>> {
+    for prefix in account.additional_prefixes.iter() {
+        let routed: bool = connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM routes WHERE prefix = ?1 AND account_id != ?2)",
+            params![prefix.to_string(), account.id.to_string()],
+            |row| row.get(0),
+        )?;
+        if routed {
+            return Ok(Some(prefix.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Removes the routes to the account's address and additional prefixes
+fn delete_local_routes(connection: &Connection, account: &Account) -> rusqlite::Result<()> {
+    for prefix in account.local_prefixes() {
+        connection.execute(
+            "DELETE FROM routes WHERE prefix = ?1 AND account_id = ?2",
+            params![prefix.to_string(), account.id.to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+fn set_route(
+    connection: &Connection,
+    table: &str,
+    prefix: &str,
+    id: Uuid,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "INSERT OR REPLACE INTO {} (prefix, account_id) VALUES (?1, ?2)",
+            table
+        ),
+        params![prefix, id.to_string()],
+    )
+}
+
+fn set_setting(connection: &Connection, key: &str, value: &str) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+}
+
+#[async_trait]
+impl NodeStore for SqliteStore {
+    type Account = Account;
+
+    async fn insert_account(&self, account: AccountDetails) -> Result<Account, ()> {
+        self.blocking(move |store| {
+            let id = Uuid::new_v4();
+            let account = Account::try_from(id, account, store.get_ilp_address())?;
+            debug!(
+                "Generated account id for {}: {}",
+                account.username.clone(),
+                id
+            );
+            store.insert(account)
+        })
+        .await
+    }
+
+    async fn delete_account(&self, id: Uuid) -> Result<Account, ()> {
+        self.blocking(move |store| {
+            let account = store.load_accounts(&[id])?.pop().ok_or(())?;
+
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            transaction
+                .execute(
+                    "DELETE FROM accounts WHERE id = ?1",
+                    params![id.to_string()],
+                )
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM account_prefixes WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM amounts_sent WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM account_destination_prefixes WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM api_keys WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| delete_local_routes(&transaction, &account))
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM uncredited_settlement_amounts WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| {
+                    transaction.execute(
+                        "DELETE FROM balance_journal WHERE account_id = ?1",
+                        params![id.to_string()],
+                    )
+                })
+                .and_then(|_| transaction.commit())
+                .map_err(|err| error!("Error deleting account from DB: {:?}", err))?;
+            store.update_routes(&connection)?;
+
+            debug!("Deleted account {}", account.id);
+            Ok(account)
+        })
+        .await
+    }
+
+    async fn update_account(&self, id: Uuid, account: AccountDetails) -> Result<Self::Account, ()> {
+        self.blocking(move |store| {
+            let account = Account::try_from(id, account, store.get_ilp_address())?;
+            let previous = store.load_accounts(&[id]).map_err(|_| {
+                warn!(
+                    "No account exists with ID {}, cannot update account {:?}",
+                    id, account
+                )
+            })?;
+            let mut connection = store.connection.lock();
+            let encrypted = account.clone().encrypt_tokens(&store.keys());
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let routed_prefix = prefix_routed_to_other_account(&transaction, &account)
+                .map_err(|err| error!("Error checking the routes to the prefixes: {:?}", err))?;
+            if let Some(prefix) = routed_prefix {
+                warn!(
+                    "The prefix {} is already routed to another account. Cannot update account: {}",
+                    prefix, id
+                );
+                return Err(());
+            }
+            upsert_account(&transaction, &encrypted)
+                .and_then(|_| set_account_prefixes(&transaction, &account))
+                .and_then(|_| set_destination_prefixes(&transaction, &account))
+                .and_then(|_| delete_local_routes(&transaction, &previous[0]))
+                .and_then(|_| set_local_routes(&transaction, &account))
+                .and_then(|_| transaction.commit())
+                .map_err(|err| error!("Error updating account in DB: {:?}", err))?;
+            store.update_routes(&connection)?;
+            drop(connection);
+
+            debug!("Updated account {}", id);
+            store.load_accounts(&[id])?.pop().ok_or(())
+        })
+        .await
+    }
+
+    async fn modify_account_settings(
+        &self,
+        id: Uuid,
+        settings: AccountSettings,
+    ) -> Result<Self::Account, ()> {
+        self.blocking(move |store| {
+            validate_incoming_token(settings.ilp_over_btp_incoming_token.as_ref())?;
+            validate_incoming_token(settings.ilp_over_http_incoming_token.as_ref())?;
+            // The incoming tokens are hashed with the username as part of the salt
+            let username = if settings.ilp_over_btp_incoming_token.is_some()
+                || settings.ilp_over_http_incoming_token.is_some()
+            {
+                let username: Option<String> = store
+                    .connection
+                    .lock()
+                    .query_row(
+                        "SELECT username FROM accounts WHERE id = ?1",
+                        params![id.to_string()],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|err| error!("Error loading username of account {}: {:?}", id, err))?;
+                username.ok_or_else(|| {
+                    warn!(
+                        "No account exists with ID {}, cannot modify its settings",
+                        id
+                    )
+                })?
+            } else {
+                String::new()
+            };
+            let hash = |token: &SecretString| {
+                hash_token(&username, token.expose_secret().as_bytes()).to_vec()
+            };
+
+            let mut updates: Vec<(&str, Box<dyn rusqlite::ToSql>)> = Vec::new();
+            if let Some(ref url) = settings.ilp_over_btp_url {
+                let url = Url::parse(url).map_err(|err| error!("Invalid BTP URL: {:?}", err))?;
+                updates.push(("ilp_over_btp_url", Box::new(url.to_string())));
+            }
+            if let Some(ref url) = settings.ilp_over_http_url {
+                let url = Url::parse(url).map_err(|err| error!("Invalid HTTP URL: {:?}", err))?;
+                updates.push(("ilp_over_http_url", Box::new(url.to_string())));
+            }
+            if let Some(ref token) = settings.ilp_over_btp_incoming_token {
+                updates.push(("ilp_over_btp_incoming_token", Box::new(hash(token))));
+            }
+            if let Some(ref token) = settings.ilp_over_http_incoming_token {
+                updates.push(("ilp_over_http_incoming_token", Box::new(hash(token))));
+            }
+            if let Some(settle_threshold) = settings.settle_threshold {
+                updates.push(("settle_threshold", Box::new(settle_threshold)));
+            }
+            if let Some(settle_to) = settings.settle_to {
+                if settle_to > std::i64::MAX as u64 {
+                    // Balances are stored as i64 so settle_to cannot be larger than that
+                    error!(
+                        "Provided settle_to value {} is greater than i64::MAX",
+                        settle_to
+                    );
+                    return Err(());
+                }
+                updates.push(("settle_to", Box::new(settle_to as i64)));
+            }
+
+            {
+                let mut connection = store.connection.lock();
+                if !account_exists(&connection, id)
+                    .map_err(|err| error!("Error checking whether account exists: {:?}", err))?
+                {
+                    warn!(
+                        "No account exists with ID {}, cannot modify its settings",
+                        id
+                    );
+                    return Err(());
+                }
+                // Like in `insert`, the tokens are encrypted while the connection is locked
+                let keys = store.keys();
+                let encrypt = |token: &SecretString| {
+                    keys.encrypt_token(token.expose_secret().as_bytes())
+                        .to_vec()
+                };
+                if let Some(ref token) = settings.ilp_over_btp_outgoing_token {
+                    updates.push(("ilp_over_btp_outgoing_token", Box::new(encrypt(token))));
+                }
+                if let Some(ref token) = settings.ilp_over_http_outgoing_token {
+                    updates.push(("ilp_over_http_outgoing_token", Box::new(encrypt(token))));
+                }
+                let transaction = connection
+                    .transaction()
+                    .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+                for (column, value) in updates.iter() {
+                    transaction
+                        .execute(
+                            &format!("UPDATE accounts SET {} = ?1 WHERE id = ?2", column),
+                            &[value.as_ref(), &id.to_string() as &dyn rusqlite::ToSql],
+                        )
+                        .map_err(|err| error!("Error modifying account settings: {:?}", err))?;
+                }
+                transaction
+                    .commit()
+                    .map_err(|err| error!("Error modifying account settings: {:?}", err))?;
+            }
+
+            debug!("Modified account {} settings", id);
+            store.load_accounts(&[id])?.pop().ok_or(())
+        })
+        .await
+    }
+
+    async fn set_account_status(
+        &self,
+        id: Uuid,
+        status: AccountStatus,
+    ) -> Result<Self::Account, ()> {
+        self.blocking(move |store| {
+            {
+                let connection = store.connection.lock();
+                if !account_exists(&connection, id)
+                    .map_err(|err| error!("Error checking whether account exists: {:?}", err))?
+                {
+                    warn!("No account exists with ID {}, cannot set its status", id);
+                    return Err(());
+                }
+                set_account_status(&connection, id, status).map_err(|err| {
+                    error!("Error setting the status of account {}: {:?}", id, err)
+                })?;
+            }
+
+            debug!("Set the status of account {} to {}", id, status);
+            store.load_accounts(&[id])?.pop().ok_or(())
+        })
+        .await
+    }
+
+    async fn insert_api_key(&self, key: ApiKey, secret: SecretString) -> Result<ApiKey, ()> {
+        self.blocking(move |store| {
+            let secret_hash = hash_token(&key.id.to_string(), secret.expose_secret().as_bytes());
+            let scopes: Vec<&str> = key.scopes.iter().map(ApiKeyScope::as_ref).collect();
+
+            let connection = store.connection.lock();
+            if !account_exists(&connection, key.account_id)
+                .map_err(|err| error!("Error checking whether account exists: {:?}", err))?
+            {
+                warn!(
+                    "No account exists with ID {}, cannot insert API key",
+                    key.account_id
+                );
+                return Err(());
+            }
+            connection
+                .execute(
+                    "INSERT INTO api_keys (id, account_id, secret_hash, scopes, description)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        key.id.to_string(),
+                        key.account_id.to_string(),
+                        secret_hash.to_vec(),
+                        scopes.join(","),
+                        key.description,
+                    ],
+                )
+                .map_err(|err| error!("Error inserting API key: {:?}", err))?;
+
+            debug!("Inserted API key {} of account {}", key.id, key.account_id);
+            Ok(key)
+        })
+        .await
+    }
+
+    async fn get_api_keys(&self, account_id: Uuid) -> Result<Vec<ApiKey>, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare(
+                    "SELECT id, account_id, scopes, description FROM api_keys
+                    WHERE account_id = ?1 ORDER BY id",
+                )
+                .map_err(|err| error!("Error getting API keys: {:?}", err))?;
+            let rows = statement
+                .query_map(params![account_id.to_string()], api_key_from_row)
+                .map_err(|err| error!("Error getting API keys: {:?}", err))?;
+            rows.collect::<rusqlite::Result<_>>()
+                .map_err(|err| error!("Error getting API keys: {:?}", err))
+        })
+        .await
+    }
+
+    async fn delete_api_key(&self, account_id: Uuid, key_id: Uuid) -> Result<ApiKey, ()> {
+        self.blocking(move |store| {
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let key = transaction
+                .query_row(
+                    "SELECT id, account_id, scopes, description FROM api_keys
+                    WHERE id = ?1 AND account_id = ?2",
+                    params![key_id.to_string(), account_id.to_string()],
+                    api_key_from_row,
+                )
+                .optional()
+                .map_err(|err| error!("Error loading API key: {:?}", err))?
+                .ok_or_else(|| warn!("Account {} has no API key {}", account_id, key_id))?;
+            transaction
+                .execute(
+                    "DELETE FROM api_keys WHERE id = ?1",
+                    params![key_id.to_string()],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|err| error!("Error deleting API key: {:?}", err))?;
+
+            debug!("Deleted API key {} of account {}", key_id, account_id);
+            Ok(key)
+        })
+        .await
+    }
+
+    async fn get_api_key_from_auth(
+        &self,
+        account_id: Uuid,
+        key_id: Uuid,
+        secret: &str,
+    ) -> Result<ApiKey, ()> {
+        let row = self
+            .blocking(move |store| {
+                store
+                    .connection
+                    .lock()
+                    .query_row(
+                        "SELECT id, account_id, scopes, description, secret_hash FROM api_keys
+                        WHERE id = ?1 AND account_id = ?2",
+                        params![key_id.to_string(), account_id.to_string()],
+                        |row| Ok((api_key_from_row(row)?, row.get::<_, Vec<u8>>(4)?)),
+                    )
+                    .optional()
+                    .map_err(|err| error!("Error loading API key: {:?}", err))
+            })
+            .await?;
+        let (key, secret_hash) =
+            row.ok_or_else(|| warn!("Account {} has no API key {}", account_id, key_id))?;
+        if self
+            .token_verifier
+            .verify(&key_id.to_string(), &secret_hash, secret.as_bytes())
+            .await
+        {
+            Ok(key)
+        } else {
+            Err(())
+        }
+    }
+
+    async fn get_all_accounts(&self) -> Result<Vec<Self::Account>, ()> {
+        self.blocking(move |store| {
+            let accounts = store.load_accounts_where("", &[])?;
+            trace!("Loaded {} accounts", accounts.len());
+            Ok(accounts)
+        })
+        .await
+    }
+
+    async fn get_accounts_page(
+        &self,
+        query: AccountsQuery,
+    ) -> Result<AccountsPage<Self::Account>, ()> {
+        self.blocking(move |store| {
+            let mut conditions = Vec::new();
+            let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            if let Some(cursor) = query.cursor {
+                params.push(Box::new(cursor.to_string()));
+                conditions.push(format!("a.id > ?{}", params.len()));
+            }
+            if let Some(ref asset_code) = query.asset_code {
+                // Asset codes are stored in uppercase
+                params.push(Box::new(asset_code.to_uppercase()));
+                conditions.push(format!("a.asset_code = ?{}", params.len()));
+            }
+            if let Some(routing_relation) = query.routing_relation {
+                params.push(Box::new(routing_relation.to_string()));
+                conditions.push(format!("a.routing_relation = ?{}", params.len()));
+            }
+
+            let mut condition = if conditions.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", conditions.join(" AND "))
+            };
+            condition.push_str(" ORDER BY a.id");
+            // SQLite only compares ASCII characters case-insensitively, so the username
+            // prefix is checked in `from_sorted_accounts` and we cannot limit the query then
+            if let (Some(limit), None) = (query.limit, &query.username_prefix) {
+                let limit = limit.saturating_add(1).min(std::i64::MAX as usize);
+                condition.push_str(&format!(" LIMIT {}", limit));
+            }
+
+            let params: Vec<&dyn rusqlite::ToSql> =
+                params.iter().map(|param| param.as_ref()).collect();
+            let accounts = store.load_accounts_where(&condition, &params)?;
+            Ok(AccountsPage::from_sorted_accounts(accounts, &query))
+        })
+        .await
+    }
+
+    async fn set_static_routes<R>(&self, routes: R) -> Result<(), ()>
+    where
+        R: IntoIterator<Item = (String, Uuid)> + Send + 'async_trait,
+    {
+        let routes: Vec<(String, Uuid)> = routes.into_iter().collect();
+        self.blocking(move |store| {
+            let mut connection = store.connection.lock();
+            // Check that all of the accounts exist
+            for (_, account_id) in routes.iter() {
+                if !account_exists(&connection, *account_id)
+                    .map_err(|err| error!("Error checking if accounts exist: {:?}", err))?
+                {
+                    error!(
+                        "Error setting static routes because account {} does not exist",
+                        account_id
+                    );
+                    return Err(());
+                }
+            }
+
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            transaction
+                .execute("DELETE FROM static_routes", NO_PARAMS)
+                .map_err(|err| error!("Error setting static routes: {:?}", err))?;
+            for (prefix, account_id) in routes.iter() {
+                set_route(&transaction, "static_routes", prefix, *account_id)
+                    .map_err(|err| error!("Error setting static routes: {:?}", err))?;
+            }
+            transaction
+                .commit()
+                .map_err(|err| error!("Error setting static routes: {:?}", err))?;
+            store.update_routes(&connection)
+        })
+        .await
+    }
+
+    async fn get_static_routes(&self) -> Result<HashMap<String, Uuid>, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare("SELECT prefix, account_id FROM static_routes")
+                .map_err(|err| error!("Error getting static routes: {:?}", err))?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| Ok((row.get(0)?, parse_column(row, 1)?)))
+                .map_err(|err| error!("Error getting static routes: {:?}", err))?;
+            rows.collect::<rusqlite::Result<_>>()
+                .map_err(|err| error!("Error getting static routes: {:?}", err))
+        })
+        .await
+    }
+
+    async fn set_static_route(&self, prefix: String, account_id: Uuid) -> Result<(), ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            if !account_exists(&connection, account_id)
+                .map_err(|err| error!("Error checking if account exists: {:?}", err))?
+            {
+                error!(
+                    "Cannot set static route for prefix: {} because account {} does not exist",
+                    prefix, account_id
+                );
+                return Err(());
+            }
+
+            set_route(&connection, "static_routes", &prefix, account_id)
+                .map_err(|err| error!("Error setting static route: {:?}", err))?;
+            store.update_routes(&connection)
+        })
+        .await
+    }
+
+    async fn set_default_route(&self, account_id: Uuid) -> Result<(), ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            if !account_exists(&connection, account_id)
+                .map_err(|err| error!("Error checking if account exists: {:?}", err))?
+            {
+                error!(
+                    "Cannot set default route because account {} does not exist",
+                    account_id
+                );
+                return Err(());
+            }
+
+            set_setting(&connection, DEFAULT_ROUTE_KEY, &account_id.to_string())
+                .map_err(|err| error!("Error setting default route: {:?}", err))?;
+            debug!("Set default route to account id: {}", account_id);
+            store.update_routes(&connection)
+        })
+        .await
+    }
+
+    async fn set_settlement_engines(
+        &self,
+        asset_to_url_map: impl IntoIterator<Item = (String, Url)> + Send + 'async_trait,
+    ) -> Result<(), ()> {
+        let asset_to_url_map: Vec<(String, Url)> = asset_to_url_map.into_iter().collect();
+        self.blocking(move |store| {
+            debug!("Setting settlement engines to {:?}", asset_to_url_map);
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            for (asset_code, url) in asset_to_url_map {
+                transaction
+                    .execute(
+                        "INSERT OR REPLACE INTO settlement_engines (asset_code, url) VALUES (?1, ?2)",
+                        params![asset_code, url.to_string()],
+                    )
+                    .map_err(|err| error!("Error setting settlement engines: {:?}", err))?;
+            }
+            transaction
+                .commit()
+                .map_err(|err| error!("Error setting settlement engines: {:?}", err))
+        })
+        .await
+    }
+
+    async fn get_asset_settlement_engine(&self, asset_code: &str) -> Result<Option<Url>, ()> {
+        let asset_code = asset_code.to_string();
+        self.blocking(move |store| {
+            let url: Option<String> = store
+                .connection
+                .lock()
+                .query_row(
+                    "SELECT url FROM settlement_engines WHERE asset_code = ?1",
+                    params![asset_code],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|err| error!("Error getting settlement engine: {:?}", err))?;
+            if let Some(url) = url {
+                match Url::parse(url.as_str()) {
+                    Ok(url) => Ok(Some(url)),
+                    Err(err) => {
+                        error!(
+                            "Settlement engine URL loaded from SQLite was not a valid URL: {:?}",
+                            err
+                        );
+                        Err(())
+                    }
+                }
+            } else {
+                Ok(None)
+            }
+        })
+        .await
+    }
+
+    async fn get_settlement_engines(&self) -> Result<HashMap<String, Url>, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare("SELECT asset_code, url FROM settlement_engines")
+                .map_err(|err| error!("Error getting settlement engines: {:?}", err))?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| Ok((row.get(0)?, parse_column(row, 1)?)))
+                .map_err(|err| error!("Error getting settlement engines: {:?}", err))?;
+            rows.collect::<rusqlite::Result<_>>()
+                .map_err(|err| error!("Error getting settlement engines: {:?}", err))
+        })
+        .await
+    }
+
+    async fn export_accounts(&self) -> Result<Vec<ExportedAccount>, ()> {
+        self.blocking(move |store| {
+            let accounts = store.load_accounts_where("", &[])?;
+            Ok(accounts.iter().map(Account::export).collect())
+        })
+        .await
+    }
+
+    async fn import_account(&self, account: ExportedAccount) -> Result<Account, ()> {
+        self.blocking(move |store| {
+            let account = Account::try_from_exported(account, store.get_ilp_address())?;
+            store.insert(account)
+        })
+        .await
+    }
+
+    async fn rotate_secret(&self, new_secret: [u8; 32]) -> Result<(), ()> {
+        self.blocking(move |store| {
+            if store
+                .secret_rotation_in_progress
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                error!("Cannot rotate the secret while it is already being rotated");
+                return Err(());
+            }
+            let result = store.reencrypt_tokens(new_secret);
+            store
+                .secret_rotation_in_progress
+                .store(false, Ordering::SeqCst);
+            result
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AddressStore for SqliteStore {
+    // Updates the ILP address of the store & iterates over all children and
+    // updates their ILP Address to match the new address.
+    async fn set_ilp_address(&self, ilp_address: Address) -> Result<(), ()> {
+        self.blocking(move |store| {
+            debug!("Setting ILP address to: {}", ilp_address);
+
+            // Set the ILP address we have in memory
+            (*store.ilp_address.write()) = ilp_address.clone();
+
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            set_setting(&transaction, PARENT_ILP_KEY, &ilp_address.to_string())
+                .map_err(|err| error!("Error setting ILP address {:?}", err))?;
+
+            // Update the address and routes of all children and non-routing accounts.
+            let accounts: Vec<(String, String, String)> = {
+                let mut statement = transaction
+                    .prepare(
+                        "SELECT id, username, ilp_address FROM accounts
+                        WHERE routing_relation NOT IN ('Parent', 'Peer')",
+                    )
+                    .map_err(|err| error!("Error loading accounts: {:?}", err))?;
+                let rows = statement
+                    .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                    .map_err(|err| error!("Error loading accounts: {:?}", err))?;
+                rows.collect::<rusqlite::Result<_>>()
+                    .map_err(|err| error!("Error loading accounts: {:?}", err))?
+            };
+            for (id, username, old_ilp_address) in accounts {
+                // If the node's address ends with the account's username,
+                // this account represents the node's non routing account
+                // and keeps the node's address.
+                let new_ilp_address = if ilp_address.segments().rev().next().unwrap() == username {
+                    ilp_address.clone()
+                } else {
+                    ilp_address
+                        .with_suffix(username.as_bytes())
+                        .map_err(|err| error!("Error appending username to address: {:?}", err))?
+                };
+                transaction
+                    .execute(
+                        "UPDATE accounts SET ilp_address = ?1 WHERE id = ?2",
+                        params![new_ilp_address.to_string(), id],
+                    )
+                    .and_then(|_| {
+                        transaction.execute(
+                            "DELETE FROM routes WHERE prefix = ?1",
+                            params![old_ilp_address],
+                        )
+                    })
+                    .and_then(|_| {
+                        transaction.execute(
+                            "INSERT OR REPLACE INTO routes (prefix, account_id) VALUES (?1, ?2)",
+                            params![new_ilp_address.to_string(), id],
+                        )
+                    })
+                    .map_err(|err| error!("Error updating children: {:?}", err))?;
+            }
+            transaction
+                .commit()
+                .map_err(|err| error!("Error updating children: {:?}", err))?;
+            store.update_routes(&connection)
+        })
+        .await
+    }
+
+    async fn clear_ilp_address(&self) -> Result<(), ()> {
+        self.blocking(move |store| {
+            store
+                .connection
+                .lock()
+                .execute(
+                    "DELETE FROM settings WHERE key = ?1",
+                    params![PARENT_ILP_KEY],
+                )
+                .map_err(|err| error!("Error removing parent address: {:?}", err))?;
+
+            // overwrite the ilp address with the default value
+            *(store.ilp_address.write()) = DEFAULT_ILP_ADDRESS.clone();
+            Ok(())
+        })
+        .await
+    }
+
+    fn get_ilp_address(&self) -> Address {
+        // read consumes the Arc<RwLock<T>> so we cannot return a reference
+        self.ilp_address.read().clone()
+    }
+}
+
+type RoutingTable<A> = HashMap<String, A>;
+
+#[async_trait]
+impl RouteManagerStore for SqliteStore {
+    type Account = Account;
+
+    async fn get_accounts_to_send_routes_to(
+        &self,
+        ignore_accounts: Vec<Uuid>,
+    ) -> Result<Vec<Account>, ()> {
+        self.blocking(move |store| {
+            let accounts =
+                store.load_accounts_where("WHERE a.routing_relation IN ('Child', 'Peer')", &[])?;
+            Ok(accounts
+                .into_iter()
+                .filter(|account| !ignore_accounts.contains(&account.id))
+                .collect())
+        })
+        .await
+    }
+
+    async fn get_accounts_to_receive_routes_from(&self) -> Result<Vec<Account>, ()> {
+        self.blocking(move |store| {
+            store.load_accounts_where("WHERE a.routing_relation IN ('Parent', 'Peer')", &[])
+        })
+        .await
+    }
+
+    async fn get_local_and_configured_routes(
+        &self,
+    ) -> Result<(RoutingTable<Account>, RoutingTable<Account>), ()> {
+        let static_routes = self.get_static_routes().await?;
+
+        let accounts = self.get_all_accounts().await?;
+
+        let local_table = HashMap::from_iter(accounts.iter().flat_map(|account| {
+            account
+                .local_prefixes()
+                .map(move |prefix| (prefix.to_string(), account.clone()))
+        }));
+
+        let account_map: HashMap<Uuid, &Account> =
+            HashMap::from_iter(accounts.iter().map(|account| (account.id, account)));
+        let configured_table: HashMap<String, Account> = HashMap::from_iter(
+            static_routes
+                .into_iter()
+                .filter_map(|(prefix, account_id)| {
+                    if let Some(account) = account_map.get(&account_id) {
+                        Some((prefix, (*account).clone()))
+                    } else {
+                        warn!(
+                            "No account for ID: {}, ignoring configured route for prefix: {}",
+                            account_id, prefix
+                        );
+                        None
+                    }
+                }),
+        );
+
+        Ok((local_table, configured_table))
+    }
+
+    async fn set_routes(
+        &mut self,
+        routes: impl IntoIterator<Item = (String, Account)> + Send + 'async_trait,
+    ) -> Result<(), ()> {
+        let routes: Vec<(String, Uuid)> = routes
+            .into_iter()
+            .map(|(prefix, account)| (prefix, account.id))
+            .collect();
+        self.blocking(move |store| {
+            let num_routes = routes.len();
+
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            transaction
+                .execute("DELETE FROM routes", NO_PARAMS)
+                .map_err(|err| error!("Error setting routes: {:?}", err))?;
+            for (prefix, account_id) in routes.iter() {
+                set_route(&transaction, "routes", prefix, *account_id)
+                    .map_err(|err| error!("Error setting routes: {:?}", err))?;
+            }
+            transaction
+                .commit()
+                .map_err(|err| error!("Error setting routes: {:?}", err))?;
+            trace!("Saved {} routes to SQLite", num_routes);
+
+            store.update_routes(&connection)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for SqliteStore {
+    type Account = Account;
+
+    /// Apply rate limits for number of packets per minute and amount of money per minute,
+    /// then the daily and monthly amount limits
+    ///
+    /// The per minute limits are kept in memory, so they are reset when the node restarts.
+    /// The amounts sent in the current day and month are saved in the database
+    async fn apply_rate_limits(
+        &self,
+        account: Account,
+        prepare_amount: u64,
+    ) -> Result<(), RateLimitError> {
+        self.rate_limits
+            .lock()
+            .apply_rate_limits(&account, prepare_amount)?;
+        if account.amount_per_day_limit.is_none() && account.amount_per_month_limit.is_none() {
+            return Ok(());
+        }
+
+        let limited_account = account.clone();
+        let result = self
+            .blocking(move |store| {
+                let mut connection = store.connection.lock();
+                add_to_amounts_sent(&mut connection, &limited_account, prepare_amount).map_err(
+                    |err| {
+                        error!(
+                            "Error applying the daily and monthly amount limits: {:?}",
+                            err
+                        )
+                    },
+                )
+            })
+            .await
+            .unwrap_or(Err(RateLimitError::StoreError));
+        if result.is_err() {
+            // The packet will not be forwarded, so it does not count towards the throughput
+            self.rate_limits
+                .lock()
+                .refund_throughput_limit(&account, prepare_amount);
+        }
+        result
+    }
+
+    async fn refund_throughput_limit(
+        &self,
+        account: Account,
+        prepare_amount: u64,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            store
+                .rate_limits
+                .lock()
+                .refund_throughput_limit(&account, prepare_amount);
+            if account.amount_per_day_limit.is_none() && account.amount_per_month_limit.is_none() {
+                return Ok(());
+            }
+
+            let connection = store.connection.lock();
+            for period in AmountLimitPeriod::ALL.iter() {
+                connection
+                    .execute(
+                        "UPDATE amounts_sent SET amount = MAX(amount - ?4, 0)
+                        WHERE account_id = ?1 AND period = ?2 AND period_id = ?3",
+                        params![
+                            account.id.to_string(),
+                            period.as_ref(),
+                            period.current_id(),
+                            prepare_amount as i64
+                        ],
+                    )
+                    .map_err(|err| error!("Error refunding the amount sent: {:?}", err))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_cumulative_amounts(&self, account: Account) -> Result<CumulativeAmounts, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            get_amounts_sent(&connection, account.id).map_err(|err| {
+                error!(
+                    "Error getting the amounts sent by account {}: {:?}",
+                    account.id, err
+                )
+            })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl FeeStore for SqliteStore {
+    async fn record_fee(
+        &self,
+        from_account_id: Uuid,
+        to_account_id: Uuid,
+        amount: u64,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            connection
+                .execute(
+                    "INSERT INTO fees_earned (from_account_id, to_account_id, amount)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT(from_account_id, to_account_id)
+                    DO UPDATE SET amount = amount + excluded.amount",
+                    params![
+                        from_account_id.to_string(),
+                        to_account_id.to_string(),
+                        amount as i64
+                    ],
+                )
+                .map_err(|err| error!("Error recording fee: {:?}", err))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_fees_earned(&self) -> Result<Vec<FeesEarned>, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            let mut statement = connection
+                .prepare("SELECT from_account_id, to_account_id, amount FROM fees_earned")
+                .map_err(|err| error!("Error preparing query: {:?}", err))?;
+            let fees = statement
+                .query_map(NO_PARAMS, |row| {
+                    let amount: i64 = row.get(2)?;
+                    Ok(FeesEarned {
+                        from_account_id: parse_column(row, 0)?,
+                        to_account_id: parse_column(row, 1)?,
+                        amount: amount as u64,
+                    })
+                })
+                .and_then(|rows| rows.collect::<Result<Vec<FeesEarned>, _>>())
+                .map_err(|err| error!("Error getting fees earned: {:?}", err))?;
+            Ok(fees)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl IdempotentStore for SqliteStore {
+    async fn load_idempotent_data(
+        &self,
+        idempotency_key: String,
+    ) -> Result<Option<IdempotentData>, ()> {
+        self.blocking(move |store| {
+            let connection = store.connection.lock();
+            connection
+                .execute(
+                    "DELETE FROM idempotency_keys WHERE expires_at <= ?1",
+                    params![now()],
+                )
+                .map_err(|err| error!("Error removing expired idempotency keys: {:?}", err))?;
+            let ret: Option<(u16, Vec<u8>, Vec<u8>)> = connection
+                .query_row(
+                    "SELECT status_code, data, input_hash FROM idempotency_keys WHERE key = ?1",
+                    params![idempotency_key],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()
+                .map_err(|err| {
+                    error!(
+                        "Error loading idempotency key {}: {:?}",
+                        idempotency_key, err
+                    )
+                })?;
+
+            if let Some((status_code, data, input_hash_slice)) = ret {
+                trace!(
+                    "Loaded idempotency key {:?} - {:?}",
+                    idempotency_key,
+                    status_code
+                );
+                let mut input_hash: [u8; 32] = Default::default();
+                input_hash.copy_from_slice(input_hash_slice.as_ref());
+                Ok(Some(IdempotentData::new(
+                    StatusCode::from_u16(status_code)
+                        .map_err(|err| error!("Invalid status code stored: {:?}", err))?,
+                    Bytes::from(data),
+                    input_hash,
+                )))
+            } else {
+                Ok(None)
+            }
+        })
+        .await
+    }
+
+    async fn save_idempotent_data(
+        &self,
+        idempotency_key: String,
+        input_hash: [u8; 32],
+        status_code: StatusCode,
+        data: Bytes,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            store.connection
+                .lock()
+                .execute(
+                    "INSERT OR REPLACE INTO idempotency_keys (key, status_code, data, input_hash, expires_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        idempotency_key,
+                        status_code.as_u16(),
+                        data.as_ref(),
+                        &input_hash[..],
+                        now() + IDEMPOTENCY_KEY_EXPIRY as i64
+                    ],
+                )
+                .map_err(|err| error!("Error caching: {:?}", err))?;
+
+            trace!(
+                "Cached {:?}: {:?}, {:?}",
+                idempotency_key,
+                status_code,
+                data,
+            );
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl SettlementStore for SqliteStore {
+    type Account = Account;
+
+    async fn update_balance_for_incoming_settlement(
+        &self,
+        account_id: Uuid,
+        amount: u64,
+        idempotency_key: Option<String>,
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+
+            // Make sure each settlement is only credited once
+            if let Some(ref idempotency_key) = idempotency_key {
+                let already_processed: bool = transaction
+                    .query_row(
+                        "SELECT EXISTS(SELECT 1 FROM settlement_idempotency_keys
+                        WHERE key = ?1 AND expires_at > ?2)",
+                        params![idempotency_key, now()],
+                        |row| row.get(0),
+                    )
+                    .map_err(|err| error!("Error checking idempotency key: {:?}", err))?;
+                if already_processed {
+                    trace!(
+                        "Incoming settlement with idempotency key {} was already processed",
+                        idempotency_key
+                    );
+                    return Ok(());
+                }
+                transaction
+                    .execute(
+                        "INSERT OR REPLACE INTO settlement_idempotency_keys (key, expires_at) VALUES (?1, ?2)",
+                        params![idempotency_key, now() + IDEMPOTENCY_KEY_EXPIRY as i64],
+                    )
+                    .map_err(|err| error!("Error saving idempotency key: {:?}", err))?;
+            }
+
+            let (balance, prepaid_amount) = get_balances(&transaction, account_id).map_err(|err| {
+                error!(
+                    "Error processing incoming settlement from account: {} for amount: {}: {:?}",
+                    account_id, amount, err
+                )
+            })?;
+            let amount = amount as i64;
+
+            // If the balance is negative, the settlement first pays off the debt
+            // and any remaining amount is added to the prepaid amount
+            let (balance, prepaid_amount) = if balance >= 0 {
+                (balance, prepaid_amount + amount)
+            } else if balance.abs() >= amount {
+                (balance + amount, prepaid_amount)
+            } else {
+                (0, prepaid_amount + amount + balance)
+            };
+            set_balances(&transaction, account_id, balance, prepaid_amount)
+                .and_then(|_| {
+                    append_journal_entry(
+                        &transaction,
+                        account_id,
+                        BalanceChangeKind::IncomingSettlement,
+                        amount,
+                        None,
+                    )
+                })
+                .and_then(|_| transaction.commit())
+                .map_err(|err| {
+                    error!(
+                        "Error processing incoming settlement from account: {} for amount: {}: {:?}",
+                        account_id, amount, err
+                    )
+                })?;
+
+            trace!(
+                "Processed incoming settlement from account: {} for amount: {}. Balance is now: {}",
+                account_id,
+                amount,
+                balance + prepaid_amount
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    async fn refund_settlement(&self, account_id: Uuid, settle_amount: u64) -> Result<(), ()> {
+        self.blocking(move |store| {
+            trace!(
+                "Refunding settlement for account: {} of amount: {}",
+                account_id,
+                settle_amount
+            );
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            let updated = transaction
+                .execute(
+                    "UPDATE accounts SET balance = balance + ?1 WHERE id = ?2",
+                    params![settle_amount as i64, account_id.to_string()],
+                )
+                .and_then(|updated| {
+                    if updated > 0 {
+                        append_journal_entry(
+                            &transaction,
+                            account_id,
+                            BalanceChangeKind::SettlementRefund,
+                            settle_amount as i64,
+                            None,
+                        )?;
+                    }
+                    Ok(updated)
+                })
+                .and_then(|updated| transaction.commit().map(|_| updated))
+                .map_err(|err| {
+                    error!(
+                        "Error refunding settlement for account: {} of amount: {}: {:?}",
+                        account_id, settle_amount, err
+                    )
+                })?;
+            if updated == 0 {
+                error!(
+                    "Error refunding settlement for account that does not exist: {}",
+                    account_id
+                );
+                return Err(());
+            }
+
+            trace!(
+                "Refunded settlement for account: {} of amount: {}",
+                account_id,
+                settle_amount
+            );
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Loads the uncredited amounts of the account along with their scales
+fn select_uncredited_amounts(
+    connection: &Connection,
+    account_id: Uuid,
+) -> rusqlite::Result<Vec<(BigUint, u8)>> {
+    let mut statement = connection
+        .prepare("SELECT amount, scale FROM uncredited_settlement_amounts WHERE account_id = ?1")?;
+    let rows = statement.query_map(params![account_id.to_string()], |row| {
+        Ok((parse_column(row, 0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Sums up the amounts after normalizing them to the largest scale
+fn sum_uncredited_amounts(amounts: Vec<(BigUint, u8)>) -> Result<(BigUint, u8), ()> {
+    let max_scale = amounts.iter().map(|(_, scale)| *scale).max().unwrap_or(0);
+    let mut sum = BigUint::from(0u32);
+    for (num, scale) in amounts {
+        sum += num
+            .normalize_scale(ConvertDetails {
+                from: scale,
+                to: max_scale,
+            })
+            .map_err(|_| error!("Error normalizing uncredited_settlement_amount"))?;
+    }
+    Ok((sum, max_scale))
+}
+
+#[async_trait]
+impl LeftoversStore for SqliteStore {
+    type AccountId = Uuid;
+    type AssetType = BigUint;
+
+    async fn get_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        self.blocking(move |store| {
+            let mut connection = store.connection.lock();
+            let transaction = connection
+                .transaction()
+                .map_err(|err| error!("Error starting transaction: {:?}", err))?;
+            // get the amounts and instantly delete them
+            let amounts = select_uncredited_amounts(&transaction, account_id)
+                .map_err(|err| error!("Error getting uncredited_settlement_amount {:?}", err))?;
+            transaction
+                .execute(
+                    "DELETE FROM uncredited_settlement_amounts WHERE account_id = ?1",
+                    params![account_id.to_string()],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|err| error!("Error getting uncredited_settlement_amount {:?}", err))?;
+            sum_uncredited_amounts(amounts)
+        })
+        .await
+    }
+
+    async fn peek_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+    ) -> Result<(Self::AssetType, u8), ()> {
+        self.blocking(move |store| {
+            let amounts = select_uncredited_amounts(&store.connection.lock(), account_id)
+                .map_err(|err| error!("Error getting uncredited_settlement_amount {:?}", err))?;
+            sum_uncredited_amounts(amounts)
+        })
+        .await
+    }
+
+    async fn save_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+        uncredited_settlement_amount: (Self::AssetType, u8),
+    ) -> Result<(), ()> {
+        self.blocking(move |store| {
+            trace!(
+                "Saving uncredited_settlement_amount {:?} {:?}",
+                account_id,
+                uncredited_settlement_amount
+            );
+            // The amounts are stored as strings because SQLite integers
+            // cannot hold arbitrarily large numbers. When loading the amounts,
+            // we convert them to the appropriate data type and sum them up.
+            store.connection
+                .lock()
+                .execute(
+                    "INSERT INTO uncredited_settlement_amounts (account_id, amount, scale) VALUES (?1, ?2, ?3)",
+                    params![
+                        account_id.to_string(),
+                        uncredited_settlement_amount.0.to_string(),
+                        uncredited_settlement_amount.1
+                    ],
+                )
+                .map_err(|err| error!("Error saving uncredited_settlement_amount: {:?}", err))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_uncredited_settlement_amount(
+        &self,
+        account_id: Uuid,
+        local_scale: u8,
+    ) -> Result<Self::AssetType, ()> {
+        trace!("Loading uncredited_settlement_amount {:?}", account_id);
+        let amount = self.get_uncredited_settlement_amount(account_id).await?;
+        // scale the amount from the max scale to the local scale, and then
+        // save any potential leftovers to the store
+        let (scaled_amount, precision_loss) =
+            scale_with_precision_loss(amount.0, local_scale, amount.1);
+
+        if precision_loss > BigUint::from(0u32) {
+            self.save_uncredited_settlement_amount(
+                account_id,
+                (precision_loss, std::cmp::max(local_scale, amount.1)),
+            )
+            .await?;
+        }
+
+        Ok(scaled_amount)
+    }
+
+    async fn clear_uncredited_settlement_amount(&self, account_id: Uuid) -> Result<(), ()> {
+        self.blocking(move |store| {
+            trace!("Clearing uncredited_settlement_amount {:?}", account_id);
+            store
+                .connection
+                .lock()
+                .execute(
+                    "DELETE FROM uncredited_settlement_amounts WHERE account_id = ?1",
+                    params![account_id.to_string()],
+                )
+                .map_err(|err| error!("Error clearing uncredited_settlement_amount: {:?}", err))?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,170 @@
+use super::account::Account;
+use interledger_service_util::{
+    AmountLimitPeriod, CumulativeAmounts, RateLimitAccount, RateLimitError,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// The per minute limits are the rates at which the burst limits refill
+const RATE_LIMIT_PERIOD: Duration = Duration::from_secs(60);
+
+/// Keeps the packet and throughput limits of each account in the process' memory.
+///
+/// This implements the Generic Cell Rate Algorithm the same way as the redis-cell
+/// module's `CL.THROTTLE` command which the RedisStore uses, so that accounts
+/// are limited the same way regardless of the store backing the node.
+pub(crate) struct Throttle {
+    started_at: Instant,
+    /// Theoretical arrival times (in nanoseconds since `started_at`) of each limiter
+    tats: HashMap<String, i128>,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle {
+            started_at: Instant::now(),
+            tats: HashMap::new(),
+        }
+    }
+}
+
+impl Throttle {
+    /// Apply rate limits for number of packets and amount of money, which accounts can
+    /// send in bursts up to their burst limits and then at their per minute limits
+    pub(crate) fn apply_rate_limits(
+        &mut self,
+        account: &Account,
+        prepare_amount: u64,
+    ) -> Result<(), RateLimitError> {
+        let packet_limit_exceeded = match account.packets_per_minute_limit {
+            Some(limit) => {
+                let burst = account.packets_burst_limit.unwrap_or(limit);
+                self.throttle(
+                    format!("limit:packets:{}", account.id),
+                    u64::from(burst).saturating_sub(1),
+                    u64::from(limit),
+                    1,
+                )
+            }
+            None => false,
+        };
+        let throughput_limit_exceeded = match account.amount_per_minute_limit {
+            Some(limit) => {
+                let burst = account.amount_burst_limit.unwrap_or(limit);
+                self.throttle(
+                    format!("limit:throughput:{}", account.id),
+                    burst.saturating_sub(1),
+                    limit,
+                    i64::try_from(prepare_amount).unwrap_or(i64::MAX),
+                )
+            }
+            None => false,
+        };
+
+        if packet_limit_exceeded {
+            Err(RateLimitError::PacketLimitExceeded)
+        } else if throughput_limit_exceeded {
+            Err(RateLimitError::ThroughputLimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gives the amount of a rejected packet back to the account's throughput limit
+    pub(crate) fn refund_throughput_limit(&mut self, account: &Account, prepare_amount: u64) {
+        if let Some(limit) = account.amount_per_minute_limit {
+            let burst = account.amount_burst_limit.unwrap_or(limit);
+            self.throttle(
+                format!("limit:throughput:{}", account.id),
+                burst.saturating_sub(1),
+                limit,
+                -i64::try_from(prepare_amount).unwrap_or(i64::MAX),
+            );
+        }
+    }
+
+    /// Returns true if the request was limited. Negative quantities give capacity
+    /// back to the limiter.
+    fn throttle(&mut self, key: String, max_burst: u64, count: u64, quantity: i64) -> bool {
+        let now = self.started_at.elapsed().as_nanos() as i128;
+        let emission_interval = RATE_LIMIT_PERIOD.as_nanos() as i128 / count.max(1) as i128;
+        let delay_variation_tolerance = emission_interval * (max_burst as i128 + 1);
+
+        let tat = self.tats.get(&key).copied().unwrap_or(now).max(now);
+        let new_tat = tat + emission_interval * quantity as i128;
+        if new_tat - delay_variation_tolerance > now {
+            true
+        } else {
+            self.tats.insert(key, new_tat);
+            false
+        }
+    }
+}
+
+/// Keeps the amounts each account sent in the current day and month in the process' memory
+#[derive(Default)]
+pub(crate) struct AmountLimits {
+    /// The id of the period each amount was sent in, so that amounts sent
+    /// in a previous day or month are not counted
+    sent: HashMap<(Uuid, AmountLimitPeriod), (String, u64)>,
+}
+
+impl AmountLimits {
+    /// Adds the amount to what the account sent in the current periods,
+    /// unless that would exceed one of its limits
+    pub(crate) fn apply(&mut self, account: &Account, amount: u64) -> Result<(), RateLimitError> {
+        for (period, sent) in add_amount_sent(account, self.get(account.id), amount)? {
+            self.sent
+                .insert((account.id, period), (period.current_id(), sent));
+        }
+        Ok(())
+    }
+
+    /// Gives the amount of a rejected packet back to the account
+    pub(crate) fn refund(&mut self, account: &Account, amount: u64) {
+        for period in AmountLimitPeriod::ALL.iter() {
+            if let Some((id, sent)) = self.sent.get_mut(&(account.id, *period)) {
+                if *id == period.current_id() {
+                    *sent = sent.saturating_sub(amount);
+                }
+            }
+        }
+    }
+
+    /// Returns the amounts the account sent in the current periods
+    pub(crate) fn get(&self, account_id: Uuid) -> CumulativeAmounts {
+        let mut amounts = CumulativeAmounts::default();
+        for period in AmountLimitPeriod::ALL.iter() {
+            if let Some((id, sent)) = self.sent.get(&(account_id, *period)) {
+                if *id == period.current_id() {
+                    amounts.set(*period, *sent);
+                }
+            }
+        }
+        amounts
+    }
+}
+
+/// Returns the amounts the account will have sent in each period it has a limit for
+/// if it sends `amount`, or an error if that would exceed one of the limits
+pub(crate) fn add_amount_sent(
+    account: &Account,
+    sent: CumulativeAmounts,
+    amount: u64,
+) -> Result<Vec<(AmountLimitPeriod, u64)>, RateLimitError> {
+    let mut amounts = Vec::with_capacity(AmountLimitPeriod::ALL.len());
+    for period in AmountLimitPeriod::ALL.iter() {
+        if let Some(limit) = account.amount_limit(*period) {
+            let sent = sent.get(*period).saturating_add(amount);
+            if sent > limit {
+                return Err(period.limit_exceeded());
+            }
+            amounts.push((*period, sent));
+        }
+    }
+    Ok(amounts)
+}
@@ -0,0 +1,361 @@
+use super::{fixtures::*, store_helpers::*};
+use interledger_api::{
+    AccountSettings, AccountsQuery, ApiKey, ApiKeyScope, ExportedAccount, NodeStore,
+};
+use interledger_btp::BtpAccount;
+use interledger_ccp::{CcpRoutingAccount, RoutingRelation};
+use interledger_http::HttpAccount;
+use interledger_packet::Address;
+use interledger_service::Account as AccountTrait;
+use interledger_service::{AccountStatus, AccountStore, AddressStore, Username};
+use interledger_service_util::{BalanceStore, DestinationFilterAccount, StatusAccount};
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn insert_accounts() {
+    let (store, _) = test_store().await.unwrap();
+    let account = store
+        .insert_account(ACCOUNT_DETAILS_2.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        *account.ilp_address(),
+        Address::from_str("example.alice.user1.charlie").unwrap()
+    );
+}
+
+#[tokio::test]
+async fn rejects_duplicate_usernames() {
+    let (store, _) = test_store().await.unwrap();
+    let mut acc = ACCOUNT_DETAILS_2.clone();
+    acc.username = Username::from_str("bob").unwrap();
+    let res = store.insert_account(acc).await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn update_ilp_and_children_addresses() {
+    let (store, accs) = test_store().await.unwrap();
+    // Add a NonRoutingAccount to make sure its address
+    // gets updated as well
+    let acc2 = store
+        .insert_account(ACCOUNT_DETAILS_2.clone())
+        .await
+        .unwrap();
+    let mut accs = accs.clone();
+    accs.push(acc2);
+    accs.sort_by_key(|a| a.username().clone());
+    let ilp_address = Address::from_str("test.parent.our_address").unwrap();
+
+    store.set_ilp_address(ilp_address.clone()).await.unwrap();
+    let ret = store.get_ilp_address();
+    assert_eq!(ilp_address, ret);
+
+    let mut accounts = store.get_all_accounts().await.unwrap();
+    accounts.sort_by_key(|a| a.username().clone());
+    for (a, b) in accounts.into_iter().zip(&accs) {
+        if a.routing_relation() == RoutingRelation::Child
+            || a.routing_relation() == RoutingRelation::NonRoutingAccount
+        {
+            assert_eq!(
+                *a.ilp_address(),
+                ilp_address.with_suffix(a.username().as_bytes()).unwrap()
+            );
+        } else {
+            assert_eq!(a.ilp_address(), b.ilp_address());
+        }
+    }
+}
+
+#[tokio::test]
+async fn only_one_parent_allowed() {
+    let mut acc = ACCOUNT_DETAILS_2.clone();
+    acc.routing_relation = Some("Parent".to_owned());
+    acc.username = Username::from_str("another_name").unwrap();
+    acc.ilp_address = Some(Address::from_str("example.another_name").unwrap());
+    let (store, accs) = test_store().await.unwrap();
+    let res = store.insert_account(acc.clone()).await;
+    // This should fail
+    assert!(res.is_err());
+    store.delete_account(accs[0].id()).await.unwrap();
+    // must also clear the ILP Address to indicate that we no longer
+    // have a parent account configured
+    store.clear_ilp_address().await.unwrap();
+    let res = store.insert_account(acc).await;
+    assert!(res.is_ok());
+}
+
+#[tokio::test]
+async fn delete_accounts() {
+    let (store, _) = test_store().await.unwrap();
+    let accounts = store.get_all_accounts().await.unwrap();
+    let id = accounts[0].id();
+    let username = accounts[0].username().clone();
+    store.delete_account(id).await.unwrap();
+    let accounts = store.get_all_accounts().await.unwrap();
+    for a in accounts {
+        assert_ne!(id, a.id());
+    }
+    assert!(store.get_account_id_from_username(&username).await.is_err());
+}
+
+#[tokio::test]
+async fn update_accounts() {
+    let (store, accounts) = test_store().await.unwrap();
+    let id = accounts[0].id();
+    store
+        .update_balances_for_prepare(accounts[0].clone(), 500, packet_context())
+        .await
+        .unwrap();
+    let mut new = ACCOUNT_DETAILS_0.clone();
+    new.asset_code = String::from("TUV");
+    let account = store.update_account(id, new).await.unwrap();
+    assert_eq!(account.asset_code(), "TUV");
+    // the balance is kept when the account is updated
+    let balance = store.get_balance(account).await.unwrap();
+    assert_eq!(balance, -500);
+}
+
+#[tokio::test]
+async fn modify_account_settings_settle_to_overflow() {
+    let (store, accounts) = test_store().await.unwrap();
+    let mut settings = AccountSettings::default();
+    settings.settle_to = Some(std::i64::MAX as u64 + 1);
+    let id = accounts[0].id();
+    let ret = store.modify_account_settings(id, settings).await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn modify_account_settings() {
+    let (store, accounts) = test_store().await.unwrap();
+    let settings = AccountSettings {
+        ilp_over_http_outgoing_token: Some(SecretString::new("test_token".to_owned())),
+        ilp_over_http_incoming_token: Some(SecretString::new("http_in_new".to_owned())),
+        ilp_over_btp_outgoing_token: Some(SecretString::new("dylan:test".to_owned())),
+        ilp_over_btp_incoming_token: Some(SecretString::new("btp_in_new".to_owned())),
+        ilp_over_http_url: Some("http://example.com/accounts/dylan/ilp".to_owned()),
+        ilp_over_btp_url: Some("http://example.com/accounts/dylan/ilp/btp".to_owned()),
+        settle_threshold: Some(-50),
+        settle_to: Some(100),
+    };
+    let id = accounts[0].id();
+    let ret = store.modify_account_settings(id, settings).await.unwrap();
+    assert_eq!(
+        ret.get_http_auth_token().unwrap().expose_secret(),
+        "test_token",
+    );
+    assert_eq!(
+        ret.get_ilp_over_btp_outgoing_token().unwrap(),
+        &b"dylan:test"[..],
+    );
+}
+
+#[tokio::test]
+async fn sets_account_status() {
+    let (store, accounts) = test_store().await.unwrap();
+    let id = accounts[1].id();
+    store.set_balance(accounts[1].clone(), 100).await.unwrap();
+
+    let suspended = store
+        .set_account_status(id, AccountStatus::Suspended)
+        .await
+        .unwrap();
+    assert_eq!(suspended.status(), AccountStatus::Suspended);
+    assert!(!suspended.is_routable());
+    // Updating the account's details keeps its status and balance
+    let updated = store
+        .update_account(id, ACCOUNT_DETAILS_1.clone())
+        .await
+        .unwrap();
+    assert_eq!(updated.status(), AccountStatus::Suspended);
+    assert_eq!(store.get_balance(updated).await.unwrap(), 100);
+
+    let active = store
+        .set_account_status(id, AccountStatus::Active)
+        .await
+        .unwrap();
+    assert_eq!(active.status(), AccountStatus::Active);
+    assert!(active.is_routable());
+    assert!(store
+        .set_account_status(Uuid::new_v4(), AccountStatus::Closing)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn manages_api_keys() {
+    let (store, accounts) = test_store().await.unwrap();
+    let id = accounts[0].id();
+    let (key, secret) = ApiKey::generate(id, vec![ApiKeyScope::ReadBalance], None);
+    let key = store.insert_api_key(key, secret.clone()).await.unwrap();
+    assert_eq!(store.get_api_keys(id).await.unwrap(), vec![key.clone()]);
+    assert!(store
+        .get_api_keys(accounts[1].id())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let authorized = store
+        .get_api_key_from_auth(id, key.id, secret.expose_secret())
+        .await
+        .unwrap();
+    assert_eq!(authorized, key);
+    assert!(store
+        .get_api_key_from_auth(id, key.id, "wrong")
+        .await
+        .is_err());
+    // The key only authorizes requests on behalf of its own account
+    assert!(store
+        .get_api_key_from_auth(accounts[1].id(), key.id, secret.expose_secret())
+        .await
+        .is_err());
+
+    assert_eq!(store.delete_api_key(id, key.id).await.unwrap(), key);
+    assert!(store.get_api_keys(id).await.unwrap().is_empty());
+    assert!(store.delete_api_key(id, key.id).await.is_err());
+}
+
+#[tokio::test]
+async fn fetches_account_from_username() {
+    let (store, accs) = test_store().await.unwrap();
+    let account_id = store
+        .get_account_id_from_username(&Username::from_str("alice").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(account_id, accs[0].id());
+}
+
+#[tokio::test]
+async fn pages_through_accounts() {
+    let (store, accs) = test_store().await.unwrap();
+    let mut ids: Vec<Uuid> = accs.iter().map(|acc| acc.id()).collect();
+    ids.sort();
+
+    let query = AccountsQuery {
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = store.get_accounts_page(query.clone()).await.unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].id(), ids[0]);
+    assert_eq!(page.next_cursor, Some(ids[0]));
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            cursor: page.next_cursor,
+            ..query
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].id(), ids[1]);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[tokio::test]
+async fn filters_accounts_page() {
+    let (store, _) = test_store().await.unwrap();
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            asset_code: Some("abc".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "bob");
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            routing_relation: Some(RoutingRelation::Parent),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "alice");
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            username_prefix: Some("AL".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "alice");
+    assert_eq!(page.next_cursor, None);
+}
+
+#[tokio::test]
+async fn gets_multiple() {
+    let (store, accs) = test_store().await.unwrap();
+    // set account ids in reverse order
+    let account_ids: Vec<Uuid> = accs.iter().rev().map(|a| a.id()).collect::<_>();
+    let accounts = store.get_accounts(account_ids).await.unwrap();
+    // note reverse order is intentional
+    assert_eq!(accounts[0].ilp_address(), accs[1].ilp_address());
+    assert_eq!(accounts[1].ilp_address(), accs[0].ilp_address());
+}
+
+#[tokio::test]
+async fn errors_for_unknown_accounts() {
+    let (store, _) = test_store().await.unwrap();
+    let result = store
+        .get_accounts(vec![Uuid::new_v4(), Uuid::new_v4()])
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn imports_exported_accounts() {
+    let (store, accs) = test_store().await.unwrap();
+    store.set_balance(accs[1].clone(), 42).await.unwrap();
+    assert_eq!(store.get_balance(accs[1].clone()).await.unwrap(), 42);
+    let exported = store.export_accounts().await.unwrap();
+    assert_eq!(exported.len(), 2);
+
+    let (other, _) = test_store().await.unwrap();
+    let bob = exported.into_iter().find(|a| a.id == accs[1].id()).unwrap();
+    let mut bob_details = bob.details.clone();
+    bob_details.username = Username::from_str("bob2").unwrap();
+    let imported = other
+        .import_account(ExportedAccount {
+            details: bob_details,
+            ..bob
+        })
+        .await
+        .unwrap();
+    assert_eq!(imported.id(), accs[1].id());
+    assert_eq!(imported.username().as_ref(), "bob2");
+    assert_eq!(
+        imported.get_ilp_over_btp_outgoing_token().unwrap(),
+        b"btp_token"
+    );
+    assert_eq!(other.get_balance(imported).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn saves_destination_prefixes() {
+    let (store, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.allowed_destination_prefixes = vec!["example.".to_string(), "g.partner.".to_string()];
+    details.denied_destination_prefixes = vec!["example.blocked".to_string()];
+    let id = store.insert_account(details.clone()).await.unwrap().id();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(
+        account.allowed_destination_prefixes(),
+        &["example.", "g.partner."]
+    );
+    assert_eq!(account.denied_destination_prefixes(), &["example.blocked"]);
+
+    details.allowed_destination_prefixes.clear();
+    store.update_account(id, details).await.unwrap();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert!(account.allowed_destination_prefixes().is_empty());
+    assert_eq!(account.denied_destination_prefixes(), &["example.blocked"]);
+}
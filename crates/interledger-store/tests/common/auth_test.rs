@@ -0,0 +1,107 @@
+use super::{fixtures::*, store_helpers::*};
+
+use interledger_api::NodeStore;
+use interledger_btp::{BtpAccount, BtpStore};
+use interledger_http::{HttpAccount, HttpStore};
+use interledger_packet::Address;
+use interledger_service::{Account as AccountTrait, Username};
+use secrecy::{ExposeSecret, SecretString};
+use std::str::FromStr;
+
+#[tokio::test]
+async fn gets_account_from_btp_auth() {
+    let (store, _) = test_store().await.unwrap();
+    let account = store
+        .get_account_from_btp_auth(&Username::from_str("bob").unwrap(), "other_btp_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        *account.ilp_address(),
+        Address::from_str("example.alice.user1.bob").unwrap()
+    );
+    assert_eq!(
+        &account.get_ilp_over_btp_outgoing_token().unwrap(),
+        b"btp_token"
+    );
+}
+
+#[tokio::test]
+async fn errors_on_unknown_user_or_wrong_btp_token() {
+    let (store, _) = test_store().await.unwrap();
+    let result = store
+        .get_account_from_btp_auth(&Username::from_str("asdf").unwrap(), "other_btp_token")
+        .await;
+    assert!(result.is_err());
+
+    let result = store
+        .get_account_from_btp_auth(&Username::from_str("bob").unwrap(), "wrong_token")
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn gets_btp_outgoing_accounts() {
+    let (store, _) = test_store().await.unwrap();
+    // charlie does not have a BTP URL
+    store
+        .insert_account(ACCOUNT_DETAILS_2.clone())
+        .await
+        .unwrap();
+    let accounts = store.get_btp_outgoing_accounts().await.unwrap();
+    assert_eq!(accounts.len(), 2);
+}
+
+#[tokio::test]
+async fn gets_account_from_http_bearer_token() {
+    let (store, _) = test_store().await.unwrap();
+    let account = store
+        .get_account_from_http_auth(&Username::from_str("alice").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        *account.ilp_address(),
+        Address::from_str("example.alice").unwrap()
+    );
+    assert_eq!(
+        account.get_http_auth_token().unwrap().expose_secret(),
+        "outgoing_auth_token",
+    );
+}
+
+#[tokio::test]
+async fn errors_on_unknown_user_or_wrong_http_token() {
+    let (store, _) = test_store().await.unwrap();
+    // wrong password
+    let result = store
+        .get_account_from_http_auth(&Username::from_str("alice").unwrap(), "unknown_token")
+        .await;
+    assert!(result.is_err());
+
+    // wrong user
+    let result = store
+        .get_account_from_http_auth(&Username::from_str("asdf").unwrap(), "incoming_auth_token")
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn duplicate_http_incoming_auth_works() {
+    let mut duplicate = ACCOUNT_DETAILS_2.clone();
+    duplicate.ilp_over_http_incoming_token =
+        Some(SecretString::new("incoming_auth_token".to_string()));
+    let (store, accs) = test_store().await.unwrap();
+    let duplicate = store.insert_account(duplicate).await.unwrap();
+    let alice = store
+        .get_account_from_http_auth(&Username::from_str("alice").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    let charlie = store
+        .get_account_from_http_auth(
+            &Username::from_str("charlie").unwrap(),
+            "incoming_auth_token",
+        )
+        .await
+        .unwrap();
+    assert_eq!(alice.id(), accs[0].id());
+    assert_eq!(charlie.id(), duplicate.id());
+}
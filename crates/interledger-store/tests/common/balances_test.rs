@@ -0,0 +1,261 @@
+use super::{fixtures::*, store_helpers::*};
+
+use interledger_api::NodeStore;
+use interledger_packet::Address;
+use interledger_service::{Account as AccountTrait, AccountStore, Username};
+use interledger_service_util::{
+    BalanceChangeKind, BalanceHistoryQuery, BalanceStore, PacketContext,
+};
+use interledger_settlement::core::types::SettlementStore;
+use std::str::FromStr;
+
+#[tokio::test]
+async fn starts_with_zero_balance() {
+    let (store, accs) = test_store().await.unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 0);
+}
+
+#[tokio::test]
+async fn prepare_then_fulfill_with_settlement() {
+    let (store, accs) = test_store().await.unwrap();
+    let accounts = store
+        .get_accounts(vec![accs[0].id(), accs[1].id()])
+        .await
+        .unwrap();
+    let account0 = accounts[0].clone();
+    let account1 = accounts[1].clone();
+    // reduce account 0's balance by 100
+    store
+        .update_balances_for_prepare(account0.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    let balance0 = store.get_balance(account0.clone()).await.unwrap();
+    let balance1 = store.get_balance(account1.clone()).await.unwrap();
+    assert_eq!(balance0, -100);
+    assert_eq!(balance1, 0);
+
+    // Account 1 goes over its settle threshold and settles down to settle_to
+    let (balance, amount_to_settle) = store
+        .update_balances_for_fulfill(account1.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    assert_eq!(balance, -1000);
+    assert_eq!(amount_to_settle, 1100);
+    let balance0 = store.get_balance(account0).await.unwrap();
+    let balance1 = store.get_balance(account1).await.unwrap();
+    assert_eq!(balance0, -100);
+    assert_eq!(balance1, -1000);
+}
+
+#[tokio::test]
+async fn process_fulfill_no_settle_to() {
+    // account without a settle_to
+    let acc = {
+        let mut acc = ACCOUNT_DETAILS_1.clone();
+        acc.username = Username::from_str("charlie").unwrap();
+        acc.ilp_address = Some(Address::from_str("example.charlie").unwrap());
+        acc.settle_to = None;
+        acc
+    };
+    let (store, _accs) = test_store().await.unwrap();
+    let acc = store.insert_account(acc).await.unwrap();
+    let (balance, amount_to_settle) = store
+        .update_balances_for_fulfill(acc.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    assert_eq!(balance, 100);
+    assert_eq!(amount_to_settle, 0);
+}
+
+#[tokio::test]
+async fn process_fulfill_settle_to_over_threshold() {
+    // account misconfigured with settle_to >= settle_threshold does not get settlements
+    let acc = {
+        let mut acc = ACCOUNT_DETAILS_1.clone();
+        acc.username = Username::from_str("charlie").unwrap();
+        acc.ilp_address = Some(Address::from_str("example.b").unwrap());
+        acc.settle_to = Some(101);
+        acc.settle_threshold = Some(100);
+        acc
+    };
+    let (store, _accs) = test_store().await.unwrap();
+    let acc = store.insert_account(acc).await.unwrap();
+    let (balance, amount_to_settle) = store
+        .update_balances_for_fulfill(acc.clone(), 1000, packet_context())
+        .await
+        .unwrap();
+    assert_eq!(balance, 1000);
+    assert_eq!(amount_to_settle, 0);
+}
+
+#[tokio::test]
+async fn prepare_then_reject() {
+    let (store, accs) = test_store().await.unwrap();
+    store
+        .update_balances_for_prepare(accs[0].clone(), 100, packet_context())
+        .await
+        .unwrap();
+    let balance0 = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance0, -100);
+    store
+        .update_balances_for_reject(accs[0].clone(), 100, packet_context())
+        .await
+        .unwrap();
+    let balance0 = store.get_balance(accs[0].clone()).await.unwrap();
+    let balance1 = store.get_balance(accs[1].clone()).await.unwrap();
+    assert_eq!(balance0, 0);
+    assert_eq!(balance1, 0);
+}
+
+#[tokio::test]
+async fn enforces_minimum_balance() {
+    let (store, accs) = test_store().await.unwrap();
+    let result = store
+        .update_balances_for_prepare(accs[0].clone(), 10000, packet_context())
+        .await;
+    assert!(result.is_err());
+    // a rejected prepare does not change the balance
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 0);
+}
+
+#[tokio::test]
+async fn prepare_uses_prepaid_amount_first() {
+    let (store, accs) = test_store().await.unwrap();
+    let id = accs[0].id();
+    store
+        .update_balance_for_incoming_settlement(id, 100, None)
+        .await
+        .unwrap();
+    // alice's min balance is -1000, so the prepaid amount extends how much she can send
+    store
+        .update_balances_for_prepare(accs[0].clone(), 1050, packet_context())
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, -950);
+    let result = store
+        .update_balances_for_prepare(accs[0].clone(), 51, packet_context())
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+// Prepare and Fulfill a packet for 100 units from Account 0 to Account 1
+// Then, Prepare and Fulfill a packet for 80 units from Account 1 to Account 0
+async fn netting_fulfilled_balances() {
+    let (store, accs) = test_store().await.unwrap();
+    let acc = store
+        .insert_account(ACCOUNT_DETAILS_2.clone())
+        .await
+        .unwrap();
+    let account0 = accs[0].clone();
+    let account1 = acc;
+
+    store
+        .update_balances_for_prepare(account0.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balances_for_fulfill(account1.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balances_for_prepare(account1.clone(), 80, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balances_for_fulfill(account0.clone(), 80, packet_context())
+        .await
+        .unwrap();
+
+    let balance0 = store.get_balance(account0).await.unwrap();
+    let balance1 = store.get_balance(account1).await.unwrap();
+    assert_eq!(balance0, -20);
+    assert_eq!(balance1, 20);
+}
+
+#[tokio::test]
+async fn records_balance_history() {
+    let (store, accs) = test_store().await.unwrap();
+    let context = PacketContext {
+        execution_condition: [1; 32],
+        counterparty: accs[0].id(),
+    };
+    store
+        .update_balances_for_prepare(accs[0].clone(), 100, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balances_for_fulfill(accs[1].clone(), 100, context)
+        .await
+        .unwrap();
+    store.refund_settlement(accs[1].id(), 1100).await.unwrap();
+
+    let query = BalanceHistoryQuery {
+        from: 0,
+        to: std::u64::MAX,
+        cursor: None,
+        limit: 100,
+    };
+    let page = store
+        .get_balance_history(accs[1].clone(), query.clone())
+        .await
+        .unwrap();
+    assert_eq!(page.next_cursor, None);
+    let history = page.entries;
+    let kinds: Vec<BalanceChangeKind> = history.iter().map(|entry| entry.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            BalanceChangeKind::Fulfill,
+            BalanceChangeKind::OutgoingSettlement,
+            BalanceChangeKind::SettlementRefund
+        ]
+    );
+    assert_eq!((history[0].delta, history[0].balance), (100, 100));
+    assert_eq!(history[0].execution_condition, Some(hex::encode(&[1; 32])));
+    assert_eq!(history[0].counterparty, Some(accs[0].id()));
+    assert_eq!((history[1].delta, history[1].balance), (-1100, -1000));
+    assert_eq!(history[1].counterparty, None);
+    assert_eq!((history[2].delta, history[2].balance), (1100, 100));
+
+    let earlier = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                to: history[0].timestamp - 1,
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert!(earlier.entries.is_empty());
+
+    let first_page = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                limit: 2,
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(first_page.entries, history[..2].to_vec());
+    assert!(first_page.next_cursor.is_some());
+    let second_page = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                cursor: first_page.next_cursor,
+                limit: 2,
+                ..query
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(second_page.entries, history[2..].to_vec());
+    assert_eq!(second_page.next_cursor, None);
+}
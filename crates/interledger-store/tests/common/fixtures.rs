@@ -0,0 +1,111 @@
+use interledger_api::AccountDetails;
+use interledger_packet::Address;
+use interledger_service::Username;
+use interledger_service_util::PacketContext;
+use lazy_static::lazy_static;
+use secrecy::SecretString;
+use std::str::FromStr;
+use uuid::Uuid;
+
+lazy_static! {
+    // We are dylan starting a connection with all these accounts
+    pub static ref ACCOUNT_DETAILS_0: AccountDetails = AccountDetails {
+        ilp_address: Some(Address::from_str("example.alice").unwrap()),
+        additional_prefixes: Vec::new(),
+        username: Username::from_str("alice").unwrap(),
+        asset_scale: 6,
+        asset_code: "XYZ".to_string(),
+        max_packet_amount: 1000,
+        min_balance: Some(-1000),
+        ilp_over_http_url: Some("http://example.com/accounts/dylan/ilp".to_string()),
+        ilp_over_http_incoming_token: Some(SecretString::new("incoming_auth_token".to_string())),
+        ilp_over_http_outgoing_token: Some(SecretString::new("outgoing_auth_token".to_string())),
+        ilp_over_btp_url: Some("btp+ws://example.com/accounts/dylan/ilp/btp".to_string()),
+        ilp_over_btp_incoming_token: Some(SecretString::new("btp_token".to_string())),
+        ilp_over_btp_outgoing_token: Some(SecretString::new("btp_token".to_string())),
+        settle_threshold: Some(0),
+        settle_to: Some(-1000),
+        routing_relation: Some("Parent".to_owned()),
+        round_trip_time: None,
+        amount_per_minute_limit: Some(1000),
+        packets_per_minute_limit: Some(2),
+        amount_burst_limit: None,
+        packets_burst_limit: None,
+        amount_per_day_limit: None,
+        amount_per_month_limit: None,
+        fixed_fee: None,
+        fee_basis_points: None,
+        allowed_destination_prefixes: Vec::new(),
+        denied_destination_prefixes: Vec::new(),
+        settlement_engine_url: Some("http://settlement.example".to_string()),
+    };
+    pub static ref ACCOUNT_DETAILS_1: AccountDetails = AccountDetails {
+        ilp_address: None,
+        additional_prefixes: Vec::new(),
+        username: Username::from_str("bob").unwrap(),
+        asset_scale: 9,
+        asset_code: "ABC".to_string(),
+        max_packet_amount: 1_000_000,
+        min_balance: Some(0),
+        ilp_over_http_url: Some("http://example.com/accounts/dylan/ilp".to_string()),
+        // incoming token has is the account's username concatenated wiht the password
+        ilp_over_http_incoming_token: Some(SecretString::new("incoming_auth_token".to_string())),
+        ilp_over_http_outgoing_token: Some(SecretString::new("outgoing_auth_token".to_string())),
+        ilp_over_btp_url: Some("btp+ws://example.com/accounts/dylan/ilp/btp".to_string()),
+        ilp_over_btp_incoming_token: Some(SecretString::new("other_btp_token".to_string())),
+        ilp_over_btp_outgoing_token: Some(SecretString::new("btp_token".to_string())),
+        settle_threshold: Some(0),
+        settle_to: Some(-1000),
+        routing_relation: Some("Child".to_owned()),
+        round_trip_time: None,
+        amount_per_minute_limit: Some(1000),
+        packets_per_minute_limit: Some(20),
+        amount_burst_limit: None,
+        packets_burst_limit: None,
+        amount_per_day_limit: None,
+        amount_per_month_limit: None,
+        fixed_fee: None,
+        fee_basis_points: None,
+        allowed_destination_prefixes: Vec::new(),
+        denied_destination_prefixes: Vec::new(),
+        settlement_engine_url: None,
+    };
+    pub static ref ACCOUNT_DETAILS_2: AccountDetails = AccountDetails {
+        ilp_address: None,
+        additional_prefixes: Vec::new(),
+        username: Username::from_str("charlie").unwrap(),
+        asset_scale: 9,
+        asset_code: "XRP".to_string(),
+        max_packet_amount: 1000,
+        min_balance: Some(0),
+        ilp_over_http_url: None,
+        ilp_over_http_incoming_token: None,
+        ilp_over_http_outgoing_token: None,
+        ilp_over_btp_url: None,
+        ilp_over_btp_incoming_token: None,
+        ilp_over_btp_outgoing_token: None,
+        settle_threshold: Some(0),
+        settle_to: None,
+        routing_relation: None,
+        round_trip_time: None,
+        amount_per_minute_limit: None,
+        packets_per_minute_limit: None,
+        amount_burst_limit: None,
+        packets_burst_limit: None,
+        amount_per_day_limit: None,
+        amount_per_month_limit: None,
+        fixed_fee: None,
+        fee_basis_points: None,
+        allowed_destination_prefixes: Vec::new(),
+        denied_destination_prefixes: Vec::new(),
+        settlement_engine_url: None,
+    };
+}
+
+/// Context for balance changes in tests which don't care about the packet
+pub fn packet_context() -> PacketContext {
+    PacketContext {
+        execution_condition: [0; 32],
+        counterparty: Uuid::nil(),
+    }
+}
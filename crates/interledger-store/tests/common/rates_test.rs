@@ -0,0 +1,209 @@
+use super::store_helpers::*;
+
+use interledger_service_util::{
+    ExchangeRate, ExchangeRateHistoryQuery, ExchangeRateHistoryStore, ExchangeRateStore, PairQuote,
+    Spreads,
+};
+use std::{collections::HashMap, str::FromStr, time::SystemTime};
+
+fn exchange_rates(rates: &[(&str, &str)]) -> HashMap<String, ExchangeRate> {
+    rates
+        .iter()
+        .map(|(asset_code, rate)| {
+            (
+                asset_code.to_string(),
+                ExchangeRate::from_str(rate).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn set_rates() {
+    let (store, _) = test_store().await.unwrap();
+    let rates = store.get_exchange_rates(&["ABC", "XYZ"]);
+    assert!(rates.is_err());
+    store
+        .set_exchange_rates(exchange_rates(&[("ABC", "500"), ("XYZ", "0.005")]), "test")
+        .unwrap();
+
+    let rates = store.get_exchange_rates(&["XYZ", "ABC"]).unwrap();
+    assert_eq!(rates[0].to_string(), "0.005");
+    assert_eq!(rates[1].to_string(), "500");
+}
+
+#[tokio::test]
+async fn keeps_exact_rates() {
+    let (store, _) = test_store().await.unwrap();
+    let rate = "0.1000000000000000000001";
+    store
+        .set_exchange_rates(exchange_rates(&[("ABC", rate)]), "test")
+        .unwrap();
+    assert_eq!(
+        store.get_exchange_rates(&["ABC"]).unwrap()[0].as_ref(),
+        rate
+    );
+    let history = store
+        .get_exchange_rate_history(history_query(Some("ABC"), 0, std::u64::MAX))
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(history[0].rate.as_ref(), rate);
+}
+
+#[tokio::test]
+async fn records_when_rates_were_set() {
+    let (store, _) = test_store().await.unwrap();
+    assert!(store.get_exchange_rates_updated_at(&["ABC"]).is_err());
+    let before = SystemTime::now();
+    store
+        .set_exchange_rates(exchange_rates(&[("ABC", "500")]), "test")
+        .unwrap();
+
+    let updated_at = store.get_exchange_rates_updated_at(&["ABC"]).unwrap();
+    assert!(updated_at[0] >= before && updated_at[0] <= SystemTime::now());
+    assert!(store
+        .get_exchange_rates_updated_at(&["ABC", "XYZ"])
+        .is_err());
+}
+
+fn history_query(asset_code: Option<&str>, from: u64, to: u64) -> ExchangeRateHistoryQuery {
+    ExchangeRateHistoryQuery {
+        asset_code: asset_code.map(str::to_owned),
+        from,
+        to,
+        cursor: None,
+        limit: 100,
+    }
+}
+
+#[tokio::test]
+async fn records_rate_history() {
+    let (store, _) = test_store().await.unwrap();
+    store
+        .set_exchange_rates(
+            exchange_rates(&[("XYZ", "0.005"), ("ABC", "500")]),
+            "CoinCap",
+        )
+        .unwrap();
+    store
+        .set_exchange_rates(exchange_rates(&[("ABC", "501")]), "api")
+        .unwrap();
+    let history = store
+        .get_exchange_rate_history(history_query(None, 0, std::u64::MAX))
+        .await
+        .unwrap()
+        .records;
+    let rates: Vec<(&str, &str, &str)> = history
+        .iter()
+        .map(|record| {
+            (
+                record.asset_code.as_str(),
+                record.rate.as_ref(),
+                record.source.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        rates,
+        vec![
+            ("ABC", "500", "CoinCap"),
+            ("XYZ", "0.005", "CoinCap"),
+            ("ABC", "501", "api")
+        ]
+    );
+    assert!(history[0].timestamp <= history[2].timestamp);
+
+    // The history can be read one page at a time
+    let mut query = history_query(None, 0, std::u64::MAX);
+    query.limit = 2;
+    let page = store
+        .get_exchange_rate_history(query.clone())
+        .await
+        .unwrap();
+    assert_eq!(page.records, history[..2].to_vec());
+    query.cursor = page.next_cursor;
+    assert!(query.cursor.is_some());
+    let page = store.get_exchange_rate_history(query).await.unwrap();
+    assert_eq!(page.records, history[2..].to_vec());
+    assert!(page.next_cursor.is_none());
+
+    let abc_history = store
+        .get_exchange_rate_history(history_query(
+            Some("ABC"),
+            history[2].timestamp,
+            std::u64::MAX,
+        ))
+        .await
+        .unwrap()
+        .records;
+    assert_eq!(abc_history.last().unwrap().rate.as_ref(), "501");
+    assert!(abc_history.iter().all(|record| record.asset_code == "ABC"));
+    assert!(store
+        .get_exchange_rate_history(history_query(Some("XYZ"), 0, 0))
+        .await
+        .unwrap()
+        .records
+        .is_empty());
+}
+
+#[tokio::test]
+async fn set_spreads() {
+    let (store, _) = test_store().await.unwrap();
+    assert_eq!(store.get_spread("ABC", "XYZ"), 0.0);
+
+    let mut spreads = Spreads::new(0.01);
+    spreads
+        .pairs
+        .entry("ABC".to_string())
+        .or_default()
+        .insert("XYZ".to_string(), 0.02);
+    store.set_spreads(spreads.clone()).unwrap();
+    assert_eq!(store.get_spread("ABC", "XYZ"), 0.02);
+    assert_eq!(store.get_spread("XYZ", "ABC"), 0.01);
+    assert_eq!(store.get_all_spreads().unwrap(), spreads);
+}
+
+#[tokio::test]
+async fn default_spreads_apply_until_spreads_are_set() {
+    let (store, _) = test_store().await.unwrap();
+    store.set_default_spreads(Spreads::new(0.01)).unwrap();
+    assert_eq!(store.get_spread("ABC", "XYZ"), 0.01);
+
+    store.set_spreads(Spreads::new(0.02)).unwrap();
+    store.set_default_spreads(Spreads::new(0.03)).unwrap();
+    assert_eq!(store.get_all_spreads().unwrap(), Spreads::new(0.02));
+}
+
+#[tokio::test]
+async fn set_pair_quotes() {
+    let (store, _) = test_store().await.unwrap();
+    assert!(store.get_pair_quote("ABC", "XYZ").is_none());
+    let quotes = vec![
+        PairQuote {
+            base: "XYZ".to_string(),
+            quote: "ABC".to_string(),
+            bid: 0.5,
+            ask: 0.6,
+        },
+        PairQuote {
+            base: "ABC".to_string(),
+            quote: "XYZ".to_string(),
+            bid: 1.5,
+            ask: 2.0,
+        },
+    ];
+    store.set_pair_quotes(quotes.clone()).unwrap();
+
+    let (quote, _) = store.get_pair_quote("ABC", "XYZ").unwrap();
+    assert_eq!(quote, quotes[1]);
+    assert!(store.get_pair_quote("ABC", "DEF").is_none());
+    // Sorted by base and quote asset codes
+    assert_eq!(
+        store.get_all_pair_quotes().unwrap(),
+        vec![quotes[1].clone(), quotes[0].clone()]
+    );
+
+    store.set_pair_quotes(Vec::new()).unwrap();
+    assert!(store.get_all_pair_quotes().unwrap().is_empty());
+}
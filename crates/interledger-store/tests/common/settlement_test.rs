@@ -0,0 +1,219 @@
+use super::{fixtures::*, store_helpers::*};
+use bytes::Bytes;
+
+use http::StatusCode;
+use interledger_api::NodeStore;
+use interledger_service::{Account, AccountStore};
+use interledger_service_util::BalanceStore;
+use interledger_settlement::core::{
+    idempotency::{IdempotentData, IdempotentStore},
+    types::{LeftoversStore, SettlementAccount, SettlementStore},
+};
+use lazy_static::lazy_static;
+use num_bigint::BigUint;
+use url::Url;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref IDEMPOTENCY_KEY: String = String::from("AJKJNUjM0oyiAN46");
+}
+
+#[tokio::test]
+async fn saves_gets_clears_uncredited_settlement_amount_properly() {
+    let (store, _accs) = test_store().await.unwrap();
+    let amounts: Vec<(BigUint, u8)> = vec![
+        (BigUint::from(5u32), 11),   // 5
+        (BigUint::from(855u32), 12), // 905
+        (BigUint::from(1u32), 10),   // 1005 total
+    ];
+    let acc = Uuid::new_v4();
+    for a in amounts {
+        store
+            .save_uncredited_settlement_amount(acc, a)
+            .await
+            .unwrap();
+    }
+    let ret = store
+        .load_uncredited_settlement_amount(acc, 9u8)
+        .await
+        .unwrap();
+    // 1 uncredited unit for scale 9
+    assert_eq!(ret, BigUint::from(1u32));
+    // rest should be in the leftovers store
+    let ret = store.peek_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(5u32), 12));
+    let ret = store.get_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(5u32), 12));
+
+    // getting the amount removes it
+    let ret = store.peek_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(0u32), 0));
+
+    // clears uncredited amount
+    store
+        .save_uncredited_settlement_amount(acc, (BigUint::from(5u32), 12))
+        .await
+        .unwrap();
+    store.clear_uncredited_settlement_amount(acc).await.unwrap();
+    let ret = store.get_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(0u32), 0));
+}
+
+#[tokio::test]
+async fn saves_and_loads_idempotency_key_data_properly() {
+    let (store, _) = test_store().await.unwrap();
+    let input_hash: [u8; 32] = Default::default();
+    store
+        .save_idempotent_data(
+            IDEMPOTENCY_KEY.clone(),
+            input_hash,
+            StatusCode::OK,
+            Bytes::from("TEST"),
+        )
+        .await
+        .unwrap();
+    let data1 = store
+        .load_idempotent_data(IDEMPOTENCY_KEY.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        data1.unwrap(),
+        IdempotentData::new(StatusCode::OK, Bytes::from("TEST"), input_hash)
+    );
+
+    let data2 = store
+        .load_idempotent_data("asdf".to_string())
+        .await
+        .unwrap();
+    assert!(data2.is_none());
+}
+
+#[tokio::test]
+async fn idempotent_settlement_calls() {
+    let (store, accs) = test_store().await.unwrap();
+    let id = accs[0].id();
+    store
+        .update_balance_for_incoming_settlement(id, 100, Some(IDEMPOTENCY_KEY.clone()))
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 100);
+
+    store
+        .update_balance_for_incoming_settlement(
+            id,
+            100,
+            Some(IDEMPOTENCY_KEY.clone()), // Reuse key to make idempotent request.
+        )
+        .await
+        .unwrap();
+    // Since it's idempotent there will be no state update.
+    // Otherwise it'd be 200 (100 + 100)
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 100);
+}
+
+#[tokio::test]
+async fn credits_balance_owed() {
+    let (store, accs) = test_store().await.unwrap();
+    store
+        .update_balances_for_prepare(accs[0].clone(), 200, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balance_for_incoming_settlement(accs[0].id(), 100, Some(IDEMPOTENCY_KEY.clone()))
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, -100);
+}
+
+#[tokio::test]
+async fn does_not_save_idempotency_key_of_failed_settlement() {
+    let (store, accs) = test_store().await.unwrap();
+    assert!(store
+        .update_balance_for_incoming_settlement(Uuid::new_v4(), 100, Some(IDEMPOTENCY_KEY.clone()))
+        .await
+        .is_err());
+
+    // the settlement can be retried with the same key
+    store
+        .update_balance_for_incoming_settlement(accs[0].id(), 100, Some(IDEMPOTENCY_KEY.clone()))
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 100);
+}
+
+#[tokio::test]
+async fn clears_balance_owed_and_puts_remainder_as_prepaid() {
+    let (store, accs) = test_store().await.unwrap();
+    store
+        .update_balances_for_prepare(accs[0].clone(), 40, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balance_for_incoming_settlement(accs[0].id(), 100, Some(IDEMPOTENCY_KEY.clone()))
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 60);
+
+    // the prepaid amount is used up before the balance goes negative again
+    store
+        .update_balances_for_prepare(accs[0].clone(), 60, packet_context())
+        .await
+        .unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 0);
+}
+
+#[tokio::test]
+async fn refunds_settlement() {
+    let (store, accs) = test_store().await.unwrap();
+    store.refund_settlement(accs[0].id(), 100).await.unwrap();
+    let balance = store.get_balance(accs[0].clone()).await.unwrap();
+    assert_eq!(balance, 100);
+}
+
+#[tokio::test]
+async fn loads_globally_configured_settlement_engine_url() {
+    let (store, accs) = test_store().await.unwrap();
+    assert!(accs[0].settlement_engine_details().is_some());
+    assert!(accs[1].settlement_engine_details().is_none());
+    let account_ids = vec![accs[0].id(), accs[1].id()];
+
+    store
+        .set_settlement_engines(vec![
+            (
+                "ABC".to_string(),
+                Url::parse("http://settle-abc.example").unwrap(),
+            ),
+            (
+                "XYZ".to_string(),
+                Url::parse("http://settle-xyz.example").unwrap(),
+            ),
+        ])
+        .await
+        .unwrap();
+    let accounts = store.get_accounts(account_ids).await.unwrap();
+    // It should not overwrite the one that was individually configured
+    assert_eq!(
+        accounts[0]
+            .settlement_engine_details()
+            .unwrap()
+            .url
+            .as_str(),
+        "http://settlement.example/"
+    );
+
+    // It should set the URL for the account that did not have one configured
+    assert_eq!(
+        accounts[1]
+            .settlement_engine_details()
+            .unwrap()
+            .url
+            .as_str(),
+        "http://settle-abc.example/"
+    );
+}
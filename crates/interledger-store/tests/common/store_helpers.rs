@@ -0,0 +1,31 @@
+use super::{backend::Store, fixtures::*};
+
+use interledger_api::NodeStore;
+use interledger_service::{Account as AccountTrait, AddressStore};
+use interledger_store::account::Account;
+
+pub use super::backend::new_store;
+
+pub async fn test_store() -> Result<(Store, Vec<Account>), ()> {
+    let store = new_store().await;
+    let mut accs = Vec::new();
+    let acc = store
+        .insert_account(ACCOUNT_DETAILS_0.clone())
+        .await
+        .unwrap();
+    accs.push(acc.clone());
+    // alice is a Parent, so the store's ilp address is updated to
+    // the value that would be received by the ILDCP request. here,
+    // we just assume alice appended some data to her address
+    store
+        .set_ilp_address(acc.ilp_address().with_suffix(b"user1").unwrap())
+        .await
+        .unwrap();
+
+    let acc = store
+        .insert_account(ACCOUNT_DETAILS_1.clone())
+        .await
+        .unwrap();
+    accs.push(acc);
+    Ok((store, accs))
+}
@@ -1,20 +1,21 @@
 use super::{fixtures::*, redis_helpers::*, store_helpers::*};
 use futures::future::Either;
 use futures::TryFutureExt;
-use interledger_api::{AccountSettings, NodeStore};
+use interledger_api::{AccountSettings, AccountsQuery, ApiKey, ApiKeyScope, NodeStore};
 use interledger_btp::BtpAccount;
 use interledger_ccp::{CcpRoutingAccount, RoutingRelation};
-use interledger_http::HttpAccount;
+use interledger_http::{HttpAccount, HttpStore};
 use interledger_packet::Address;
 use interledger_service::Account as AccountTrait;
-use interledger_service::{AccountStore, AddressStore, Username};
-use interledger_service_util::BalanceStore;
+use interledger_service::{AccountStatus, AccountStore, AddressStore, Username};
+use interledger_service_util::{BalanceStore, DestinationFilterAccount, StatusAccount};
 use interledger_store::redis::RedisStoreBuilder;
 use redis_crate::Client;
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
 use std::default::Default;
 use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -28,7 +29,7 @@ async fn picks_up_parent_during_initialization() {
     // to the store we will always get the configured parent (if
     // there was one))
     let _: redis_crate::Value = redis_crate::cmd("SET")
-        .arg("parent_node_account_address")
+        .arg("{ilp}:parent_node_account_address")
         .arg("example.bob.node")
         .query_async(&mut connection)
         .await
@@ -137,7 +138,7 @@ async fn update_accounts() {
     let mut connection = context.async_connection().await.unwrap();
     let id = accounts[0].id();
     let _: redis_crate::Value = redis_crate::cmd("HMSET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg(600u64)
         .arg("prepaid_amount")
@@ -153,6 +154,56 @@ async fn update_accounts() {
     assert_eq!(balance, 1000);
 }
 
+#[tokio::test]
+async fn refreshes_cached_accounts_after_updates() {
+    let (store, _context, accounts) = test_store().await.unwrap();
+    let id = accounts[0].id();
+    // load the account into the cache
+    store.get_accounts(vec![id]).await.unwrap();
+    let mut new = ACCOUNT_DETAILS_0.clone();
+    new.asset_code = String::from("TUV");
+    store.update_account(id, new).await.unwrap();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(account.asset_code(), "TUV");
+}
+
+#[tokio::test]
+async fn refreshes_cached_accounts_after_updates_from_other_stores() {
+    let (store, context, accounts) = test_store().await.unwrap();
+    let other_store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    let id = accounts[0].id();
+    let username = accounts[0].username().clone();
+    // load the account into the cache
+    store
+        .get_account_from_http_auth(&username, "incoming_auth_token")
+        .await
+        .unwrap();
+
+    let settings = AccountSettings {
+        ilp_over_http_incoming_token: Some(SecretString::new("new_token".to_owned())),
+        ..Default::default()
+    };
+    other_store
+        .modify_account_settings(id, settings)
+        .await
+        .unwrap();
+    // give the subscription thread time to receive the update
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+
+    let result = store
+        .get_account_from_http_auth(&username, "incoming_auth_token")
+        .await;
+    assert!(result.is_err());
+    let account = store
+        .get_account_from_http_auth(&username, "new_token")
+        .await
+        .unwrap();
+    assert_eq!(account.id(), id);
+}
+
 #[tokio::test]
 async fn modify_account_settings_settle_to_overflow() {
     let (store, _context, accounts) = test_store().await.unwrap();
@@ -211,6 +262,71 @@ async fn modify_account_settings() {
     );
 }
 
+#[tokio::test]
+async fn sets_account_status() {
+    let (store, _context, accounts) = test_store().await.unwrap();
+    let id = accounts[1].id();
+    store.set_balance(accounts[1].clone(), 100).await.unwrap();
+
+    let suspended = store
+        .set_account_status(id, AccountStatus::Suspended)
+        .await
+        .unwrap();
+    assert_eq!(suspended.status(), AccountStatus::Suspended);
+    assert!(!suspended.is_routable());
+    // Updating the account's details keeps its status and balance
+    let updated = store
+        .update_account(id, ACCOUNT_DETAILS_1.clone())
+        .await
+        .unwrap();
+    assert_eq!(updated.status(), AccountStatus::Suspended);
+    assert_eq!(store.get_balance(updated).await.unwrap(), 100);
+
+    let active = store
+        .set_account_status(id, AccountStatus::Active)
+        .await
+        .unwrap();
+    assert_eq!(active.status(), AccountStatus::Active);
+    assert!(active.is_routable());
+    assert!(store
+        .set_account_status(Uuid::new_v4(), AccountStatus::Closing)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn manages_api_keys() {
+    let (store, _context, accounts) = test_store().await.unwrap();
+    let id = accounts[0].id();
+    let (key, secret) = ApiKey::generate(id, vec![ApiKeyScope::ReadBalance], None);
+    let key = store.insert_api_key(key, secret.clone()).await.unwrap();
+    assert_eq!(store.get_api_keys(id).await.unwrap(), vec![key.clone()]);
+    assert!(store
+        .get_api_keys(accounts[1].id())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let authorized = store
+        .get_api_key_from_auth(id, key.id, secret.expose_secret())
+        .await
+        .unwrap();
+    assert_eq!(authorized, key);
+    assert!(store
+        .get_api_key_from_auth(id, key.id, "wrong")
+        .await
+        .is_err());
+    // The key only authorizes requests on behalf of its own account
+    assert!(store
+        .get_api_key_from_auth(accounts[1].id(), key.id, secret.expose_secret())
+        .await
+        .is_err());
+
+    assert_eq!(store.delete_api_key(id, key.id).await.unwrap(), key);
+    assert!(store.get_api_keys(id).await.unwrap().is_empty());
+    assert!(store.delete_api_key(id, key.id).await.is_err());
+}
+
 #[tokio::test]
 async fn starts_with_zero_balance() {
     let (store, _context, accs) = test_store().await.unwrap();
@@ -236,6 +352,88 @@ async fn get_all_accounts() {
     assert_eq!(accounts.len(), 2);
 }
 
+#[tokio::test]
+async fn pages_through_accounts() {
+    let (store, _context, accs) = test_store().await.unwrap();
+    let mut ids: Vec<Uuid> = accs.iter().map(|acc| acc.id()).collect();
+    ids.sort();
+
+    let query = AccountsQuery {
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = store.get_accounts_page(query.clone()).await.unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].id(), ids[0]);
+    assert_eq!(page.next_cursor, Some(ids[0]));
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            cursor: page.next_cursor,
+            ..query
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].id(), ids[1]);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[tokio::test]
+async fn accounts_page_skips_accounts_deleted_while_loading() {
+    let (store, context, accs) = test_store().await.unwrap();
+    let mut connection = context.async_connection().await.unwrap();
+    // Deleting only the account's details leaves it in the index, like
+    // an account which is deleted after the page's ids were loaded
+    let _: redis_crate::Value = redis_crate::cmd("DEL")
+        .arg(format!("{{ilp}}:accounts:{}", accs[0].id()))
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+
+    let page = store
+        .get_accounts_page(AccountsQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].id(), accs[1].id());
+}
+
+#[tokio::test]
+async fn filters_accounts_page() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            asset_code: Some("abc".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "bob");
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            routing_relation: Some(RoutingRelation::Parent),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "alice");
+
+    let page = store
+        .get_accounts_page(AccountsQuery {
+            username_prefix: Some("AL".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    assert_eq!(page.accounts[0].username().as_ref(), "alice");
+    assert_eq!(page.next_cursor, None);
+}
+
 #[tokio::test]
 async fn gets_single_account() {
     let (store, _context, accs) = test_store().await.unwrap();
@@ -279,3 +477,24 @@ async fn errors_for_unknown_accounts() {
         .await;
     assert!(result.is_err());
 }
+
+#[tokio::test]
+async fn saves_destination_prefixes() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.allowed_destination_prefixes = vec!["example.".to_string(), "g.partner.".to_string()];
+    details.denied_destination_prefixes = vec!["example.blocked".to_string()];
+    let id = store.insert_account(details.clone()).await.unwrap().id();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(
+        account.allowed_destination_prefixes(),
+        &["example.", "g.partner."]
+    );
+    assert_eq!(account.denied_destination_prefixes(), &["example.blocked"]);
+
+    details.allowed_destination_prefixes.clear();
+    store.update_account(id, details).await.unwrap();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert!(account.allowed_destination_prefixes().is_empty());
+    assert_eq!(account.denied_destination_prefixes(), &["example.blocked"]);
+}
@@ -4,7 +4,10 @@ use interledger_api::NodeStore;
 use interledger_packet::Address;
 use interledger_service::{Account as AccountTrait, AddressStore};
 use interledger_service::{AccountStore, Username};
-use interledger_service_util::BalanceStore;
+use interledger_service_util::{
+    BalanceChangeKind, BalanceHistoryQuery, BalanceStore, PacketContext,
+};
+use interledger_settlement::core::types::SettlementStore;
 use interledger_store::account::Account;
 use std::str::FromStr;
 use uuid::Uuid;
@@ -15,7 +18,7 @@ async fn get_balance() {
     let account_id = Uuid::new_v4();
     let mut connection = context.async_connection().await.unwrap();
     let _: redis_crate::Value = redis_crate::cmd("HMSET")
-        .arg(format!("accounts:{}", account_id))
+        .arg(format!("{{ilp}}:accounts:{}", account_id))
         .arg("balance")
         .arg(600u64)
         .arg("prepaid_amount")
@@ -44,7 +47,7 @@ async fn prepare_then_fulfill_with_settlement() {
     let account1 = accounts[1].clone();
     // reduce account 0's balance by 100
     store
-        .update_balances_for_prepare(account0.clone(), 100)
+        .update_balances_for_prepare(account0.clone(), 100, packet_context())
         .await
         .unwrap();
     // TODO:Can we make get_balance take a reference to the account?
@@ -56,7 +59,7 @@ async fn prepare_then_fulfill_with_settlement() {
 
     // Account 1 hits the settlement limit (?) TODO
     store
-        .update_balances_for_fulfill(account1.clone(), 100)
+        .update_balances_for_fulfill(account1.clone(), 100, packet_context())
         .await
         .unwrap();
     let balance0 = store.get_balance(account0).await.unwrap();
@@ -84,7 +87,7 @@ async fn process_fulfill_no_settle_to() {
     let accounts = store.get_accounts(vec![id]).await.unwrap();
     let acc = accounts[0].clone();
     let (balance, amount_to_settle) = store
-        .update_balances_for_fulfill(acc.clone(), 100)
+        .update_balances_for_fulfill(acc.clone(), 100, packet_context())
         .await
         .unwrap();
     assert_eq!(balance, 100);
@@ -111,7 +114,7 @@ async fn process_fulfill_settle_to_over_threshold() {
     let accounts = store.get_accounts(vec![id]).await.unwrap();
     let acc = accounts[0].clone();
     let (balance, amount_to_settle) = store
-        .update_balances_for_fulfill(acc.clone(), 1000)
+        .update_balances_for_fulfill(acc.clone(), 1000, packet_context())
         .await
         .unwrap();
     assert_eq!(balance, 1000);
@@ -138,7 +141,7 @@ async fn process_fulfill_ok() {
     let accounts = store.get_accounts(vec![id]).await.unwrap();
     let acc = accounts[0].clone();
     let (balance, amount_to_settle) = store
-        .update_balances_for_fulfill(acc.clone(), 101)
+        .update_balances_for_fulfill(acc.clone(), 101, packet_context())
         .await
         .unwrap();
     assert_eq!(balance, 0);
@@ -155,7 +158,7 @@ async fn prepare_then_reject() {
     let account0 = accounts[0].clone();
     let _account1 = accounts[1].clone();
     store
-        .update_balances_for_prepare(accounts[0].clone(), 100)
+        .update_balances_for_prepare(accounts[0].clone(), 100, packet_context())
         .await
         .unwrap();
     let balance0 = store.get_balance(accounts[0].clone()).await.unwrap();
@@ -163,7 +166,7 @@ async fn prepare_then_reject() {
     assert_eq!(balance0, -100);
     assert_eq!(balance1, 0);
     store
-        .update_balances_for_reject(account0.clone(), 100)
+        .update_balances_for_reject(account0.clone(), 100, packet_context())
         .await
         .unwrap();
     let balance0 = store.get_balance(accounts[0].clone()).await.unwrap();
@@ -180,7 +183,7 @@ async fn enforces_minimum_balance() {
         .await
         .unwrap();
     let result = store
-        .update_balances_for_prepare(accounts[0].clone(), 10000)
+        .update_balances_for_prepare(accounts[0].clone(), 10000, packet_context())
         .await;
     assert!(result.is_err());
 }
@@ -203,23 +206,23 @@ async fn netting_fulfilled_balances() {
 
     // decrement account 0 by 100
     store
-        .update_balances_for_prepare(account0.clone(), 100)
+        .update_balances_for_prepare(account0.clone(), 100, packet_context())
         .await
         .unwrap();
     // increment account 1 by 100
     store
-        .update_balances_for_fulfill(account1.clone(), 100)
+        .update_balances_for_fulfill(account1.clone(), 100, packet_context())
         .await
         .unwrap();
 
     // decrement account 1 by 80
     store
-        .update_balances_for_prepare(account1.clone(), 80)
+        .update_balances_for_prepare(account1.clone(), 80, packet_context())
         .await
         .unwrap();
     // increment account 0 by 80
     store
-        .update_balances_for_fulfill(account0.clone(), 80)
+        .update_balances_for_fulfill(account0.clone(), 80, packet_context())
         .await
         .unwrap();
 
@@ -228,3 +231,87 @@ async fn netting_fulfilled_balances() {
     assert_eq!(balance0, -20);
     assert_eq!(balance1, 20);
 }
+
+#[tokio::test]
+async fn records_balance_history() {
+    let (store, _context, accs) = test_store().await.unwrap();
+    let context = PacketContext {
+        execution_condition: [1; 32],
+        counterparty: accs[0].id(),
+    };
+    store
+        .update_balances_for_prepare(accs[0].clone(), 100, packet_context())
+        .await
+        .unwrap();
+    store
+        .update_balances_for_fulfill(accs[1].clone(), 100, context)
+        .await
+        .unwrap();
+    store.refund_settlement(accs[1].id(), 1100).await.unwrap();
+
+    let query = BalanceHistoryQuery {
+        from: 0,
+        to: std::u64::MAX,
+        cursor: None,
+        limit: 100,
+    };
+    let page = store
+        .get_balance_history(accs[1].clone(), query.clone())
+        .await
+        .unwrap();
+    assert_eq!(page.next_cursor, None);
+    let history = page.entries;
+    let kinds: Vec<BalanceChangeKind> = history.iter().map(|entry| entry.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            BalanceChangeKind::Fulfill,
+            BalanceChangeKind::OutgoingSettlement,
+            BalanceChangeKind::SettlementRefund
+        ]
+    );
+    assert_eq!((history[0].delta, history[0].balance), (100, 100));
+    assert_eq!(history[0].execution_condition, Some(hex::encode(&[1; 32])));
+    assert_eq!(history[0].counterparty, Some(accs[0].id()));
+    assert_eq!((history[1].delta, history[1].balance), (-1100, -1000));
+    assert_eq!(history[1].counterparty, None);
+    assert_eq!((history[2].delta, history[2].balance), (1100, 100));
+
+    let earlier = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                to: history[0].timestamp - 1,
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert!(earlier.entries.is_empty());
+
+    let first_page = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                limit: 2,
+                ..query.clone()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(first_page.entries, history[..2].to_vec());
+    assert!(first_page.next_cursor.is_some());
+    let second_page = store
+        .get_balance_history(
+            accs[1].clone(),
+            BalanceHistoryQuery {
+                cursor: first_page.next_cursor,
+                limit: 2,
+                ..query
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(second_page.entries, history[2..].to_vec());
+    assert_eq!(second_page.next_cursor, None);
+}
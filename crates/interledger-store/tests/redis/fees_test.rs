@@ -0,0 +1,51 @@
+use super::{fixtures::*, store_helpers::*};
+use interledger_api::NodeStore;
+use interledger_service::{Account as AccountTrait, AccountStore};
+use interledger_service_util::{FeeAccount, FeeStore, FeesEarned};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn saves_account_fees() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.fixed_fee = Some(10);
+    details.fee_basis_points = Some(25);
+    let id = store.insert_account(details.clone()).await.unwrap().id();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(account.fixed_fee(), 10);
+    assert_eq!(account.fee_basis_points(), 25);
+
+    details.fixed_fee = None;
+    details.fee_basis_points = None;
+    store.update_account(id, details).await.unwrap();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(account.fixed_fee(), 0);
+    assert_eq!(account.fee_basis_points(), 0);
+}
+
+#[tokio::test]
+async fn records_fees_earned_per_account_pair() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    store.record_fee(alice, bob, 10).await.unwrap();
+    store.record_fee(alice, bob, 5).await.unwrap();
+    store.record_fee(bob, alice, 1).await.unwrap();
+
+    let mut fees = store.get_fees_earned().await.unwrap();
+    fees.sort_by_key(|fees| fees.amount);
+    assert_eq!(
+        fees,
+        vec![
+            FeesEarned {
+                from_account_id: bob,
+                to_account_id: alice,
+                amount: 1,
+            },
+            FeesEarned {
+                from_account_id: alice,
+                to_account_id: bob,
+                amount: 15,
+            },
+        ]
+    );
+}
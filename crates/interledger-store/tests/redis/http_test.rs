@@ -1,11 +1,15 @@
 use super::fixtures::*;
 use super::store_helpers::*;
 
-use interledger_api::NodeStore;
+use interledger_api::{AccountSettings, NodeStore};
 use interledger_btp::BtpAccount;
 use interledger_http::{HttpAccount, HttpStore};
 use interledger_packet::Address;
 use interledger_service::{Account, Username};
+use interledger_store::{
+    crypto::{is_token_hash, StoreKeys},
+    redis::RedisStoreBuilder,
+};
 use secrecy::{ExposeSecret, SecretString};
 use std::str::FromStr;
 
@@ -75,3 +79,170 @@ async fn duplicate_http_incoming_auth_works() {
     assert_eq!(accs[0].id(), original_id);
     assert_eq!(accs[1].id(), duplicate_id);
 }
+
+#[tokio::test]
+async fn rotates_secret_and_reencrypts_tokens() {
+    let (store, context, _) = test_store().await.unwrap();
+    store.rotate_secret([1; 32]).await.unwrap();
+
+    let account = store
+        .get_account_from_http_auth(&Username::from_str("alice").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        account.get_http_auth_token().unwrap().expose_secret(),
+        "outgoing_auth_token",
+    );
+
+    // All tokens were re-encrypted, so a store using only the new secret can read them
+    let new_store = RedisStoreBuilder::new(context.get_client_connection_info(), [1; 32])
+        .connect()
+        .await
+        .unwrap();
+    let account = new_store
+        .get_account_from_http_auth(&Username::from_str("bob").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        &account.get_ilp_over_btp_outgoing_token().unwrap(),
+        b"btp_token",
+    );
+    // A store started with the previous secret switches to the new one
+    let old_store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .account_cache_ttl(0)
+        .connect()
+        .await
+        .unwrap();
+    let account = old_store
+        .get_account_from_http_auth(&Username::from_str("bob").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        account.get_http_auth_token().unwrap().expose_secret(),
+        "outgoing_auth_token",
+    );
+    let other_store = RedisStoreBuilder::new(context.get_client_connection_info(), [2; 32])
+        .account_cache_ttl(0)
+        .connect()
+        .await
+        .unwrap();
+    // The incoming tokens are hashed, but the outgoing ones cannot be decrypted
+    let account = other_store
+        .get_account_from_http_auth(&Username::from_str("bob").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert!(account.get_http_auth_token().is_none());
+}
+
+#[tokio::test]
+async fn rotates_secret_of_every_connected_store() {
+    let (store, context, accs) = test_store().await.unwrap();
+    let other_store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .account_cache_ttl(0)
+        .connect()
+        .await
+        .unwrap();
+    store.rotate_secret([1; 32]).await.unwrap();
+
+    // The other store encrypts with the new secret without being restarted
+    let settings = AccountSettings {
+        ilp_over_http_outgoing_token: Some(SecretString::new("new_token".to_owned())),
+        ..Default::default()
+    };
+    other_store
+        .modify_account_settings(accs[1].id(), settings)
+        .await
+        .unwrap();
+    let new_store = RedisStoreBuilder::new(context.get_client_connection_info(), [1; 32])
+        .connect()
+        .await
+        .unwrap();
+    let account = new_store
+        .get_account_from_http_auth(&Username::from_str("bob").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        account.get_http_auth_token().unwrap().expose_secret(),
+        "new_token",
+    );
+    let account = other_store
+        .get_account_from_http_auth(&Username::from_str("bob").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        &account.get_ilp_over_btp_outgoing_token().unwrap(),
+        b"btp_token",
+    );
+}
+
+#[tokio::test]
+async fn does_not_rotate_secret_while_another_store_rotates_it() {
+    let (store, context, _) = test_store().await.unwrap();
+    let other_store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    let (first, second) = futures::future::join(
+        store.rotate_secret([1; 32]),
+        other_store.rotate_secret([2; 32]),
+    )
+    .await;
+    assert!(first.is_ok() != second.is_ok());
+}
+
+#[tokio::test]
+async fn hashes_encrypted_incoming_tokens_on_first_authentication() {
+    let (_, context, accs) = test_store().await.unwrap();
+    let mut connection = context.async_connection().await.unwrap();
+    let key = format!("accounts:{}", accs[0].id());
+    let stored: Vec<u8> = redis_crate::cmd("HGET")
+        .arg(&key)
+        .arg("ilp_over_http_incoming_token")
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    assert!(is_token_hash(&stored));
+
+    // Incoming tokens used to be encrypted like the outgoing ones
+    let encrypted = StoreKeys::new(&[0; 32]).encrypt_token(b"incoming_auth_token");
+    let _: redis_crate::Value = redis_crate::cmd("HSET")
+        .arg(&key)
+        .arg("ilp_over_http_incoming_token")
+        .arg(encrypted.as_ref())
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    let store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .account_cache_ttl(0)
+        .connect()
+        .await
+        .unwrap();
+    let username = Username::from_str("alice").unwrap();
+    assert!(store
+        .get_account_from_http_auth(&username, "unknown_token")
+        .await
+        .is_err());
+    let stored: Vec<u8> = redis_crate::cmd("HGET")
+        .arg(&key)
+        .arg("ilp_over_http_incoming_token")
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    assert_eq!(stored, encrypted.to_vec());
+
+    store
+        .get_account_from_http_auth(&username, "incoming_auth_token")
+        .await
+        .unwrap();
+    let stored: Vec<u8> = redis_crate::cmd("HGET")
+        .arg(&key)
+        .arg("ilp_over_http_incoming_token")
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    assert!(is_token_hash(&stored));
+    store
+        .get_account_from_http_auth(&username, "incoming_auth_token")
+        .await
+        .unwrap();
+}
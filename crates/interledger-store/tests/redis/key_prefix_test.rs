@@ -0,0 +1,134 @@
+use super::fixtures::*;
+use super::redis_helpers::*;
+
+use interledger_api::NodeStore;
+use interledger_ccp::RouteManagerStore;
+use interledger_packet::Address;
+use interledger_service::{Account as AccountTrait, AccountStore};
+use interledger_service_util::{BalanceStore, ExchangeRate, ExchangeRateStore};
+use interledger_store::redis::{RedisStore, RedisStoreBuilder};
+use redis_crate::AsyncCommands;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn exchange_rates(rates: &[(&str, &str)]) -> HashMap<String, ExchangeRate> {
+    rates
+        .iter()
+        .map(|(asset_code, rate)| {
+            (
+                asset_code.to_string(),
+                ExchangeRate::from_str(rate).unwrap(),
+            )
+        })
+        .collect()
+}
+
+async fn prefixed_store(context: &TestContext, key_prefix: &str) -> RedisStore {
+    RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .node_ilp_address(Address::from_str("example.node").unwrap())
+        .key_prefix(key_prefix)
+        .connect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stores_with_different_prefixes_are_isolated() {
+    let context = TestContext::new();
+    let store_a = prefixed_store(&context, "node-a").await;
+    let store_b = prefixed_store(&context, "node-b").await;
+
+    let account_a = store_a
+        .insert_account(ACCOUNT_DETAILS_0.clone())
+        .await
+        .unwrap();
+    // The same username can be used in both stores
+    let account_b = store_b
+        .insert_account(ACCOUNT_DETAILS_0.clone())
+        .await
+        .unwrap();
+    assert_ne!(account_a.id(), account_b.id());
+
+    assert_eq!(store_a.get_all_accounts().await.unwrap().len(), 1);
+    assert_eq!(store_b.get_all_accounts().await.unwrap().len(), 1);
+    assert!(store_a.get_accounts(vec![account_b.id()]).await.is_err());
+    assert_eq!(
+        store_a
+            .get_account_id_from_username(&ACCOUNT_DETAILS_0.username)
+            .await
+            .unwrap(),
+        account_a.id()
+    );
+
+    store_a
+        .update_balances_for_prepare(account_a.clone(), 100, packet_context())
+        .await
+        .unwrap();
+    assert_eq!(store_a.get_balance(account_a).await.unwrap(), -100);
+    assert_eq!(store_b.get_balance(account_b.clone()).await.unwrap(), 0);
+
+    store_b.delete_account(account_b.id()).await.unwrap();
+    assert_eq!(store_a.get_all_accounts().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn routes_and_rates_are_not_shared_between_prefixes() {
+    let context = TestContext::new();
+    let store_a = prefixed_store(&context, "node-a").await;
+    let store_b = prefixed_store(&context, "node-b").await;
+    let account_a = store_a
+        .insert_account(ACCOUNT_DETAILS_0.clone())
+        .await
+        .unwrap();
+
+    store_a
+        .set_exchange_rates(exchange_rates(&[("ABC", "500")]), "test")
+        .unwrap();
+    store_a
+        .set_static_route("example.static".to_string(), account_a.id())
+        .await
+        .unwrap();
+    // the default poll interval is much longer than this
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+
+    assert!(store_b.get_exchange_rates(&["ABC"]).is_err());
+    assert!(store_b.get_static_routes().await.unwrap().is_empty());
+    let (local_routes, _) = store_b.get_local_and_configured_routes().await.unwrap();
+    assert!(local_routes.is_empty());
+    assert!(store_a
+        .get_static_routes()
+        .await
+        .unwrap()
+        .contains_key("example.static"));
+}
+
+#[tokio::test]
+async fn keys_contain_the_prefix() {
+    let context = TestContext::new();
+    let store = prefixed_store(&context, "node-a").await;
+    let account = store
+        .insert_account(ACCOUNT_DETAILS_0.clone())
+        .await
+        .unwrap();
+
+    let mut connection = context.async_connection().await.unwrap();
+    let keys: Vec<String> = connection.keys("*").await.unwrap();
+    assert!(!keys.is_empty());
+    assert!(keys.iter().all(|key| key.starts_with("{node-a}:")));
+    let exists: bool = connection
+        .exists(format!("{{node-a}}:accounts:{}", account.id()))
+        .await
+        .unwrap();
+    assert!(exists);
+}
+
+#[tokio::test]
+async fn rejects_invalid_prefix() {
+    let context = TestContext::new();
+    let result = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .key_prefix("node}a")
+        .connect()
+        .await;
+    assert!(result.is_err());
+}
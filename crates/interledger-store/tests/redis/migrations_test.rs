@@ -0,0 +1,274 @@
+use super::redis_helpers::*;
+use interledger_btp::BtpAccount;
+use interledger_http::{HttpAccount, HttpStore};
+use interledger_service::{Account as AccountTrait, AccountStore, Username};
+use interledger_service_util::{ExchangeRateHistoryQuery, ExchangeRateHistoryStore};
+use interledger_store::{
+    crypto::StoreKeys,
+    redis::{RedisStoreBuilder, SCHEMA_VERSION},
+};
+use redis_crate::AsyncCommands;
+use secrecy::ExposeSecret;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn saves_schema_version_on_connect() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let version: Option<u32> = connection.get("{ilp}:schema_version").await.unwrap();
+    assert_eq!(version, None);
+
+    RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    let version: Option<u32> = connection.get("{ilp}:schema_version").await.unwrap();
+    assert_eq!(version, Some(SCHEMA_VERSION));
+}
+
+#[tokio::test]
+async fn renames_account_fields_of_previous_versions() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let keys = StoreKeys::new(&[0; 32]);
+    let id = Uuid::new_v4();
+    let _: redis_crate::Value = redis_crate::cmd("HMSET")
+        .arg(format!("accounts:{}", id))
+        .arg("id")
+        .arg(id.to_string())
+        .arg("username")
+        .arg("alice")
+        .arg("ilp_address")
+        .arg("example.alice")
+        .arg("asset_code")
+        .arg("XYZ")
+        .arg("asset_scale")
+        .arg(6)
+        .arg("max_packet_amount")
+        .arg(1000)
+        .arg("http_endpoint")
+        .arg("http://example.com/accounts/dylan/ilp")
+        .arg("http_incoming_token")
+        .arg(keys.encrypt_token(b"incoming_auth_token").as_ref())
+        .arg("http_outgoing_token")
+        .arg(keys.encrypt_token(b"outgoing_auth_token").as_ref())
+        .arg("btp_uri")
+        .arg("btp+ws://example.com/accounts/dylan/ilp/btp")
+        .arg("btp_outgoing_token")
+        .arg(keys.encrypt_token(b"btp_token").as_ref())
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    let _: () = connection.sadd("accounts", id.to_string()).await.unwrap();
+    let _: () = connection
+        .hset("usernames", "alice", id.to_string())
+        .await
+        .unwrap();
+
+    let version = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .migrate()
+        .await
+        .unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+    let previous_field: Option<String> = connection
+        .hget(format!("{{ilp}}:accounts:{}", id), "http_endpoint")
+        .await
+        .unwrap();
+    assert_eq!(previous_field, None);
+
+    let store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    let account = store
+        .get_account_from_http_auth(&Username::from_str("alice").unwrap(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(account.id(), id);
+    assert_eq!(
+        account.get_http_url().unwrap().as_str(),
+        "http://example.com/accounts/dylan/ilp"
+    );
+    assert_eq!(
+        account.get_http_auth_token().unwrap().expose_secret(),
+        "outgoing_auth_token"
+    );
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(
+        account.get_ilp_over_btp_url().unwrap().as_str(),
+        "btp+ws://example.com/accounts/dylan/ilp/btp"
+    );
+    assert_eq!(
+        &account.get_ilp_over_btp_outgoing_token().unwrap(),
+        b"btp_token"
+    );
+}
+
+#[tokio::test]
+async fn adds_hash_tag_to_keys_of_previous_versions() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let id = Uuid::new_v4();
+    let _: () = connection.set("schema_version", 1).await.unwrap();
+    let _: () = connection
+        .hset(format!("accounts:{}", id), "username", "alice")
+        .await
+        .unwrap();
+    let _: () = connection.sadd("accounts", id.to_string()).await.unwrap();
+    let _: () = connection
+        .hset("usernames", "alice", id.to_string())
+        .await
+        .unwrap();
+    let _: () = connection
+        .hset("routes:static", "example.alice", id.to_string())
+        .await
+        .unwrap();
+    let _: () = connection
+        .rpush(format!("uncredited-amount:{}", id), "1")
+        .await
+        .unwrap();
+    let _: () = connection
+        .hset("idempotency-key:settlement", "status_code", 200)
+        .await
+        .unwrap();
+    let _: () = connection.set("settlement", "true").await.unwrap();
+
+    let version = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .migrate()
+        .await
+        .unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+
+    let tagged_keys = vec![
+        "{ilp}:schema_version".to_string(),
+        format!("{{ilp}}:accounts:{}", id),
+        "{ilp}:accounts".to_string(),
+        "{ilp}:usernames".to_string(),
+        "{ilp}:routes:static".to_string(),
+        format!("{{ilp}}:uncredited-amount:{}", id),
+        "{ilp}:idempotency-key:settlement".to_string(),
+        "{ilp}:settlement-idempotency-key:settlement".to_string(),
+    ];
+    for key in tagged_keys {
+        let exists: bool = connection.exists(&key).await.unwrap();
+        assert!(exists, "{} does not exist", key);
+    }
+    let untagged_keys = vec![
+        "schema_version".to_string(),
+        format!("accounts:{}", id),
+        "accounts".to_string(),
+        "usernames".to_string(),
+        "routes:static".to_string(),
+        format!("uncredited-amount:{}", id),
+        "idempotency-key:settlement".to_string(),
+        "settlement".to_string(),
+    ];
+    for key in untagged_keys {
+        let exists: bool = connection.exists(&key).await.unwrap();
+        assert!(!exists, "{} still exists", key);
+    }
+    let username: Option<String> = connection
+        .hget(format!("{{ilp}}:accounts:{}", id), "username")
+        .await
+        .unwrap();
+    assert_eq!(username, Some("alice".to_string()));
+}
+
+#[tokio::test]
+async fn indexes_account_ids_of_previous_versions() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let mut ids = vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    let _: () = connection.set("{ilp}:schema_version", 2).await.unwrap();
+    for id in ids.iter() {
+        let _: () = connection
+            .sadd("{ilp}:accounts", id.to_string())
+            .await
+            .unwrap();
+    }
+
+    let version = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .migrate()
+        .await
+        .unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+    let indexed: Vec<String> = connection.zrange("{ilp}:account_ids", 0, -1).await.unwrap();
+    ids.sort();
+    assert_eq!(
+        indexed,
+        ids.iter().map(|id| id.to_string()).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn splits_rate_history_of_previous_versions() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let _: () = connection.set("{ilp}:schema_version", 3).await.unwrap();
+    for (id, asset_code, rate) in &[
+        ("1000-0", "XYZ", "2"),
+        ("1000-1", "ABC", "1"),
+        ("2000-0", "XYZ", "3"),
+    ] {
+        let _: String = redis_crate::cmd("XADD")
+            .arg("{ilp}:rates:history")
+            .arg(*id)
+            .arg("asset_code")
+            .arg(*asset_code)
+            .arg("rate")
+            .arg(*rate)
+            .arg("source")
+            .arg("api")
+            .query_async(&mut connection)
+            .await
+            .unwrap();
+    }
+
+    let store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    let page = store
+        .get_exchange_rate_history(ExchangeRateHistoryQuery {
+            asset_code: None,
+            from: 0,
+            to: std::u64::MAX,
+            cursor: None,
+            limit: 10,
+        })
+        .await
+        .unwrap();
+    let records: Vec<(u64, &str, &str)> = page
+        .records
+        .iter()
+        .map(|record| {
+            (
+                record.timestamp,
+                record.asset_code.as_str(),
+                record.rate.as_ref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        records,
+        vec![(1000, "XYZ", "2"), (1000, "ABC", "1"), (2000, "XYZ", "3")]
+    );
+    let exists: bool = connection.exists("{ilp}:rates:history").await.unwrap();
+    assert!(!exists);
+}
+
+#[tokio::test]
+async fn refuses_data_of_newer_versions() {
+    let context = TestContext::new();
+    let mut connection = context.async_connection().await.unwrap();
+    let _: () = connection
+        .set("{ilp}:schema_version", SCHEMA_VERSION + 1)
+        .await
+        .unwrap();
+
+    let result = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,52 @@
+use super::{fixtures::*, store_helpers::*};
+
+use futures::{channel::mpsc, StreamExt};
+use interledger_packet::Address;
+use interledger_service::Account as AccountTrait;
+use interledger_stream::{PaymentNotification, StreamNotificationsStore};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn payment_notification() -> PaymentNotification {
+    PaymentNotification {
+        to_username: ACCOUNT_DETAILS_0.username.clone(),
+        from_username: ACCOUNT_DETAILS_1.username.clone(),
+        destination: Address::from_str("example.alice").unwrap(),
+        amount: 100,
+        timestamp: "2020-01-01T00:00:00Z".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn resubscribes_after_the_subscription_is_closed() {
+    let (store, context, accounts) = test_store().await.unwrap();
+    let (sender, mut receiver) = mpsc::unbounded();
+    store.add_payment_notification_subscription(accounts[0].id(), sender);
+
+    let killed: u32 = redis_crate::cmd("CLIENT")
+        .arg("KILL")
+        .arg("TYPE")
+        .arg("pubsub")
+        .query_async(&mut context.async_connection().await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(killed, 1);
+
+    // Notifications published before the store resubscribed are missed
+    let received = async {
+        loop {
+            store.publish_payment_notification(payment_notification());
+            if let Ok(Some(notification)) =
+                timeout(Duration::from_millis(100), receiver.next()).await
+            {
+                return notification;
+            }
+        }
+    };
+    let notification = timeout(Duration::from_secs(5), received)
+        .await
+        .expect("Notifications were not received after the subscription was closed");
+    assert_eq!(notification.to_username, ACCOUNT_DETAILS_0.username);
+    assert_eq!(notification.amount, 100);
+}
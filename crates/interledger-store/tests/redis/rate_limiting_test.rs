@@ -1,8 +1,10 @@
 use super::{fixtures::*, store_helpers::*};
 use futures::future::join_all;
-use interledger_service::AddressStore;
-use interledger_service_util::{RateLimitError, RateLimitStore};
+use interledger_api::NodeStore;
+use interledger_service::{Account as AccountTrait, AccountStore, AddressStore, Username};
+use interledger_service_util::{CumulativeAmounts, RateLimitError, RateLimitStore};
 use interledger_store::account::Account;
+use std::str::FromStr;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -78,3 +80,87 @@ async fn refunds_throughput_limit_for_rejected_packets() {
     let result = store.apply_rate_limits(account.clone(), 1).await;
     assert_eq!(result.unwrap_err(), RateLimitError::ThroughputLimitExceeded);
 }
+
+#[tokio::test]
+async fn limits_bursts_separately_from_per_minute_limits() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.packets_per_minute_limit = Some(1);
+    details.packets_burst_limit = Some(3);
+    details.amount_per_minute_limit = Some(10);
+    details.amount_burst_limit = Some(100);
+    let id = store.insert_account(details).await.unwrap().id();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+
+    let mut results = Vec::new();
+    for amount in &[60, 40, 1, 0] {
+        results.push(store.apply_rate_limits(account.clone(), *amount).await);
+    }
+    // The account can send 100 units and 3 packets at once, even though it is
+    // only allowed 10 units and 1 packet per minute
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Ok(()),
+            Err(RateLimitError::ThroughputLimitExceeded),
+            Err(RateLimitError::PacketLimitExceeded)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn allows_one_unit_per_minute_with_limits_of_one() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.packets_per_minute_limit = Some(1);
+    let id = store.insert_account(details).await.unwrap().id();
+    let packets_limited = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.username = Username::from_str("dave").unwrap();
+    details.amount_per_minute_limit = Some(1);
+    let id = store.insert_account(details).await.unwrap().id();
+    let amount_limited = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+
+    store
+        .apply_rate_limits(packets_limited.clone(), 100)
+        .await
+        .unwrap();
+    let result = store.apply_rate_limits(packets_limited, 100).await;
+    assert_eq!(result.unwrap_err(), RateLimitError::PacketLimitExceeded);
+
+    store
+        .apply_rate_limits(amount_limited.clone(), 1)
+        .await
+        .unwrap();
+    let result = store.apply_rate_limits(amount_limited, 1).await;
+    assert_eq!(result.unwrap_err(), RateLimitError::ThroughputLimitExceeded);
+}
+
+#[tokio::test]
+async fn limits_amounts_sent_per_day_and_month() {
+    let (store, _context, _) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.amount_per_day_limit = Some(100);
+    details.amount_per_month_limit = Some(150);
+    let id = store.insert_account(details).await.unwrap().id();
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+
+    store.apply_rate_limits(account.clone(), 60).await.unwrap();
+    let result = store.apply_rate_limits(account.clone(), 41).await;
+    assert_eq!(result.unwrap_err(), RateLimitError::DailyLimitExceeded);
+
+    // Rejected packets do not count towards the limits
+    store
+        .refund_throughput_limit(account.clone(), 60)
+        .await
+        .unwrap();
+    store.apply_rate_limits(account.clone(), 100).await.unwrap();
+    assert_eq!(
+        store.get_cumulative_amounts(account.clone()).await.unwrap(),
+        CumulativeAmounts {
+            day: 100,
+            month: 100
+        }
+    );
+}
@@ -1,7 +1,11 @@
 mod accounts_test;
 mod balances_test;
 mod btp_test;
+mod fees_test;
 mod http_test;
+mod key_prefix_test;
+mod migrations_test;
+mod pubsub_test;
 mod rate_limiting_test;
 mod rates_test;
 mod routing_test;
@@ -12,14 +16,17 @@ mod fixtures {
     use interledger_api::AccountDetails;
     use interledger_packet::Address;
     use interledger_service::Username;
+    use interledger_service_util::PacketContext;
     use lazy_static::lazy_static;
     use secrecy::SecretString;
     use std::str::FromStr;
+    use uuid::Uuid;
 
     lazy_static! {
         // We are dylan starting a connection with all these accounts
         pub static ref ACCOUNT_DETAILS_0: AccountDetails = AccountDetails {
             ilp_address: Some(Address::from_str("example.alice").unwrap()),
+            additional_prefixes: Vec::new(),
             username: Username::from_str("alice").unwrap(),
             asset_scale: 6,
             asset_code: "XYZ".to_string(),
@@ -37,10 +44,19 @@ mod fixtures {
             round_trip_time: None,
             amount_per_minute_limit: Some(1000),
             packets_per_minute_limit: Some(2),
+            amount_burst_limit: None,
+            packets_burst_limit: None,
+            amount_per_day_limit: None,
+            amount_per_month_limit: None,
+            fixed_fee: None,
+            fee_basis_points: None,
+            allowed_destination_prefixes: Vec::new(),
+            denied_destination_prefixes: Vec::new(),
             settlement_engine_url: Some("http://settlement.example".to_string()),
         };
         pub static ref ACCOUNT_DETAILS_1: AccountDetails = AccountDetails {
             ilp_address: None,
+            additional_prefixes: Vec::new(),
             username: Username::from_str("bob").unwrap(),
             asset_scale: 9,
             asset_code: "ABC".to_string(),
@@ -59,10 +75,19 @@ mod fixtures {
             round_trip_time: None,
             amount_per_minute_limit: Some(1000),
             packets_per_minute_limit: Some(20),
+            amount_burst_limit: None,
+            packets_burst_limit: None,
+            amount_per_day_limit: None,
+            amount_per_month_limit: None,
+            fixed_fee: None,
+            fee_basis_points: None,
+            allowed_destination_prefixes: Vec::new(),
+            denied_destination_prefixes: Vec::new(),
             settlement_engine_url: None,
         };
         pub static ref ACCOUNT_DETAILS_2: AccountDetails = AccountDetails {
             ilp_address: None,
+            additional_prefixes: Vec::new(),
             username: Username::from_str("charlie").unwrap(),
             asset_scale: 9,
             asset_code: "XRP".to_string(),
@@ -80,9 +105,25 @@ mod fixtures {
             round_trip_time: None,
             amount_per_minute_limit: None,
             packets_per_minute_limit: None,
+            amount_burst_limit: None,
+            packets_burst_limit: None,
+            amount_per_day_limit: None,
+            amount_per_month_limit: None,
+            fixed_fee: None,
+            fee_basis_points: None,
+            allowed_destination_prefixes: Vec::new(),
+            denied_destination_prefixes: Vec::new(),
             settlement_engine_url: None,
         };
     }
+
+    /// Context for balance changes in tests which don't care about the packet
+    pub fn packet_context() -> PacketContext {
+        PacketContext {
+            execution_condition: [0; 32],
+            counterparty: Uuid::nil(),
+        }
+    }
 }
 
 mod redis_helpers {
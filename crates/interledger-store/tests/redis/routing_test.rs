@@ -34,6 +34,7 @@ async fn polls_for_route_updates() {
     let bob = store_clone_1
         .insert_account(AccountDetails {
             ilp_address: Some(Address::from_str("example.bob").unwrap()),
+            additional_prefixes: Vec::new(),
             username: Username::from_str("bob").unwrap(),
             asset_scale: 6,
             asset_code: "XYZ".to_string(),
@@ -51,6 +52,14 @@ async fn polls_for_route_updates() {
             round_trip_time: None,
             amount_per_minute_limit: None,
             packets_per_minute_limit: None,
+            amount_burst_limit: None,
+            packets_burst_limit: None,
+            amount_per_day_limit: None,
+            amount_per_month_limit: None,
+            fixed_fee: None,
+            fee_basis_points: None,
+            allowed_destination_prefixes: Vec::new(),
+            denied_destination_prefixes: Vec::new(),
             settlement_engine_url: None,
         })
         .await
@@ -63,7 +72,7 @@ async fn polls_for_route_updates() {
     let bob_id = bob.id();
     let mut connection = connection.await.unwrap();
     let _: redis_crate::Value = redis_crate::cmd("HMSET")
-        .arg("routes:current")
+        .arg("{ilp}:routes:current")
         .arg("example.alice")
         .arg(bob_id.to_string())
         .arg("example.charlie")
@@ -81,6 +90,24 @@ async fn polls_for_route_updates() {
     assert!(routing_table.get("example.other").is_none());
 }
 
+#[tokio::test]
+async fn publishes_route_updates_to_other_stores() {
+    let (store, context, accs) = test_store().await.unwrap();
+    let other_store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    store
+        .set_static_route("example.other".to_string(), accs[1].id())
+        .await
+        .unwrap();
+
+    // the default poll interval is much longer than this
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    let routing_table = other_store.routing_table();
+    assert_eq!(*routing_table.get("example.other").unwrap(), accs[1].id());
+}
+
 #[tokio::test]
 async fn gets_accounts_to_send_routes_to() {
     let (store, _context, _) = test_store().await.unwrap();
@@ -113,6 +140,79 @@ async fn gets_accounts_to_receive_routes_from() {
     );
 }
 
+#[tokio::test]
+async fn routes_additional_prefixes_to_account() {
+    let context = TestContext::new();
+    let store = RedisStoreBuilder::new(context.get_client_connection_info(), [0; 32])
+        .node_ilp_address(Address::from_str("example.node").unwrap())
+        .connect()
+        .await
+        .unwrap();
+    let mut details = ACCOUNT_DETAILS_0.clone();
+    details.additional_prefixes = vec![
+        Address::from_str("example.alice-other").unwrap(),
+        Address::from_str("test.alice").unwrap(),
+    ];
+    let alice = store.insert_account(details.clone()).await.unwrap();
+    let routing_table = store.routing_table();
+    assert_eq!(routing_table.len(), 3);
+    assert_eq!(
+        *routing_table.get("example.alice-other").unwrap(),
+        alice.id()
+    );
+    assert_eq!(*routing_table.get("test.alice").unwrap(), alice.id());
+    let (local, _) = store.get_local_and_configured_routes().await.unwrap();
+    assert_eq!(local.len(), 3);
+    assert_eq!(local.get("test.alice").unwrap().id(), alice.id());
+
+    // Removing a prefix withdraws its route
+    details.additional_prefixes.pop();
+    let alice = store.update_account(alice.id(), details).await.unwrap();
+    assert_eq!(alice.additional_prefixes().len(), 1);
+    let routing_table = store.routing_table();
+    assert_eq!(routing_table.len(), 2);
+    assert!(routing_table.get("test.alice").is_none());
+
+    store.delete_account(alice.id()).await.unwrap();
+    assert_eq!(store.routing_table().len(), 0);
+}
+
+#[tokio::test]
+async fn rejects_prefixes_routed_to_other_accounts() {
+    let (store, context, accs) = test_store().await.unwrap();
+    let mut details = ACCOUNT_DETAILS_2.clone();
+    details.additional_prefixes = vec![Address::from_str("example.alice").unwrap()];
+    assert!(store.insert_account(details.clone()).await.is_err());
+
+    details.additional_prefixes = vec![Address::from_str("test.charlie").unwrap()];
+    let charlie = store.insert_account(details).await.unwrap();
+    let mut details = ACCOUNT_DETAILS_1.clone();
+    details.additional_prefixes = vec![Address::from_str("test.charlie").unwrap()];
+    assert!(store.update_account(accs[1].id(), details).await.is_err());
+    assert_eq!(
+        *store.routing_table().get("test.charlie").unwrap(),
+        charlie.id()
+    );
+
+    // Deleting the account keeps the routes which were routed to another account since
+    let mut connection = context.async_connection().await.unwrap();
+    let _: redis_crate::Value = redis_crate::cmd("HSET")
+        .arg("{ilp}:routes:current")
+        .arg("test.charlie")
+        .arg(accs[1].id().to_string())
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    store.delete_account(charlie.id()).await.unwrap();
+    let route: Option<String> = redis_crate::cmd("HGET")
+        .arg("{ilp}:routes:current")
+        .arg("test.charlie")
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    assert_eq!(route, Some(accs[1].id().to_string()));
+}
+
 #[tokio::test]
 async fn gets_local_and_configured_routes() {
     let (store, _context, _) = test_store().await.unwrap();
@@ -153,7 +253,7 @@ async fn saves_routes_to_db() {
 
     let mut connection = get_connection.await.unwrap();
     let routes: HashMap<String, String> = redis_crate::cmd("HGETALL")
-        .arg("routes:current")
+        .arg("{ilp}:routes:current")
         .query_async(&mut connection)
         .await
         .unwrap();
@@ -184,7 +284,7 @@ async fn adds_static_routes_to_redis() {
         .unwrap();
     let mut connection = get_connection.await.unwrap();
     let routes: HashMap<String, String> = redis_crate::cmd("HGETALL")
-        .arg("routes:static")
+        .arg("{ilp}:routes:static")
         .query_async(&mut connection)
         .await
         .unwrap();
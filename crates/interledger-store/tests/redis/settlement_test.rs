@@ -38,11 +38,21 @@ async fn saves_gets_clears_uncredited_settlement_amount_properly() {
     // 1 uncredited unit for scale 9
     assert_eq!(ret, BigUint::from(1u32));
     // rest should be in the leftovers store
+    let ret = store.peek_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(5u32), 12));
     let ret = store.get_uncredited_settlement_amount(acc).await.unwrap();
     // 1 uncredited unit for scale 9
     assert_eq!(ret, (BigUint::from(5u32), 12));
 
+    // getting the amount removes it
+    let ret = store.peek_uncredited_settlement_amount(acc).await.unwrap();
+    assert_eq!(ret, (BigUint::from(0u32), 0));
+
     // clears uncredited amount
+    store
+        .save_uncredited_settlement_amount(acc, (BigUint::from(5u32), 12))
+        .await
+        .unwrap();
     store.clear_uncredited_settlement_amount(acc).await.unwrap();
     let ret = store.get_uncredited_settlement_amount(acc).await.unwrap();
     assert_eq!(ret, (BigUint::from(0u32), 0));
@@ -58,7 +68,7 @@ async fn credits_prepaid_amount() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
@@ -107,7 +117,7 @@ async fn idempotent_settlement_calls() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
@@ -125,7 +135,7 @@ async fn idempotent_settlement_calls() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
@@ -144,7 +154,7 @@ async fn credits_balance_owed() {
     let id = accs[0].id();
     let mut conn = context.shared_async_connection().await.unwrap();
     let _balance: i64 = cmd("HSET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg(-200i64)
         .query_async(&mut conn)
@@ -155,7 +165,7 @@ async fn credits_balance_owed() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
@@ -171,7 +181,7 @@ async fn clears_balance_owed() {
     let id = accs[0].id();
     let mut conn = context.shared_async_connection().await.unwrap();
     let _balance: i64 = cmd("HSET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg(-100i64)
         .query_async(&mut conn)
@@ -182,7 +192,7 @@ async fn clears_balance_owed() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
@@ -198,7 +208,7 @@ async fn clears_balance_owed_and_puts_remainder_as_prepaid() {
     let id = accs[0].id();
     let mut conn = context.shared_async_connection().await.unwrap();
     let _balance: i64 = cmd("HSET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg(-40)
         .query_async(&mut conn)
@@ -209,7 +219,7 @@ async fn clears_balance_owed_and_puts_remainder_as_prepaid() {
         .await
         .unwrap();
     let (balance, prepaid_amount): (i64, i64) = cmd("HMGET")
-        .arg(format!("accounts:{}", id))
+        .arg(format!("{{ilp}}:accounts:{}", id))
         .arg("balance")
         .arg("prepaid_amount")
         .query_async(&mut conn)
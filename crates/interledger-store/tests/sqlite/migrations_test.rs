@@ -0,0 +1,218 @@
+use interledger_service::{AccountStatus, AccountStore};
+use interledger_service_util::{
+    ExchangeRateHistoryQuery, ExchangeRateHistoryStore, FeeAccount, RateLimitAccount, StatusAccount,
+};
+use interledger_store::sqlite::{SqliteStoreBuilder, SCHEMA_VERSION};
+use rusqlite::{params, Connection, NO_PARAMS};
+use std::{env, fs, path::PathBuf};
+use uuid::Uuid;
+
+fn database_path() -> PathBuf {
+    let (a, b) = rand::random::<(u64, u64)>();
+    env::temp_dir().join(format!("ilp-sqlite-test-{:x}{:x}.db", a, b))
+}
+
+fn user_version(path: &PathBuf) -> u32 {
+    Connection::open(path)
+        .unwrap()
+        .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn saves_schema_version_on_connect() {
+    let path = database_path();
+    let version = SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .migrate()
+        .await
+        .unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+
+    SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn moves_account_columns_of_databases_without_version() {
+    let path = database_path();
+    let id = Uuid::new_v4();
+    {
+        // The accounts table and the per account tables of the first versions
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE accounts (
+                    id TEXT PRIMARY KEY,
+                    username TEXT NOT NULL UNIQUE,
+                    ilp_address TEXT NOT NULL,
+                    asset_code TEXT NOT NULL,
+                    asset_scale INTEGER NOT NULL,
+                    max_packet_amount INTEGER NOT NULL,
+                    min_balance INTEGER,
+                    ilp_over_http_url TEXT,
+                    ilp_over_http_incoming_token BLOB,
+                    ilp_over_http_outgoing_token BLOB,
+                    ilp_over_btp_url TEXT,
+                    ilp_over_btp_incoming_token BLOB,
+                    ilp_over_btp_outgoing_token BLOB,
+                    settle_threshold INTEGER,
+                    settle_to INTEGER,
+                    routing_relation TEXT NOT NULL,
+                    round_trip_time INTEGER NOT NULL,
+                    packets_per_minute_limit INTEGER,
+                    amount_per_minute_limit INTEGER,
+                    settlement_engine_url TEXT,
+                    balance INTEGER NOT NULL DEFAULT 0,
+                    prepaid_amount INTEGER NOT NULL DEFAULT 0
+                );
+                CREATE TABLE account_statuses (
+                    account_id TEXT PRIMARY KEY,
+                    status TEXT NOT NULL
+                );
+                CREATE TABLE account_burst_limits (
+                    account_id TEXT PRIMARY KEY,
+                    packets_burst_limit INTEGER,
+                    amount_burst_limit INTEGER
+                );
+                CREATE TABLE account_fees (
+                    account_id TEXT PRIMARY KEY,
+                    fixed_fee INTEGER,
+                    fee_basis_points INTEGER
+                );",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO accounts (id, username, ilp_address, asset_code, asset_scale,
+                    max_packet_amount, routing_relation, round_trip_time, balance)
+                VALUES (?1, 'alice', 'example.alice', 'XYZ', 9, 1000, 'Peer', 500, 100)",
+                params![id.to_string()],
+            )
+            .unwrap();
+        connection
+            .execute_batch(&format!(
+                "INSERT INTO account_statuses VALUES ('{0}', 'suspended');
+                INSERT INTO account_burst_limits VALUES ('{0}', 10, NULL);
+                INSERT INTO account_fees VALUES ('{0}', 5, 20);",
+                id
+            ))
+            .unwrap();
+    }
+
+    let store = SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+    let account = store.get_accounts(vec![id]).await.unwrap().pop().unwrap();
+    assert_eq!(account.status(), AccountStatus::Suspended);
+    assert_eq!(account.packets_burst_limit(), Some(10));
+    assert_eq!(account.amount_burst_limit(), None);
+    assert_eq!(account.amount_per_day_limit(), None);
+    assert_eq!(account.fixed_fee(), 5);
+    assert_eq!(account.fee_basis_points(), 20);
+    drop(store);
+
+    let tables: u32 = Connection::open(&path)
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'
+            AND name IN ('account_statuses', 'account_burst_limits', 'account_fees')",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(tables, 0);
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn converts_rate_history_to_decimal_strings() {
+    let path = database_path();
+    SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .migrate()
+        .await
+        .unwrap();
+    {
+        // The rates were saved as floats until version 3
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "DROP TABLE exchange_rate_history;
+                CREATE TABLE exchange_rate_history (
+                    timestamp INTEGER NOT NULL,
+                    asset_code TEXT NOT NULL,
+                    rate REAL NOT NULL,
+                    source TEXT NOT NULL
+                );
+                CREATE INDEX exchange_rate_history_timestamp
+                    ON exchange_rate_history (timestamp);
+                CREATE INDEX exchange_rate_history_asset_code
+                    ON exchange_rate_history (asset_code);
+                INSERT INTO exchange_rate_history (rowid, timestamp, asset_code, rate, source)
+                VALUES (5, 1000, 'ABC', 0.1, 'api'), (7, 2000, 'XYZ', 0.00001, 'api'),
+                    (9, 3000, 'ABC', 2, 'CoinCap');
+                PRAGMA user_version = 3;",
+            )
+            .unwrap();
+    }
+
+    let store = SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+    let page = store
+        .get_exchange_rate_history(ExchangeRateHistoryQuery {
+            asset_code: None,
+            from: 0,
+            to: std::u64::MAX,
+            cursor: Some("5".to_string()),
+            limit: 10,
+        })
+        .await
+        .unwrap();
+    let rates: Vec<(&str, String)> = page
+        .records
+        .iter()
+        .map(|record| (record.asset_code.as_str(), record.rate.to_string()))
+        .collect();
+    assert_eq!(
+        rates,
+        vec![("XYZ", "0.00001".to_string()), ("ABC", "2".to_string())]
+    );
+    drop(store);
+
+    let rate_type: String = Connection::open(&path)
+        .unwrap()
+        .query_row(
+            "SELECT typeof(rate) FROM exchange_rate_history LIMIT 1",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(rate_type, "text");
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn refuses_data_of_newer_versions() {
+    let path = database_path();
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1))
+        .unwrap();
+    let builder = SqliteStoreBuilder::new(path.clone(), [0; 32]);
+    assert!(builder.migrate().await.is_err());
+    let result = SqliteStoreBuilder::new(path.clone(), [0; 32])
+        .connect()
+        .await;
+    assert!(result.is_err());
+    assert_eq!(user_version(&path), SCHEMA_VERSION + 1);
+    fs::remove_file(path).unwrap();
+}
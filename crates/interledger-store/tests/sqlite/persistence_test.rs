@@ -0,0 +1,191 @@
+use super::fixtures::*;
+
+use interledger_api::NodeStore;
+use interledger_btp::BtpStore;
+use interledger_http::{HttpAccount, HttpStore};
+use interledger_packet::Address;
+use interledger_router::RouterStore;
+use interledger_service::{Account as AccountTrait, AccountStore, AddressStore};
+use interledger_service_util::{BalanceStore, ExchangeRateStore, Spreads};
+use interledger_store::{
+    crypto::{is_token_hash, StoreKeys},
+    sqlite::SqliteStoreBuilder,
+};
+use rusqlite::{params, Connection};
+use std::{env, fs, str::FromStr};
+
+#[tokio::test]
+async fn keeps_data_after_reopening() {
+    let (a, b) = rand::random::<(u64, u64)>();
+    let path = env::temp_dir().join(format!("ilp-sqlite-test-{:x}{:x}.db", a, b));
+    let secret = [1; 32];
+
+    let (alice, bob) = {
+        let store = SqliteStoreBuilder::new(path.clone(), secret)
+            .node_ilp_address(Address::from_str("example.node").unwrap())
+            .connect()
+            .await
+            .unwrap();
+        let alice = store
+            .insert_account(ACCOUNT_DETAILS_0.clone())
+            .await
+            .unwrap();
+        store
+            .set_ilp_address(alice.ilp_address().with_suffix(b"user1").unwrap())
+            .await
+            .unwrap();
+        let bob = store
+            .insert_account(ACCOUNT_DETAILS_1.clone())
+            .await
+            .unwrap();
+        store
+            .update_balances_for_reject(bob.clone(), 100, packet_context())
+            .await
+            .unwrap();
+        store.set_spreads(Spreads::new(0.02)).unwrap();
+        (alice, bob)
+    };
+
+    // The builder's address is ignored because the parent's address was saved
+    let store = SqliteStoreBuilder::new(path.clone(), secret)
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_ilp_address(),
+        Address::from_str("example.alice.user1").unwrap()
+    );
+    let accounts = store
+        .get_accounts(vec![alice.id(), bob.id()])
+        .await
+        .unwrap();
+    assert_eq!(accounts[0].username().as_ref(), "alice");
+    assert_eq!(
+        *accounts[1].ilp_address(),
+        Address::from_str("example.alice.user1.bob").unwrap()
+    );
+    assert_eq!(store.get_balance(accounts[1].clone()).await.unwrap(), 100);
+    // Tokens encrypted before reopening can be decrypted with the same secret
+    let account = store
+        .get_account_from_http_auth(bob.username(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(account.id(), bob.id());
+    assert_eq!(
+        *store
+            .routing_table()
+            .get("example.alice.user1.bob")
+            .unwrap(),
+        bob.id()
+    );
+    // The saved spreads take precedence over the configured ones
+    store.set_default_spreads(Spreads::new(0.01)).unwrap();
+    assert_eq!(store.get_all_spreads().unwrap(), Spreads::new(0.02));
+
+    drop(store);
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn reopens_with_rotated_secret() {
+    let (a, b) = rand::random::<(u64, u64)>();
+    let path = env::temp_dir().join(format!("ilp-sqlite-test-{:x}{:x}.db", a, b));
+
+    let bob = {
+        let store = SqliteStoreBuilder::new(path.clone(), [1; 32])
+            .node_ilp_address(Address::from_str("example.node").unwrap())
+            .connect()
+            .await
+            .unwrap();
+        let bob = store
+            .insert_account(ACCOUNT_DETAILS_1.clone())
+            .await
+            .unwrap();
+        store.rotate_secret([2; 32]).await.unwrap();
+        // The rotating store keeps working with the new secret
+        store
+            .get_account_from_http_auth(bob.username(), "incoming_auth_token")
+            .await
+            .unwrap();
+        bob
+    };
+
+    let store = SqliteStoreBuilder::new(path.clone(), [2; 32])
+        .connect()
+        .await
+        .unwrap();
+    let account = store
+        .get_account_from_http_auth(bob.username(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert_eq!(account.id(), bob.id());
+    drop(store);
+
+    let store = SqliteStoreBuilder::new(path.clone(), [1; 32])
+        .connect()
+        .await
+        .unwrap();
+    // The incoming tokens are hashed, but the outgoing ones can no longer be decrypted
+    let account = store
+        .get_account_from_http_auth(bob.username(), "incoming_auth_token")
+        .await
+        .unwrap();
+    assert!(account.get_http_auth_token().is_none());
+
+    drop(store);
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn hashes_encrypted_incoming_tokens_on_first_authentication() {
+    let (a, b) = rand::random::<(u64, u64)>();
+    let path = env::temp_dir().join(format!("ilp-sqlite-test-{:x}{:x}.db", a, b));
+    let store = SqliteStoreBuilder::new(path.clone(), [1; 32])
+        .node_ilp_address(Address::from_str("example.node").unwrap())
+        .connect()
+        .await
+        .unwrap();
+    let bob = store
+        .insert_account(ACCOUNT_DETAILS_1.clone())
+        .await
+        .unwrap();
+    let connection = Connection::open(&path).unwrap();
+    let stored = || -> Vec<u8> {
+        connection
+            .query_row(
+                "SELECT ilp_over_btp_incoming_token FROM accounts WHERE id = ?1",
+                params![bob.id().to_string()],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert!(is_token_hash(&stored()));
+
+    // Incoming tokens used to be encrypted like the outgoing ones
+    let encrypted = StoreKeys::new(&[1; 32]).encrypt_token(b"other_btp_token");
+    connection
+        .execute(
+            "UPDATE accounts SET ilp_over_btp_incoming_token = ?1 WHERE id = ?2",
+            params![encrypted.to_vec(), bob.id().to_string()],
+        )
+        .unwrap();
+    assert!(store
+        .get_account_from_btp_auth(bob.username(), "unknown_token")
+        .await
+        .is_err());
+    assert_eq!(stored(), encrypted.to_vec());
+
+    store
+        .get_account_from_btp_auth(bob.username(), "other_btp_token")
+        .await
+        .unwrap();
+    assert!(is_token_hash(&stored()));
+    store
+        .get_account_from_btp_auth(bob.username(), "other_btp_token")
+        .await
+        .unwrap();
+
+    drop(connection);
+    drop(store);
+    fs::remove_file(path).unwrap();
+}
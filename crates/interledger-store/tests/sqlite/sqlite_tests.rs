@@ -0,0 +1,42 @@
+// The tests in the common directory are shared with the in-memory store and run
+// against the store created by `backend::new_store`
+#[path = "../common/accounts_test.rs"]
+mod accounts_test;
+#[path = "../common/auth_test.rs"]
+mod auth_test;
+#[path = "../common/balances_test.rs"]
+mod balances_test;
+#[path = "../common/fees_test.rs"]
+mod fees_test;
+#[path = "../common/rate_limiting_test.rs"]
+mod rate_limiting_test;
+#[path = "../common/rates_test.rs"]
+mod rates_test;
+#[path = "../common/routing_test.rs"]
+mod routing_test;
+#[path = "../common/settlement_test.rs"]
+mod settlement_test;
+
+mod migrations_test;
+mod persistence_test;
+
+#[path = "../common/fixtures.rs"]
+mod fixtures;
+#[path = "../common/store_helpers.rs"]
+mod store_helpers;
+
+mod backend {
+    use interledger_packet::Address;
+    use interledger_store::sqlite::{SqliteStore, SqliteStoreBuilder};
+    use std::str::FromStr;
+
+    pub type Store = SqliteStore;
+
+    pub async fn new_store() -> Store {
+        SqliteStoreBuilder::new(":memory:", [0; 32])
+            .node_ilp_address(Address::from_str("example.node").unwrap())
+            .connect()
+            .await
+            .unwrap()
+    }
+}
@@ -580,6 +580,7 @@ mod stream_receiver_service {
                 },
                 original_amount: prepare.amount(),
                 prepare,
+                applied_rate: None,
             })
             .await;
         assert!(result.is_ok());
@@ -640,6 +641,7 @@ mod stream_receiver_service {
                 },
                 original_amount: prepare.amount(),
                 prepare,
+                applied_rate: None,
             })
             .await;
         assert!(result.is_err());
@@ -698,6 +700,7 @@ mod stream_receiver_service {
                     asset_scale: 9,
                 },
                 prepare,
+                applied_rate: None,
             })
             .await;
         assert!(result.is_err());